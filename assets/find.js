@@ -0,0 +1,161 @@
+// Find-in-page for content webviews, injected by Rust. Matches are wrapped in
+// <mark> elements a slice at a time so long pages stay responsive, and the
+// search re-runs (debounced) when the page changes. Results are reported with
+// the `find_result` IPC op.
+(function() {
+  if (window.__syncfloFind) return;
+
+  var MARK_CLASS = '__syncflo-find';
+  var SLICE = 200;
+  var RERUN_DELAY = 300;
+  var state = { query: '', caseSensitive: false, marks: [], current: -1, run: 0 };
+  var rerunTimer = null;
+
+  var style = document.createElement('style');
+  style.textContent = 'mark.' + MARK_CLASS + ' { background: #ffe066; color: #000; }' +
+    'mark.' + MARK_CLASS + '.current { background: #ff9632; }';
+
+  var observer = new MutationObserver(function() {
+    if (!state.query) return;
+    clearTimeout(rerunTimer);
+    rerunTimer = setTimeout(function() { search(false); }, RERUN_DELAY);
+  });
+  function observe() {
+    if (document.body) observer.observe(document.body, { childList: true, subtree: true, characterData: true });
+  }
+  // Our own DOM changes shouldn't trigger a re-run
+  function quietly(fn) {
+    observer.disconnect();
+    try { fn(); } finally { observe(); }
+  }
+
+  function report() {
+    try {
+      window.ipc.postMessage(JSON.stringify({ op: 'find_result', payload: {
+        query: state.query, current: state.current + 1, total: state.marks.length
+      } }));
+    } catch (e) {}
+  }
+
+  function unmark() {
+    quietly(function() {
+      state.marks.forEach(function(mark) {
+        var parent = mark.parentNode;
+        if (!parent) return;
+        parent.replaceChild(document.createTextNode(mark.textContent), mark);
+        parent.normalize();
+      });
+    });
+    state.marks = [];
+  }
+
+  function fold(text) {
+    return state.caseSensitive ? text : text.toLowerCase();
+  }
+
+  // Text nodes containing the query, in document order. Only reads the DOM,
+  // which is fast even on very long pages.
+  function matchingNodes(needle) {
+    var walker = document.createTreeWalker(document.body, NodeFilter.SHOW_TEXT, {
+      acceptNode: function(node) {
+        var tag = node.parentNode && node.parentNode.nodeName;
+        if (tag === 'SCRIPT' || tag === 'STYLE' || tag === 'NOSCRIPT' || tag === 'TEXTAREA') {
+          return NodeFilter.FILTER_REJECT;
+        }
+        return fold(node.nodeValue).indexOf(needle) === -1 ? NodeFilter.FILTER_SKIP : NodeFilter.FILTER_ACCEPT;
+      }
+    });
+    var nodes = [];
+    var node;
+    while ((node = walker.nextNode())) nodes.push(node);
+    return nodes;
+  }
+
+  // Wraps each occurrence of `needle` in `node` and returns the marks.
+  function markNode(node, needle) {
+    var text = fold(node.nodeValue);
+    var starts = [];
+    for (var i = text.indexOf(needle); i !== -1; i = text.indexOf(needle, i + needle.length)) starts.push(i);
+    var marks = [];
+    // Split from the end so earlier offsets stay valid
+    for (var k = starts.length - 1; k >= 0; k--) {
+      var match = node.splitText(starts[k]);
+      match.splitText(needle.length);
+      var mark = document.createElement('mark');
+      mark.className = MARK_CLASS;
+      match.parentNode.replaceChild(mark, match);
+      mark.appendChild(match);
+      marks.unshift(mark);
+    }
+    return marks;
+  }
+
+  function select(index) {
+    var old = state.marks[state.current];
+    if (old) old.classList.remove('current');
+    state.current = index;
+    var mark = state.marks[index];
+    if (mark) {
+      mark.classList.add('current');
+      mark.scrollIntoView({ block: 'center' });
+    }
+    report();
+  }
+
+  // Marks all matches a slice at a time. A newer search cancels this one.
+  // `fresh` selects the first match; a re-run keeps the current position.
+  function search(fresh) {
+    var run = ++state.run;
+    var keep = state.current;
+    unmark();
+    state.current = -1;
+    if (!state.query || !document.body) {
+      report();
+      return;
+    }
+    if (!style.parentNode) quietly(function() { (document.head || document.documentElement).appendChild(style); });
+    var needle = fold(state.query);
+    var nodes = matchingNodes(needle);
+    var next = 0;
+    (function slice() {
+      if (run !== state.run) return;
+      quietly(function() {
+        nodes.slice(next, next + SLICE).forEach(function(node) {
+          if (node.parentNode) state.marks.push.apply(state.marks, markNode(node, needle));
+        });
+      });
+      next += SLICE;
+      if (next < nodes.length) {
+        report();
+        setTimeout(slice, 0);
+      } else if (state.marks.length) {
+        select(fresh ? 0 : Math.min(Math.max(keep, 0), state.marks.length - 1));
+      } else {
+        report();
+      }
+    })();
+  }
+
+  window.__syncfloFind = {
+    find: function(query, forward, caseSensitive) {
+      if (query !== state.query || caseSensitive !== state.caseSensitive) {
+        state.query = query;
+        state.caseSensitive = caseSensitive;
+        search(true);
+      } else if (state.marks.length) {
+        var n = state.marks.length;
+        select((state.current + (forward ? 1 : n - 1)) % n);
+      } else {
+        report();
+      }
+    },
+    clear: function() {
+      state.query = '';
+      state.run++;
+      clearTimeout(rerunTimer);
+      unmark();
+      state.current = -1;
+    }
+  };
+  observe();
+})();
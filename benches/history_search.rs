@@ -0,0 +1,53 @@
+//! Address-bar history search over a full history.
+
+// Only what navigation needs of these modules is used
+#![allow(dead_code)]
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+#[path = "../src/ai/embeddings.rs"]
+mod embeddings;
+#[path = "../src/browser/navigation.rs"]
+mod navigation;
+#[path = "../src/browser/worker.rs"]
+mod worker;
+
+/// Where navigation finds the modules in the browser.
+mod ai {
+    pub use crate::embeddings::Embedding;
+}
+mod browser {
+    pub(crate) use crate::worker;
+}
+
+use navigation::{Navigation, MAX_HISTORY_ENTRIES};
+use url::Url;
+
+const WORDS: &[&str] = &[
+    "rust", "tokio", "async", "browser", "privacy", "recipe", "weather", "news", "travel", "music", "docs", "guide",
+];
+
+/// A history as full as it gets, over a few hundred sites.
+fn full_history() -> Navigation {
+    let mut nav = Navigation::new();
+    for i in 0..MAX_HISTORY_ENTRIES {
+        let word = WORDS[i % WORDS.len()];
+        let url = Url::parse(&format!("https://site{}.example/{}/{}", i % 300, word, i)).unwrap();
+        nav.visit(&url);
+        nav.set_title(&url, format!("{} article {} - Site {}", word, i, i % 300));
+        nav.set_keywords(&url, vec![word.to_string(), WORDS[(i + 5) % WORDS.len()].to_string()]);
+    }
+    nav
+}
+
+fn search(c: &mut Criterion) {
+    let nav = full_history();
+    let mut group = c.benchmark_group("history_search");
+    for query in ["s", "site42", "rust", "privacy guide", "no match at all"] {
+        group.bench_function(query, |b| b.iter(|| nav.search(black_box(query), 8).len()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, search);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+//! Keyword extraction from a page's worth of text, which should take
+//! under 10 ms for 100 KB.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+#[path = "../src/ai/keywords.rs"]
+mod keywords;
+
+const ENGLISH: &str = "The borrow checker makes sure references never outlive the data they point to. \
+    Lifetimes name how long a reference is valid, and most of the time the compiler infers them. \
+    When a function returns a reference, its lifetime must be tied to one of the arguments. ";
+const KOREAN: &str = "빌림 검사기는 참조가 가리키는 데이터보다 오래 살지 않도록 보장합니다. \
+    라이프타임은 참조가 유효한 기간을 나타내며 대부분 컴파일러가 추론합니다. ";
+
+/// `paragraph` repeated, with numbered words mixed in so not every word
+/// recurs, up to `bytes`.
+fn text(paragraph: &str, bytes: usize) -> String {
+    let mut text = String::with_capacity(bytes + paragraph.len());
+    let mut i = 0;
+    while text.len() < bytes {
+        text.push_str(paragraph);
+        text.push_str(&format!("section{} item{} ", i, i % 37));
+        i += 1;
+    }
+    text
+}
+
+fn extract(c: &mut Criterion) {
+    let mut group = c.benchmark_group("extract_keywords");
+    for (name, paragraph) in [("english_100kb", ENGLISH), ("korean_100kb", KOREAN)] {
+        let text = text(paragraph, 100 * 1024);
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function(name, |b| b.iter(|| keywords::extract(black_box(&text), 10)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, extract);
+criterion_main!(benches);
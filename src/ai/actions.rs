@@ -0,0 +1,212 @@
+//! Things the assistant can propose doing in the browser. The model picks
+//! one of a few typed actions; nothing is done until the user confirms it.
+
+use super::forms::{FormField, FormInfo};
+use super::processor::{collapse_whitespace, ContentProcessor, PageLink};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Most links of a page offered to the model, in document order.
+const MAX_LINKS: usize = 200;
+/// Most headings of a page offered to the model.
+const MAX_HEADINGS: usize = 50;
+/// Most forms of a page offered to the model.
+const MAX_FORMS: usize = 10;
+/// Most structured data items of a page offered to the model, and the
+/// characters of JSON of each.
+const MAX_FACTS: usize = 10;
+const MAX_FACT_CHARS: usize = 1000;
+
+/// An action the assistant proposes, checked against the page it's about.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AiAction {
+    /// Open `url`, a link of the page or an absolute HTTPS URL.
+    Navigate { url: String },
+    SearchWeb { query: String },
+    /// Scroll to the page's heading with the text `to_heading`.
+    Scroll { to_heading: String },
+    /// Nothing fits what was asked.
+    None,
+}
+
+impl AiAction {
+    /// This action with `f` applied to its text, e.g. to put back personal
+    /// data replaced before sending.
+    pub(super) fn map_text(self, f: impl Fn(&str) -> String) -> Self {
+        match self {
+            AiAction::Navigate { url } => AiAction::Navigate { url: f(&url) },
+            AiAction::SearchWeb { query } => AiAction::SearchWeb { query: f(&query) },
+            AiAction::Scroll { to_heading } => AiAction::Scroll { to_heading: f(&to_heading) },
+            AiAction::None => AiAction::None,
+        }
+    }
+}
+
+/// An action and why the assistant proposes it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProposedAction {
+    pub action: AiAction,
+    #[serde(default)]
+    pub explanation: Option<String>,
+}
+
+/// What actions may refer to on the page they're about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionPage {
+    pub url: Url,
+    pub title: String,
+    /// What the page says it's about, from its meta tags.
+    pub description: Option<String>,
+    /// The links offered to the model, at most [`MAX_LINKS`].
+    pub links: Vec<PageLink>,
+    /// Every link of the page, which actions may go to even if the model
+    /// wasn't offered it.
+    pub link_urls: Vec<Url>,
+    pub headings: Vec<String>,
+    /// The forms shown on the page, so the model can tell e.g. a sign-in
+    /// page.
+    pub forms: Vec<FormInfo>,
+    /// The page's JSON-LD and microdata items, e.g. the `Product` it's
+    /// about.
+    pub facts: Vec<serde_json::Value>,
+}
+
+impl ActionPage {
+    /// The metadata, links, headings, forms and structured data of `html`,
+    /// the page at `url`.
+    pub fn from_html(html: &str, url: &Url) -> Self {
+        let processor = ContentProcessor::new();
+        let mut links = processor.extract_labeled_links(html, url).unwrap_or_default();
+        links.retain(|link| matches!(link.url.scheme(), "http" | "https"));
+        links.truncate(MAX_LINKS);
+        let link_urls = processor.extract_links_absolute(html, url).unwrap_or_default();
+        let mut headings: Vec<String> =
+            processor.extract_outline(html).unwrap_or_default().into_iter().map(|item| item.text).collect();
+        headings.dedup();
+        headings.truncate(MAX_HEADINGS);
+        let mut forms = processor.extract_forms(html).unwrap_or_default();
+        forms.retain(|form| !form.hidden);
+        forms.truncate(MAX_FORMS);
+        let mut facts = processor.extract_structured_data(html).unwrap_or_default();
+        facts.truncate(MAX_FACTS);
+        Self {
+            url: url.clone(),
+            title: processor.extract_title(html).ok().flatten().unwrap_or_default(),
+            description: processor.extract_metadata(html, url).ok().and_then(|metadata| metadata.description),
+            links,
+            link_urls,
+            headings,
+            forms,
+            facts,
+        }
+    }
+
+    /// The page as the model is told about it.
+    pub(super) fn describe(&self) -> String {
+        let mut description = format!("Page: {} ({})\n", self.title, self.url);
+        if let Some(about) = &self.description {
+            description.push_str(&format!("Description: {}\n", about));
+        }
+        description.push('\n');
+        if !self.forms.is_empty() {
+            description.push_str("Forms:\n");
+            for form in &self.forms {
+                let kind = if form.is_login() { "Sign-in form" } else { "Form" };
+                let fields: Vec<String> =
+                    form.fields.iter().filter(|field| !field.hidden).map(describe_field).collect();
+                description.push_str(&format!("- {} ({}): {}\n", kind, form.method, fields.join("; ")));
+            }
+            description.push('\n');
+        }
+        if !self.facts.is_empty() {
+            description.push_str("Structured data:\n");
+            for fact in &self.facts {
+                let json: String = fact.to_string().chars().take(MAX_FACT_CHARS).collect();
+                description.push_str(&format!("- {}\n", json));
+            }
+            description.push('\n');
+        }
+        description.push_str("Links:\n");
+        for link in &self.links {
+            description.push_str(&format!("- {}: {}\n", link.text, link.url));
+        }
+        description.push_str("\nHeadings:\n");
+        for heading in &self.headings {
+            description.push_str(&format!("- {}\n", heading));
+        }
+        description
+    }
+
+    /// `action` if it may be done on this page, with URLs made absolute and
+    /// headings spelled as on the page; else why not. URLs must be links
+    /// of the page or absolute HTTPS URLs.
+    pub(super) fn check(&self, action: AiAction) -> Result<AiAction, String> {
+        match action {
+            AiAction::Navigate { url } => {
+                let resolved = self.url.join(url.trim()).map_err(|_| format!("{:?} isn't a URL", url))?;
+                let linked = self.link_urls.contains(&resolved);
+                let https = Url::parse(url.trim()).is_ok_and(|url| url.scheme() == "https");
+                if linked || https {
+                    Ok(AiAction::Navigate { url: resolved.into() })
+                } else {
+                    Err(format!("{} is neither a link of the page nor an absolute https URL", url))
+                }
+            }
+            AiAction::SearchWeb { query } => match collapse_whitespace(&query) {
+                query if query.is_empty() => Err("The search query is empty".to_string()),
+                query => Ok(AiAction::SearchWeb { query }),
+            },
+            AiAction::Scroll { to_heading } => {
+                let wanted = collapse_whitespace(&to_heading).to_lowercase();
+                match self.headings.iter().find(|heading| heading.to_lowercase() == wanted) {
+                    Some(heading) => Ok(AiAction::Scroll { to_heading: heading.clone() }),
+                    None => Err(format!("The page has no heading {:?}", to_heading)),
+                }
+            }
+            AiAction::None => Ok(AiAction::None),
+        }
+    }
+}
+
+/// `field` as the model is told about it, e.g. `Email (email, required)`.
+fn describe_field(field: &FormField) -> String {
+    let name = field.label.as_deref().or(field.name.as_deref()).unwrap_or(&field.kind);
+    let mut details = field.kind.clone();
+    if field.required {
+        details.push_str(", required");
+    }
+    if !field.options.is_empty() {
+        details.push_str(&format!(": {}", field.options.join(" / ")));
+    }
+    format!("{} ({})", name, details)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page() -> ActionPage {
+        let mut html = String::from(r#"<head><base href="https://example.com/docs/"></head><body>"#);
+        for i in 0..MAX_LINKS + 10 {
+            html.push_str(&format!(r#"<a href="page{}.html">Page {}</a>"#, i, i));
+        }
+        ActionPage::from_html(&html, &Url::parse("https://example.com/index.html").unwrap())
+    }
+
+    #[test]
+    fn any_link_of_the_page_may_be_opened() {
+        let page = page();
+        assert_eq!(page.links.len(), MAX_LINKS);
+        let last = format!("https://example.com/docs/page{}.html", MAX_LINKS + 9);
+        assert_eq!(page.check(AiAction::Navigate { url: last.clone() }), Ok(AiAction::Navigate { url: last }));
+    }
+
+    #[test]
+    fn other_urls_must_be_absolute_https() {
+        let page = page();
+        assert!(page.check(AiAction::Navigate { url: "http://elsewhere.example/".into() }).is_err());
+        assert!(page.check(AiAction::Navigate { url: "https://elsewhere.example/".into() }).is_ok());
+    }
+}
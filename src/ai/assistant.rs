@@ -1,44 +1,745 @@
-use anyhow::Result;
+use super::actions::{ActionPage, ProposedAction};
+use super::cache::ResponseCache;
+use super::config::{AiConfig, ModelPrice};
+use super::context::{build_context, estimate_tokens, truncate};
+use super::conversation::Conversation;
+use super::embeddings::{Embedding, MAX_EMBED_TOKENS};
+use super::offline;
+use super::privacy::{self, ScrubReport};
+use super::processor::{collapse_whitespace, Article, ContentProcessor};
+use super::provider::{self, ChatMessage, ChatRequest, ChatResponse, Provider, Role, TokenUsage};
+use super::translate;
+use super::usage::{Usage, UsageLog};
+use futures_util::stream::{self, StreamExt};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+
+/// Most tokens of page content sent, however large the context window:
+/// more costs more without making answers much better.
+const MAX_PAGE_TOKENS: usize = 12_000;
+/// Share of the context window left for the answer in chats, in percent.
+const ANSWER_SHARE: usize = 25;
+/// Most tokens translated per request. Smaller pieces are translated more
+/// faithfully, and fewer are lost when one fails.
+const MAX_CHUNK_TOKENS: usize = 1_500;
+/// Most pieces of a text translated at once.
+const MAX_CONCURRENT_CHUNKS: usize = 4;
+/// Sentences in summaries made without a model.
+const OFFLINE_SENTENCES: usize = 3;
+/// Answers asked for before giving up on getting a valid action.
+const MAX_ACTION_ATTEMPTS: usize = 3;
+/// Put before paragraphs that couldn't be translated.
+const UNTRANSLATED: &str = "[번역 실패]";
+
+pub(super) const SYSTEM_PROMPT: &str = "You are the browsing assistant of a web browser. You help the user understand \
+the page they're on and decide what to do next. Answer in Korean, briefly. Reply with only a JSON object: \
+{\"suggestion\": \"<one or two sentences>\", \"explanation\": \"<why, or null>\"}.";
+const SUMMARY_PROMPT: &str = "Summarize this page in three to five sentences as the suggestion, and list its \
+key points as the explanation.";
+const SELECTION_PROMPT: &str = "The user selected the text above on the page. Explain it, or answer it if it's \
+a question.";
+/// `{lang}` is replaced with the target language.
+const TRANSLATE_PROMPT: &str = "Translate the user's text into the language with the BCP 47 tag {lang}. Keep \
+paragraph breaks, HTML tags and attributes, and Markdown as they are. Reply with only the translation.";
+const ACTION_PROMPT: &str = "You are the browsing assistant of a web browser. The user asks you to do something \
+on the page described below. Pick one action and reply with only a JSON object: {\"action\": <action>, \
+\"explanation\": \"<why, in Korean, in one sentence>\"}, where <action> is {\"type\": \"navigate\", \"url\": \
+\"<a link of the page, or an absolute https URL>\"}, {\"type\": \"search_web\", \"query\": \"<query>\"}, \
+{\"type\": \"scroll\", \"to_heading\": \"<a heading of the page, as written>\"}, or {\"type\": \"none\"} if \
+none fits.";
+/// For streamed answers, which are shown as they're typed out.
+const STREAM_SYSTEM_PROMPT: &str = "You are the browsing assistant of a web browser. You help the user \
+understand the page they're on and decide what to do next. Answer in Korean, briefly, in plain text.";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AIResponse {
     pub suggestion: String,
     pub explanation: Option<String>,
+    /// Whether what was asked about was cut to fit the context window.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// How many pieces of personal data were replaced by placeholders
+    /// before sending, in privacy mode.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub redacted: usize,
+    /// What asking took; `None` for answers that weren't asked for, like
+    /// cached ones.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+/// What [`AIAssistant::validate`] found answering.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProviderInfo {
+    /// e.g. `OpenAI`.
+    pub provider: String,
+    pub model: String,
+    /// How long the test request took.
+    #[serde(rename = "latency_ms", serialize_with = "as_millis")]
+    pub latency: Duration,
 }
 
+/// Why a request to the AI API failed.
+#[derive(Debug, Error)]
+pub enum AiError {
+    #[error("Set AI_API_KEY or configure a provider in settings")]
+    NotConfigured,
+    #[error("The AI API rejected the API key")]
+    Unauthorized,
+    #[error("The AI API rate limit was exceeded{}", retry_hint(.retry_after))]
+    RateLimited { retry_after: Option<Duration> },
+    #[error("The AI API failed with {0}")]
+    Server(StatusCode),
+    #[error("The AI API returned {status}: {message}")]
+    Api { status: StatusCode, message: String },
+    #[error("The AI API didn't answer in time")]
+    Timeout,
+    #[error("Failed to reach the AI API: {0}")]
+    Network(#[source] reqwest::Error),
+    #[error("Invalid response from the AI API: {0}")]
+    InvalidResponse(String),
+    #[error("The AI request was cancelled")]
+    Cancelled,
+    #[error("The AI provider doesn't support this")]
+    Unsupported,
+    #[error("The endpoint doesn't answer like the {0} API; check its URL")]
+    WrongEndpoint(&'static str),
+}
+
+impl AiError {
+    /// Whether the request may well succeed if sent again.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, AiError::RateLimited { .. } | AiError::Server(_) | AiError::Timeout | AiError::Network(_))
+    }
+}
+
+/// When to try again after being rate limited, for the error message.
+fn retry_hint(retry_after: &Option<Duration>) -> String {
+    match retry_after {
+        Some(wait) => format!("; try again in {} s", wait.as_secs().max(1)),
+        None => String::new(),
+    }
+}
+
+impl From<reqwest::Error> for AiError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            AiError::Timeout
+        } else {
+            AiError::Network(e)
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct AIAssistant {
-    api_key: Option<String>,
-    api_endpoint: String,
+    /// `None` without the API key the configured provider needs.
+    provider: Option<Arc<dyn Provider>>,
+    cache: Option<Arc<ResponseCache>>,
+    /// Whether to ask again rather than use cached answers.
+    regenerate: bool,
+    /// Whether personal data is replaced before sending.
+    privacy: bool,
+    /// Where the usage of requests is added up, if anywhere.
+    usage: Option<Arc<UsageLog>>,
+    /// Prices of the chat model and of the embedding model.
+    price: ModelPrice,
+    embedding_price: ModelPrice,
 }
 
 impl AIAssistant {
-    pub fn new() -> Self {
+    /// An assistant using the provider `config` selects, with the
+    /// `AI_*` environment variables taking precedence. Its requests are
+    /// sent with `client`.
+    pub fn new(config: &AiConfig, client: reqwest::Client) -> Self {
+        let provider: Option<Arc<dyn Provider>> =
+            provider::from_config(&config.clone().with_env_overrides(), client).map(Arc::from);
+        let price = |model: Option<&str>| model.map(|model| config.price(model)).unwrap_or_default();
         Self {
-            api_key: std::env::var("AI_API_KEY").ok(),
-            api_endpoint: std::env::var("AI_API_ENDPOINT")
-                .unwrap_or_else(|_| "https://api.openai.com/v1/chat/completions".to_string()),
+            price: price(provider.as_ref().map(|provider| provider.model())),
+            embedding_price: price(provider.as_ref().and_then(|provider| provider.embedding_model())),
+            provider,
+            cache: None,
+            regenerate: false,
+            privacy: config.privacy_mode(),
+            usage: None,
         }
     }
 
-    pub async fn process_page(&self, url: &str, content: &str) -> Result<AIResponse> {
-        // TODO: Implement AI API integration
-        // For now, return a placeholder response
-        Ok(AIResponse {
-            suggestion: format!("Analyzing page: {}", url),
-            explanation: Some("AI assistant is ready to help you navigate this page.".to_string()),
-        })
+    /// Keeps answers about pages and translations in `cache`, and uses
+    /// them instead of asking again.
+    pub fn with_cache(mut self, cache: Arc<ResponseCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Adds up the tokens and cost of every request in `log`.
+    pub fn with_usage_log(mut self, log: Arc<UsageLog>) -> Self {
+        self.usage = Some(log);
+        self
+    }
+
+    /// With `regenerate`, asks again even for cached answers, replacing
+    /// them.
+    pub fn regenerate(mut self, regenerate: bool) -> Self {
+        self.regenerate = regenerate;
+        self
+    }
+
+    /// Asks the model `question` about the page at `url` with `article`.
+    /// Long articles are shortened to fit the model's context window.
+    /// Without a configured provider, the answer is an extractive summary
+    /// made offline, whatever the question.
+    pub async fn process_page(&self, url: &str, article: &Article, question: &str) -> Result<AIResponse, AiError> {
+        let Ok(provider) = self.provider() else {
+            return offline_summary(article);
+        };
+        let prompt = page_prompt(url, article, provider.context_window());
+        let mut report = ScrubReport::default();
+        let prompt = self.scrub(&mut report, &format!("{}\n\n{}", prompt.trim_end(), question));
+        let request = chat_request(SYSTEM_PROMPT, prompt);
+        // Cached with the placeholders, so personal data isn't stored
+        let response = self.cached("page", &request, self.answer(&request)).await?;
+        Ok(restore(response, &report))
+    }
+
+    /// Summarizes the page at `url` with `article`.
+    pub async fn summarize_page(&self, url: &str, article: &Article) -> Result<AIResponse, AiError> {
+        self.process_page(url, article, SUMMARY_PROMPT).await
     }
 
-    pub async fn suggest_action(&self, context: &str) -> Result<AIResponse> {
-        // TODO: Implement AI-powered action suggestions
-        Ok(AIResponse {
-            suggestion: "AI suggestion feature coming soon".to_string(),
-            explanation: None,
+    /// Explains `selection`, text the user selected on the page at `url`
+    /// titled `title`, or answers it. Long selections are cut to fit the
+    /// context window like pages are.
+    pub async fn explain_selection(&self, url: &str, title: &str, selection: &str) -> Result<AIResponse, AiError> {
+        let budget = input_budget(self.provider()?.context_window());
+        let (text, truncated) = truncate(selection.trim(), budget);
+        let notice = if truncated { "\n[The selection was cut off here.]" } else { "" };
+        let prompt = format!(
+            "Page: {} ({})\n\nSelected text:\n\"\"\"\n{}{}\n\"\"\"\n\n{}",
+            title, url, text, notice, SELECTION_PROMPT
+        );
+        let mut response = self.complete(&prompt).await?;
+        response.truncated = truncated;
+        Ok(response)
+    }
+
+    /// `text` translated into `target_lang`, a BCP 47 tag like `ko`. Long
+    /// texts are translated a few paragraphs at a time, several at once. A
+    /// piece that fails twice is left untranslated with a marker; only if
+    /// every piece fails does the translation fail.
+    pub async fn translate(&self, text: &str, target_lang: &str) -> Result<String, AiError> {
+        let budget = self.chunk_budget()?;
+        let chunks = translate::chunks(text, budget);
+        let system = ChatMessage::new(Role::System, TRANSLATE_PROMPT.replace("{lang}", target_lang));
+        // The requests are made up front: a closure mapping borrowed chunks
+        // inside the stream would keep the future from being `Send`
+        let requests: Vec<_> = chunks.iter().map(|chunk| self.translate_chunk(&system, chunk, budget)).collect();
+        let results: Vec<_> = stream::iter(requests).buffered(MAX_CONCURRENT_CHUNKS).collect().await;
+        let mut parts = Vec::with_capacity(chunks.len());
+        let mut failed = 0;
+        let mut error = None;
+        for (chunk, result) in chunks.iter().zip(results) {
+            match result {
+                Ok(translation) => parts.push(translation),
+                Err(e) => {
+                    log::warn!("Leaving part of a text untranslated: {}", e);
+                    parts.push(format!("{}\n\n{}", UNTRANSLATED, chunk));
+                    failed += 1;
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        match error {
+            Some(e) if failed == chunks.len() => Err(e),
+            _ => Ok(parts.join("\n\n")),
+        }
+    }
+
+    /// `article` with its title and content translated into `target_lang`,
+    /// like [`translate`](Self::translate) does.
+    pub async fn translate_article(&self, article: &Article, target_lang: &str) -> Result<Article, AiError> {
+        let body = translate::html_paragraphs(&article.html, self.chunk_budget()?);
+        let (title, html) =
+            futures_util::join!(self.translate(&article.title, target_lang), self.translate(&body, target_lang));
+        // The model's HTML is as untrusted as the page's
+        let processor = ContentProcessor::new();
+        let html = processor.sanitize(&html?);
+        let text = collapse_whitespace(&processor.extract_text(&html).unwrap_or_default());
+        Ok(Article {
+            title: title.unwrap_or_else(|_| article.title.clone()),
+            byline: article.byline.clone(),
+            html,
+            text,
         })
     }
 
+    /// Like [`process_page`](Self::process_page), but streams the answer:
+    /// `on_token` gets each piece of text as it arrives, and the whole
+    /// answer is returned at the end. In privacy mode, only the whole
+    /// answer has the placeholders replaced back. Cancelling `cancel` drops
+    /// the connection and fails with [`AiError::Cancelled`].
+    pub async fn process_page_stream(
+        &self,
+        url: &str,
+        article: &Article,
+        cancel: &CancellationToken,
+        mut on_token: impl FnMut(&str) + Send,
+    ) -> Result<String, AiError> {
+        let provider = self.provider()?;
+        let mut report = ScrubReport::default();
+        let prompt = self.scrub(&mut report, &page_prompt(url, article, provider.context_window()));
+        let request = chat_request(STREAM_SYSTEM_PROMPT, prompt);
+        let response = tokio::select! {
+            _ = cancel.cancelled() => return Err(AiError::Cancelled),
+            response = provider.chat_stream(&request, &mut on_token) => response?,
+        };
+        self.record(&request, &response);
+        Ok(report.restore(&response.content))
+    }
+
+    /// Makes `conversation` about `article`, the page at `url`, shortened
+    /// like in [`process_page`](Self::process_page).
+    pub fn set_page(&self, conversation: &mut Conversation, url: &str, article: &Article) -> Result<(), AiError> {
+        let prompt = page_prompt(url, article, self.provider()?.context_window());
+        let prompt = self.scrub(&mut conversation.redactions, &prompt);
+        conversation.set_page(prompt);
+        Ok(())
+    }
+
+    /// Sends `message` in `conversation` and adds it and the answer to the
+    /// history. The oldest turns are dropped when the history no longer
+    /// fits the model's context window. A failed request leaves the
+    /// history as it was. The history keeps the placeholders personal data
+    /// was replaced by.
+    pub async fn chat(&self, conversation: &mut Conversation, message: &str) -> Result<AIResponse, AiError> {
+        let provider = self.provider()?;
+        let window = provider.context_window();
+        let message = self.scrub(&mut conversation.redactions, message);
+        conversation.push(Role::User, message);
+        conversation.trim(window - window * ANSWER_SHARE / 100);
+        let request = ChatRequest { messages: conversation.messages(), max_tokens: None };
+        match provider.chat(&request).await {
+            Ok(response) => {
+                let mut answer = parse_answer(&response.content);
+                answer.usage = Some(self.record(&request, &response));
+                conversation.push(Role::Assistant, response.content);
+                Ok(restore(answer, &conversation.redactions))
+            }
+            Err(e) => {
+                conversation.pop();
+                Err(e)
+            }
+        }
+    }
+
+    /// Proposes an action doing what the user asked in `request` on
+    /// `page`. The model must answer with one of the typed actions,
+    /// referring only to what's on the page; otherwise it's told why and
+    /// asked again, a few times at most. The action is only proposed: the
+    /// caller has the user confirm it before doing anything.
+    pub async fn suggest_action(&self, request: &str, page: &ActionPage) -> Result<ProposedAction, AiError> {
+        let provider = self.provider()?;
+        let description = page.describe();
+        let (description, _) = truncate(&description, input_budget(provider.context_window()));
+        let mut report = ScrubReport::default();
+        let prompt = self.scrub(&mut report, &format!("{}\nRequest: {}", description, request.trim()));
+        let mut chat = chat_request(ACTION_PROMPT, prompt);
+        let mut rejected = String::new();
+        for _ in 0..MAX_ACTION_ATTEMPTS {
+            let response = provider.chat(&chat).await?;
+            self.record(&chat, &response);
+            let proposed = serde_json::from_str::<ProposedAction>(strip_code_fence(&response.content))
+                .map_err(|e| format!("The reply isn't a valid action: {}", e))
+                .and_then(|proposed| {
+                    let action = page.check(proposed.action.map_text(|text| report.restore(text)))?;
+                    let explanation = proposed.explanation.map(|explanation| report.restore(&explanation));
+                    Ok(ProposedAction { action, explanation })
+                });
+            match proposed {
+                Ok(proposed) => return Ok(proposed),
+                Err(reason) => {
+                    log::warn!("Rejected the action the AI proposed: {}", reason);
+                    rejected = self.scrub(&mut report, &reason);
+                    chat.messages.push(ChatMessage::new(Role::Assistant, response.content));
+                    let retry = format!("That was rejected: {}. Reply with one valid JSON object.", rejected);
+                    chat.messages.push(ChatMessage::new(Role::User, retry));
+                }
+            }
+        }
+        Err(AiError::InvalidResponse(rejected))
+    }
+
+    /// The embedding of `text`, for finding it by meaning. Only the start of
+    /// long texts is embedded.
+    pub async fn embed(&self, text: &str) -> Result<Embedding, AiError> {
+        let provider = self.provider()?;
+        let text = truncate(text.trim(), MAX_EMBED_TOKENS).0;
+        // Nothing is put back into embeddings, so the report isn't kept
+        let text = if self.privacy { privacy::scrub(text).0 } else { text.to_string() };
+        let embedding = Embedding::new(provider.embed(&text).await?);
+        let tokens = TokenUsage { prompt_tokens: estimate_tokens(&text) as u64, completion_tokens: 0 };
+        self.log(Usage::new(tokens, self.embedding_price));
+        Ok(embedding)
+    }
+
+    /// Whether the provider can [`embed`](Self::embed) texts.
+    pub fn supports_embeddings(&self) -> bool {
+        self.provider.as_ref().is_some_and(|provider| provider.supports_embeddings())
+    }
+
+    /// Sends the smallest request there is, a one-token answer, to check
+    /// that the API key, endpoint and model work. Failures are told apart:
+    /// a rejected key is [`AiError::Unauthorized`], an unreachable server
+    /// [`AiError::Network`], and an endpoint that isn't the provider's API
+    /// [`AiError::WrongEndpoint`].
+    pub async fn validate(&self) -> Result<ProviderInfo, AiError> {
+        let provider = self.provider()?;
+        let request = ChatRequest { messages: vec![ChatMessage::new(Role::User, "Say OK.")], max_tokens: Some(1) };
+        let started = Instant::now();
+        let result = provider.chat(&request).await;
+        let info = ProviderInfo {
+            provider: provider.kind().name().to_string(),
+            model: provider.model().to_string(),
+            latency: started.elapsed(),
+        };
+        let wrong_endpoint = AiError::WrongEndpoint(provider.kind().name());
+        match result {
+            Ok(response) => {
+                self.record(&request, &response);
+                Ok(info)
+            }
+            // A one-token answer may well be blank
+            Err(AiError::InvalidResponse(message)) if message == provider::NO_ANSWER => Ok(info),
+            // Not JSON, or not the JSON the provider sends
+            Err(AiError::InvalidResponse(_)) => Err(wrong_endpoint),
+            // Missing models are reported as not found too, by name
+            Err(AiError::Api { status: StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED, message })
+                if !message.contains(provider.model()) =>
+            {
+                Err(wrong_endpoint)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     pub fn is_configured(&self) -> bool {
-        self.api_key.is_some()
+        self.provider.is_some()
+    }
+
+    fn provider(&self) -> Result<&dyn Provider, AiError> {
+        self.provider.as_deref().ok_or(AiError::NotConfigured)
+    }
+
+    /// `text` as sent to the model: in privacy mode, with personal data
+    /// replaced by placeholders, which `report` notes.
+    fn scrub(&self, report: &mut ScrubReport, text: &str) -> String {
+        if self.privacy {
+            report.redact(text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Most tokens of text to translate per request: a quarter of the
+    /// context window, as the translation takes about as much again.
+    fn chunk_budget(&self) -> Result<usize, AiError> {
+        Ok((self.provider()?.context_window() / 4).min(MAX_CHUNK_TOKENS))
+    }
+
+    /// Translates one piece of text, trying again once if that fails.
+    async fn translate_chunk(&self, system: &ChatMessage, chunk: &str, budget: usize) -> Result<String, AiError> {
+        let mut report = ScrubReport::default();
+        let request = ChatRequest {
+            messages: vec![system.clone(), ChatMessage::new(Role::User, self.scrub(&mut report, chunk))],
+            // Translations can take more tokens than the original
+            max_tokens: Some((budget * 2) as u32),
+        };
+        let provider = self.provider()?;
+        let translation = async {
+            // The provider already retried transient errors
+            let response = match provider.chat(&request).await {
+                Ok(response) => response,
+                Err(e) if !e.is_transient() => {
+                    log::warn!("Translating again after: {}", e);
+                    provider.chat(&request).await?
+                }
+                Err(e) => return Err(e),
+            };
+            self.record(&request, &response);
+            Ok(response.content.trim().to_string())
+        };
+        let translation: String = self.cached("translation", &request, translation).await?;
+        Ok(report.restore(&translation))
+    }
+
+    /// Sends `prompt` with the system prompt and parses the answer.
+    async fn complete(&self, prompt: &str) -> Result<AIResponse, AiError> {
+        let mut report = ScrubReport::default();
+        let request = chat_request(SYSTEM_PROMPT, self.scrub(&mut report, prompt));
+        Ok(restore(self.answer(&request).await?, &report))
+    }
+
+    /// Sends `request` and parses the answer.
+    async fn answer(&self, request: &ChatRequest) -> Result<AIResponse, AiError> {
+        let response = self.provider()?.chat(request).await?;
+        let mut answer = parse_answer(&response.content);
+        answer.usage = Some(self.record(request, &response));
+        Ok(answer)
+    }
+
+    /// Adds up what `request` and its `response` took, estimated from their
+    /// text if the provider didn't say.
+    fn record(&self, request: &ChatRequest, response: &ChatResponse) -> Usage {
+        let tokens = response.usage.unwrap_or_else(|| Usage::estimate(request, &response.content));
+        let usage = Usage::new(tokens, self.price);
+        self.log(usage);
+        usage
+    }
+
+    fn log(&self, usage: Usage) {
+        if let Some(log) = &self.usage {
+            log.record(&usage);
+        }
+    }
+
+    /// The cached answer of `kind` to `request`, else the one `ask` gets,
+    /// which is then cached.
+    async fn cached<T: Serialize + DeserializeOwned>(
+        &self,
+        kind: &str,
+        request: &ChatRequest,
+        ask: impl Future<Output = Result<T, AiError>>,
+    ) -> Result<T, AiError> {
+        let (Some(cache), Some(provider)) = (&self.cache, &self.provider) else {
+            return ask.await;
+        };
+        let messages = serde_json::to_string(&request.messages).unwrap_or_default();
+        let key = ResponseCache::key(provider.kind(), provider.model(), kind, &messages);
+        if !self.regenerate {
+            if let Some(answer) = cache.get(&key) {
+                return Ok(answer);
+            }
+        }
+        let answer = ask.await?;
+        cache.put(&key, &answer);
+        Ok(answer)
+    }
+}
+
+/// Tokens of a `context_window` for what's asked about: half of it, as the
+/// rest is for the system prompt and the answer.
+fn input_budget(context_window: usize) -> usize {
+    (context_window / 2).min(MAX_PAGE_TOKENS)
+}
+
+/// The user message asking about a page, shortened to the
+/// [`input_budget`].
+fn page_prompt(url: &str, article: &Article, context_window: usize) -> String {
+    format!("Page: {}\n\n{}", url, build_context(article, input_budget(context_window)))
+}
+
+/// A summary of `article` picked from its own sentences, for when there's
+/// no model to ask.
+fn offline_summary(article: &Article) -> Result<AIResponse, AiError> {
+    let summary = offline::summarize(&article.text, OFFLINE_SENTENCES);
+    if summary.is_empty() {
+        return Err(AiError::NotConfigured);
+    }
+    Ok(AIResponse {
+        suggestion: summary,
+        explanation: Some("offline summary".to_string()),
+        truncated: false,
+        redacted: 0,
+        usage: None,
+    })
+}
+
+/// `response` with the placeholders `report` notes replaced back, and
+/// their count.
+fn restore(mut response: AIResponse, report: &ScrubReport) -> AIResponse {
+    response.suggestion = report.restore(&response.suggestion);
+    response.explanation = response.explanation.map(|explanation| report.restore(&explanation));
+    response.redacted = report.count();
+    response
+}
+
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
+
+fn as_millis<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+fn chat_request(system: &str, prompt: String) -> ChatRequest {
+    ChatRequest {
+        messages: vec![ChatMessage::new(Role::System, system), ChatMessage::new(Role::User, prompt)],
+        max_tokens: None,
+    }
+}
+
+/// The model's answer as a response. Models don't always stick to the
+/// requested JSON; anything else is taken as the suggestion itself.
+fn parse_answer(content: &str) -> AIResponse {
+    let content = content.trim();
+    serde_json::from_str(strip_code_fence(content)).unwrap_or_else(|_| AIResponse {
+        suggestion: content.to_string(),
+        explanation: None,
+        truncated: false,
+        redacted: 0,
+        usage: None,
+    })
+}
+
+/// `content` without the Markdown code fence models like to put around
+/// JSON.
+fn strip_code_fence(content: &str) -> &str {
+    let content = content.trim();
+    content
+        .strip_prefix("```json")
+        .or_else(|| content.strip_prefix("```"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .unwrap_or(content)
+        .trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::config::ProviderKind;
+    use mockito::Matcher;
+
+    fn assistant(server: &mockito::Server) -> AIAssistant {
+        let config = AiConfig {
+            provider: ProviderKind::OpenAi,
+            api_key: Some("test-key".into()),
+            endpoint: Some(format!("{}/v1/chat/completions", server.url())),
+            model: Some("gpt-4o-mini".into()),
+            privacy_mode: Some(false),
+            ..AiConfig::default()
+        };
+        AIAssistant::new(&config, reqwest::Client::new())
+    }
+
+    fn article() -> Article {
+        Article {
+            title: "Rust 1.80".into(),
+            byline: None,
+            html: String::new(),
+            text: "Rust 1.80 stabilizes LazyCell and LazyLock for lazily initialized values.".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_are_parsed_into_a_response() {
+        let mut server = mockito::Server::new_async().await;
+        let answer = r#"{"suggestion": "Try LazyLock.", "explanation": "It's stable now."}"#;
+        let mock = server
+            .mock("POST", "/v1/chat/completions")
+            .match_header("authorization", "Bearer test-key")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::PartialJson(serde_json::json!({ "model": "gpt-4o-mini", "stream": false })),
+                Matcher::Regex("https://blog.example/rust-1-80".into()),
+                Matcher::Regex("LazyCell".into()),
+            ]))
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({
+                    "choices": [{ "message": { "role": "assistant", "content": answer } }],
+                    "usage": { "prompt_tokens": 120, "completion_tokens": 12 },
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let response = assistant(&server)
+            .process_page("https://blog.example/rust-1-80", &article(), "What's new?")
+            .await
+            .unwrap();
+        mock.assert_async().await;
+        assert_eq!(response.suggestion, "Try LazyLock.");
+        assert_eq!(response.explanation.as_deref(), Some("It's stable now."));
+    }
+
+    #[tokio::test]
+    async fn requests_are_sent_with_the_given_client() {
+        let mut proxy = mockito::Server::new_async().await;
+        let mock = proxy
+            .mock("POST", "/v1/chat/completions")
+            .match_header("host", "api.example")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"choices": [{"message": {"role": "assistant", "content": "OK"}}]}"#)
+            .create_async()
+            .await;
+        let client = reqwest::Client::builder().proxy(reqwest::Proxy::all(proxy.url()).unwrap()).build().unwrap();
+        let config = AiConfig {
+            api_key: Some("test-key".into()),
+            endpoint: Some("http://api.example/v1/chat/completions".into()),
+            ..AiConfig::default()
+        };
+
+        let info = AIAssistant::new(&config, client).validate().await.unwrap();
+        mock.assert_async().await;
+        assert_eq!(info.provider, "OpenAI");
+    }
+
+    #[tokio::test]
+    async fn a_rejected_key_is_unauthorized() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/chat/completions")
+            .with_status(401)
+            .with_body(r#"{"error": {"message": "Incorrect API key provided"}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let error = assistant(&server).summarize_page("https://blog.example/", &article()).await.unwrap_err();
+        // Not worth retrying
+        mock.assert_async().await;
+        assert!(matches!(error, AiError::Unauthorized), "{:?}", error);
+        assert!(!error.is_transient());
+    }
+
+    #[tokio::test]
+    async fn malformed_json_is_an_invalid_response() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/chat/completions")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"choices": [{"message": "#)
+            .create_async()
+            .await;
+
+        let error = assistant(&server).summarize_page("https://blog.example/", &article()).await.unwrap_err();
+        assert!(matches!(error, AiError::InvalidResponse(_)), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn an_answer_that_isnt_json_is_the_suggestion() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/v1/chat/completions")
+            .with_body(r#"{"choices": [{"message": {"content": "It adds LazyLock."}}]}"#)
+            .create_async()
+            .await;
+
+        let response = assistant(&server).summarize_page("https://blog.example/", &article()).await.unwrap();
+        assert_eq!(response.suggestion, "It adds LazyLock.");
+        assert_eq!(response.explanation, None);
+    }
+
+    #[test]
+    fn code_fences_around_answers_are_stripped() {
+        let response = parse_answer("```json\n{\"suggestion\": \"Yes\", \"explanation\": null}\n```");
+        assert_eq!(response.suggestion, "Yes");
     }
 }
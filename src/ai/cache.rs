@@ -0,0 +1,123 @@
+//! Answers of the AI API cached on disk, so asking the same about the same
+//! content again costs no request.
+
+use super::config::ProviderKind;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Limit on the size of the cache.
+const MAX_BYTES: u64 = 20 * 1024 * 1024;
+
+/// A directory of answers, one file per question. When it grows past its
+/// limit, the answers used longest ago are dropped.
+pub struct ResponseCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    /// Held while evicting, so two evictions don't race.
+    evicting: Mutex<()>,
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir, max_bytes: MAX_BYTES, hits: AtomicU64::new(0), misses: AtomicU64::new(0), evicting: Mutex::new(()) }
+    }
+
+    /// Location of the cache in the data directory `data_dir`.
+    pub fn dir_in(data_dir: &Path) -> PathBuf {
+        data_dir.join("ai-cache")
+    }
+
+    /// The key of asking `model` of `provider` for a `kind` of answer about
+    /// `content`.
+    pub(super) fn key(provider: ProviderKind, model: &str, kind: &str, content: &str) -> String {
+        let mut hash = Sha256::new();
+        // Separated so that e.g. ("ab", "c") and ("a", "bc") differ
+        for part in [provider.name(), model, kind, content] {
+            hash.update((part.len() as u64).to_le_bytes());
+            hash.update(part.as_bytes());
+        }
+        hash.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// The answer stored under `key`, if any.
+    pub(super) fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let path = self.dir.join(key);
+        let value = fs::read(&path).ok().and_then(|bytes| serde_json::from_slice(&bytes).ok());
+        match value {
+            Some(value) => {
+                let hits = self.hits.fetch_add(1, Ordering::Relaxed) + 1;
+                log::info!("AI cache hit ({} hits, {} misses)", hits, self.misses.load(Ordering::Relaxed));
+                // The modification time says when it was last used
+                let touched = fs::File::options().append(true).open(&path).and_then(|f| f.set_modified(SystemTime::now()));
+                if let Err(e) = touched {
+                    log::warn!("Failed to touch {}: {}", path.display(), e);
+                }
+                Some(value)
+            }
+            None => {
+                let misses = self.misses.fetch_add(1, Ordering::Relaxed) + 1;
+                log::info!("AI cache miss ({} hits, {} misses)", self.hits.load(Ordering::Relaxed), misses);
+                None
+            }
+        }
+    }
+
+    /// Stores `value` under `key`, then drops the answers used longest ago
+    /// if the cache has grown too large.
+    pub(super) fn put<T: Serialize>(&self, key: &str, value: &T) {
+        if let Err(e) = self.write(key, value) {
+            log::warn!("Failed to cache an AI answer: {:#}", e);
+            return;
+        }
+        self.evict();
+    }
+
+    fn write<T: Serialize>(&self, key: &str, value: &T) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_vec(value)?;
+        // Write to a temp file first so readers never see half an answer
+        let path = self.dir.join(key);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn evict(&self) {
+        let Ok(_guard) = self.evicting.lock() else {
+            return;
+        };
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return;
+        };
+        let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let metadata = entry.metadata().ok().filter(|metadata| metadata.is_file())?;
+                Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+            })
+            .collect();
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        if total <= self.max_bytes {
+            return;
+        }
+        files.sort();
+        for (_, len, path) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => total -= len,
+                Err(e) => log::warn!("Failed to remove {}: {}", path.display(), e),
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Decoding fetched HTML in whatever encoding it declares, e.g. EUC-KR or
+//! Shift_JIS, the way browsers pick it.
+
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use std::borrow::Cow;
+
+/// How far into the document `<meta charset>` is looked for, as browsers do.
+const META_PRESCAN_BYTES: usize = 1024;
+
+/// `bytes` of an HTML page as text. The encoding comes from a byte order
+/// mark, else the `Content-Type` header, else a `<meta>` tag; pages that
+/// declare none are UTF-8 if they're valid UTF-8, else windows-1252.
+pub fn decode<'a>(bytes: &'a [u8], content_type: Option<&str>) -> Cow<'a, str> {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return encoding.decode_without_bom_handling(&bytes[bom_length..]).0;
+    }
+    let declared = content_type.and_then(header_charset).or_else(|| meta_charset(bytes));
+    let encoding = match declared {
+        Some(encoding) => encoding,
+        None if std::str::from_utf8(bytes).is_ok() => UTF_8,
+        None => WINDOWS_1252,
+    };
+    encoding.decode_without_bom_handling(bytes).0
+}
+
+/// The `charset` parameter of a `Content-Type` header.
+fn header_charset(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("charset") {
+            return None;
+        }
+        Encoding::for_label(value.trim().trim_matches(['"', '\'']).as_bytes())
+    })
+}
+
+/// The encoding declared by `<meta charset>` or `<meta http-equiv
+/// content="...; charset=...">` near the start of the page.
+fn meta_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+    let start = &bytes[..bytes.len().min(META_PRESCAN_BYTES)];
+    let head = String::from_utf8_lossy(start).to_ascii_lowercase();
+    let encoding = head.match_indices("<meta").find_map(|(at, _)| {
+        let tag = &head[at..];
+        let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+        let value = &tag[tag.find("charset")? + "charset".len()..];
+        let value = value.trim_start().strip_prefix('=')?.trim_start();
+        let value = value.trim_start_matches(['"', '\'']);
+        let end = value.find(|c: char| matches!(c, '"' | '\'' | ';' | '/' | '>') || c.is_whitespace());
+        Encoding::for_label(&value.as_bytes()[..end.unwrap_or(value.len())])
+    })?;
+    // A page can't really be UTF-16 if its meta tag was readable as ASCII
+    if encoding == encoding_rs::UTF_16LE || encoding == encoding_rs::UTF_16BE {
+        return Some(UTF_8);
+    }
+    if encoding == encoding_rs::X_USER_DEFINED {
+        return Some(WINDOWS_1252);
+    }
+    Some(encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EUC_KR: &[u8] = include_bytes!("testdata/euc-kr.html");
+    const SHIFT_JIS: &[u8] = include_bytes!("testdata/shift_jis.html");
+
+    #[test]
+    fn meta_tags_declare_the_encoding() {
+        assert!(decode(EUC_KR, None).contains("마을 회관에서 벼룩시장이 열립니다"));
+        assert!(decode(SHIFT_JIS, None).contains("公民館でフリーマーケットを開きます"));
+    }
+
+    #[test]
+    fn the_header_wins_over_the_meta_tag() {
+        let (bytes, _, _) = encoding_rs::EUC_KR.encode("<meta charset=\"shift_jis\"><p>한글</p>");
+        assert!(decode(&bytes, Some("text/html; charset=\"EUC-KR\"")).contains("한글"));
+        assert!(!decode(&bytes, None).contains("한글"));
+    }
+
+    #[test]
+    fn a_byte_order_mark_wins_over_everything() {
+        let bytes = [b"\xEF\xBB\xBF".as_slice(), "<p>한글</p>".as_bytes()].concat();
+        assert_eq!(decode(&bytes, Some("text/html; charset=euc-kr")), "<p>한글</p>");
+    }
+
+    #[test]
+    fn undeclared_pages_are_utf8_or_windows_1252() {
+        assert_eq!(decode("<p>한글</p>".as_bytes(), None), "<p>한글</p>");
+        assert_eq!(decode(b"<p>caf\xE9</p>", Some("text/html")), "<p>café</p>");
+    }
+
+    #[test]
+    fn meta_tags_claiming_utf16_mean_utf8() {
+        let html = "<meta charset=utf-16><p>한글</p>";
+        assert_eq!(decode(html.as_bytes(), None), html);
+    }
+}
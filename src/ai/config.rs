@@ -0,0 +1,147 @@
+//! The `[ai]` section of the config file.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_REQUESTS_PER_MINUTE: u32 = 30;
+/// Prices of models by name prefix, in US dollars per million tokens of
+/// prompt and of answer. More specific prefixes come first.
+const PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4.1-nano", 0.10, 0.40),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4-turbo", 10.00, 30.00),
+    ("gpt-4", 30.00, 60.00),
+    ("gpt-3.5", 0.50, 1.50),
+    ("o1", 15.00, 60.00),
+    ("o3-mini", 1.10, 4.40),
+    ("o3", 2.00, 8.00),
+    ("o4-mini", 1.10, 4.40),
+    ("text-embedding-3-small", 0.02, 0.0),
+    ("text-embedding-3-large", 0.13, 0.0),
+    ("claude-3-haiku", 0.25, 1.25),
+    ("claude-3-5-haiku", 0.80, 4.00),
+    ("claude-haiku-4", 1.00, 5.00),
+    ("claude-3-5-sonnet", 3.00, 15.00),
+    ("claude-3-7-sonnet", 3.00, 15.00),
+    ("claude-sonnet-4", 3.00, 15.00),
+    ("claude-3-opus", 15.00, 75.00),
+    ("claude-opus-4-5", 5.00, 25.00),
+    ("claude-opus-4", 15.00, 75.00),
+];
+
+/// Which API the assistant talks to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// OpenAI or any endpoint compatible with its chat completions API.
+    #[default]
+    OpenAi,
+    Anthropic,
+    /// A local Ollama server.
+    Ollama,
+}
+
+impl ProviderKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ProviderKind::OpenAi => "OpenAI",
+            ProviderKind::Anthropic => "Anthropic",
+            ProviderKind::Ollama => "Ollama",
+        }
+    }
+
+    /// Whether requests need an API key; a local Ollama doesn't.
+    pub fn needs_api_key(self) -> bool {
+        self != ProviderKind::Ollama
+    }
+}
+
+/// The `[ai]` section of the config file. `AI_PROVIDER`, `AI_API_KEY`,
+/// `AI_API_ENDPOINT` and `AI_MODEL` override it when set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiConfig {
+    pub provider: ProviderKind,
+    pub api_key: Option<String>,
+    /// The provider's usual endpoint if unset.
+    pub endpoint: Option<String>,
+    /// The provider's default model if unset.
+    pub model: Option<String>,
+    /// Longest wait for a whole answer, in seconds; 60 if unset.
+    pub timeout_secs: Option<u64>,
+    /// Most requests sent in a minute, so a runaway loop can't use up the
+    /// quota; 30 if unset, 0 for no limit.
+    pub requests_per_minute: Option<u32>,
+    /// Whether personal data like email addresses and card numbers is
+    /// replaced by placeholders before being sent; on if unset.
+    pub privacy_mode: Option<bool>,
+    /// Whether visited pages are embedded to search history by meaning.
+    /// Off unless turned on, as it sends the text of every page visited.
+    pub semantic_history: bool,
+    /// The provider's default embedding model if unset.
+    pub embedding_model: Option<String>,
+    /// Prices of models by name prefix, for estimating what requests
+    /// cost. Take precedence over the built-in prices.
+    pub prices: HashMap<String, ModelPrice>,
+}
+
+/// What a model charges, in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl AiConfig {
+    /// This config with the environment variables applied.
+    pub fn with_env_overrides(mut self) -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+        if let Some(provider) = var("AI_PROVIDER") {
+            match serde_json::from_value(serde_json::Value::String(provider.trim().to_ascii_lowercase())) {
+                Ok(provider) => self.provider = provider,
+                Err(_) => log::warn!("Ignoring unknown AI_PROVIDER {:?}", provider),
+            }
+        }
+        self.api_key = var("AI_API_KEY").or(self.api_key);
+        self.endpoint = var("AI_API_ENDPOINT").or(self.endpoint);
+        self.model = var("AI_MODEL").or(self.model);
+        self
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout_secs.filter(|&secs| secs > 0).map_or(DEFAULT_TIMEOUT, Duration::from_secs)
+    }
+
+    pub fn requests_per_minute(&self) -> u32 {
+        self.requests_per_minute.unwrap_or(DEFAULT_REQUESTS_PER_MINUTE)
+    }
+
+    pub fn privacy_mode(&self) -> bool {
+        self.privacy_mode.unwrap_or(true)
+    }
+
+    /// The price of `model`, from [`prices`](Self::prices) or else the
+    /// built-in ones. Models with no known price, like local ones, are free.
+    pub fn price(&self, model: &str) -> ModelPrice {
+        let model = model.to_ascii_lowercase();
+        // Names may have a vendor prefix, e.g. `openai/gpt-4o`
+        let name = model.rsplit('/').next().unwrap_or(&model);
+        let configured = self
+            .prices
+            .iter()
+            .filter(|(prefix, _)| name.starts_with(&prefix.to_ascii_lowercase()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, price)| *price);
+        configured
+            .or_else(|| {
+                let (_, input, output) = PRICES.iter().find(|(prefix, _, _)| name.starts_with(prefix))?;
+                Some(ModelPrice { input: *input, output: *output })
+            })
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,232 @@
+//! Fitting page content into a model's context window.
+
+use super::processor::{is_cjk, Article, ContentProcessor};
+
+/// Shown where text was left out.
+const ELLIPSIS: &str = "[…]";
+/// Share of the budget the outline may take, in percent.
+const OUTLINE_SHARE: usize = 15;
+/// Share of the body's budget for its start, in percent; its end gets the
+/// rest.
+const LEAD_SHARE: usize = 70;
+
+/// Roughly how many tokens `text` takes: about 4 characters per token,
+/// but a token per character of Chinese, Japanese or Korean.
+pub fn estimate_tokens(text: &str) -> usize {
+    cost(text).div_ceil(4)
+}
+
+/// The start of `text` taking at most `budget_tokens` by
+/// [`estimate_tokens`], and whether anything was cut.
+pub fn truncate(text: &str, budget_tokens: usize) -> (&str, bool) {
+    let budget = budget_tokens * 4;
+    let mut spent = 0;
+    for (i, c) in text.char_indices() {
+        spent += char_cost(c);
+        if spent > budget {
+            return (&text[..i], true);
+        }
+    }
+    (text, false)
+}
+
+/// `article` as text for a prompt, taking at most `budget_tokens` by
+/// [`estimate_tokens`]. The title, byline and outline come first, then the
+/// body; a body too long for the rest of the budget keeps its start and
+/// end, with the middle left out.
+pub fn build_context(article: &Article, budget_tokens: usize) -> String {
+    let mut out = Context { text: String::new(), used: 0, budget: budget_tokens * 4 };
+    let processor = ContentProcessor::new();
+
+    out.push_cut(&format!("# {}", article.title.trim()), out.budget / 10);
+    if let Some(byline) = &article.byline {
+        out.push_cut(byline.trim(), out.budget / 20);
+    }
+
+    let outline = processor.extract_outline(&article.html).unwrap_or_default();
+    if outline.len() > 1 {
+        let limit = out.used + out.budget * OUTLINE_SHARE / 100;
+        let top = outline.iter().map(|item| item.level).min().unwrap_or(1);
+        out.push_line("Outline:", limit);
+        for item in &outline {
+            let indent = "  ".repeat(usize::from(item.level - top));
+            if !out.push_line(&format!("{}- {}", indent, item.text), limit) {
+                break;
+            }
+        }
+        out.push_line("", limit);
+    }
+
+    let layout = processor.extract_text_with_layout(&article.html).unwrap_or_default();
+    let body = if layout.trim().is_empty() { article.text.as_str() } else { layout.as_str() };
+    let blocks: Vec<&str> = body.lines().map(str::trim_end).filter(|line| !line.trim().is_empty()).collect();
+    let total: usize = blocks.iter().map(|block| cost(block) + 1).sum();
+    if out.used + total <= out.budget {
+        for block in &blocks {
+            out.push_line(block, out.budget);
+        }
+        return out.text;
+    }
+
+    // Too long: the start, then the end, with a marker in between
+    let marker = cost(ELLIPSIS) + 1;
+    let available = out.budget.saturating_sub(out.used + marker);
+    let lead_limit = out.used + available * LEAD_SHARE / 100;
+    let mut lead_end = 0;
+    for block in &blocks {
+        if !out.push_line(block, lead_limit) {
+            out.push_cut(block, lead_limit - out.used);
+            break;
+        }
+        lead_end += 1;
+    }
+    let tail_budget = out.budget.saturating_sub(out.used + marker);
+    let mut tail: Vec<&str> = Vec::new();
+    let mut tail_used = 0;
+    for block in blocks[lead_end..].iter().skip(1).rev() {
+        let block_cost = cost(block) + 1;
+        if tail_used + block_cost > tail_budget {
+            break;
+        }
+        tail_used += block_cost;
+        tail.push(block);
+    }
+    out.push_line(ELLIPSIS, out.budget);
+    for block in tail.iter().rev() {
+        out.push_line(block, out.budget);
+    }
+    out.text
+}
+
+/// Text being built, with its cost in quarter tokens.
+struct Context {
+    text: String,
+    used: usize,
+    budget: usize,
+}
+
+impl Context {
+    /// Appends `line` if it fits within `limit`; returns whether it did.
+    fn push_line(&mut self, line: &str, limit: usize) -> bool {
+        let line_cost = cost(line) + 1;
+        if self.used + line_cost > limit.min(self.budget) {
+            return false;
+        }
+        self.text.push_str(line);
+        self.text.push('\n');
+        self.used += line_cost;
+        true
+    }
+
+    /// Appends as much of the start of `line` as `allowance` leaves room
+    /// for, marking a cut with an ellipsis.
+    fn push_cut(&mut self, line: &str, allowance: usize) {
+        let limit = (self.used + allowance).min(self.budget);
+        if self.push_line(line, limit) {
+            return;
+        }
+        // The line break and the ellipsis take a quarter token each
+        let room = limit.saturating_sub(self.used + 2);
+        let mut end = 0;
+        let mut spent = 0;
+        for (i, c) in line.char_indices() {
+            spent += char_cost(c);
+            if spent > room {
+                break;
+            }
+            end = i + c.len_utf8();
+        }
+        if end > 0 {
+            self.push_line(&format!("{}…", &line[..end]), limit);
+        }
+    }
+}
+
+/// Tokens of `text` in quarter tokens.
+fn cost(text: &str) -> usize {
+    text.chars().map(char_cost).sum()
+}
+
+fn char_cost(c: char) -> usize {
+    if is_cjk(c) {
+        4
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// About 500 KB of text in paragraphs, with headings.
+    fn long_article(paragraph: &str) -> Article {
+        let mut html = String::from("<h1>Field notes</h1>");
+        let mut text = String::new();
+        let mut i = 0;
+        while text.len() < 500 * 1024 {
+            if i % 20 == 0 {
+                html.push_str(&format!("<h2>Section {}</h2>", i / 20 + 1));
+            }
+            let paragraph = format!("Paragraph {}. {}", i, paragraph);
+            html.push_str(&format!("<p>{}</p>", paragraph));
+            text.push_str(&paragraph);
+            text.push('\n');
+            i += 1;
+        }
+        Article { title: "Field notes".into(), byline: Some("A. Writer".into()), html, text }
+    }
+
+    #[test]
+    fn tokens_are_estimated_per_script() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_tokens("안녕하세요"), 5);
+    }
+
+    #[test]
+    fn truncating_keeps_whole_characters() {
+        assert_eq!(truncate("short", 10), ("short", false));
+        assert_eq!(truncate("안녕하세요", 2), ("안녕", true));
+    }
+
+    #[test]
+    fn a_500kb_article_stays_under_budget() {
+        let article = long_article("The quick brown fox jumps over the lazy dog, again and again, all afternoon.");
+        for budget in [100, 2_000, 30_000] {
+            let context = build_context(&article, budget);
+            let tokens = estimate_tokens(&context);
+            assert!(tokens <= budget, "{} tokens over a budget of {}", tokens, budget);
+        }
+    }
+
+    #[test]
+    fn a_500kb_korean_article_stays_under_budget() {
+        let article = long_article("다람쥐 헌 쳇바퀴에 타고파. 오늘도 날씨가 맑아서 산책하기 좋았습니다.");
+        let context = build_context(&article, 4_000);
+        assert!(estimate_tokens(&context) <= 4_000);
+    }
+
+    #[test]
+    fn long_bodies_keep_the_title_outline_start_and_end() {
+        let article = long_article("Nothing much happened here.");
+        let context = build_context(&article, 4_000);
+        assert!(context.starts_with("# Field notes\nA. Writer\nOutline:\n"));
+        assert!(context.contains("- Section 1"));
+        assert!(context.contains("Paragraph 0. "));
+        assert!(context.contains(ELLIPSIS));
+        let last = article.text.lines().last().unwrap();
+        assert!(context.ends_with(&format!("{}\n", last)));
+    }
+
+    #[test]
+    fn short_articles_are_kept_whole() {
+        let article = Article {
+            title: "Short".into(),
+            byline: None,
+            html: "<p>One.</p><p>Two.</p>".into(),
+            text: "One. Two.".into(),
+        };
+        assert_eq!(build_context(&article, 1_000), "# Short\nOne.\nTwo.\n");
+    }
+}
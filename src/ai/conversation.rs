@@ -0,0 +1,100 @@
+//! Chat history of a conversation with the assistant.
+
+use super::assistant::SYSTEM_PROMPT;
+use super::context::estimate_tokens;
+use super::privacy::ScrubReport;
+use super::provider::{ChatMessage, Role};
+use std::collections::VecDeque;
+
+/// Most messages kept besides the system prompt and the page, however
+/// short they are.
+const MAX_TURNS: usize = 40;
+
+/// The messages of a chat with the assistant: the system prompt, the page
+/// being talked about, if any, and the user's and assistant's turns.
+#[derive(Debug, Clone)]
+pub struct Conversation {
+    system: ChatMessage,
+    page: Option<ChatMessage>,
+    turns: VecDeque<ChatMessage>,
+    /// Personal data replaced in the messages, to put back into answers.
+    pub(super) redactions: ScrubReport,
+    /// Kept when the tab navigates elsewhere.
+    pub pinned: bool,
+}
+
+impl Default for Conversation {
+    fn default() -> Self {
+        Self {
+            system: ChatMessage::new(Role::System, SYSTEM_PROMPT),
+            page: None,
+            turns: VecDeque::new(),
+            redactions: ScrubReport::default(),
+            pinned: false,
+        }
+    }
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the page and all turns; the system prompt and pin stay.
+    pub fn clear(&mut self) {
+        self.page = None;
+        self.turns.clear();
+        self.redactions = ScrubReport::default();
+    }
+
+    pub fn has_page(&self) -> bool {
+        self.page.is_some()
+    }
+
+    /// Sets the message describing the page the conversation is about.
+    pub(super) fn set_page(&mut self, context: String) {
+        self.page = Some(ChatMessage::new(Role::User, context));
+    }
+
+    pub(super) fn push(&mut self, role: Role, content: impl Into<String>) {
+        self.turns.push_back(ChatMessage::new(role, content));
+        while self.turns.len() > MAX_TURNS {
+            self.drop_oldest_turn();
+        }
+    }
+
+    /// Takes back the last message, e.g. a question that got no answer.
+    pub(super) fn pop(&mut self) -> Option<ChatMessage> {
+        self.turns.pop_back()
+    }
+
+    /// Drops the oldest turns until all messages fit in `budget_tokens`.
+    /// The system prompt, the page and the latest message are always kept.
+    pub(super) fn trim(&mut self, budget_tokens: usize) {
+        let mut used = self.messages().iter().map(|message| estimate_tokens(&message.content)).sum::<usize>();
+        while used > budget_tokens && self.turns.len() > 1 {
+            used -= self.drop_oldest_turn();
+        }
+    }
+
+    /// All messages, as sent to the model.
+    pub(super) fn messages(&self) -> Vec<ChatMessage> {
+        std::iter::once(&self.system).chain(&self.page).chain(&self.turns).cloned().collect()
+    }
+
+    /// Drops the oldest question with its answer, keeping the latest
+    /// message. Returns the tokens freed.
+    fn drop_oldest_turn(&mut self) -> usize {
+        let mut freed = 0;
+        if let Some(message) = self.turns.pop_front() {
+            freed += estimate_tokens(&message.content);
+        }
+        // A question without its answer, or the reverse, confuses models
+        while self.turns.len() > 1 && self.turns.front().is_some_and(|message| message.role != Role::User) {
+            if let Some(message) = self.turns.pop_front() {
+                freed += estimate_tokens(&message.content);
+            }
+        }
+        freed
+    }
+}
@@ -0,0 +1,58 @@
+//! Vectors standing for the meaning of texts, for finding pages by what
+//! they're about rather than by their words.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Most tokens of a text embedded. Embedding models take about 8k at most,
+/// and the start of a page says most of what it's about.
+pub(super) const MAX_EMBED_TOKENS: usize = 2_000;
+
+/// A text's embedding, scaled to length 1. Stored as base64 of its
+/// little-endian `f32`s, which is far smaller than a JSON array.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Embedding(Vec<f32>);
+
+impl Embedding {
+    /// `vector` scaled to length 1.
+    pub fn new(mut vector: Vec<f32>) -> Self {
+        let length = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if length > 0.0 {
+            for x in &mut vector {
+                *x /= length;
+            }
+        }
+        Self(vector)
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Cosine similarity with `other`, from -1 to 1; 0 if they come from
+    /// models with different dimensions.
+    pub fn similarity(&self, other: &Embedding) -> f32 {
+        if self.dimensions() != other.dimensions() {
+            return 0.0;
+        }
+        self.0.iter().zip(&other.0).map(|(a, b)| a * b).sum()
+    }
+}
+
+impl Serialize for Embedding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes: Vec<u8> = self.0.iter().flat_map(|x| x.to_le_bytes()).collect();
+        serializer.serialize_str(&base64::encode(bytes))
+    }
+}
+
+impl<'de> Deserialize<'de> for Embedding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        let bytes = base64::decode(encoded).map_err(serde::de::Error::custom)?;
+        if bytes.len() % 4 != 0 {
+            return Err(serde::de::Error::custom("embedding isn't a whole number of floats"));
+        }
+        let vector = bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+        Ok(Self(vector))
+    }
+}
@@ -0,0 +1,144 @@
+//! The structure of a page's forms: what they submit and which fields
+//! they ask for, with the labels a person would read.
+
+use super::page::selector;
+use super::processor::{collapse_whitespace, is_hidden, visible_text, NON_TEXT_TAGS};
+use once_cell::sync::Lazy;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+
+static FORMS: Lazy<Selector> = Lazy::new(|| selector("form"));
+static FIELDS: Lazy<Selector> = Lazy::new(|| selector("input, select, textarea"));
+static LABELS: Lazy<Selector> = Lazy::new(|| selector("label[for]"));
+static OPTIONS: Lazy<Selector> = Lazy::new(|| selector("option"));
+
+/// Inputs that are buttons rather than something to fill in.
+const BUTTON_TYPES: &[&str] = &["submit", "reset", "button", "image"];
+/// Left out of label text, so a wrapped control's own text doesn't end up
+/// in its label.
+const NON_LABEL_TAGS: &[&str] = &["script", "style", "noscript", "template", "svg", "select", "textarea", "option"];
+
+/// A form on a page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormInfo {
+    /// As written by the page.
+    pub action: Option<String>,
+    /// Uppercase, `GET` unless the form says otherwise.
+    pub method: String,
+    pub fields: Vec<FormField>,
+    /// The form isn't shown: it's hidden or inside a `<template>`.
+    pub hidden: bool,
+}
+
+/// A field of a [`FormInfo`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormField {
+    pub name: Option<String>,
+    /// The input's `type`, or `select` or `textarea`.
+    pub kind: String,
+    pub label: Option<String>,
+    pub required: bool,
+    /// Option texts of a `select`.
+    pub options: Vec<String>,
+    /// The field isn't shown: a hidden input, hidden itself or in a hidden
+    /// form.
+    pub hidden: bool,
+}
+
+impl FormInfo {
+    /// Whether the form looks like a sign-in form: it has a visible
+    /// password field.
+    pub fn is_login(&self) -> bool {
+        !self.hidden && self.fields.iter().any(|field| field.kind == "password" && !field.hidden)
+    }
+}
+
+/// The forms of `document`, in document order.
+pub fn extract(document: &Html) -> Vec<FormInfo> {
+    document
+        .select(&FORMS)
+        .map(|form| {
+            let hidden = is_concealed(form);
+            let value = form.value();
+            let fields = form
+                .select(&FIELDS)
+                .filter_map(|field| {
+                    let mut field = form_field(document, field)?;
+                    field.hidden |= hidden;
+                    Some(field)
+                })
+                .collect();
+            FormInfo {
+                action: value.attr("action").map(str::trim).filter(|action| !action.is_empty()).map(str::to_string),
+                method: value
+                    .attr("method")
+                    .map(str::trim)
+                    .filter(|method| !method.is_empty())
+                    .unwrap_or("get")
+                    .to_ascii_uppercase(),
+                fields,
+                hidden,
+            }
+        })
+        .collect()
+}
+
+/// `element` as a field, unless it's a button.
+fn form_field(document: &Html, element: ElementRef) -> Option<FormField> {
+    let value = element.value();
+    let kind = match value.name() {
+        "input" => value.attr("type").map(|kind| kind.trim().to_ascii_lowercase()).unwrap_or_else(|| "text".into()),
+        tag => tag.to_string(),
+    };
+    if BUTTON_TYPES.contains(&kind.as_str()) {
+        return None;
+    }
+    let options = if kind == "select" {
+        element
+            .select(&OPTIONS)
+            .map(|option| {
+                let text = collapse_whitespace(&option.text().collect::<String>());
+                if text.is_empty() {
+                    option.value().attr("value").unwrap_or("").to_string()
+                } else {
+                    text
+                }
+            })
+            .filter(|option| !option.is_empty())
+            .collect()
+    } else {
+        Vec::new()
+    };
+    Some(FormField {
+        name: value.attr("name").filter(|name| !name.is_empty()).map(str::to_string),
+        label: label(document, element),
+        required: value.attr("required").is_some(),
+        options,
+        hidden: kind == "hidden" || is_concealed(element),
+        kind,
+    })
+}
+
+/// The text of the field's `<label for>`, else of the label around it,
+/// else its `aria-label`.
+fn label(document: &Html, field: ElementRef) -> Option<String> {
+    let by_id = field
+        .value()
+        .attr("id")
+        .filter(|id| !id.is_empty())
+        .and_then(|id| document.select(&LABELS).find(|label| label.value().attr("for") == Some(id)));
+    let wrapping = || field.ancestors().filter_map(ElementRef::wrap).find(|element| element.value().name() == "label");
+    by_id
+        .or_else(wrapping)
+        .map(|label| collapse_whitespace(&visible_text(label, NON_LABEL_TAGS)))
+        .filter(|label| !label.is_empty())
+        .or_else(|| field.value().attr("aria-label").map(collapse_whitespace))
+        .filter(|label| !label.is_empty())
+}
+
+/// Whether `element` or one of its ancestors is hidden or isn't rendered.
+fn is_concealed(element: ElementRef) -> bool {
+    std::iter::once(element)
+        .chain(element.ancestors().filter_map(ElementRef::wrap))
+        .any(|element| NON_TEXT_TAGS.contains(&element.value().name()) || is_hidden(element))
+}
@@ -0,0 +1,132 @@
+//! Keyword and key phrase extraction, for indexing pages by their content.
+
+use std::collections::HashMap;
+
+/// Words too common to say anything about a page, in the languages the
+/// UI and most pages are in.
+const STOPWORDS: &[&str] = &[
+    // English
+    "a", "about", "after", "all", "also", "am", "an", "and", "any", "are", "as", "at", "be", "because", "been",
+    "before", "being", "but", "by", "can", "could", "did", "do", "does", "for", "from", "get", "had", "has",
+    "have", "he", "her", "here", "him", "his", "how", "i", "if", "in", "into", "is", "it", "its", "just", "more",
+    "most", "my", "new", "no", "not", "now", "of", "on", "one", "only", "or", "other", "our", "out", "over",
+    "she", "so", "some", "such", "than", "that", "the", "their", "them", "then", "there", "these", "they",
+    "this", "those", "to", "up", "us", "use", "used", "very", "was", "we", "were", "what", "when", "where",
+    "which", "while", "who", "will", "with", "would", "you", "your",
+    // Spanish
+    "al", "como", "con", "de", "del", "el", "en", "es", "esta", "este", "la", "las", "lo", "los", "más", "para",
+    "pero", "por", "que", "se", "sin", "sobre", "su", "sus", "un", "una", "y",
+    // German
+    "auch", "auf", "aus", "bei", "das", "dass", "dem", "den", "der", "die", "ein", "eine", "einen", "er", "es",
+    "für", "ich", "ist", "mit", "nicht", "noch", "sich", "sie", "sind", "und", "von", "wie", "wir", "zu", "zum",
+    // French
+    "au", "aux", "ce", "cette", "dans", "des", "du", "elle", "est", "et", "il", "ils", "le", "les", "mais", "ne",
+    "nous", "ou", "par", "pas", "plus", "pour", "qui", "sa", "ses", "son", "sur", "une", "vous",
+    // Korean
+    "그", "그리고", "그러나", "하지만", "또는", "및", "이", "저", "것", "수", "등", "더", "또", "때", "있다", "있는",
+    "없는", "하는", "한다", "했다", "합니다", "있습니다", "입니다", "위해", "대한", "통해", "이번", "우리",
+];
+
+/// Korean particles, stripped so `브라우저를` and `브라우저의` count as
+/// `브라우저`. Longest first.
+const PARTICLES: &[&str] = &[
+    "에서는", "으로는", "에게서", "에서", "에게", "으로", "까지", "부터", "처럼", "보다", "은", "는", "이", "가", "을",
+    "를", "의", "에", "로", "와", "과", "도", "만",
+];
+
+/// The `n` most characteristic terms of `text` with weights from 0 to 1,
+/// most characteristic first. Terms are single words, or two-word phrases
+/// that recur. Scoring is TF-IDF-like without a corpus: repeated words
+/// count with diminishing returns and longer (rarer) words count more.
+pub fn extract(text: &str, n: usize) -> Vec<(String, f32)> {
+    let words = tokenize(text);
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for word in words.iter().flatten() {
+        *counts.entry(word.as_str()).or_default() += 1;
+    }
+    let mut phrases: HashMap<String, usize> = HashMap::new();
+    for pair in words.windows(2) {
+        if let [Some(first), Some(second)] = pair {
+            *phrases.entry(format!("{} {}", first, second)).or_default() += 1;
+        }
+    }
+
+    let weight = |term: &str, count: usize| (1.0 + (count as f32).ln()) * (1.0 + term.chars().count() as f32).ln();
+    let mut scored: Vec<(String, f32)> = counts
+        .into_iter()
+        .map(|(word, count)| (word.to_string(), weight(word, count)))
+        .chain(
+            phrases
+                .into_iter()
+                .filter(|(_, count)| *count >= 2)
+                // A recurring phrase says more than its words alone
+                .map(|(phrase, count)| {
+                    let score = weight(&phrase, count) * 1.2;
+                    (phrase, score)
+                }),
+        )
+        .collect();
+    // Ties are broken by the term so results don't depend on hash order
+    scored.sort_by(|(a, a_score), (b, b_score)| b_score.total_cmp(a_score).then_with(|| a.cmp(b)));
+    scored.truncate(n);
+
+    if let Some(max) = scored.first().map(|(_, score)| *score).filter(|max| *max > 0.0) {
+        for (_, score) in &mut scored {
+            *score /= max;
+        }
+    }
+    scored
+}
+
+/// Lowercased words of `text` in order, with `None` for stopwords and
+/// punctuation so phrases don't span them. Chinese and Japanese, written
+/// without spaces, are split into overlapping pairs of Han characters.
+pub(super) fn tokenize(text: &str) -> Vec<Option<String>> {
+    let mut words = Vec::new();
+    for token in text.split(|c: char| !c.is_alphanumeric() && c != '\'' && c != '-') {
+        let token = token.trim_matches(|c| c == '\'' || c == '-').to_lowercase();
+        if token.is_empty() {
+            words.push(None);
+            continue;
+        }
+        if token.chars().any(is_ideographic) {
+            // Pairs overlap, so they don't make phrases; kana are mostly grammar
+            let chars: Vec<char> = token.chars().collect();
+            for pair in chars.windows(2).filter(|pair| pair.iter().all(|c| is_han(*c))) {
+                words.push(Some(pair.iter().collect()));
+                words.push(None);
+            }
+            words.push(None);
+            continue;
+        }
+        let word = strip_particle(&token);
+        let keep = !STOPWORDS.contains(&word)
+            && !word.chars().all(|c| c.is_ascii_digit())
+            && word.chars().count() >= if word.is_ascii() { 3 } else { 2 };
+        words.push(keep.then(|| word.to_string()));
+    }
+    words
+}
+
+fn strip_particle(word: &str) -> &str {
+    if !word.chars().last().is_some_and(is_hangul) {
+        return word;
+    }
+    PARTICLES
+        .iter()
+        .find_map(|particle| word.strip_suffix(particle).filter(|stem| stem.chars().count() >= 2))
+        .unwrap_or(word)
+}
+
+fn is_hangul(c: char) -> bool {
+    matches!(c, '\u{AC00}'..='\u{D7A3}')
+}
+
+/// Han characters and kana.
+fn is_ideographic(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30FF}') || is_han(c)
+}
+
+fn is_han(c: char) -> bool {
+    matches!(c, '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}')
+}
@@ -0,0 +1,93 @@
+//! Offline language detection. CJK languages are told apart by script,
+//! others by their most common letter trigrams.
+
+/// Texts with fewer letters are too short to tell.
+const MIN_LETTERS: usize = 20;
+
+/// Most common trigrams of each language, most common first. Word
+/// boundaries are spaces.
+const PROFILES: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            " th", "the", "he ", " an", "nd ", "and", "ed ", " of", "of ", " to", "ing", "ng ", "to ", " in", "er ",
+            "in ", "is ", "ion", " a ", "on ", "tio", "es ", " is", "at ", "ent", " co", "for", " fo", "or ", "re ",
+            " be", "hat", "tha", " wh", "ly ", "ter", "his", "as ", "it ", " it", "you", " yo", "ou ", "ati", " re",
+            "all", "wit", "ith", " wi", "are", "was",
+        ],
+    ),
+    (
+        "es",
+        &[
+            " de", "de ", "os ", " la", "la ", "el ", " el", "es ", " en", "en ", "que", " qu", "ue ", "as ", " co",
+            "ón ", "ión", "aci", "ent", "ado", " lo", "los", " se", "con", "nte", "ra ", " pa", "par", "est", " es",
+            "del", "da ", "do ", "una", " un", "las", " po", "por", "ien", "ica", "ara", "ció", " y ", "ada", "sta",
+            "ar ", "ene", "res", "ero", "ía ",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "en ", "er ", "ch ", "der", "ie ", "ich", "sch", "ein", " di", "die", " de", "und", " un", "nd ", "cht",
+            " ei", "den", "ine", "gen", "ung", "che", " da", "te ", "es ", "ist", " is", " ge", "st ", "ten", "auf",
+            " au", "mit", " mi", "sie", " si", "nic", "ht ", " zu", "zu ", "das", "ber", "ers", "ter", "ach", "nen",
+            "hen", " we", "ür ", " fü", "ßen",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "es ", " de", "de ", "le ", " le", "ent", "nt ", " la", "la ", "les", "re ", "on ", "ion", " et", "et ",
+            "que", " qu", "ue ", "tio", " pa", "des", " un", "une", "ne ", "par", "our", " po", "men", "ait", "est",
+            " es", " en", "en ", "ans", " da", "dan", "qui", " ce", "eme", "ur ", "ais", " à ", "és ", "té ", "ées",
+            "ell", " il", "il ", "pas", "sur",
+        ],
+    ),
+];
+
+/// ISO 639-1 code of the language `text` is mostly in, if it's one of
+/// English, Korean, Japanese, Chinese, Spanish, German or French.
+pub fn detect(text: &str) -> Option<&'static str> {
+    let (mut hangul, mut kana, mut han, mut letters) = (0, 0, 0, 0);
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        letters += 1;
+        match c {
+            '\u{AC00}'..='\u{D7A3}' | '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' => hangul += 1,
+            '\u{3040}'..='\u{30FF}' => kana += 1,
+            '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' => han += 1,
+            _ => {}
+        }
+    }
+    if letters < MIN_LETTERS {
+        return None;
+    }
+    // Pages in CJK languages often have some English in them
+    if hangul * 5 >= letters {
+        return Some("ko");
+    }
+    if kana * 10 >= letters && kana + han > 0 {
+        return Some("ja");
+    }
+    if han * 5 >= letters {
+        return Some("zh");
+    }
+
+    let normalized: String = text
+        .chars()
+        .map(|c| if c.is_alphabetic() { c.to_lowercase().next().unwrap_or(c) } else { ' ' })
+        .collect();
+    let mut scores = [0usize; PROFILES.len()];
+    for word in normalized.split_whitespace() {
+        let padded: Vec<char> = format!(" {} ", word).chars().collect();
+        for trigram in padded.windows(3) {
+            let trigram: String = trigram.iter().collect();
+            for (score, (_, profile)) in scores.iter_mut().zip(PROFILES) {
+                if let Some(rank) = profile.iter().position(|t| *t == trigram) {
+                    *score += profile.len() - rank;
+                }
+            }
+        }
+    }
+    let (best, score) = scores.iter().enumerate().max_by_key(|(_, score)| **score)?;
+    (*score > 0).then(|| PROFILES[best].0)
+}
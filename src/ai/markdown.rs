@@ -0,0 +1,341 @@
+//! Conversion of page HTML to GitHub-flavored Markdown, for saving pages
+//! and as compact page context for the AI.
+
+use super::processor::{is_hidden, NON_TEXT_TAGS};
+use scraper::{ElementRef, Node};
+use url::Url;
+
+/// Elements converted as blocks. Anything else is inline, so unknown
+/// elements keep their text.
+const BLOCK_TAGS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "body", "center", "details", "div", "dl", "dd", "dt",
+    "fieldset", "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr",
+    "html", "li", "main", "nav", "ol", "p", "pre", "section", "summary", "table", "ul",
+];
+
+/// Markdown for the content of `element`, with links and images resolved
+/// against `base`.
+pub fn convert(element: ElementRef, base: &Url) -> String {
+    let converter = Converter { base };
+    let mut markdown = converter.blocks(element).join("\n\n");
+    markdown.push('\n');
+    markdown
+}
+
+struct Converter<'a> {
+    base: &'a Url,
+}
+
+impl Converter<'_> {
+    /// The blocks of `element`'s content. Runs of inline content between
+    /// blocks become paragraphs.
+    fn blocks(&self, element: ElementRef) -> Vec<String> {
+        let mut blocks = Vec::new();
+        let mut inline = String::new();
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => inline.push_str(&escape_text(text)),
+                Node::Element(_) => {
+                    let Some(child) = ElementRef::wrap(child) else {
+                        continue;
+                    };
+                    if is_skipped(child) {
+                        continue;
+                    }
+                    if BLOCK_TAGS.contains(&child.value().name()) {
+                        push_paragraph(&mut blocks, &std::mem::take(&mut inline));
+                        blocks.extend(self.block(child));
+                    } else {
+                        inline.push_str(&self.inline(child));
+                    }
+                }
+                _ => {}
+            }
+        }
+        push_paragraph(&mut blocks, &inline);
+        blocks
+    }
+
+    fn block(&self, element: ElementRef) -> Vec<String> {
+        let tag = element.value().name();
+        let block = match tag {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let text = tidy_inline(&self.inline_children(element)).replace('\n', " ");
+                if text.is_empty() {
+                    return Vec::new();
+                }
+                format!("{} {}", "#".repeat(usize::from(tag.as_bytes()[1] - b'0')), text)
+            }
+            "p" | "dt" | "summary" | "figcaption" => tidy_inline(&self.inline_children(element)),
+            "ul" | "ol" => self.list(element, tag == "ol"),
+            "blockquote" => {
+                let content = self.blocks(element).join("\n\n");
+                content
+                    .lines()
+                    .map(|line| if line.is_empty() { ">".to_string() } else { format!("> {}", line) })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            "pre" => code_block(element),
+            "table" => self.table(element),
+            "hr" => "---".to_string(),
+            _ => return self.blocks(element),
+        };
+        if block.trim().is_empty() {
+            Vec::new()
+        } else {
+            vec![block]
+        }
+    }
+
+    /// A list with nested lists indented under their item.
+    fn list(&self, element: ElementRef, ordered: bool) -> String {
+        let mut number = element.value().attr("start").and_then(|start| start.parse::<i64>().ok()).unwrap_or(1);
+        let mut items = Vec::new();
+        for item in element.children().filter_map(ElementRef::wrap) {
+            if item.value().name() != "li" || is_skipped(item) {
+                continue;
+            }
+            let marker = if ordered { format!("{}. ", number) } else { "- ".to_string() };
+            number += 1;
+            let indent = " ".repeat(marker.len());
+            // Nested lists keep the list tight, other blocks make it loose
+            let mut content = String::new();
+            for block in self.blocks(item) {
+                if !content.is_empty() {
+                    content.push_str(if is_list(&block) { "\n" } else { "\n\n" });
+                }
+                content.push_str(&block);
+            }
+            let mut lines = content.lines();
+            let mut text = format!("{}{}", marker, lines.next().unwrap_or(""));
+            for line in lines {
+                text.push('\n');
+                if !line.is_empty() {
+                    text.push_str(&indent);
+                    text.push_str(line);
+                }
+            }
+            items.push(text.trim_end().to_string());
+        }
+        items.join("\n")
+    }
+
+    /// A table, with spanned cells repeated as empty ones. The first row is
+    /// the header, as Markdown tables need one.
+    fn table(&self, element: ElementRef) -> String {
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        // Rows still covered by a `rowspan` cell above, per column
+        let mut spans: Vec<usize> = Vec::new();
+        for row in table_rows(element) {
+            let mut cells = Vec::new();
+            let mut column = 0;
+            let push = |cells: &mut Vec<String>, column: &mut usize, text: String| {
+                cells.push(text);
+                *column += 1;
+            };
+            for cell in row.children().filter_map(ElementRef::wrap) {
+                if !matches!(cell.value().name(), "td" | "th") {
+                    continue;
+                }
+                while spans.get(column).is_some_and(|rows| *rows > 0) {
+                    push(&mut cells, &mut column, String::new());
+                }
+                let span = |name: &str| cell.value().attr(name).and_then(|v| v.parse::<usize>().ok()).unwrap_or(1);
+                let (colspan, rowspan) = (span("colspan").clamp(1, 100), span("rowspan").clamp(1, 1000));
+                let text = tidy_inline(&self.inline_children(cell)).replace('\n', " ").replace('|', "\\|");
+                for i in 0..colspan {
+                    if spans.len() <= column {
+                        spans.resize(column + 1, 0);
+                    }
+                    spans[column] = rowspan;
+                    push(&mut cells, &mut column, if i == 0 { text.clone() } else { String::new() });
+                }
+            }
+            if let Some(last) = spans.iter().rposition(|rows| *rows > 0) {
+                while column <= last {
+                    push(&mut cells, &mut column, String::new());
+                }
+            }
+            for rows in &mut spans {
+                *rows = rows.saturating_sub(1);
+            }
+            rows.push(cells);
+        }
+
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if width == 0 {
+            return String::new();
+        }
+        let line = |cells: &[String]| {
+            let mut cells = cells.to_vec();
+            cells.resize(width, String::new());
+            format!("| {} |", cells.join(" | "))
+        };
+        let mut lines = vec![line(&rows[0]), line(&vec!["---".to_string(); width])];
+        lines.extend(rows[1..].iter().map(|row| line(row)));
+        lines.join("\n")
+    }
+
+    fn inline(&self, element: ElementRef) -> String {
+        let value = element.value();
+        let content = || self.inline_children(element);
+        let wrap = |marker: &str| around(&content(), |text| format!("{}{}{}", marker, text, marker));
+        match value.name() {
+            "strong" | "b" => wrap("**"),
+            "em" | "i" | "cite" | "var" => wrap("*"),
+            "s" | "del" | "strike" => wrap("~~"),
+            "code" | "kbd" | "samp" | "tt" => code_span(&element.text().collect::<String>()),
+            "br" => "\\\n".to_string(),
+            "a" => {
+                let text = content();
+                match value.attr("href").and_then(|href| self.resolve(href, &["http", "https", "mailto"])) {
+                    Some(href) => around(&text, |text| format!("[{}]({})", text, escape_url(&href))),
+                    None => text,
+                }
+            }
+            "img" => {
+                let src = value
+                    .attr("src")
+                    .and_then(|src| self.resolve(src, &["http", "https", "data"]))
+                    .or_else(|| value.attr("data-src").and_then(|src| self.resolve(src, &["http", "https"])));
+                match src {
+                    Some(src) => {
+                        let alt = escape_text(value.attr("alt").unwrap_or(""));
+                        format!("![{}]({})", collapse(&alt), escape_url(&src))
+                    }
+                    None => String::new(),
+                }
+            }
+            _ => content(),
+        }
+    }
+
+    fn inline_children(&self, element: ElementRef) -> String {
+        let mut text = String::new();
+        for child in element.children() {
+            match child.value() {
+                Node::Text(t) => text.push_str(&escape_text(t)),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child).filter(|child| !is_skipped(*child)) {
+                        // Blocks inside inline content, e.g. a `<div>` in a
+                        // link, are just separated by spaces
+                        let block = BLOCK_TAGS.contains(&child.value().name());
+                        if block {
+                            text.push(' ');
+                        }
+                        text.push_str(&self.inline(child));
+                        if block {
+                            text.push(' ');
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        text
+    }
+
+    fn resolve(&self, url: &str, schemes: &[&str]) -> Option<String> {
+        let url = self.base.join(url.trim()).ok()?;
+        schemes.contains(&url.scheme()).then(|| url.to_string())
+    }
+}
+
+fn is_skipped(element: ElementRef) -> bool {
+    NON_TEXT_TAGS.contains(&element.value().name()) || is_hidden(element)
+}
+
+fn is_list(block: &str) -> bool {
+    let number = block.trim_start_matches(|c: char| c.is_ascii_digit());
+    block.starts_with("- ") || (number.len() < block.len() && number.starts_with(". "))
+}
+
+/// Rows of `table`, not including those of tables nested in it.
+fn table_rows(table: ElementRef) -> Vec<ElementRef> {
+    let mut rows = Vec::new();
+    for child in table.children().filter_map(ElementRef::wrap) {
+        match child.value().name() {
+            "tr" => rows.push(child),
+            "thead" | "tbody" | "tfoot" => {
+                rows.extend(child.children().filter_map(ElementRef::wrap).filter(|row| row.value().name() == "tr"))
+            }
+            _ => {}
+        }
+    }
+    rows
+}
+
+/// A fenced code block, in the language named by a `language-*` class.
+fn code_block(pre: ElementRef) -> String {
+    let code = pre.text().collect::<String>();
+    let code = code.trim_matches('\n');
+    let language = std::iter::once(pre)
+        .chain(pre.children().filter_map(ElementRef::wrap))
+        .filter_map(|element| element.value().attr("class"))
+        .flat_map(str::split_whitespace)
+        .find_map(|class| class.strip_prefix("language-").or_else(|| class.strip_prefix("lang-")))
+        .unwrap_or("");
+    let fence = "`".repeat(longest_run(code, '`').max(2) + 1);
+    format!("{}{}\n{}\n{}", fence, language, code, fence)
+}
+
+fn code_span(code: &str) -> String {
+    let code = code.replace('\n', " ");
+    if code.trim().is_empty() {
+        return code;
+    }
+    let fence = "`".repeat(longest_run(&code, '`') + 1);
+    let pad = if code.starts_with('`') || code.ends_with('`') { " " } else { "" };
+    format!("{}{}{}{}{}", fence, pad, code, pad, fence)
+}
+
+fn longest_run(text: &str, c: char) -> usize {
+    text.split(|other| other != c).map(str::len).max().unwrap_or(0)
+}
+
+/// Applies `format` to `content` without its surrounding whitespace, which
+/// stays outside: emphasis and link text can't start or end with a space.
+fn around(content: &str, format: impl FnOnce(&str) -> String) -> String {
+    let trimmed = content.trim();
+    if trimmed.is_empty() {
+        return content.to_string();
+    }
+    let lead = if content.starts_with(char::is_whitespace) { " " } else { "" };
+    let trail = if content.ends_with(char::is_whitespace) { " " } else { "" };
+    format!("{}{}{}", lead, format(trimmed), trail)
+}
+
+/// Adds inline content as a paragraph, unless it's only whitespace.
+fn push_paragraph(blocks: &mut Vec<String>, inline: &str) {
+    let paragraph = tidy_inline(inline);
+    if !paragraph.is_empty() {
+        blocks.push(paragraph);
+    }
+}
+
+/// Collapses whitespace in converted inline content, keeping the line
+/// breaks of `<br>`s.
+fn tidy_inline(inline: &str) -> String {
+    inline.split("\\\n").map(collapse).filter(|line| !line.is_empty()).collect::<Vec<_>>().join("\\\n")
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Escapes characters Markdown would read as formatting.
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn escape_url(url: &str) -> String {
+    url.replace(' ', "%20").replace('(', "%28").replace(')', "%29")
+}
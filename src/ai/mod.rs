@@ -1,5 +1,30 @@
+mod actions;
 mod assistant;
+mod cache;
+pub mod charset;
+mod config;
+mod context;
+mod conversation;
+mod embeddings;
+mod forms;
+mod keywords;
+mod language;
+mod markdown;
+pub mod offline;
+mod page;
+pub mod privacy;
 mod processor;
+mod provider;
+mod sanitize;
+mod translate;
+mod usage;
 
-pub use assistant::AIAssistant;
-pub use processor::ContentProcessor;
+pub use actions::{ActionPage, AiAction, ProposedAction};
+pub use assistant::{AIAssistant, AIResponse, AiError, ProviderInfo};
+pub use cache::ResponseCache;
+pub use config::{AiConfig, ProviderKind};
+pub use conversation::Conversation;
+pub use embeddings::Embedding;
+pub use processor::{Article, ContentProcessor, TextStats};
+pub use sanitize::ImageMode;
+pub use usage::{UsageLog, UsageTotals};
@@ -0,0 +1,132 @@
+//! Summaries made without a model, for when no AI API is configured.
+
+use super::keywords;
+use std::collections::HashMap;
+
+/// Sentences with fewer words worth scoring say too little to sum up a
+/// text, however common their words are.
+const MIN_WORDS: usize = 4;
+/// Ends of sentences.
+const TERMINATORS: &[char] = &['.', '!', '?', '。', '！', '？'];
+/// May follow a sentence's terminator, e.g. `"Yes."`.
+const CLOSERS: &[char] = &['"', '\'', ')', ']', '”', '’', '」', '』'];
+/// Words ending with a period that don't end a sentence, lowercased.
+const ABBREVIATIONS: &[&str] = &["mr", "mrs", "ms", "dr", "prof", "st", "vs", "etc", "e.g", "i.e", "inc", "no", "fig"];
+
+/// The `sentences` sentences of `text` that best sum it up, in their
+/// original order. Sentences are scored by how often the whole text uses
+/// their words, on average, so those about what the text keeps coming back
+/// to win.
+pub fn summarize(text: &str, sentences: usize) -> String {
+    let all = split_sentences(text);
+    let words: Vec<Vec<String>> = all.iter().map(|sentence| keywords::tokenize(sentence).into_iter().flatten().collect()).collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for word in words.iter().flatten() {
+        *counts.entry(word.as_str()).or_default() += 1;
+    }
+    let Some(max) = counts.values().copied().max() else {
+        return String::new();
+    };
+
+    let mut scored: Vec<(usize, f32)> = words
+        .iter()
+        .enumerate()
+        .filter(|(_, words)| words.len() >= MIN_WORDS)
+        .map(|(i, words)| {
+            let total: f32 = words.iter().map(|word| counts[word.as_str()] as f32 / max as f32).sum();
+            (i, total / words.len() as f32)
+        })
+        .collect();
+    // Ties go to the earlier sentence, which is more often the lead
+    scored.sort_by(|(a, a_score), (b, b_score)| b_score.total_cmp(a_score).then(a.cmp(b)));
+    scored.truncate(sentences);
+    scored.sort_by_key(|(i, _)| *i);
+    scored.iter().map(|(i, _)| all[*i]).collect::<Vec<_>>().join(" ")
+}
+
+/// The sentences of `text`, split at line breaks and at terminators followed
+/// by a space and not by a lowercase word. Korean sentences end with a
+/// period like English ones.
+fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    for line in text.lines() {
+        let mut start = 0;
+        let mut chars = line.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if !TERMINATORS.contains(&c) {
+                continue;
+            }
+            let mut end = i + c.len_utf8();
+            while let Some(&(j, next)) = chars.peek() {
+                if !TERMINATORS.contains(&next) && !CLOSERS.contains(&next) {
+                    break;
+                }
+                end = j + next.len_utf8();
+                chars.next();
+            }
+            // Full-width terminators need no space after them
+            let ends = c.len_utf8() > 1 || chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+            // `"Really?" she asked.` goes on after the question mark
+            let goes_on = line[end..].trim_start().starts_with(|c: char| c.is_lowercase());
+            if ends && !goes_on && !(c == '.' && is_abbreviation(&line[start..i])) {
+                sentences.push(line[start..end].trim());
+                start = end;
+            }
+        }
+        sentences.push(line[start..].trim());
+    }
+    sentences.retain(|sentence| !sentence.is_empty());
+    sentences
+}
+
+/// Whether `before`, the text up to a period, ends with an abbreviation or
+/// an initial rather than a sentence, e.g. `Dr` or `J` in `J. Smith`.
+fn is_abbreviation(before: &str) -> bool {
+    let word = before.rsplit(char::is_whitespace).next().unwrap_or(before);
+    let word = word.trim_start_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+    ABBREVIATIONS.contains(&word.as_str()) || (word.len() == 1 && word.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_central_sentences_of_an_article_are_picked() {
+        let text = include_str!("testdata/bees.txt");
+        let central: Vec<&str> = text.lines().filter(|line| line.contains("bees")).collect();
+        assert_eq!(summarize(text, 3), central.join(" "));
+        for aside in ["weather", "bakery", "council"] {
+            assert!(!summarize(text, 2).contains(aside));
+        }
+    }
+
+    #[test]
+    fn the_central_sentences_of_a_korean_article_are_picked() {
+        let summary = summarize(include_str!("testdata/library.txt"), 2);
+        assert!(summary.contains("도서관 회원증으로 이용할 수 있다."), "{}", summary);
+        assert!(!summary.contains("비빔밥"));
+        assert!(!summary.contains("비가 올"));
+    }
+
+    #[test]
+    fn sentences_are_split_at_their_ends() {
+        let text = "Dr. Kim arrived at 9 a.m. on Monday. \"Is it ready?\" she asked. It was!\n첫 문장입니다. 두 번째 문장입니다.";
+        assert_eq!(
+            split_sentences(text),
+            vec![
+                "Dr. Kim arrived at 9 a.m. on Monday.",
+                "\"Is it ready?\" she asked.",
+                "It was!",
+                "첫 문장입니다.",
+                "두 번째 문장입니다.",
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_text_has_no_summary() {
+        assert_eq!(summarize("", 3), "");
+        assert_eq!(summarize("   \n ", 3), "");
+    }
+}
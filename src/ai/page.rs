@@ -0,0 +1,236 @@
+//! A page parsed once, with what's extracted from it computed on first use.
+
+use super::processor::{collapse_whitespace, has_rel, visible_text, NON_TEXT_TAGS};
+use once_cell::sync::Lazy;
+use once_cell::unsync::OnceCell;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use url::Url;
+
+/// HTML beyond this many bytes is cut off before parsing, unless the
+/// processor is configured otherwise.
+pub const DEFAULT_MAX_HTML_BYTES: usize = 5 * 1024 * 1024;
+
+static BODY: Lazy<Selector> = Lazy::new(|| selector("body"));
+static TITLE: Lazy<Selector> = Lazy::new(|| selector("title"));
+static LINKS: Lazy<Selector> = Lazy::new(|| selector("a[href]"));
+static METAS: Lazy<Selector> = Lazy::new(|| selector("meta[content]"));
+static LINK_TAGS: Lazy<Selector> = Lazy::new(|| selector("link[rel][href]"));
+
+/// Compiles one of the processor's built-in selectors.
+pub(super) fn selector(css: &str) -> Selector {
+    Selector::parse(css).expect("built-in selector is valid")
+}
+
+/// What a page says about itself in `<head>`: Open Graph, Twitter card
+/// and standard meta tags. URLs are absolute.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PageMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub canonical_url: Option<Url>,
+    pub image: Option<Url>,
+    pub site_name: Option<String>,
+    pub author: Option<String>,
+    /// As written by the page, usually ISO 8601.
+    pub published_time: Option<String>,
+    pub favicon: Option<Url>,
+}
+
+/// A parsed page. Its text, links, title and metadata are extracted when
+/// first asked for and then kept.
+pub struct ParsedPage {
+    document: Html,
+    base: Option<Url>,
+    truncated: bool,
+    text: OnceCell<String>,
+    links: OnceCell<Vec<String>>,
+    title: OnceCell<Option<String>>,
+    metadata: OnceCell<PageMetadata>,
+}
+
+impl ParsedPage {
+    /// Parses `html`, cut off after `max_bytes` so a huge page can't hold
+    /// up the caller. Parsing never fails; a cut-off page is still a
+    /// valid document, with the open elements closed.
+    pub(super) fn parse(html: &str, max_bytes: usize) -> Self {
+        let truncated = html.len() > max_bytes;
+        let html = if truncated {
+            log::warn!("Parsing only the first {} of {} bytes of HTML", max_bytes, html.len());
+            truncate(html, max_bytes)
+        } else {
+            html
+        };
+        Self {
+            document: Html::parse_document(html),
+            base: None,
+            truncated,
+            text: OnceCell::new(),
+            links: OnceCell::new(),
+            title: OnceCell::new(),
+            metadata: OnceCell::new(),
+        }
+    }
+
+    /// Resolves relative URLs in [`metadata`](Self::metadata) against
+    /// `base`, usually the page's own URL. Without one, only absolute URLs
+    /// are kept.
+    pub fn with_base(mut self, base: &Url) -> Self {
+        self.base = Some(base.clone());
+        self.metadata = OnceCell::new();
+        self
+    }
+
+    pub fn document(&self) -> &Html {
+        &self.document
+    }
+
+    pub fn body(&self) -> Option<ElementRef<'_>> {
+        self.document.select(&BODY).next()
+    }
+
+    /// Whether the HTML was too long and only its start was parsed.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Visible text of the body, with whitespace collapsed.
+    pub fn text(&self) -> &str {
+        self.text.get_or_init(|| {
+            self.body()
+                .map(|body| collapse_whitespace(&visible_text(body, NON_TEXT_TAGS)))
+                .unwrap_or_default()
+        })
+    }
+
+    /// `href`s of the page's links exactly as written.
+    pub fn links(&self) -> &[String] {
+        self.links.get_or_init(|| {
+            self.document
+                .select(&LINKS)
+                .filter_map(|element| element.value().attr("href"))
+                .map(str::to_string)
+                .collect()
+        })
+    }
+
+    /// The `<title>`, with whitespace collapsed.
+    pub fn title(&self) -> Option<&str> {
+        self.title
+            .get_or_init(|| {
+                let title = self.document.select(&TITLE).next()?;
+                Some(collapse_whitespace(&title.text().collect::<String>())).filter(|title| !title.is_empty())
+            })
+            .as_deref()
+    }
+
+    /// The page's metadata, preferring Open Graph tags over Twitter cards
+    /// over plain `<meta name>` and `<title>`.
+    pub fn metadata(&self) -> &PageMetadata {
+        self.metadata.get_or_init(|| {
+            // Pages mix up `property` and `name`; the first tag of a kind
+            // wins, e.g. the main one of several `og:image`s
+            let mut metas = HashMap::new();
+            for element in self.document.select(&METAS) {
+                let value = element.value();
+                let Some(key) = value.attr("property").or_else(|| value.attr("name")) else {
+                    continue;
+                };
+                let content = collapse_whitespace(value.attr("content").unwrap_or(""));
+                if !content.is_empty() {
+                    metas.entry(key.trim().to_ascii_lowercase()).or_insert(content);
+                }
+            }
+            let meta = |keys: &[&str]| keys.iter().find_map(|key| metas.get(*key).cloned());
+            let url = |href: String| self.resolve(&href);
+
+            let canonical = self
+                .link_href("canonical")
+                .map(str::to_string)
+                .or_else(|| meta(&["og:url"]));
+
+            PageMetadata {
+                title: meta(&["og:title", "twitter:title"]).or_else(|| self.title().map(str::to_string)),
+                description: meta(&["og:description", "twitter:description", "description"]),
+                canonical_url: canonical.and_then(url),
+                image: meta(&["og:image", "og:image:url", "og:image:secure_url", "twitter:image", "twitter:image:src"])
+                    .and_then(url),
+                site_name: meta(&["og:site_name", "application-name"]),
+                author: meta(&["author", "article:author", "twitter:creator"]),
+                published_time: meta(&["article:published_time", "og:published_time", "date", "pubdate"]),
+                favicon: self.link_href("icon").and_then(|href| self.resolve(href)),
+            }
+        })
+    }
+
+    /// `href` of the first `<link>` whose `rel` includes `rel`.
+    fn link_href(&self, rel: &str) -> Option<&str> {
+        self.document
+            .select(&LINK_TAGS)
+            .find(|link| has_rel(*link, rel))
+            .and_then(|link| link.value().attr("href"))
+    }
+
+    /// `href` as an absolute URL, resolved against the base if there is one.
+    fn resolve(&self, href: &str) -> Option<Url> {
+        let href = href.trim();
+        let url = match &self.base {
+            Some(base) => base.join(href).ok()?,
+            None => Url::parse(href).ok()?,
+        };
+        (url.scheme() != "javascript").then_some(url)
+    }
+}
+
+/// The start of `html`, at most `max_bytes` long and not ending inside a
+/// tag.
+fn truncate(html: &str, max_bytes: usize) -> &str {
+    let mut end = max_bytes;
+    while !html.is_char_boundary(end) {
+        end -= 1;
+    }
+    let html = &html[..end];
+    match html.rfind('<') {
+        Some(tag) if !html[tag..].contains('>') => &html[..tag],
+        _ => html,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_pages_are_parsed_whole() {
+        let page = ParsedPage::parse("<p>Hello</p>", DEFAULT_MAX_HTML_BYTES);
+        assert!(!page.is_truncated());
+        assert_eq!(page.text(), "Hello");
+    }
+
+    #[test]
+    fn long_pages_are_cut_off_outside_a_tag() {
+        let html = "<p>one</p><p class=\"second\">two</p>";
+        let cut = html.find("class").unwrap();
+        assert_eq!(truncate(html, cut), "<p>one</p>");
+        let page = ParsedPage::parse(html, cut);
+        assert!(page.is_truncated());
+        assert_eq!(page.text(), "one");
+    }
+
+    #[test]
+    fn cutting_off_never_splits_a_character() {
+        // Each syllable is three bytes
+        assert_eq!(truncate("<p>안녕하세요</p>", 8), "<p>안");
+    }
+
+    #[test]
+    fn a_cut_off_page_is_still_a_document() {
+        let html = format!("<html><head><title>Long</title></head><body><div><p>{}", "word ".repeat(1000));
+        let page = ParsedPage::parse(&html, 200);
+        assert!(page.is_truncated());
+        assert_eq!(page.title(), Some("Long"));
+        assert!(page.text().starts_with("word word"));
+        assert!(page.body().is_some());
+    }
+}
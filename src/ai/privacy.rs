@@ -0,0 +1,188 @@
+//! Keeping personal data in pages from being sent to the AI API.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::fmt;
+
+/// A kind of personal data [`scrub`] replaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiiKind {
+    Email,
+    Phone,
+    Iban,
+    /// A payment card number.
+    Card,
+    /// A national ID number, e.g. a US SSN or Korean resident registration
+    /// number.
+    NationalId,
+}
+
+impl PiiKind {
+    /// The name in its placeholders, e.g. `EMAIL` in `[EMAIL_1]`.
+    fn label(self) -> &'static str {
+        match self {
+            PiiKind::Email => "EMAIL",
+            PiiKind::Phone => "PHONE",
+            PiiKind::Iban => "IBAN",
+            PiiKind::Card => "CARD",
+            PiiKind::NationalId => "ID",
+        }
+    }
+}
+
+/// What a kind of personal data looks like.
+struct Pattern {
+    kind: PiiKind,
+    regex: Regex,
+    /// Checks a match further, e.g. a card number's check digit.
+    valid: fn(&str) -> bool,
+}
+
+/// The patterns looked for. Earlier ones go first, so e.g. a resident
+/// registration number isn't taken for a card number, nor a card number
+/// for a phone number.
+static PATTERNS: Lazy<Vec<Pattern>> = Lazy::new(|| {
+    let pattern = |kind, regex: &str, valid| Pattern { kind, regex: Regex::new(regex).expect("valid regex"), valid };
+    vec![
+        pattern(PiiKind::Email, r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b", |_| true),
+        pattern(PiiKind::Iban, r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,3})?\b", is_iban),
+        pattern(PiiKind::NationalId, r"\b\d{3}-\d{2}-\d{4}\b", is_ssn),
+        pattern(PiiKind::NationalId, r"\b\d{6}-[1-8]\d{6}\b", |_| true),
+        pattern(PiiKind::Card, r"\b\d(?:[ -]?\d){12,18}\b", passes_luhn),
+        pattern(PiiKind::Phone, r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{2,4}\) ?|\d{2,4}[ .-])\d{3,4}[ .-]\d{4}\b", |_| true),
+    ]
+});
+
+/// What [`scrub`] replaced, to put back into the answers. Only ever kept in
+/// memory: it's neither serialized nor shown by `Debug`.
+#[derive(Clone, Default)]
+pub struct ScrubReport {
+    /// The kind, placeholder and original of each value replaced.
+    replaced: Vec<(PiiKind, String, String)>,
+}
+
+impl ScrubReport {
+    /// How many different values were replaced.
+    pub fn count(&self) -> usize {
+        self.replaced.len()
+    }
+
+    /// How many different values of `kind` were replaced.
+    pub fn count_of(&self, kind: PiiKind) -> usize {
+        self.replaced.iter().filter(|(k, _, _)| *k == kind).count()
+    }
+
+    /// `text` with the placeholders replaced by what they stand for.
+    pub fn restore(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (_, placeholder, original) in &self.replaced {
+            if text.contains(placeholder.as_str()) {
+                text = text.replace(placeholder.as_str(), original);
+            }
+        }
+        text
+    }
+
+    /// Like [`scrub`], but numbering on from the values replaced before,
+    /// and giving a value seen before the same placeholder.
+    pub(super) fn redact(&mut self, text: &str) -> String {
+        let mut text = text.to_string();
+        for pattern in PATTERNS.iter() {
+            let mut out = String::with_capacity(text.len());
+            let mut last = 0;
+            for found in pattern.regex.find_iter(&text) {
+                if is_glued(&text, found.start(), found.end()) || !(pattern.valid)(found.as_str()) {
+                    continue;
+                }
+                out.push_str(&text[last..found.start()]);
+                out.push_str(&self.placeholder(pattern.kind, found.as_str()));
+                last = found.end();
+            }
+            out.push_str(&text[last..]);
+            text = out;
+        }
+        text
+    }
+
+    fn placeholder(&mut self, kind: PiiKind, original: &str) -> String {
+        if let Some((_, placeholder, _)) = self.replaced.iter().find(|(k, _, o)| *k == kind && o == original) {
+            return placeholder.clone();
+        }
+        let placeholder = format!("[{}_{}]", kind.label(), self.count_of(kind) + 1);
+        self.replaced.push((kind, placeholder.clone(), original.to_string()));
+        placeholder
+    }
+}
+
+impl fmt::Debug for ScrubReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScrubReport").field("count", &self.count()).finish_non_exhaustive()
+    }
+}
+
+/// `text` with email addresses, phone numbers, IBANs, card numbers and
+/// national ID numbers replaced by placeholders like `[EMAIL_1]`, and a
+/// report of what was replaced.
+pub fn scrub(text: &str) -> (String, ScrubReport) {
+    let mut report = ScrubReport::default();
+    let text = report.redact(text);
+    (text, report)
+}
+
+/// Whether `text[start..end]` is part of a longer word or number, like an
+/// order ID or a number with more groups of digits.
+fn is_glued(text: &str, start: usize, end: usize) -> bool {
+    let mut before = text[..start].chars().rev();
+    let mut after = text[end..].chars();
+    let joins = |c: Option<char>, next: Option<char>| match c {
+        Some(c) if c.is_ascii_alphanumeric() => true,
+        Some('-' | '.') => next.is_some_and(|c| c.is_ascii_digit()),
+        _ => false,
+    };
+    joins(before.next(), before.next()) || joins(after.next(), after.next())
+}
+
+/// Whether `number`'s digits pass the Luhn check card numbers have.
+fn passes_luhn(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| match i % 2 {
+            0 => digit,
+            _ if digit > 4 => digit * 2 - 9,
+            _ => digit * 2,
+        })
+        .sum();
+    (13..=19).contains(&digits.len()) && sum.is_multiple_of(10)
+}
+
+/// Whether `iban` has a valid IBAN checksum.
+fn is_iban(iban: &str) -> bool {
+    let iban: String = iban.chars().filter(|c| !c.is_whitespace()).collect();
+    if !(15..=34).contains(&iban.len()) {
+        return false;
+    }
+    // The country and check digits move to the end, letters count as
+    // 10 to 35, and the whole must be 1 modulo 97
+    let mut remainder = 0;
+    for c in iban[4..].chars().chain(iban[..4].chars()) {
+        let Some(value) = c.to_digit(36) else {
+            return false;
+        };
+        let width = if value < 10 { 10 } else { 100 };
+        remainder = (remainder * width + value) % 97;
+    }
+    remainder == 1
+}
+
+/// Whether `ssn` could be a US Social Security number, which never starts
+/// with 000, 666 or 9, nor has a group of zeros.
+fn is_ssn(ssn: &str) -> bool {
+    let mut parts = ssn.split('-');
+    let (Some(area), Some(group), Some(serial)) = (parts.next(), parts.next(), parts.next()) else {
+        return false;
+    };
+    !matches!(area, "000" | "666") && !area.starts_with('9') && group != "00" && serial != "0000"
+}
@@ -1,47 +1,1276 @@
+use super::forms::{self, FormInfo};
+use super::sanitize::{self, ImageMode};
+use super::{charset, keywords, language, markdown};
 use anyhow::Result;
-use scraper::{Html, Selector};
+use super::page::{selector, PageMetadata, ParsedPage, DEFAULT_MAX_HTML_BYTES};
+use once_cell::sync::Lazy;
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use url::Url;
 
-pub struct ContentProcessor;
+/// Elements dropped from articles along with their content.
+const SKIPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "iframe", "object", "embed", "form", "button", "input",
+    "select", "textarea", "nav", "aside", "footer", "header", "svg", "canvas", "video", "audio", "dialog",
+];
+
+/// Elements kept in article HTML. Other elements are replaced by their
+/// content, as a `<div>` if they're blocks.
+const KEPT_TAGS: &[&str] = &[
+    "p", "h1", "h2", "h3", "h4", "h5", "h6", "ul", "ol", "li", "blockquote", "pre", "code", "em", "strong", "b",
+    "i", "u", "s", "sub", "sup", "mark", "small", "a", "img", "figure", "figcaption", "table", "caption",
+    "thead", "tbody", "tfoot", "tr", "th", "td", "br", "hr", "dl", "dt", "dd",
+];
+const BLOCK_TAGS: &[&str] = &["div", "section", "article", "main"];
+const VOID_TAGS: &[&str] = &["img", "br", "hr"];
+/// Elements whose content isn't text the reader sees.
+pub(super) const NON_TEXT_TAGS: &[&str] = &["script", "style", "noscript", "template", "svg"];
+/// Elements whose text runs on from the text around them.
+const INLINE_TAGS: &[&str] = &[
+    "a", "abbr", "b", "code", "em", "i", "mark", "s", "small", "span", "strong", "sub", "sup", "time", "u",
+];
+
+/// Smaller `data:` images are tracking pixels or spacers.
+const MIN_DATA_IMAGE_BYTES: usize = 100;
+/// Link texts, classes and ids of pagination links, for pages without
+/// `rel="next"`/`rel="prev"`. Texts are compared without arrows.
+const NEXT_TEXTS: &[&str] = &["다음", "다음 페이지", "다음페이지", "다음 글", "next", "next page"];
+const PREV_TEXTS: &[&str] = &["이전", "이전 페이지", "이전페이지", "이전 글", "previous", "prev", "previous page"];
+const NEXT_CLASSES: &[&str] = &["next", "next-page", "nextpage", "pagination-next", "page-next", "nav-next", "pager-next"];
+const PREV_CLASSES: &[&str] = &[
+    "prev", "previous", "prev-page", "prevpage", "pagination-prev", "pagination-previous", "page-prev", "nav-previous",
+    "pager-prev",
+];
+/// Average silent reading speed.
+const WORDS_PER_MINUTE: f32 = 230.0;
+
+static BASE: Lazy<Selector> = Lazy::new(|| selector("base[href]"));
+static LINKS_AND_AREAS: Lazy<Selector> = Lazy::new(|| selector("a[href], area[href]"));
+static IDS: Lazy<Selector> = Lazy::new(|| selector("[id]"));
+static HEADINGS: Lazy<Selector> = Lazy::new(|| selector("h1, h2, h3, h4, h5, h6"));
+static SCRIPTS: Lazy<Selector> = Lazy::new(|| selector("script[type]"));
+static IMAGES_WITH_SOURCE: Lazy<Selector> = Lazy::new(|| selector("img[src], img[srcset], img[data-src]"));
+static REL_LINKS: Lazy<Selector> = Lazy::new(|| selector("link[rel][href], a[rel][href]"));
+static ANCHORS_WITH_HREF: Lazy<Selector> = Lazy::new(|| selector("a[href]"));
+static ITEMS: Lazy<Selector> = Lazy::new(|| selector("[itemscope]"));
+static ANCHORS: Lazy<Selector> = Lazy::new(|| selector("a"));
+static IMAGES: Lazy<Selector> = Lazy::new(|| selector("img"));
+static BYLINES: Lazy<Selector> = Lazy::new(|| selector("[rel=author], [itemprop=author], .byline, .author"));
+
+/// Main content of a page, cleaned up for reading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Article {
+    pub title: String,
+    pub byline: Option<String>,
+    /// Content with only basic formatting tags; links and images are absolute.
+    pub html: String,
+    pub text: String,
+}
+
+/// A heading of the page, for a table of contents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutlineItem {
+    /// 1 for `<h1>` to 6 for `<h6>`.
+    pub level: u8,
+    pub text: String,
+    /// The heading's id, or a slug of its text if it has none. Generated
+    /// slugs aren't in the page, so headings with one are found by their
+    /// position among the page's headings.
+    pub id: Option<String>,
+}
+
+/// A link on a page, with what it says.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageLink {
+    pub url: Url,
+    /// The link's visible text, else its `aria-label`, `title` or `alt`, or
+    /// that of an image in it; empty if it has none.
+    pub text: String,
+}
+
+/// An image on a page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageImage {
+    pub url: Url,
+    pub alt: Option<String>,
+    /// From the `width`/`height` attributes, if set.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// Where a page says it belongs: its canonical address and, for paged
+/// content, the next and previous pages.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelLinks {
+    pub next: Option<Url>,
+    pub prev: Option<Url>,
+    pub canonical: Option<Url>,
+}
+
+/// Language and length of a text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextStats {
+    /// ISO 639-1 code, e.g. `ko`.
+    pub language: Option<String>,
+    /// Words, with CJK text counted as two characters a word.
+    pub word_count: usize,
+    pub reading_minutes: f32,
+}
+
+pub struct ContentProcessor {
+    max_html_bytes: usize,
+}
 
 impl ContentProcessor {
     pub fn new() -> Self {
-        Self
+        Self {
+            max_html_bytes: DEFAULT_MAX_HTML_BYTES,
+        }
+    }
+
+    /// Only parses the first `bytes` of HTML, so huge pages don't take
+    /// long; see [`DEFAULT_MAX_HTML_BYTES`].
+    pub fn with_max_html_bytes(mut self, bytes: usize) -> Self {
+        self.max_html_bytes = bytes;
+        self
+    }
+
+    /// Parses `html` once, for extracting several things from it.
+    pub fn parse(&self, html: &str) -> ParsedPage {
+        ParsedPage::parse(html, self.max_html_bytes)
+    }
+
+    /// Parses a fetched page, decoding it from the charset given by
+    /// `content_type` (the `Content-Type` header), a `<meta charset>` tag
+    /// or a byte order mark. Cut off like [`parse`](Self::parse).
+    // Takes `self` for its size cap
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_bytes(&self, bytes: &[u8], content_type: Option<&str>) -> Result<ParsedPage> {
+        Ok(self.parse(&charset::decode(bytes, content_type)))
     }
 
+    /// Visible text of the page's body, with whitespace collapsed.
     pub fn extract_text(&self, html: &str) -> Result<String> {
-        let document = Html::parse_document(html);
-        let selector = Selector::parse("body").unwrap();
-        
-        let body = document.select(&selector).next();
-        if let Some(body) = body {
-            Ok(body.text().collect::<Vec<_>>().join(" "))
-        } else {
-            Ok(String::new())
+        Ok(self.parse(html).text().to_string())
+    }
+
+    /// Like [`extract_text`](Self::extract_text), but with a line per
+    /// paragraph or other block, and list items as `- ` bullet lines.
+    pub fn extract_text_with_layout(&self, html: &str) -> Result<String> {
+        let page = self.parse(html);
+        let mut layout = Layout::default();
+        if let Some(body) = page.body() {
+            layout.walk(body, 0);
         }
+        Ok(layout.finish())
     }
 
-    pub fn extract_links(&self, html: &str) -> Result<Vec<String>> {
-        let document = Html::parse_document(html);
-        let selector = Selector::parse("a[href]").unwrap();
-        
-        let mut links = Vec::new();
-        for element in document.select(&selector) {
-            if let Some(href) = element.value().attr("href") {
-                links.push(href.to_string());
+    /// Links of the page resolved to absolute URLs, in document order and
+    /// without duplicates. Relative links are resolved against the page's
+    /// `<base href>` if it has one, otherwise against `base`. Script,
+    /// `mailto:` and same-page `#fragment` links are left out.
+    pub fn extract_links_absolute(&self, html: &str, base: &Url) -> Result<Vec<Url>> {
+        Ok(self.extract_labeled_links(html, base)?.into_iter().map(|link| link.url).collect())
+    }
+
+    /// Like [`extract_links_absolute`](Self::extract_links_absolute), with
+    /// the text of each link; a link found more than once has the text of
+    /// the first.
+    pub fn extract_labeled_links(&self, html: &str, base: &Url) -> Result<Vec<PageLink>> {
+        let page = self.parse(html);
+        let base = document_base(&page, base);
+
+        let mut links: Vec<PageLink> = Vec::new();
+        for element in page.document().select(&LINKS_AND_AREAS) {
+            let href = element.value().attr("href").unwrap_or("").trim();
+            if href.is_empty() || href.starts_with('#') {
+                continue;
+            }
+            let Ok(url) = base.join(href) else {
+                continue;
+            };
+            if matches!(url.scheme(), "javascript" | "mailto") || links.iter().any(|link| link.url == url) {
+                continue;
+            }
+            let mut text = collapse_whitespace(&visible_text(element, NON_TEXT_TAGS));
+            if text.is_empty() {
+                // Image links are labeled by their image's `alt`
+                let label = ["aria-label", "title", "alt"]
+                    .iter()
+                    .find_map(|name| element.value().attr(name))
+                    .or_else(|| element.descendants().filter_map(ElementRef::wrap).find_map(|e| e.value().attr("alt")));
+                text = collapse_whitespace(label.unwrap_or(""));
             }
+            links.push(PageLink { url, text });
         }
-        
+
         Ok(links)
     }
 
+    /// The page's images in document order, without duplicates. Sources
+    /// are resolved like links in
+    /// [`extract_links_absolute`](Self::extract_links_absolute); images
+    /// with only a `srcset` use its first candidate. Tracking pixels (1×1
+    /// or tiny data URLs) are left out.
+    pub fn extract_images(&self, html: &str, base: &Url) -> Result<Vec<PageImage>> {
+        let page = self.parse(html);
+        let base = document_base(&page, base);
+
+        let mut images: Vec<PageImage> = Vec::new();
+        for element in page.document().select(&IMAGES_WITH_SOURCE) {
+            let value = element.value();
+            let srcset = || value.attr("srcset")?.split_whitespace().next().map(|url| url.trim_end_matches(','));
+            let src = value
+                .attr("src")
+                .map(str::trim)
+                .filter(|src| !src.is_empty())
+                .or_else(srcset)
+                // Lazy-loading pages keep the real address in `data-src`
+                .or_else(|| value.attr("data-src"));
+            let Some(url) = src.and_then(|src| base.join(src.trim()).ok()) else {
+                continue;
+            };
+            if !matches!(url.scheme(), "http" | "https" | "data")
+                || (url.scheme() == "data" && url.as_str().len() < MIN_DATA_IMAGE_BYTES)
+            {
+                continue;
+            }
+            let dimension = |name: &str| value.attr(name).and_then(|v| v.trim().trim_end_matches("px").parse().ok());
+            let (width, height) = (dimension("width"), dimension("height"));
+            if width.is_some_and(|w: u32| w <= 1) && height.is_some_and(|h: u32| h <= 1) {
+                continue;
+            }
+            if images.iter().any(|image| image.url == url) {
+                continue;
+            }
+            images.push(PageImage {
+                url,
+                alt: value.attr("alt").map(collapse_whitespace).filter(|alt| !alt.is_empty()),
+                width,
+                height,
+            });
+        }
+
+        Ok(images)
+    }
+
+    /// The page's canonical URL and next/previous pages, from `<link>` and
+    /// `<a>` tags with `rel`. Without those, the canonical URL comes from
+    /// `og:url` and pagination from links reading "다음"/"Next" or with
+    /// classes like `pagination-next`.
+    pub fn extract_rel_links(&self, html: &str, base: &Url) -> Result<RelLinks> {
+        let page = self.parse(html).with_base(base);
+        let base = document_base(&page, base);
+        let resolve = |element: ElementRef| {
+            let url = base.join(element.value().attr("href")?.trim()).ok()?;
+            matches!(url.scheme(), "http" | "https").then_some(url)
+        };
+        let by_rel = |rels: &[&str]| {
+            page.document()
+                .select(&REL_LINKS)
+                .filter(|element| rels.iter().any(|rel| has_rel(*element, rel)))
+                .find_map(resolve)
+        };
+        let by_hint = |texts: &[&str], classes: &[&str]| {
+            page.document()
+                .select(&ANCHORS_WITH_HREF)
+                .filter(|anchor| is_pagination_link(*anchor, texts, classes))
+                .filter_map(resolve)
+                // A link to the page itself isn't another page
+                .find(|url| url.as_str().split('#').next() != base.as_str().split('#').next())
+        };
+
+        Ok(RelLinks {
+            next: by_rel(&["next"]).or_else(|| by_hint(NEXT_TEXTS, NEXT_CLASSES)),
+            prev: by_rel(&["prev", "previous"]).or_else(|| by_hint(PREV_TEXTS, PREV_CLASSES)),
+            canonical: page.metadata().canonical_url.clone().filter(|url| matches!(url.scheme(), "http" | "https")),
+        })
+    }
+
+    /// `href`s of the page's links exactly as written. Most callers want
+    /// [`extract_links_absolute`](Self::extract_links_absolute) instead.
+    #[allow(dead_code)] // Nothing in the browser wants hrefs unresolved
+    pub fn extract_links(&self, html: &str) -> Result<Vec<String>> {
+        Ok(self.parse(html).links().to_vec())
+    }
+
     pub fn extract_title(&self, html: &str) -> Result<Option<String>> {
-        let document = Html::parse_document(html);
-        let selector = Selector::parse("title").unwrap();
-        
-        if let Some(title) = document.select(&selector).next() {
-            Ok(Some(title.text().collect::<Vec<_>>().join(" ")))
+        Ok(self.parse(html).title().map(str::to_string))
+    }
+
+    /// The page's icon from `<link rel="icon">`, resolved against `base`.
+    pub fn extract_icon_url(&self, html: &str, base: &Url) -> Result<Option<Url>> {
+        Ok(self.parse(html).with_base(base).metadata().favicon.clone())
+    }
+
+    /// The page's metadata, preferring Open Graph tags over Twitter cards
+    /// over plain `<meta name>` and `<title>`. Relative URLs are resolved
+    /// against `base`.
+    pub fn extract_metadata(&self, html: &str, base: &Url) -> Result<PageMetadata> {
+        Ok(self.parse(html).with_base(base).metadata().clone())
+    }
+
+    /// The page's body as GitHub-flavored Markdown, with links and images
+    /// resolved against `base`.
+    pub fn to_markdown(&self, html: &str, base: &Url) -> String {
+        let page = self.parse(html);
+        let root = page.body().unwrap_or_else(|| page.document().root_element());
+        markdown::convert(root, base)
+    }
+
+    /// The page's visible headings in document order. Headings in `<nav>`
+    /// are left out.
+    pub fn extract_outline(&self, html: &str) -> Result<Vec<OutlineItem>> {
+        let page = self.parse(html);
+        // Generated slugs mustn't match an id of another element
+        let mut ids: HashSet<String> = page
+            .document()
+            .select(&IDS)
+            .filter_map(|element| element.value().attr("id"))
+            .map(str::to_string)
+            .collect();
+
+        let mut outline = Vec::new();
+        for heading in page.document().select(&HEADINGS) {
+            let excluded = std::iter::once(heading)
+                .chain(heading.ancestors().filter_map(ElementRef::wrap))
+                .any(|element| {
+                    let tag = element.value().name();
+                    tag == "nav" || NON_TEXT_TAGS.contains(&tag) || is_hidden(element)
+                });
+            let text = collapse_whitespace(&visible_text(heading, NON_TEXT_TAGS));
+            if excluded || text.is_empty() {
+                continue;
+            }
+            let id = match heading.value().attr("id").filter(|id| !id.is_empty()) {
+                Some(id) => id.to_string(),
+                None => unique_slug(&text, &mut ids),
+            };
+            outline.push(OutlineItem {
+                level: heading.value().name()[1..].parse().unwrap_or(1),
+                text,
+                id: Some(id),
+            });
+        }
+
+        Ok(outline)
+    }
+
+    /// Detects the language of `text` and estimates how long it takes
+    /// to read.
+    pub fn analyze(&self, text: &str) -> TextStats {
+        let mut word_count = 0;
+        let mut cjk_chars = 0;
+        for word in text.split_whitespace() {
+            let cjk = word.chars().filter(|c| is_cjk(*c)).count();
+            cjk_chars += cjk;
+            if word.chars().any(|c| c.is_alphanumeric() && !is_cjk(c)) {
+                word_count += 1;
+            }
+        }
+        word_count += cjk_chars.div_ceil(2);
+
+        TextStats {
+            language: language::detect(text).map(String::from),
+            word_count,
+            reading_minutes: word_count as f32 / WORDS_PER_MINUTE,
+        }
+    }
+
+    /// The `n` terms that best characterize `text`, with weights from 0 to
+    /// 1, most characteristic first. Deterministic for the same text.
+    pub fn extract_keywords(&self, text: &str, n: usize) -> Vec<(String, f32)> {
+        keywords::extract(text, n)
+    }
+
+    /// `html` made safe to show inside an internal page: only basic
+    /// formatting is kept, without scripts, event handlers or `javascript:`
+    /// links. Relative links and images are dropped.
+    pub fn sanitize(&self, html: &str) -> String {
+        sanitize::sanitize(html, &ImageMode::Keep)
+    }
+
+    /// Like [`sanitize`](Self::sanitize), with a choice of where images
+    /// load from.
+    pub fn sanitize_with(&self, html: &str, images: &ImageMode) -> String {
+        sanitize::sanitize(html, images)
+    }
+
+    /// The page's forms with their fields, for filling them in or telling
+    /// a sign-in page. Forms and fields that aren't shown are flagged as
+    /// hidden rather than left out.
+    pub fn extract_forms(&self, html: &str) -> Result<Vec<FormInfo>> {
+        Ok(forms::extract(self.parse(html).document()))
+    }
+
+    /// Machine-readable facts about the page: every JSON-LD object and
+    /// microdata item with an `@type`, e.g. a `Recipe` or `Product`.
+    /// JSON-LD arrays and `@graph`s are flattened; microdata items get
+    /// their `itemtype` as a schema.org-style `@type` name.
+    pub fn extract_structured_data(&self, html: &str) -> Result<Vec<serde_json::Value>> {
+        let page = self.parse(html);
+        let mut items = Vec::new();
+
+        for script in page.document().select(&SCRIPTS) {
+            let kind = script.value().attr("type").unwrap_or("");
+            if !kind.trim().eq_ignore_ascii_case("application/ld+json") {
+                continue;
+            }
+            let source = script.text().collect::<String>();
+            match parse_lenient_json(&source) {
+                Some(value) => flatten_json_ld(value, &mut items),
+                None => log::warn!("Skipping JSON-LD block that isn't valid JSON"),
+            }
+        }
+
+        // Items nested in another one are part of its properties
+        for element in page.document().select(&ITEMS) {
+            if element.value().attr("itemprop").is_none() {
+                items.push(microdata_item(element));
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// The page's main content, found the way Readability does it: blocks
+    /// of text are scored by length and punctuation, their ancestors collect
+    /// the scores, and class/id hints and link density adjust them. Scripts,
+    /// navigation, sidebars and other page furniture are left out. Links and
+    /// images are resolved against `base`.
+    pub fn extract_article(&self, html: &str, base: &Url) -> Result<Article> {
+        self.extract_article_from(&self.parse(html), base)
+    }
+
+    /// Like [`extract_article`](Self::extract_article), for a page that's
+    /// already parsed, e.g. by [`from_bytes`](Self::from_bytes).
+    pub fn extract_article_from(&self, page: &ParsedPage, base: &Url) -> Result<Article> {
+        let body = page.body().ok_or_else(|| anyhow::anyhow!("Page has no body"))?;
+        let metadata = page.metadata();
+        let title = metadata.title.clone().unwrap_or_default();
+
+        let mut candidates = Vec::new();
+        score_paragraphs(body, &mut candidates);
+        let top = candidates
+            .iter()
+            .map(|c| (c.element, c.score * (1.0 - link_density(c.element))))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        let writer = Writer { base, title: &title };
+        let mut article_html = String::new();
+        let mut text = String::new();
+        match top {
+            Some((top, top_score)) => {
+                // Content is often split over siblings, e.g. paragraphs around an ad
+                let threshold = (top_score * 0.2).max(10.0);
+                let siblings: Vec<ElementRef> = match top.parent().and_then(ElementRef::wrap) {
+                    Some(parent) if top != body => parent.children().filter_map(ElementRef::wrap).collect(),
+                    _ => vec![top],
+                };
+                for sibling in siblings {
+                    let include = sibling == top
+                        || candidates.iter().any(|c| c.element == sibling && c.score >= threshold)
+                        || (sibling.value().name() == "p"
+                            && clean_text(sibling).chars().count() > 80
+                            && link_density(sibling) < 0.25);
+                    if include {
+                        writer.write_element(sibling, &mut article_html);
+                    }
+                }
+                // Only what made it through the writer
+                text.push_str(&clean_text(Html::parse_fragment(&article_html).root_element()));
+            }
+            None => {
+                writer.write_children(body, &mut article_html);
+                text.push_str(&clean_text(body));
+            }
+        }
+        let text = collapse_whitespace(&text);
+        if text.is_empty() {
+            anyhow::bail!("No article content found");
+        }
+
+        Ok(Article {
+            title,
+            // `article:author` is often a profile URL
+            byline: metadata
+                .author
+                .clone()
+                .filter(|author| Url::parse(author).is_err())
+                .or_else(|| find_byline(body)),
+            html: article_html,
+            text,
+        })
+    }
+}
+
+/// The page's `<base href>` resolved against `base`, else `base`.
+fn document_base(page: &ParsedPage, base: &Url) -> Url {
+    page.document()
+        .select(&BASE)
+        .next()
+        .and_then(|element| base.join(element.value().attr("href")?.trim()).ok())
+        .unwrap_or_else(|| base.clone())
+}
+
+/// Whether `anchor` reads as one of `texts`, or it or its parent has one
+/// of `classes` as a class or id.
+fn is_pagination_link(anchor: ElementRef, texts: &[&str], classes: &[&str]) -> bool {
+    let value = anchor.value();
+    let label = value
+        .attr("aria-label")
+        .map(str::to_string)
+        .unwrap_or_else(|| visible_text(anchor, NON_TEXT_TAGS))
+        .to_lowercase();
+    let label = collapse_whitespace(label.trim_matches(|c: char| "‹›«»<>←→·".contains(c) || c.is_whitespace()));
+    if texts.contains(&label.as_str()) {
+        return true;
+    }
+    // Pagers often put the class on the list item around the link
+    std::iter::once(anchor).chain(anchor.parent().and_then(ElementRef::wrap)).any(|element| {
+        let value = element.value();
+        value
+            .attr("class")
+            .unwrap_or("")
+            .split_whitespace()
+            .chain(value.attr("id"))
+            .any(|name| classes.iter().any(|class| name.eq_ignore_ascii_case(class)))
+    })
+}
+
+/// Hangul, kana or a Han character.
+pub(super) fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{AC00}'..='\u{D7A3}' | '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' | '\u{3040}'..='\u{30FF}'
+            | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}'
+    )
+}
+
+/// Class/id words of page furniture, which is skipped...
+const UNLIKELY_HINTS: &[&str] = &[
+    "ad", "ads", "advert", "banner", "breadcrumb", "combx", "comment", "community", "cookie", "disqus", "extra",
+    "footer", "gdpr", "header", "legends", "menu", "modal", "nav", "newsletter", "pager", "pagination", "popup",
+    "promo", "related", "remark", "replies", "rss", "share", "shoutbox", "sidebar", "skyscraper", "social",
+    "sponsor", "subscribe", "supplemental", "widget",
+];
+/// ...unless it also has one of these.
+const MAYBE_HINTS: &[&str] = &["and", "article", "body", "column", "content", "main", "shadow"];
+const POSITIVE_HINTS: &[&str] = &[
+    "article", "blog", "body", "content", "entry", "hentry", "main", "page", "post", "story", "text",
+];
+const NEGATIVE_HINTS: &[&str] = &[
+    "ad", "ads", "advert", "banner", "comment", "contact", "cookie", "foot", "footer", "footnote", "hidden",
+    "masthead", "media", "menu", "meta", "nav", "newsletter", "outbrain", "promo", "related", "scroll", "share",
+    "shoutbox", "sidebar", "skyscraper", "social", "sponsor", "shopping", "subscribe", "tags", "tool", "widget",
+];
+/// A `<div>` with any of these inside isn't scored as one paragraph.
+const BLOCK_CHILD_TAGS: &[&str] = &[
+    "article", "blockquote", "div", "dl", "figure", "ol", "p", "pre", "section", "table", "ul",
+];
+/// Shorter paragraphs (in characters) aren't scored.
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+/// An element containing scored paragraphs.
+struct Candidate<'a> {
+    element: ElementRef<'a>,
+    score: f64,
+}
+
+/// Parses JSON as pages write it: wrapped in HTML comments or CDATA, with
+/// trailing commas or raw newlines in strings.
+fn parse_lenient_json(source: &str) -> Option<serde_json::Value> {
+    let source = source.trim();
+    if let Ok(value) = serde_json::from_str(source) {
+        return Some(value);
+    }
+    let source = source
+        .trim_start_matches("<!--")
+        .trim_end_matches("-->")
+        .trim()
+        .trim_start_matches("//<![CDATA[")
+        .trim_start_matches("<![CDATA[")
+        .trim_end_matches("//]]>")
+        .trim_end_matches("]]>");
+
+    let mut cleaned = String::with_capacity(source.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                '\n' | '\r' | '\t' => {
+                    cleaned.push(' ');
+                    continue;
+                }
+                _ => {}
+            }
+            cleaned.push(c);
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            ',' => {
+                let rest = chars.clone().find(|c| !c.is_whitespace());
+                if matches!(rest, Some('}' | ']')) {
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        cleaned.push(c);
+    }
+    serde_json::from_str(&cleaned).ok()
+}
+
+/// Adds the typed objects of a JSON-LD block to `items`.
+fn flatten_json_ld(value: serde_json::Value, items: &mut Vec<serde_json::Value>) {
+    match value {
+        serde_json::Value::Array(values) => {
+            for value in values {
+                flatten_json_ld(value, items);
+            }
+        }
+        serde_json::Value::Object(mut object) => {
+            if let Some(graph) = object.remove("@graph") {
+                flatten_json_ld(graph, items);
+            }
+            if object.contains_key("@type") {
+                items.push(serde_json::Value::Object(object));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A microdata `itemscope` element as a JSON-LD-like object.
+fn microdata_item(element: ElementRef) -> serde_json::Value {
+    let mut object = serde_json::Map::new();
+    if let Some(types) = element.value().attr("itemtype") {
+        // `https://schema.org/Recipe` -> `Recipe`
+        let mut types: Vec<serde_json::Value> = types
+            .split_whitespace()
+            .map(|t| t.trim_end_matches('/').rsplit(['/', '#']).next().unwrap_or(t).into())
+            .collect();
+        let kind = if types.len() == 1 { types.remove(0) } else { types.into() };
+        object.insert("@type".into(), kind);
+    }
+    if let Some(id) = element.value().attr("itemid") {
+        object.insert("@id".into(), id.into());
+    }
+    add_microdata_properties(element, &mut object);
+    serde_json::Value::Object(object)
+}
+
+/// Adds the `itemprop`s under `element` to `object`. Repeated properties
+/// become arrays.
+fn add_microdata_properties(element: ElementRef, object: &mut serde_json::Map<String, serde_json::Value>) {
+    for child in element.children().filter_map(ElementRef::wrap) {
+        let value = child.value();
+        let scope = value.attr("itemscope").is_some();
+        if let Some(names) = value.attr("itemprop") {
+            let property = if scope {
+                microdata_item(child)
+            } else {
+                microdata_value(child).into()
+            };
+            for name in names.split_whitespace() {
+                match object.get_mut(name) {
+                    Some(serde_json::Value::Array(values)) => values.push(property.clone()),
+                    Some(existing) => *existing = vec![existing.take(), property.clone()].into(),
+                    None => {
+                        object.insert(name.to_string(), property.clone());
+                    }
+                }
+            }
+        }
+        // A nested item's properties are its own
+        if !scope {
+            add_microdata_properties(child, object);
+        }
+    }
+}
+
+/// Value of a non-item `itemprop` element, which depends on its tag.
+fn microdata_value(element: ElementRef) -> String {
+    let value = element.value();
+    let attr = match value.name() {
+        "meta" => "content",
+        "a" | "area" | "link" => "href",
+        "img" | "audio" | "video" | "source" | "iframe" | "embed" | "track" => "src",
+        "object" => "data",
+        "time" => "datetime",
+        "data" | "meter" => "value",
+        _ => "",
+    };
+    match value.attr(attr).or_else(|| value.attr("content")) {
+        Some(content) => content.trim().to_string(),
+        None => collapse_whitespace(&element.text().collect::<String>()),
+    }
+}
+
+/// A slug of `text` like GitHub makes for headings, e.g. `Getting started!`
+/// -> `getting-started`, made unique among `taken`, to which it's added.
+fn unique_slug(text: &str, taken: &mut HashSet<String>) -> String {
+    let slug: String = text
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            '-' | '_' => Some(c),
+            _ if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect();
+    let slug = if slug.is_empty() { "section".to_string() } else { slug };
+    let mut unique = slug.clone();
+    let mut n = 1;
+    while taken.contains(&unique) {
+        unique = format!("{}-{}", slug, n);
+        n += 1;
+    }
+    taken.insert(unique.clone());
+    unique
+}
+
+/// Whether `rel` of `element` includes `token`.
+pub(super) fn has_rel(element: ElementRef, token: &str) -> bool {
+    let rel = element.value().attr("rel").unwrap_or("");
+    rel.split_whitespace().any(|t| t.eq_ignore_ascii_case(token))
+}
+
+/// Lowercased words of `element`'s class and id.
+fn hint_words(element: ElementRef) -> Vec<String> {
+    let value = element.value();
+    let names = format!("{} {}", value.attr("class").unwrap_or(""), value.attr("id").unwrap_or(""));
+    names
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect()
+}
+
+/// Whether any of `words` matches a hint. Longer hints also match as a
+/// prefix, so `sidebar` matches `sidebarleft`.
+fn has_hint(words: &[String], hints: &[&str]) -> bool {
+    words
+        .iter()
+        .any(|word| hints.iter().any(|hint| word == hint || (hint.len() >= 4 && word.starts_with(hint))))
+}
+
+fn is_unlikely(element: ElementRef) -> bool {
+    if matches!(element.value().name(), "body" | "article" | "main") {
+        return false;
+    }
+    let words = hint_words(element);
+    has_hint(&words, UNLIKELY_HINTS) && !has_hint(&words, MAYBE_HINTS)
+}
+
+fn class_weight(element: ElementRef) -> f64 {
+    let words = hint_words(element);
+    let mut weight = 0.0;
+    if has_hint(&words, POSITIVE_HINTS) {
+        weight += 25.0;
+    }
+    if has_hint(&words, NEGATIVE_HINTS) {
+        weight -= 25.0;
+    }
+    weight
+}
+
+fn tag_weight(element: ElementRef) -> f64 {
+    match element.value().name() {
+        "article" => 10.0,
+        "div" | "main" | "section" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    }
+}
+
+/// Whether `element` is a block of text worth scoring.
+fn is_paragraph(element: ElementRef) -> bool {
+    match element.value().name() {
+        "p" | "pre" | "td" | "blockquote" => true,
+        // Many sites put text straight into divs
+        "div" => !element
+            .children()
+            .filter_map(ElementRef::wrap)
+            .any(|child| BLOCK_CHILD_TAGS.contains(&child.value().name())),
+        _ => false,
+    }
+}
+
+/// Scores the paragraphs under `element` and adds the scores to their
+/// ancestors, which become the candidates.
+fn score_paragraphs<'a>(element: ElementRef<'a>, candidates: &mut Vec<Candidate<'a>>) {
+    for child in element.children().filter_map(ElementRef::wrap) {
+        let tag = child.value().name();
+        if SKIPPED_TAGS.contains(&tag) || is_hidden(child) || is_unlikely(child) {
+            continue;
+        }
+        if !is_paragraph(child) {
+            score_paragraphs(child, candidates);
+            continue;
+        }
+        let text = collapse_whitespace(&clean_text(child));
+        let len = text.chars().count();
+        if len < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+        let commas = text.chars().filter(|c| matches!(c, ',' | '，' | '、')).count();
+        let score = 1.0 + commas as f64 + (len as f64 / 100.0).min(3.0);
+        let ancestors = child.ancestors().filter_map(ElementRef::wrap).take(3);
+        for (ancestor, divider) in ancestors.zip([1.0, 2.0, 6.0]) {
+            match candidates.iter_mut().find(|c| c.element == ancestor) {
+                Some(candidate) => candidate.score += score / divider,
+                None => candidates.push(Candidate {
+                    element: ancestor,
+                    score: tag_weight(ancestor) + class_weight(ancestor) + score / divider,
+                }),
+            }
+        }
+    }
+}
+
+/// Share of `element`'s text that's inside links.
+fn link_density(element: ElementRef) -> f64 {
+    let chars = |element: ElementRef| clean_text(element).chars().filter(|c| !c.is_whitespace()).count();
+    let total = chars(element);
+    if total == 0 {
+        return 0.0;
+    }
+    let links: usize = element.select(&ANCHORS).map(chars).sum();
+    links as f64 / total as f64
+}
+
+/// A short line from an element marked as the author or byline.
+fn find_byline(body: ElementRef) -> Option<String> {
+    body.select(&BYLINES)
+        .map(|element| collapse_whitespace(&clean_text(element)))
+        .find(|text| !text.is_empty() && text.chars().count() < 100)
+}
+
+pub(super) fn is_hidden(element: ElementRef) -> bool {
+    let value = element.value();
+    let style = value.attr("style").unwrap_or("").to_ascii_lowercase().replace(' ', "");
+    value.attr("hidden").is_some()
+        || value.attr("aria-hidden") == Some("true")
+        || style.contains("display:none")
+        || style.contains("visibility:hidden")
+}
+
+/// Writes article HTML, keeping only [`KEPT_TAGS`] and their safe
+/// attributes, and leaving out furniture inside the content.
+struct Writer<'a> {
+    base: &'a Url,
+    /// Headings repeating the title are dropped, the reader shows it anyway.
+    title: &'a str,
+}
+
+impl Writer<'_> {
+    fn write_children(&self, element: ElementRef, out: &mut String) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => out.push_str(&escape_html(text)),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        self.write_element(child, out);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn write_element(&self, element: ElementRef, out: &mut String) {
+        let tag = element.value().name();
+        if SKIPPED_TAGS.contains(&tag) || is_hidden(element) || is_unlikely(element) || self.is_clutter(element) {
+            return;
+        }
+        if KEPT_TAGS.contains(&tag) {
+            let Some(attrs) = clean_attrs(element, self.base) else {
+                return;
+            };
+            out.push_str(&format!("<{}{}>", tag, attrs));
+            if !VOID_TAGS.contains(&tag) {
+                self.write_children(element, out);
+                out.push_str(&format!("</{}>", tag));
+            }
+        } else if BLOCK_TAGS.contains(&tag) {
+            out.push_str("<div>");
+            self.write_children(element, out);
+            out.push_str("</div>");
         } else {
-            Ok(None)
+            self.write_children(element, out);
+        }
+    }
+
+    /// Link lists, share bars and the like, and a repeated title.
+    fn is_clutter(&self, element: ElementRef) -> bool {
+        let tag = element.value().name();
+        if matches!(tag, "h1" | "h2") {
+            return !self.title.is_empty() && collapse_whitespace(&clean_text(element)) == self.title;
+        }
+        if !matches!(tag, "div" | "section" | "ul" | "ol" | "table") {
+            return false;
+        }
+        let len = clean_text(element).chars().filter(|c| !c.is_whitespace()).count();
+        let has_image = element.select(&IMAGES).next().is_some();
+        (link_density(element) > 0.5 && len < 500) || (class_weight(element) < 0.0 && len < 200 && !has_image)
+    }
+}
+
+/// Attributes to keep on a [`KEPT_TAGS`] element, or `None` to drop it.
+fn clean_attrs(element: ElementRef, base: &Url) -> Option<String> {
+    let value = element.value();
+    let url = |attr: &str, schemes: &[&str]| {
+        let url = base.join(value.attr(attr)?.trim()).ok()?;
+        schemes.contains(&url.scheme()).then(|| url.to_string())
+    };
+    let attr = |name: &str, content: &str| format!(" {}=\"{}\"", name, escape_html(content));
+    match value.name() {
+        "a" => Some(url("href", &["http", "https"]).map(|href| attr("href", &href)).unwrap_or_default()),
+        "img" => {
+            // Lazy-loading pages keep the real address in `data-src`
+            let src = url("src", &["http", "https", "data"]).or_else(|| url("data-src", &["http", "https"]))?;
+            let alt = value.attr("alt").map(|alt| attr("alt", alt)).unwrap_or_default();
+            Some(format!("{}{}", attr("src", &src), alt))
+        }
+        "td" | "th" => Some(
+            ["colspan", "rowspan"]
+                .iter()
+                .filter_map(|name| value.attr(name).filter(|v| v.parse::<u16>().is_ok()).map(|v| attr(name, v)))
+                .collect(),
+        ),
+        _ => Some(String::new()),
+    }
+}
+
+/// Text of `element` without [`SKIPPED_TAGS`] and hidden content.
+fn clean_text(element: ElementRef) -> String {
+    visible_text(element, SKIPPED_TAGS)
+}
+
+/// Text of `element` without `skipped` elements and hidden content, with
+/// spaces between blocks.
+pub(super) fn visible_text(element: ElementRef, skipped: &[&str]) -> String {
+    let mut text = String::new();
+    for child in element.children() {
+        match child.value() {
+            Node::Text(t) => text.push_str(t),
+            Node::Element(_) => {
+                let Some(child) = ElementRef::wrap(child) else {
+                    continue;
+                };
+                let tag = child.value().name();
+                if skipped.contains(&tag) || is_hidden(child) {
+                    continue;
+                }
+                let inline = INLINE_TAGS.contains(&tag);
+                if !inline {
+                    text.push(' ');
+                }
+                text.push_str(&visible_text(child, skipped));
+                if !inline {
+                    text.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Builds text one line per block, with list items as `- ` bullets
+/// indented by nesting.
+#[derive(Default)]
+struct Layout {
+    lines: Vec<String>,
+    line: String,
+    /// Whitespace was seen since the last word.
+    space: bool,
+    /// Prefix of the next line, set by list items.
+    bullet: Option<String>,
+}
+
+impl Layout {
+    fn walk(&mut self, element: ElementRef, depth: usize) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.push_text(text),
+                Node::Element(_) => {
+                    let Some(child) = ElementRef::wrap(child) else {
+                        continue;
+                    };
+                    let tag = child.value().name();
+                    if NON_TEXT_TAGS.contains(&tag) || is_hidden(child) {
+                        continue;
+                    }
+                    match tag {
+                        "br" => self.break_line(),
+                        "ul" | "ol" => {
+                            self.break_line();
+                            self.walk(child, depth + 1);
+                            self.break_line();
+                        }
+                        "li" => {
+                            self.break_line();
+                            self.bullet = Some(format!("{}- ", "  ".repeat(depth.saturating_sub(1))));
+                            self.walk(child, depth);
+                            self.break_line();
+                            self.bullet = None;
+                        }
+                        // Cells of a row stay on one line
+                        "td" | "th" => {
+                            self.space = true;
+                            self.walk(child, depth);
+                            self.space = true;
+                        }
+                        _ if INLINE_TAGS.contains(&tag) => self.walk(child, depth),
+                        _ => {
+                            self.break_line();
+                            self.walk(child, depth);
+                            self.break_line();
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        self.space |= text.starts_with(char::is_whitespace);
+        let mut words = text.split_whitespace().peekable();
+        if words.peek().is_none() {
+            return;
+        }
+        for word in words {
+            if self.space && !self.line.is_empty() {
+                self.line.push(' ');
+            }
+            self.line.push_str(word);
+            self.space = true;
+        }
+        self.space = text.ends_with(char::is_whitespace);
+    }
+
+    fn break_line(&mut self) {
+        if !self.line.is_empty() {
+            let bullet = self.bullet.take().unwrap_or_default();
+            self.lines.push(format!("{}{}", bullet, std::mem::take(&mut self.line)));
         }
+        self.space = false;
+    }
+
+    fn finish(mut self) -> String {
+        self.break_line();
+        self.lines.join("\n")
+    }
+}
+
+pub(super) fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub(super) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_size_cap_can_be_lowered() {
+        let html = format!("<p>{}</p><p>end</p>", "a".repeat(100));
+        let processor = ContentProcessor::new().with_max_html_bytes(50);
+        let page = processor.parse(&html);
+        assert!(page.is_truncated());
+        assert!(!page.text().contains("end"));
+        assert!(!ContentProcessor::new().parse(&html).is_truncated());
+    }
+
+    #[test]
+    fn fetched_pages_are_cut_off_at_the_processors_cap() {
+        let html = format!("<p>{}</p><p>end</p>", "a".repeat(100));
+        let page = ContentProcessor::new().with_max_html_bytes(50).from_bytes(html.as_bytes(), None).unwrap();
+        assert!(page.is_truncated());
+        assert!(!page.text().contains("end"));
+    }
+
+    fn links(html: &str) -> Vec<String> {
+        let base = Url::parse("https://example.com/docs/guide/intro.html").unwrap();
+        let links = ContentProcessor::new().extract_links_absolute(html, &base).unwrap();
+        links.iter().map(Url::to_string).collect()
+    }
+
+    #[test]
+    fn relative_links_are_resolved_against_the_page() {
+        let html = r#"<a href="/about">About</a> <a href="../api/">API</a> <a href="next.html">Next</a>"#;
+        assert_eq!(
+            links(html),
+            vec![
+                "https://example.com/about",
+                "https://example.com/docs/api/",
+                "https://example.com/docs/guide/next.html",
+            ]
+        );
+    }
+
+    #[test]
+    fn a_base_tag_overrides_the_page_url() {
+        let html = r#"<head><base href="https://cdn.example.net/v2/"></head><a href="start">Start</a>"#;
+        assert_eq!(links(html), vec!["https://cdn.example.net/v2/start"]);
+    }
+
+    #[test]
+    fn script_mail_and_fragment_links_are_left_out() {
+        let html = r##"<a href="javascript:void(0)">x</a> <a href="mailto:a@example.com">x</a>
+            <a href="#top">x</a> <a href="">x</a> <a href="https://other.example/#top">x</a>"##;
+        assert_eq!(links(html), vec!["https://other.example/#top"]);
+    }
+
+    #[test]
+    fn duplicate_links_are_kept_once_in_order() {
+        let html = r#"<a href="/b">b</a> <a href="/a">a</a> <a href="https://example.com/b">b again</a>"#;
+        assert_eq!(links(html), vec!["https://example.com/b", "https://example.com/a"]);
+    }
+
+    #[test]
+    fn raw_links_are_returned_as_written() {
+        let html = r##"<a href="/about">About</a> <a href="#top">Top</a> <a href="/about">Again</a>"##;
+        let raw = ContentProcessor::new().extract_links(html).unwrap();
+        assert_eq!(raw, vec!["/about", "#top", "/about"]);
+    }
+
+    fn article(html: &str) -> Article {
+        let base = Url::parse("https://example.com/2024/03/story.html").unwrap();
+        ContentProcessor::new().extract_article(html, &base).unwrap()
+    }
+
+    #[test]
+    fn a_news_article_leaves_out_the_page_furniture() {
+        let article = article(include_str!("testdata/news.html"));
+        assert_eq!(article.title, "서울 지하철 9호선 연장 구간 개통");
+        assert_eq!(article.byline.as_deref(), Some("김민준 기자"));
+        assert!(article.text.contains("첫 열차 운행을 시작했다"));
+        assert!(article.text.contains("환승 할인도 그대로 적용된다"));
+        for boilerplate in ["쿠키를 사용합니다", "정치", "첫 달 무료", "페이스북", "많이 본 뉴스", "무단 전재"] {
+            assert!(!article.text.contains(boilerplate), "{:?} is in the article", boilerplate);
+        }
+        assert!(!article.html.contains("<script"));
+    }
+
+    #[test]
+    fn a_blog_post_leaves_out_comments_and_signup_forms() {
+        let article = article(include_str!("testdata/blog.html"));
+        assert_eq!(article.title, "Why I stopped writing custom allocators");
+        assert_eq!(article.byline.as_deref(), Some("Dana Whitfield"));
+        assert!(article.text.contains("reach for a custom allocator"));
+        assert!(article.text.contains("keep the measurements"));
+        assert!(article.text.contains("Vec::with_capacity(4096)"));
+        for boilerplate in ["Archive", "Great post!", "fragmentation", "unsubscribe", "static site generator"] {
+            assert!(!article.text.contains(boilerplate), "{:?} is in the article", boilerplate);
+        }
+    }
+
+    #[test]
+    fn a_docs_page_keeps_its_headings_and_lists() {
+        let article = article(include_str!("testdata/docs.html"));
+        assert!(article.text.contains("routed through an HTTP or SOCKS5 proxy"));
+        assert!(article.text.contains("hosts that skip the proxy"));
+        assert!(article.text.contains("every proxy the browser tried to use"));
+        assert!(article.html.contains("<h2"));
+        assert!(article.html.contains("<li>"));
+        for boilerplate in ["Search the docs", "Keyboard shortcuts", "Send feedback", "CC BY 4.0"] {
+            assert!(!article.text.contains(boilerplate), "{:?} is in the article", boilerplate);
+        }
+    }
+
+    #[test]
+    fn article_links_are_absolute() {
+        let html = r#"<body><article><p>The new line runs four kilometres further east, with four new
+            stations and trains every four minutes at rush hour. <a href="/map.html">See the map</a>
+            for the new stations.</p></article></body>"#;
+        assert!(article(html).html.contains(r#"href="https://example.com/map.html""#));
+    }
+
+    #[test]
+    fn a_page_without_text_has_no_article() {
+        let base = Url::parse("https://example.com/").unwrap();
+        let processor = ContentProcessor::new();
+        assert!(processor.extract_article("<body><script>run()</script></body>", &base).is_err());
+    }
+
+    #[test]
+    fn text_leaves_out_scripts_styles_and_hidden_elements() {
+        let html = r#"<head><title>Title</title><style>h1 { color: red }</style></head><body>
+            <h1>Heading</h1>
+            <script>var tracking = "script text";</script>
+            <noscript>Enable JavaScript</noscript>
+            <template><p>Template text</p></template>
+            <svg><text>Chart label</text></svg>
+            <p>Shown   paragraph
+               over two lines.</p>
+            <div hidden>Hidden attribute</div>
+            <div aria-hidden="true">Aria hidden</div>
+            <div style="display: none">Display none</div>
+            <div style="visibility:hidden">Visibility hidden</div>
+            <span aria-hidden="false">Not hidden</span>
+        </body>"#;
+        let text = ContentProcessor::new().extract_text(html).unwrap();
+        assert_eq!(text, "Heading Shown paragraph over two lines. Not hidden");
+    }
+
+    #[test]
+    fn a_page_without_a_body_has_no_text() {
+        let text = ContentProcessor::new().extract_text("").unwrap();
+        assert_eq!(text, "");
+    }
+
+    #[test]
+    fn layout_keeps_paragraphs_and_list_items_on_their_own_lines() {
+        let html = r#"<body>
+            <h2>Ingredients</h2>
+            <p>You will need:</p>
+            <ul>
+                <li>Two <b>ripe</b> bananas</li>
+                <li>Flour
+                    <ul><li>plain</li><li>or wholemeal</li></ul>
+                </li>
+            </ul>
+            <p>Mix well.<br>Bake for <em>40 minutes</em>.</p>
+            <table><tr><td>Oven</td><td>180°C</td></tr></table>
+            <script>ignored()</script>
+            <p hidden>Hidden step</p>
+        </body>"#;
+        let text = ContentProcessor::new().extract_text_with_layout(html).unwrap();
+        assert_eq!(
+            text,
+            "Ingredients\nYou will need:\n- Two ripe bananas\n- Flour\n  - plain\n  - or wholemeal\nMix well.\nBake for 40 minutes.\nOven 180°C"
+        );
+    }
+
+    #[test]
+    fn fetched_pages_are_decoded_by_their_charset() {
+        let page = ContentProcessor::new().from_bytes(include_bytes!("testdata/euc-kr.html"), Some("text/html")).unwrap();
+        assert_eq!(page.title(), Some("동네 소식 - 우리 마을 게시판"));
+        assert!(page.text().contains("안 쓰는 물건을 가지고 오세요."));
+
+        let page = ContentProcessor::new().from_bytes(include_bytes!("testdata/shift_jis.html"), None).unwrap();
+        assert_eq!(page.title(), Some("お知らせ - 町内会"));
+        assert!(page.text().contains("使わない物を持ってきてください。"));
     }
 }
@@ -0,0 +1,146 @@
+//! Anthropic's messages API.
+
+use super::{non_empty, read_lines, send, ChatFuture, ChatRequest, Provider, Role, TokenUsage};
+use crate::ai::assistant::AiError;
+use crate::ai::config::ProviderKind;
+use serde::Deserialize;
+use serde_json::json;
+
+const DEFAULT_ENDPOINT: &str = "https://api.anthropic.com/v1/messages";
+const DEFAULT_MODEL: &str = "claude-3-5-haiku-latest";
+const API_VERSION: &str = "2023-06-01";
+/// The API requires a limit on the answer's length.
+const DEFAULT_MAX_TOKENS: u32 = 1024;
+
+pub struct AnthropicProvider {
+    client: reqwest::Client,
+    api_key: String,
+    endpoint: String,
+    model: String,
+}
+
+impl AnthropicProvider {
+    /// A provider sending its requests with `client`.
+    pub fn new(client: reqwest::Client, api_key: String, endpoint: Option<String>, model: Option<String>) -> Self {
+        Self {
+            client,
+            api_key,
+            endpoint: endpoint.unwrap_or_else(|| DEFAULT_ENDPOINT.to_string()),
+            model: model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+        }
+    }
+
+    fn request(&self, request: &ChatRequest, stream: bool) -> reqwest::RequestBuilder {
+        // System prompts go in their own field rather than in the messages
+        let system: Vec<&str> = request
+            .messages
+            .iter()
+            .filter(|message| message.role == Role::System)
+            .map(|message| message.content.as_str())
+            .collect();
+        let messages: Vec<_> = request.messages.iter().filter(|message| message.role != Role::System).collect();
+        let mut body = json!({
+            "model": self.model,
+            "messages": messages,
+            "max_tokens": request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            "stream": stream,
+        });
+        if !system.is_empty() {
+            body["system"] = system.join("\n\n").into();
+        }
+        self.client
+            .post(&self.endpoint)
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+            .json(&body)
+    }
+}
+
+impl Provider for AnthropicProvider {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Anthropic
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn context_window(&self) -> usize {
+        super::context_window(&self.model)
+    }
+
+    fn chat<'a>(&'a self, request: &'a ChatRequest) -> ChatFuture<'a> {
+        Box::pin(async move {
+            #[derive(Deserialize)]
+            struct Message {
+                content: Vec<Block>,
+                #[serde(default)]
+                usage: Option<Usage>,
+            }
+            #[derive(Deserialize)]
+            struct Block {
+                #[serde(default)]
+                text: String,
+            }
+            #[derive(Deserialize)]
+            struct Usage {
+                input_tokens: u64,
+                output_tokens: u64,
+            }
+            let message: Message = super::json_body(send(self.request(request, false), false).await?).await?;
+            let usage = message.usage.map(|usage| TokenUsage {
+                prompt_tokens: usage.input_tokens,
+                completion_tokens: usage.output_tokens,
+            });
+            non_empty(message.content.into_iter().map(|block| block.text).collect(), usage)
+        })
+    }
+
+    fn chat_stream<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+        on_token: &'a mut (dyn FnMut(&str) + Send),
+    ) -> ChatFuture<'a> {
+        Box::pin(async move {
+            let response = send(self.request(request, true), true).await?;
+            let mut answer = String::new();
+            let (mut prompt_tokens, mut completion_tokens) = (None, None);
+            // Server-sent events; the `type` in the data says what each is.
+            // The prompt's tokens come at the start, the answer's at the end.
+            read_lines(response, |line| {
+                let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                    return Ok(false);
+                };
+                let event: serde_json::Value =
+                    serde_json::from_str(data).map_err(|e| AiError::InvalidResponse(e.to_string()))?;
+                match event["type"].as_str() {
+                    Some("content_block_delta") => {
+                        if let Some(token) = event["delta"]["text"].as_str().filter(|t| !t.is_empty()) {
+                            on_token(token);
+                            answer.push_str(token);
+                        }
+                        Ok(false)
+                    }
+                    Some("message_start") => {
+                        prompt_tokens = event["message"]["usage"]["input_tokens"].as_u64();
+                        Ok(false)
+                    }
+                    Some("message_delta") => {
+                        completion_tokens = event["usage"]["output_tokens"].as_u64().or(completion_tokens);
+                        Ok(false)
+                    }
+                    Some("message_stop") => Ok(true),
+                    Some("error") => Err(AiError::InvalidResponse(
+                        event["error"]["message"].as_str().unwrap_or("Stream error").to_string(),
+                    )),
+                    _ => Ok(false),
+                }
+            })
+            .await?;
+            let usage = prompt_tokens
+                .zip(completion_tokens)
+                .map(|(prompt_tokens, completion_tokens)| TokenUsage { prompt_tokens, completion_tokens });
+            non_empty(answer, usage)
+        })
+    }
+}
@@ -0,0 +1,241 @@
+//! Chat APIs the assistant can talk to, behind one [`Provider`] trait.
+
+mod anthropic;
+mod ollama;
+mod openai;
+mod retry;
+
+use super::assistant::AiError;
+use super::config::{AiConfig, ProviderKind};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+pub use anthropic::AnthropicProvider;
+pub use ollama::OllamaProvider;
+pub use openai::OpenAiProvider;
+pub use retry::RetryingProvider;
+
+/// Streamed answers take longer in total but show progress all along.
+/// Whole answers are timed out by [`RetryingProvider`].
+const STREAM_TIMEOUT: Duration = Duration::from_secs(300);
+/// Context windows of models by name prefix, in tokens. More specific
+/// prefixes come first.
+const CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-4.1", 1_000_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4", 8_192),
+    ("gpt-3.5", 16_385),
+    ("o1", 128_000),
+    ("o3", 200_000),
+    ("o4", 200_000),
+    ("claude", 200_000),
+];
+/// For models not in [`CONTEXT_WINDOWS`].
+const DEFAULT_CONTEXT_WINDOW: usize = 8_192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self { role, content: content.into() }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChatRequest {
+    pub messages: Vec<ChatMessage>,
+    /// Longest answer wanted, in tokens; the provider's default if unset.
+    pub max_tokens: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatResponse {
+    pub content: String,
+    /// What the request took, if the provider said.
+    pub usage: Option<TokenUsage>,
+}
+
+/// Tokens a request took, as the provider counted them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// What a provider's requests return.
+pub type ChatFuture<'a> = Pin<Box<dyn Future<Output = Result<ChatResponse, AiError>> + Send + 'a>>;
+/// What a provider's embedding requests return.
+pub type EmbedFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<f32>, AiError>> + Send + 'a>>;
+
+/// A chat API. Dropping a returned future aborts its request.
+pub trait Provider: Send + Sync {
+    fn kind(&self) -> ProviderKind;
+
+    /// Name of the model answering requests.
+    fn model(&self) -> &str;
+
+    /// How many tokens of prompt and answer together the model takes.
+    fn context_window(&self) -> usize;
+
+    /// The model's whole answer to `request`.
+    fn chat<'a>(&'a self, request: &'a ChatRequest) -> ChatFuture<'a>;
+
+    /// Like [`chat`](Self::chat), but hands each piece of the answer to
+    /// `on_token` as it arrives.
+    fn chat_stream<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+        on_token: &'a mut (dyn FnMut(&str) + Send),
+    ) -> ChatFuture<'a>;
+
+    /// Name of the model embedding texts, for providers with an embeddings
+    /// API.
+    fn embedding_model(&self) -> Option<&str> {
+        None
+    }
+
+    /// Whether [`embed`](Self::embed) works.
+    fn supports_embeddings(&self) -> bool {
+        false
+    }
+
+    /// The embedding of `text`, for providers with an embeddings API.
+    fn embed<'a>(&'a self, _text: &'a str) -> EmbedFuture<'a> {
+        Box::pin(async { Err(AiError::Unsupported) })
+    }
+}
+
+/// The provider `config` selects, sending its requests with `client` and
+/// retrying as [`RetryingProvider`] does, or `None` without the API key it
+/// needs.
+pub fn from_config(config: &AiConfig, client: reqwest::Client) -> Option<Box<dyn Provider>> {
+    let api_key = config.api_key.clone().filter(|key| !key.trim().is_empty());
+    if config.provider.needs_api_key() && api_key.is_none() {
+        return None;
+    }
+    let endpoint = config.endpoint.clone();
+    let model = config.model.clone();
+    let embedding_model = config.embedding_model.clone();
+    let provider: Box<dyn Provider> = match config.provider {
+        ProviderKind::OpenAi => Box::new(
+            OpenAiProvider::new(client, api_key.unwrap_or_default(), endpoint, model)
+                .with_embedding_model(embedding_model),
+        ),
+        ProviderKind::Anthropic => {
+            Box::new(AnthropicProvider::new(client, api_key.unwrap_or_default(), endpoint, model))
+        }
+        ProviderKind::Ollama => {
+            Box::new(OllamaProvider::new(client, endpoint, model).with_embedding_model(embedding_model))
+        }
+    };
+    Some(Box::new(RetryingProvider::new(provider, config)))
+}
+
+/// The context window of `model`, by its name.
+fn context_window(model: &str) -> usize {
+    let model = model.to_ascii_lowercase();
+    // Names may have a vendor prefix, e.g. `openai/gpt-4o`
+    let name = model.rsplit('/').next().unwrap_or(&model);
+    CONTEXT_WINDOWS
+        .iter()
+        .find(|(prefix, _)| name.starts_with(prefix))
+        .map_or(DEFAULT_CONTEXT_WINDOW, |(_, tokens)| *tokens)
+}
+
+/// Sends `request`, failing on error statuses or, for a `stream`ed
+/// answer, if the whole response takes longer than [`STREAM_TIMEOUT`].
+async fn send(request: reqwest::RequestBuilder, stream: bool) -> Result<reqwest::Response, AiError> {
+    let request = if stream { request.timeout(STREAM_TIMEOUT) } else { request };
+    let response = request.send().await?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok()?.trim().parse().ok())
+        .map(Duration::from_secs);
+    Err(match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => AiError::Unauthorized,
+        StatusCode::TOO_MANY_REQUESTS => AiError::RateLimited { retry_after },
+        status if status.is_server_error() => AiError::Server(status),
+        status => AiError::Api { status, message: error_message(&response.text().await?) },
+    })
+}
+
+/// Parses a whole JSON response body.
+async fn json_body<T: for<'de> Deserialize<'de>>(response: reqwest::Response) -> Result<T, AiError> {
+    let body = response.text().await?;
+    serde_json::from_str(&body).map_err(|e| AiError::InvalidResponse(e.to_string()))
+}
+
+/// Reads a streamed response line by line, passing each to `on_line`
+/// until it returns `true` for the last one or the stream ends.
+async fn read_lines(
+    mut response: reqwest::Response,
+    mut on_line: impl FnMut(&str) -> Result<bool, AiError>,
+) -> Result<(), AiError> {
+    let mut pending: Vec<u8> = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        pending.extend_from_slice(&chunk);
+        // Lines can be split anywhere, even inside a UTF-8 character
+        while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            if on_line(String::from_utf8_lossy(&line).trim())? {
+                return Ok(());
+            }
+        }
+    }
+    if !pending.is_empty() {
+        on_line(String::from_utf8_lossy(&pending).trim())?;
+    }
+    Ok(())
+}
+
+/// Why a response without an answer failed.
+pub const NO_ANSWER: &str = "No answer in the response";
+
+/// `text` as a response, failing if the model said nothing.
+fn non_empty(text: String, usage: Option<TokenUsage>) -> Result<ChatResponse, AiError> {
+    if text.trim().is_empty() {
+        return Err(AiError::InvalidResponse(NO_ANSWER.to_string()));
+    }
+    Ok(ChatResponse { content: text, usage })
+}
+
+/// `embedding`, failing if there's none.
+fn non_empty_embedding(embedding: Option<Vec<f32>>) -> Result<Vec<f32>, AiError> {
+    embedding
+        .filter(|embedding| !embedding.is_empty())
+        .ok_or_else(|| AiError::InvalidResponse("No embedding in the response".to_string()))
+}
+
+/// The message of an error body, e.g. `{"error": {"message": ...}}` or
+/// `{"error": "..."}`, else the start of the body itself.
+fn error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|value| {
+            let error = &value["error"];
+            Some(error["message"].as_str().or_else(|| error.as_str())?.to_string())
+        })
+        .unwrap_or_else(|| body.chars().take(200).collect())
+}
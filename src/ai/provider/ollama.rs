@@ -0,0 +1,175 @@
+//! A local Ollama server's chat API.
+
+use super::{
+    non_empty, non_empty_embedding, read_lines, send, ChatFuture, ChatRequest, EmbedFuture, Provider, TokenUsage,
+};
+use crate::ai::assistant::AiError;
+use crate::ai::config::ProviderKind;
+use serde::Deserialize;
+use serde_json::json;
+
+const DEFAULT_ENDPOINT: &str = "http://localhost:11434/api/chat";
+const DEFAULT_MODEL: &str = "llama3.2";
+const DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
+/// Ollama cuts prompts to this many tokens unless told otherwise, whatever
+/// the model could take.
+const CONTEXT_WINDOW: usize = 4096;
+
+/// A line of Ollama's answer: the whole answer, or with streaming, one
+/// piece of it per line.
+#[derive(Deserialize)]
+struct Reply {
+    #[serde(default)]
+    message: Option<Message>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    error: Option<String>,
+    /// Tokens of the prompt, in the last line; missing if Ollama still had
+    /// the prompt from before.
+    #[serde(default)]
+    prompt_eval_count: Option<u64>,
+    /// Tokens of the answer, in the last line.
+    #[serde(default)]
+    eval_count: Option<u64>,
+}
+
+impl Reply {
+    fn usage(&self) -> Option<TokenUsage> {
+        Some(TokenUsage { prompt_tokens: self.prompt_eval_count.unwrap_or(0), completion_tokens: self.eval_count? })
+    }
+}
+
+#[derive(Deserialize)]
+struct Message {
+    #[serde(default)]
+    content: String,
+}
+
+pub struct OllamaProvider {
+    client: reqwest::Client,
+    endpoint: String,
+    model: String,
+    embedding_model: String,
+}
+
+impl OllamaProvider {
+    /// A provider sending its requests with `client`.
+    pub fn new(client: reqwest::Client, endpoint: Option<String>, model: Option<String>) -> Self {
+        Self {
+            client,
+            endpoint: endpoint.unwrap_or_else(|| DEFAULT_ENDPOINT.to_string()),
+            model: model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            embedding_model: DEFAULT_EMBEDDING_MODEL.to_string(),
+        }
+    }
+
+    /// Embeds with `model` instead of the default one.
+    pub fn with_embedding_model(mut self, model: Option<String>) -> Self {
+        if let Some(model) = model {
+            self.embedding_model = model;
+        }
+        self
+    }
+
+    /// The embed endpoint next to the chat one, if the endpoint is laid
+    /// out like Ollama's.
+    fn embed_endpoint(&self) -> Option<String> {
+        let base = self.endpoint.trim_end_matches('/').strip_suffix("/api/chat")?;
+        Some(format!("{}/api/embed", base))
+    }
+
+    fn request(&self, request: &ChatRequest, stream: bool) -> reqwest::RequestBuilder {
+        let mut body = json!({
+            "model": self.model,
+            "messages": request.messages,
+            "stream": stream,
+        });
+        if let Some(max_tokens) = request.max_tokens {
+            body["options"] = json!({ "num_predict": max_tokens });
+        }
+        self.client.post(&self.endpoint).json(&body)
+    }
+}
+
+impl Provider for OllamaProvider {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Ollama
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn context_window(&self) -> usize {
+        CONTEXT_WINDOW
+    }
+
+    fn chat<'a>(&'a self, request: &'a ChatRequest) -> ChatFuture<'a> {
+        Box::pin(async move {
+            let reply: Reply = super::json_body(send(self.request(request, false), false).await?).await?;
+            if let Some(error) = reply.error {
+                return Err(AiError::InvalidResponse(error));
+            }
+            let usage = reply.usage();
+            non_empty(reply.message.map(|message| message.content).unwrap_or_default(), usage)
+        })
+    }
+
+    fn chat_stream<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+        on_token: &'a mut (dyn FnMut(&str) + Send),
+    ) -> ChatFuture<'a> {
+        Box::pin(async move {
+            let response = send(self.request(request, true), true).await?;
+            let mut answer = String::new();
+            let mut usage = None;
+            // One JSON object per line, the last with `done` and the usage
+            read_lines(response, |line| {
+                if line.is_empty() {
+                    return Ok(false);
+                }
+                let reply: Reply = serde_json::from_str(line).map_err(|e| AiError::InvalidResponse(e.to_string()))?;
+                if let Some(error) = reply.error {
+                    return Err(AiError::InvalidResponse(error));
+                }
+                usage = reply.usage().or(usage);
+                if let Some(message) = reply.message.filter(|message| !message.content.is_empty()) {
+                    on_token(&message.content);
+                    answer.push_str(&message.content);
+                }
+                Ok(reply.done)
+            })
+            .await?;
+            non_empty(answer, usage)
+        })
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        Some(&self.embedding_model)
+    }
+
+    fn supports_embeddings(&self) -> bool {
+        self.embed_endpoint().is_some()
+    }
+
+    fn embed<'a>(&'a self, text: &'a str) -> EmbedFuture<'a> {
+        Box::pin(async move {
+            #[derive(Deserialize)]
+            struct Embeddings {
+                #[serde(default)]
+                embeddings: Vec<Vec<f32>>,
+                #[serde(default)]
+                error: Option<String>,
+            }
+            let endpoint = self.embed_endpoint().ok_or(AiError::Unsupported)?;
+            let body = json!({ "model": self.embedding_model, "input": text });
+            let reply: Embeddings = super::json_body(send(self.client.post(endpoint).json(&body), false).await?).await?;
+            if let Some(error) = reply.error {
+                return Err(AiError::InvalidResponse(error));
+            }
+            non_empty_embedding(reply.embeddings.into_iter().next())
+        })
+    }
+}
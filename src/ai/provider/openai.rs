@@ -0,0 +1,171 @@
+//! OpenAI's chat completions API, also spoken by many other services.
+
+use super::{
+    non_empty, non_empty_embedding, read_lines, send, ChatFuture, ChatRequest, EmbedFuture, Provider, TokenUsage,
+};
+use crate::ai::assistant::AiError;
+use crate::ai::config::ProviderKind;
+use serde::Deserialize;
+use serde_json::json;
+
+const DEFAULT_ENDPOINT: &str = "https://api.openai.com/v1/chat/completions";
+const DEFAULT_MODEL: &str = "gpt-4o-mini";
+const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+/// Dimensions asked of models that can shorten their embeddings: a sixth
+/// of the full size stores far smaller and searches about as well.
+const EMBEDDING_DIMENSIONS: usize = 256;
+
+pub struct OpenAiProvider {
+    client: reqwest::Client,
+    api_key: String,
+    endpoint: String,
+    model: String,
+    embedding_model: String,
+}
+
+impl OpenAiProvider {
+    /// A provider sending its requests with `client`.
+    pub fn new(client: reqwest::Client, api_key: String, endpoint: Option<String>, model: Option<String>) -> Self {
+        Self {
+            client,
+            api_key,
+            endpoint: endpoint.unwrap_or_else(|| DEFAULT_ENDPOINT.to_string()),
+            model: model.unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+            embedding_model: DEFAULT_EMBEDDING_MODEL.to_string(),
+        }
+    }
+
+    /// Embeds with `model` instead of the default one.
+    pub fn with_embedding_model(mut self, model: Option<String>) -> Self {
+        if let Some(model) = model {
+            self.embedding_model = model;
+        }
+        self
+    }
+
+    /// The embeddings endpoint next to the chat completions one, if the
+    /// endpoint is laid out like OpenAI's.
+    fn embeddings_endpoint(&self) -> Option<String> {
+        let base = self.endpoint.trim_end_matches('/').strip_suffix("/chat/completions")?;
+        Some(format!("{}/embeddings", base))
+    }
+
+    fn request(&self, request: &ChatRequest, stream: bool) -> reqwest::RequestBuilder {
+        let mut body = json!({
+            "model": self.model,
+            "messages": request.messages,
+            "stream": stream,
+        });
+        if let Some(max_tokens) = request.max_tokens {
+            body["max_tokens"] = max_tokens.into();
+        }
+        // Streams only say what they took when asked to, which not every
+        // compatible service understands
+        if stream && self.endpoint == DEFAULT_ENDPOINT {
+            body["stream_options"] = json!({ "include_usage": true });
+        }
+        self.client.post(&self.endpoint).bearer_auth(&self.api_key).json(&body)
+    }
+}
+
+impl Provider for OpenAiProvider {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::OpenAi
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn context_window(&self) -> usize {
+        super::context_window(&self.model)
+    }
+
+    fn chat<'a>(&'a self, request: &'a ChatRequest) -> ChatFuture<'a> {
+        Box::pin(async move {
+            #[derive(Deserialize)]
+            struct Completion {
+                choices: Vec<Choice>,
+                #[serde(default)]
+                usage: Option<TokenUsage>,
+            }
+            #[derive(Deserialize)]
+            struct Choice {
+                message: Message,
+            }
+            #[derive(Deserialize)]
+            struct Message {
+                content: Option<String>,
+            }
+            let completion: Completion = super::json_body(send(self.request(request, false), false).await?).await?;
+            let content = completion.choices.into_iter().next().and_then(|choice| choice.message.content);
+            non_empty(content.unwrap_or_default(), completion.usage)
+        })
+    }
+
+    fn chat_stream<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+        on_token: &'a mut (dyn FnMut(&str) + Send),
+    ) -> ChatFuture<'a> {
+        Box::pin(async move {
+            let response = send(self.request(request, true), true).await?;
+            let mut answer = String::new();
+            let mut usage = None;
+            // Server-sent events: `data: {json}` lines, then `data: [DONE]`.
+            // The usage comes in a last event without choices.
+            read_lines(response, |line| {
+                let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+                    return Ok(false);
+                };
+                if data == "[DONE]" {
+                    return Ok(true);
+                }
+                let event: serde_json::Value =
+                    serde_json::from_str(data).map_err(|e| AiError::InvalidResponse(e.to_string()))?;
+                if let Some(message) = event["error"]["message"].as_str() {
+                    return Err(AiError::InvalidResponse(message.to_string()));
+                }
+                if let Some(token) = event["choices"][0]["delta"]["content"].as_str().filter(|t| !t.is_empty()) {
+                    on_token(token);
+                    answer.push_str(token);
+                }
+                if let Ok(tokens) = serde_json::from_value(event["usage"].clone()) {
+                    usage = Some(tokens);
+                }
+                Ok(false)
+            })
+            .await?;
+            non_empty(answer, usage)
+        })
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        Some(&self.embedding_model)
+    }
+
+    fn supports_embeddings(&self) -> bool {
+        self.embeddings_endpoint().is_some()
+    }
+
+    fn embed<'a>(&'a self, text: &'a str) -> EmbedFuture<'a> {
+        Box::pin(async move {
+            #[derive(Deserialize)]
+            struct Embeddings {
+                data: Vec<Data>,
+            }
+            #[derive(Deserialize)]
+            struct Data {
+                embedding: Vec<f32>,
+            }
+            let endpoint = self.embeddings_endpoint().ok_or(AiError::Unsupported)?;
+            let mut body = json!({ "model": self.embedding_model, "input": text });
+            if self.embedding_model.starts_with("text-embedding-3") {
+                body["dimensions"] = EMBEDDING_DIMENSIONS.into();
+            }
+            let request = self.client.post(endpoint).bearer_auth(&self.api_key).json(&body);
+            let embeddings: Embeddings = super::json_body(send(request, false).await?).await?;
+            non_empty_embedding(embeddings.data.into_iter().next().map(|data| data.embedding))
+        })
+    }
+}
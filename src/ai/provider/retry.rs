@@ -0,0 +1,172 @@
+//! Retries, timeouts and a rate limit around any provider.
+
+use super::{ChatFuture, ChatRequest, EmbedFuture, Provider};
+use crate::ai::assistant::AiError;
+use crate::ai::config::{AiConfig, ProviderKind};
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Attempts at a request, the first included.
+const MAX_ATTEMPTS: u32 = 3;
+/// Wait before the first retry, doubled for each after it.
+const BACKOFF: Duration = Duration::from_secs(1);
+/// Longest `Retry-After` waited for; beyond it, the request fails.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Requests sent by all providers within the last [`RATE_WINDOW`].
+static SENT: RateLimiter = RateLimiter { sent: Mutex::new(VecDeque::new()) };
+
+/// Wraps a provider to retry requests failing with rate limits, server
+/// errors, timeouts or network errors, to time out whole answers, and to
+/// hold requests back beyond the configured rate.
+pub struct RetryingProvider {
+    inner: Box<dyn Provider>,
+    timeout: Duration,
+    requests_per_minute: u32,
+}
+
+impl RetryingProvider {
+    pub fn new(inner: Box<dyn Provider>, config: &AiConfig) -> Self {
+        Self { inner, timeout: config.timeout(), requests_per_minute: config.requests_per_minute() }
+    }
+
+    /// Sends the request `send` makes, within the rate and timeout, again
+    /// as long as it fails in a way worth retrying.
+    async fn with_retries<T, F>(&self, mut send: impl FnMut() -> F) -> Result<T, AiError>
+    where
+        F: Future<Output = Result<T, AiError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            SENT.wait(self.requests_per_minute).await;
+            let result = tokio::time::timeout(self.timeout, send()).await.unwrap_or(Err(AiError::Timeout));
+            match result {
+                Err(e) => match retry_delay(&e, attempt) {
+                    Some(delay) => {
+                        log::warn!("Retrying the AI request in {:?} after: {}", delay, e);
+                        tokio::time::sleep(delay).await;
+                        attempt += 1;
+                    }
+                    None => return Err(e),
+                },
+                response => return response,
+            }
+        }
+    }
+}
+
+impl Provider for RetryingProvider {
+    fn kind(&self) -> ProviderKind {
+        self.inner.kind()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn context_window(&self) -> usize {
+        self.inner.context_window()
+    }
+
+    fn chat<'a>(&'a self, request: &'a ChatRequest) -> ChatFuture<'a> {
+        Box::pin(self.with_retries(|| self.inner.chat(request)))
+    }
+
+    /// Streams aren't timed out here, and are only retried if no piece
+    /// of the answer arrived yet.
+    fn chat_stream<'a>(
+        &'a self,
+        request: &'a ChatRequest,
+        on_token: &'a mut (dyn FnMut(&str) + Send),
+    ) -> ChatFuture<'a> {
+        Box::pin(async move {
+            let mut attempt = 1;
+            let mut streamed = false;
+            loop {
+                SENT.wait(self.requests_per_minute).await;
+                let mut forward = |token: &str| {
+                    streamed = true;
+                    on_token(token);
+                };
+                let result = self.inner.chat_stream(request, &mut forward).await;
+                match result {
+                    Err(e) if !streamed => match retry_delay(&e, attempt) {
+                        Some(delay) => {
+                            log::warn!("Retrying the AI request in {:?} after: {}", delay, e);
+                            tokio::time::sleep(delay).await;
+                            attempt += 1;
+                        }
+                        None => return Err(e),
+                    },
+                    response => return response,
+                }
+            }
+        })
+    }
+
+    fn embedding_model(&self) -> Option<&str> {
+        self.inner.embedding_model()
+    }
+
+    fn supports_embeddings(&self) -> bool {
+        self.inner.supports_embeddings()
+    }
+
+    fn embed<'a>(&'a self, text: &'a str) -> EmbedFuture<'a> {
+        Box::pin(self.with_retries(|| self.inner.embed(text)))
+    }
+}
+
+/// How long to wait before retrying after `error` on the `attempt`th try,
+/// or `None` to give up.
+fn retry_delay(error: &AiError, attempt: u32) -> Option<Duration> {
+    if attempt >= MAX_ATTEMPTS || !error.is_transient() {
+        return None;
+    }
+    if let AiError::RateLimited { retry_after: Some(retry_after) } = error {
+        return (*retry_after <= MAX_RETRY_AFTER).then_some(*retry_after);
+    }
+    // Half to one and a half times the backoff, so clients that failed
+    // together don't retry together
+    let backoff = BACKOFF * 2u32.pow(attempt - 1);
+    let jitter = RandomState::new().hash_one(attempt) % 1000;
+    Some(backoff / 2 + backoff * jitter as u32 / 1000)
+}
+
+struct RateLimiter {
+    sent: Mutex<VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    /// Waits until another request fits in `per_minute`, then counts it.
+    async fn wait(&self, per_minute: u32) {
+        if per_minute == 0 {
+            return;
+        }
+        loop {
+            let wait = {
+                let Ok(mut sent) = self.sent.lock() else {
+                    return;
+                };
+                let now = Instant::now();
+                while sent.front().is_some_and(|time| now.duration_since(*time) >= RATE_WINDOW) {
+                    sent.pop_front();
+                }
+                match sent.front() {
+                    Some(oldest) if sent.len() >= per_minute as usize => RATE_WINDOW - now.duration_since(*oldest),
+                    _ => {
+                        sent.push_back(now);
+                        return;
+                    }
+                }
+            };
+            log::info!("Holding an AI request back for {:?} to stay within the rate limit", wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
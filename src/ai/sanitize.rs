@@ -0,0 +1,338 @@
+//! Allowlist sanitizer for showing remote HTML inside internal pages.
+//!
+//! The HTML is parsed the way a browser would and written back out from
+//! the parsed tree, so malformed markup can't smuggle anything through:
+//! only allowed elements and attributes are ever written.
+
+use super::processor::escape_html;
+use scraper::{ElementRef, Html, Node};
+use url::Url;
+
+/// Elements kept, with their allowed attributes beyond [`GLOBAL_ATTRS`].
+const ALLOWED: &[(&str, &[&str])] = &[
+    ("a", &["href"]),
+    ("abbr", &[]),
+    ("b", &[]),
+    ("blockquote", &[]),
+    ("br", &[]),
+    ("caption", &[]),
+    ("cite", &[]),
+    ("code", &[]),
+    ("dd", &[]),
+    ("del", &[]),
+    ("details", &[]),
+    ("div", &[]),
+    ("dl", &[]),
+    ("dt", &[]),
+    ("em", &[]),
+    ("figcaption", &[]),
+    ("figure", &[]),
+    ("h1", &[]),
+    ("h2", &[]),
+    ("h3", &[]),
+    ("h4", &[]),
+    ("h5", &[]),
+    ("h6", &[]),
+    ("hr", &[]),
+    ("i", &[]),
+    ("img", &["src", "alt", "width", "height"]),
+    ("ins", &[]),
+    ("kbd", &[]),
+    ("li", &[]),
+    ("mark", &[]),
+    ("ol", &["start"]),
+    ("p", &[]),
+    ("pre", &[]),
+    ("q", &[]),
+    ("s", &[]),
+    ("small", &[]),
+    ("span", &[]),
+    ("strong", &[]),
+    ("sub", &[]),
+    ("summary", &[]),
+    ("sup", &[]),
+    ("table", &[]),
+    ("tbody", &[]),
+    ("td", &["colspan", "rowspan"]),
+    ("tfoot", &[]),
+    ("th", &["colspan", "rowspan"]),
+    ("thead", &[]),
+    ("time", &["datetime"]),
+    ("tr", &[]),
+    ("u", &[]),
+    ("ul", &[]),
+];
+const GLOBAL_ATTRS: &[&str] = &["title", "lang", "dir", "style"];
+const VOID_TAGS: &[&str] = &["br", "hr", "img"];
+/// Elements dropped along with their content. Other elements that aren't
+/// allowed are replaced by their content.
+const DROPPED_TAGS: &[&str] = &[
+    "script", "style", "template", "noscript", "iframe", "frame", "frameset", "object", "embed", "applet",
+    "svg", "math", "textarea", "select", "option", "button", "input", "title", "head", "meta", "link", "base",
+];
+/// CSS properties allowed in `style`.
+const STYLE_PROPERTIES: &[&str] = &[
+    "color", "background-color", "font-weight", "font-style", "text-align", "text-decoration", "vertical-align",
+];
+
+/// Where sanitized images load from.
+#[derive(Debug, Clone, Default)]
+pub enum ImageMode {
+    /// Images load from their source.
+    #[default]
+    Keep,
+    /// Images show `placeholder` instead; the real source is kept in
+    /// `data-src` for the page to load on demand.
+    Placeholder(String),
+}
+
+/// Sanitized HTML of `html`, a document or fragment.
+pub fn sanitize(html: &str, images: &ImageMode) -> String {
+    let fragment = Html::parse_fragment(html);
+    let mut out = String::new();
+    write_children(fragment.root_element(), images, &mut out);
+    out
+}
+
+fn write_children(element: ElementRef, images: &ImageMode, out: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => out.push_str(&escape_html(text)),
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    write_element(child, images, out);
+                }
+            }
+            // Comments, doctypes and processing instructions
+            _ => {}
+        }
+    }
+}
+
+fn write_element(element: ElementRef, images: &ImageMode, out: &mut String) {
+    let tag = element.value().name();
+    if DROPPED_TAGS.contains(&tag) {
+        return;
+    }
+    let Some((_, attrs)) = ALLOWED.iter().find(|(allowed, _)| *allowed == tag) else {
+        write_children(element, images, out);
+        return;
+    };
+
+    let attrs: Vec<(String, String)> = element
+        .value()
+        .attrs()
+        .map(|(name, value)| (name.to_ascii_lowercase(), value))
+        .filter(|(name, _)| attrs.contains(&name.as_str()) || GLOBAL_ATTRS.contains(&name.as_str()))
+        .filter_map(|(name, value)| {
+            let value = clean_attr(&name, value)?;
+            Some((name, value))
+        })
+        .collect();
+    let has = |name: &str| attrs.iter().any(|(attr, _)| attr == name);
+    if tag == "img" && !has("src") {
+        return;
+    }
+
+    out.push('<');
+    out.push_str(tag);
+    for (name, value) in &attrs {
+        match (name.as_str(), images) {
+            ("src", ImageMode::Placeholder(placeholder)) => {
+                push_attr(out, "src", placeholder);
+                push_attr(out, "data-src", value);
+            }
+            _ => push_attr(out, name, value),
+        }
+    }
+    if has("href") {
+        // Links from remote content must not get a handle on the internal page
+        push_attr(out, "rel", "noopener noreferrer");
+    }
+    out.push('>');
+
+    if !VOID_TAGS.contains(&tag) {
+        write_children(element, images, out);
+        out.push_str("</");
+        out.push_str(tag);
+        out.push('>');
+    }
+}
+
+fn push_attr(out: &mut String, name: &str, value: &str) {
+    out.push_str(&format!(" {}=\"{}\"", name, escape_html(value)));
+}
+
+/// The value to write for an allowed attribute, or `None` to leave it out.
+fn clean_attr(name: &str, value: &str) -> Option<String> {
+    let value = value.trim();
+    match name {
+        "href" => absolute_url(value, &["http", "https", "mailto"]),
+        "src" => absolute_url(value, &["http", "https"]).or_else(|| {
+            let lower = value.to_ascii_lowercase();
+            // Raster images only; SVG can carry script
+            ["data:image/png;", "data:image/gif;", "data:image/jpeg;", "data:image/webp;"]
+                .iter()
+                .any(|prefix| lower.starts_with(prefix))
+                .then(|| value.to_string())
+        }),
+        "width" | "height" | "colspan" | "rowspan" | "start" => value.parse::<u32>().ok().map(|n| n.to_string()),
+        "dir" => matches!(value, "ltr" | "rtl" | "auto").then(|| value.to_string()),
+        "style" => clean_style(value),
+        _ => Some(value.to_string()),
+    }
+}
+
+/// `value` as an absolute URL with one of `schemes`. Relative URLs are
+/// dropped: there's no page to resolve them against.
+fn absolute_url(value: &str, schemes: &[&str]) -> Option<String> {
+    let url = Url::parse(value).ok()?;
+    schemes.contains(&url.scheme()).then(|| url.to_string())
+}
+
+/// The declarations of an inline style that are in [`STYLE_PROPERTIES`]
+/// and have plain values: no `url()`, expressions, escapes or comments.
+fn clean_style(style: &str) -> Option<String> {
+    let declarations: Vec<String> = style
+        .split(';')
+        .filter_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            let property = property.trim().to_ascii_lowercase();
+            let value = value.trim();
+            let plain = !value.is_empty()
+                && value.chars().all(|c| c.is_ascii_alphanumeric() || " #%.,()-".contains(c))
+                && !value.to_ascii_lowercase().contains("url")
+                && !value.to_ascii_lowercase().contains("expression");
+            (STYLE_PROPERTIES.contains(&property.as_str()) && plain).then(|| format!("{}: {}", property, value))
+        })
+        .collect();
+    (!declarations.is_empty()).then(|| declarations.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples from the usual XSS filter evasion cheat sheets.
+    const XSS_SAMPLES: &[&str] = &[
+        "<script>alert(1)</script>",
+        "<SCRIPT SRC=https://evil.example/xss.js></SCRIPT>",
+        "<scr<script>ipt>alert(1)</scr</script>ipt>",
+        "<svg onload=alert(1)>",
+        "<svg><script>alert(1)</script></svg>",
+        "<math><mtext><table><mglyph><style><img src=x onerror=alert(1)>",
+        "<img src=x onerror=alert(1)>",
+        "<IMG SRC=\"javascript:alert('XSS');\">",
+        "<IMG SRC=JaVaScRiPt:alert('XSS')>",
+        "<img src=\"https://example.com/a.png\" onerror=\"alert(1)\">",
+        "<img src=\"data:image/svg+xml;base64,PHN2ZyBvbmxvYWQ9YWxlcnQoMSk+\">",
+        "<a href=\"javascript:alert(1)\">x</a>",
+        "<a href=\"  JaVaScRiPt:alert(1)\">x</a>",
+        "<a href=\"jav&#x09;ascript:alert(1)\">x</a>",
+        "<a href=\"&#106;&#97;&#118;&#97;&#115;&#99;&#114;&#105;&#112;&#116;&#58;alert(1)\">x</a>",
+        "<a href=\"vbscript:msgbox(1)\">x</a>",
+        "<a href=\"data:text/html;base64,PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==\">x</a>",
+        "<a href=\"https://example.com/\" onclick=\"alert(1)\" onmouseover=alert(1)>x</a>",
+        "<body onload=alert(1)>",
+        "<div style=\"background-image: url(javascript:alert(1))\">x</div>",
+        "<div style=\"width: expression(alert(1))\">x</div>",
+        "<div style=\"color: red; behavior: url(xss.htc)\">x</div>",
+        "<p style=\"color:\\72 ed\">x</p>",
+        "<iframe src=\"javascript:alert(1)\"></iframe>",
+        "<object data=\"javascript:alert(1)\"></object>",
+        "<embed src=\"https://evil.example/x.swf\">",
+        "<form action=\"javascript:alert(1)\"><button>x</button></form>",
+        "<input autofocus onfocus=alert(1)>",
+        "<details open ontoggle=alert(1)>",
+        "<meta http-equiv=\"refresh\" content=\"0;url=javascript:alert(1)\">",
+        "<base href=\"javascript:alert(1)//\">",
+        "<link rel=stylesheet href=\"https://evil.example/x.css\">",
+        "<style>@import 'https://evil.example/x.css';</style>",
+        "<!--<img src=\"--><img src=x onerror=alert(1)//\">",
+        "<noscript><p title=\"</noscript><img src=x onerror=alert(1)>\">",
+        "<p><b><i>unclosed <a href=https://example.com/>nesting</b> </p></i>",
+        "<table><td><a href=javascript:alert(1)>x</table>",
+        "<div><div><div><svg><foreignObject><iframe onload=alert(1)>",
+        "<a href=\"https://example.com/\"\"onclick=alert(1)>x</a>",
+        "<img \"\"\"><script>alert(1)</script>\">",
+    ];
+
+    /// Elements and attributes of sanitized output, checked against the
+    /// allowlist after parsing it again the way a browser would.
+    fn assert_safe(input: &str, output: &str) {
+        let parsed = Html::parse_fragment(output);
+        for element in parsed.root_element().descendants().filter_map(ElementRef::wrap).skip(1) {
+            let value = element.value();
+            let Some((_, attrs)) = ALLOWED.iter().find(|(tag, _)| *tag == value.name()) else {
+                panic!("<{}> kept from {:?}: {}", value.name(), input, output);
+            };
+            for (name, attr) in value.attrs() {
+                let allowed =
+                    attrs.contains(&name) || GLOBAL_ATTRS.contains(&name) || matches!(name, "rel" | "data-src");
+                assert!(allowed, "{} kept from {:?}: {}", name, input, output);
+                let lower = attr.to_ascii_lowercase();
+                for bad in ["javascript:", "vbscript:", "data:text", "data:image/svg", "url(", "expression", "\\"] {
+                    assert!(!lower.contains(bad), "{:?} kept from {:?}: {}", attr, input, output);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cheat_sheet_samples_are_neutralized() {
+        for input in XSS_SAMPLES {
+            let output = sanitize(input, &ImageMode::Keep);
+            assert_safe(input, &output);
+            let output = sanitize(input, &ImageMode::Placeholder("data:image/gif;base64,R0lGOD".into()));
+            assert_safe(input, &output);
+        }
+    }
+
+    #[test]
+    fn script_urls_leave_the_link_text() {
+        assert_eq!(sanitize("<a href=\"javascript:alert(1)\">click</a>", &ImageMode::Keep), "<a>click</a>");
+        assert_eq!(sanitize("<svg onload=alert(1)>", &ImageMode::Keep), "");
+    }
+
+    #[test]
+    fn malformed_nesting_is_written_well_formed() {
+        let output = sanitize("<p><b>bold <i>both</b> italic?</p>", &ImageMode::Keep);
+        assert_eq!(output, "<p><b>bold <i>both</i></b><i> italic?</i></p>");
+    }
+
+    #[test]
+    fn safe_formatting_and_links_are_kept() {
+        let output = sanitize(
+            "<h2 onclick=x()>Title</h2><p style=\"color: #333; position: fixed\">Text <a href=\"https://example.com/a?b=1&c=2\">link</a></p><unknown>inner</unknown>",
+            &ImageMode::Keep,
+        );
+        assert_eq!(
+            output,
+            "<h2>Title</h2><p style=\"color: #333\">Text <a href=\"https://example.com/a?b=1&amp;c=2\" rel=\"noopener noreferrer\">link</a></p>inner"
+        );
+    }
+
+    #[test]
+    fn relative_links_and_images_are_dropped() {
+        let output = sanitize("<a href=\"/about\">about</a><img src=\"/logo.png\" alt=\"logo\">", &ImageMode::Keep);
+        assert_eq!(output, "<a>about</a>");
+    }
+
+    #[test]
+    fn images_can_be_replaced_by_a_placeholder() {
+        let html = "<img src=\"https://example.com/photo.jpg\" alt=\"A photo\" width=\"640\">";
+        let output = sanitize(html, &ImageMode::Placeholder("data:image/gif;base64,R0lGOD".into()));
+        let image = Html::parse_fragment(&output);
+        let image = image.root_element().first_child().and_then(ElementRef::wrap).unwrap();
+        assert_eq!(image.value().attr("src"), Some("data:image/gif;base64,R0lGOD"));
+        assert_eq!(image.value().attr("data-src"), Some("https://example.com/photo.jpg"));
+        assert_eq!(image.value().attr("alt"), Some("A photo"));
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        let output = sanitize("<p>&lt;script&gt;alert(1)&lt;/script&gt; &amp; \"quotes\"</p>", &ImageMode::Keep);
+        assert!(output.starts_with("<p>&lt;script&gt;alert(1)&lt;/script&gt; &amp; "));
+        assert_safe("escaped text", &output);
+    }
+}
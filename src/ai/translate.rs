@@ -0,0 +1,80 @@
+//! Splitting text into pieces small enough to translate one at a time.
+
+use super::context::{estimate_tokens, truncate};
+use super::processor::escape_html;
+use scraper::{ElementRef, Html, Node};
+
+/// `text` in chunks of at most `budget_tokens` each, split at blank lines.
+/// A paragraph longer than that is cut into pieces, at spaces where it can
+/// be.
+pub(super) fn chunks(text: &str, budget_tokens: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if !current.is_empty() && estimate_tokens(&current) + estimate_tokens(paragraph) + 1 > budget_tokens {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if estimate_tokens(paragraph) > budget_tokens {
+            chunks.extend(pieces(paragraph, budget_tokens).map(str::to_string));
+            continue;
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(paragraph);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// The blocks of `html` separated by blank lines, for [`chunks`]. Elements
+/// too long for `budget_tokens` are split into their children.
+pub(super) fn html_paragraphs(html: &str, budget_tokens: usize) -> String {
+    let fragment = Html::parse_fragment(html);
+    let mut blocks = Vec::new();
+    push_blocks(fragment.root_element(), budget_tokens, &mut blocks);
+    blocks.join("\n\n")
+}
+
+fn push_blocks(parent: ElementRef, budget_tokens: usize, blocks: &mut Vec<String>) {
+    for child in parent.children() {
+        match child.value() {
+            Node::Text(text) if !text.trim().is_empty() => blocks.push(escape_html(text.trim())),
+            Node::Element(_) => {
+                let Some(element) = ElementRef::wrap(child) else {
+                    continue;
+                };
+                let html = element.html();
+                if estimate_tokens(&html) > budget_tokens && element.children().any(|c| c.value().is_element()) {
+                    push_blocks(element, budget_tokens, blocks);
+                } else {
+                    blocks.push(html);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `text` cut into pieces of at most `budget_tokens`.
+fn pieces(mut text: &str, budget_tokens: usize) -> impl Iterator<Item = &str> {
+    std::iter::from_fn(move || {
+        if text.is_empty() {
+            return None;
+        }
+        let (mut piece, cut) = truncate(text, budget_tokens);
+        if cut {
+            // Rather at a space, unless that leaves a small piece
+            if let Some(space) = piece.rfind(char::is_whitespace).filter(|&i| i > piece.len() / 2) {
+                piece = &piece[..space];
+            }
+            if piece.is_empty() {
+                piece = &text[..text.chars().next().map_or(0, char::len_utf8)];
+            }
+        }
+        text = text[piece.len()..].trim_start();
+        Some(piece.trim_end())
+    })
+}
@@ -0,0 +1,161 @@
+//! Tokens the AI API was sent and answered with, and what they cost.
+
+use super::config::ModelPrice;
+use super::context::estimate_tokens;
+use super::provider::{ChatRequest, TokenUsage};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What a request took.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Estimated cost in US dollars.
+    pub cost: f64,
+}
+
+impl Usage {
+    /// `tokens` at `price`.
+    pub(super) fn new(tokens: TokenUsage, price: ModelPrice) -> Self {
+        let cost = (tokens.prompt_tokens as f64 * price.input + tokens.completion_tokens as f64 * price.output)
+            / 1_000_000.0;
+        Self { prompt_tokens: tokens.prompt_tokens, completion_tokens: tokens.completion_tokens, cost }
+    }
+
+    /// Roughly the tokens of `request` and of `answer` to it, for providers
+    /// that don't say.
+    pub(super) fn estimate(request: &ChatRequest, answer: &str) -> TokenUsage {
+        let prompt_tokens = request.messages.iter().map(|message| estimate_tokens(&message.content)).sum::<usize>();
+        TokenUsage { prompt_tokens: prompt_tokens as u64, completion_tokens: estimate_tokens(answer) as u64 }
+    }
+}
+
+/// Usage added up over requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Estimated cost in US dollars.
+    pub cost: f64,
+}
+
+impl UsageTotals {
+    pub fn tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    fn add(&mut self, usage: &Usage) {
+        self.requests += 1;
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        self.cost += usage.cost;
+    }
+}
+
+/// Usage totals by month (`2026-10`), kept in a file when loaded from one.
+#[derive(Debug, Default)]
+pub struct UsageLog {
+    months: Mutex<BTreeMap<String, UsageTotals>>,
+    store_path: Option<PathBuf>,
+}
+
+impl UsageLog {
+    /// Location of the usage file in the data directory `data_dir`.
+    pub fn store_path_in(data_dir: &Path) -> PathBuf {
+        data_dir.join("ai_usage.json")
+    }
+
+    /// Loads the totals from `path` and saves back to it on every change.
+    /// A missing or corrupt file yields no usage instead of an error.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let months = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                log::warn!("Ignoring corrupt AI usage file {}: {}", path.display(), e);
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        Self { months: Mutex::new(months), store_path: Some(path) }
+    }
+
+    /// Adds `usage` to this month's totals.
+    pub fn record(&self, usage: &Usage) {
+        let Ok(mut months) = self.months.lock() else {
+            return;
+        };
+        months.entry(month_of(SystemTime::now())).or_default().add(usage);
+        self.autosave(&months);
+    }
+
+    pub fn this_month(&self) -> UsageTotals {
+        let month = month_of(SystemTime::now());
+        self.months.lock().ok().and_then(|months| months.get(&month).copied()).unwrap_or_default()
+    }
+
+    /// Totals since the usage was last reset.
+    pub fn total(&self) -> UsageTotals {
+        let Ok(months) = self.months.lock() else {
+            return UsageTotals::default();
+        };
+        months.values().fold(UsageTotals::default(), |mut total, month| {
+            total.requests += month.requests;
+            total.prompt_tokens += month.prompt_tokens;
+            total.completion_tokens += month.completion_tokens;
+            total.cost += month.cost;
+            total
+        })
+    }
+
+    /// Forgets all usage.
+    pub fn reset(&self) {
+        if let Ok(mut months) = self.months.lock() {
+            months.clear();
+            self.autosave(&months);
+        }
+    }
+
+    fn autosave(&self, months: &BTreeMap<String, UsageTotals>) {
+        let Some(path) = &self.store_path else {
+            return;
+        };
+        if let Err(e) = save_to(path, months) {
+            log::warn!("Failed to save AI usage: {:#}", e);
+        }
+    }
+}
+
+fn save_to(path: &Path, months: &BTreeMap<String, UsageTotals>) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let json = serde_json::to_string(months).context("Failed to serialize AI usage")?;
+    // Write to a temp file first so a crash mid-write can't lose the totals
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+/// The month `time` falls in, in UTC, like `2026-10`.
+fn month_of(time: SystemTime) -> String {
+    let days = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs() / 86_400) as i64;
+    // Days to the civil calendar, counting in 400-year eras from 0000-03-01
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}", year, month)
+}
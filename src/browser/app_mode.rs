@@ -0,0 +1,76 @@
+//! App mode (`--app`): one site in a window of its own, without the nav bar,
+//! tabs or sidebar, as a site-specific browser. Kiosk mode (`--kiosk`) also
+//! keeps the window fullscreen and only closes with the exit combination
+//! from the settings.
+
+use crate::browser::assets;
+use crate::browser::shortcuts::KeyPress;
+use url::{Origin, Url};
+
+/// The site an app window is locked to.
+#[derive(Debug, Clone)]
+pub struct AppMode {
+    /// The page the window opens with, and goes back to as its home page.
+    pub url: Url,
+    /// Pages from elsewhere open in the system's browser.
+    origin: Origin,
+    /// Keep the window fullscreen and ignore the ways of closing it but
+    /// `exit`.
+    pub kiosk: bool,
+    /// Closes a kiosk window.
+    pub exit: KeyPress,
+}
+
+impl AppMode {
+    pub fn new(url: &Url, kiosk: bool, exit: KeyPress) -> Self {
+        Self { url: url.clone(), origin: url.origin(), kiosk, exit }
+    }
+
+    /// Whether the window may show `url` itself: a page of its site, or one
+    /// that isn't on the web at all, like `about:blank`.
+    pub fn allows(&self, url: &Url) -> bool {
+        !matches!(url.scheme(), "http" | "https") || url.origin() == self.origin
+    }
+
+    /// Whether the window may go to `url`: a page it [`allows`](Self::allows),
+    /// or one of the browser's own, like the error page.
+    pub fn allows_navigation(&self, url: &Url) -> bool {
+        self.allows(url) || assets::is_internal_url(url.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> AppMode {
+        let exit = KeyPress { key: "q".into(), primary: true, alt: false, shift: true };
+        AppMode::new(&Url::parse("https://mail.example.com/inbox").unwrap(), false, exit)
+    }
+
+    fn allows(url: &str) -> bool {
+        app().allows_navigation(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn the_window_stays_on_its_site() {
+        assert!(allows("https://mail.example.com/settings"));
+        assert!(allows("about:blank"));
+        assert!(!allows("http://mail.example.com/"));
+        assert!(!allows("https://example.com/"));
+        assert!(!allows("https://mail.example.com:8443/"));
+    }
+
+    #[test]
+    fn the_browsers_own_pages_are_allowed() {
+        assert!(allows(&assets::error_url("https://mail.example.com/", "dns")));
+    }
+
+    #[test]
+    fn lookalikes_of_the_browsers_pages_are_not() {
+        assert!(!allows("https://syncflo.evil.example/"));
+        assert!(!allows("https://syncflo.com/"));
+        assert!(!allows("http://syncflo.app.evil.example/nav.html"));
+        assert!(!allows("http://syncflo.error:8080/"));
+    }
+}
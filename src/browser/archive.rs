@@ -0,0 +1,497 @@
+//! Pages saved as one HTML file that opens from disk without a network.
+//! The page is taken as it's shown, not as it was served: stylesheets,
+//! images and fonts go into the file as data URLs, links are made absolute
+//! and scripts are left out, so the file doesn't change once it's open.
+
+use crate::ai::charset;
+use crate::browser::proxy::ProxySettings;
+use crate::browser::{favicon, pages::escape_html};
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use reqwest::header::{CONTENT_TYPE, COOKIE, REFERER};
+use scraper::{ElementRef, Html, Node};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+use url::Url;
+
+/// Resources stop going into the file once they add up to this much; the
+/// rest are left to load from the site.
+pub const MAX_TOTAL_BYTES: u64 = 100 * 1024 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// Stylesheets imported deeper than this stay links.
+const MAX_IMPORT_DEPTH: usize = 4;
+
+const VOID_TAGS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr",
+];
+/// Elements whose text is written as it is.
+const RAW_TEXT_TAGS: &[&str] = &["style", "xmp", "iframe", "noembed", "noframes", "plaintext"];
+/// `<link rel>`s that only fetch ahead or point at what the file can't use.
+const DROPPED_RELS: &[&str] =
+    &["preload", "prefetch", "modulepreload", "preconnect", "dns-prefetch", "prerender", "manifest"];
+
+static CSS_IMPORT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)@import\s+(?:url\(\s*(?:"([^"]*)"|'([^']*)'|([^)\s]*))\s*\)|"([^"]*)"|'([^']*)')([^;]*);"#)
+        .expect("valid regex")
+});
+static CSS_URL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?i)url\(\s*(?:"([^"]*)"|'([^']*)'|([^)\s'"]*))\s*\)"#).expect("valid regex"));
+
+/// Run in the page; returns [`CapturedPage`] as a JSON string. Copies the
+/// document with the images the page picked from `srcset`, what was typed
+/// into forms, styles added from script and what canvases show.
+pub const CAPTURE_SCRIPT: &str = r#"
+(function() {
+  var root = document.documentElement.cloneNode(true);
+  function pairs(selector) {
+    var live = document.documentElement.querySelectorAll(selector);
+    var copies = root.querySelectorAll(selector);
+    var out = [];
+    for (var i = 0; i < live.length && i < copies.length; i++) out.push([live[i], copies[i]]);
+    return out;
+  }
+  pairs('img').forEach(function(p) {
+    if (!p[0].currentSrc) return;
+    p[1].setAttribute('src', p[0].currentSrc);
+    p[1].removeAttribute('srcset');
+    p[1].removeAttribute('sizes');
+    p[1].removeAttribute('loading');
+    var picture = p[1].parentElement;
+    if (picture && picture.tagName === 'PICTURE') {
+      Array.prototype.slice.call(picture.querySelectorAll('source')).forEach(function(s) { s.remove(); });
+    }
+  });
+  pairs('input').forEach(function(p) {
+    if (p[0].type === 'checkbox' || p[0].type === 'radio') {
+      if (p[0].checked) p[1].setAttribute('checked', ''); else p[1].removeAttribute('checked');
+    } else if (p[0].type !== 'password' && p[0].type !== 'file') {
+      p[1].setAttribute('value', p[0].value);
+    }
+  });
+  pairs('textarea').forEach(function(p) { p[1].textContent = p[0].value; });
+  pairs('option').forEach(function(p) {
+    if (p[0].selected) p[1].setAttribute('selected', ''); else p[1].removeAttribute('selected');
+  });
+  pairs('style').forEach(function(p) {
+    try {
+      var rules = p[0].sheet && p[0].sheet.cssRules;
+      if (rules && rules.length && !p[0].textContent.trim()) {
+        p[1].textContent = Array.prototype.map.call(rules, function(r) { return r.cssText; }).join('\n');
+      }
+    } catch (e) {}
+  });
+  pairs('canvas').forEach(function(p) {
+    try {
+      var image = document.createElement('img');
+      image.src = p[0].toDataURL();
+      image.width = p[0].width;
+      image.height = p[0].height;
+      if (p[0].className) image.className = p[0].className;
+      if (p[0].getAttribute('style')) image.setAttribute('style', p[0].getAttribute('style'));
+      p[1].replaceWith(image);
+    } catch (e) {}
+  });
+  var doctype = document.doctype ? new XMLSerializer().serializeToString(document.doctype) : '';
+  return JSON.stringify({ html: doctype + root.outerHTML, base: document.baseURI });
+})()
+"#;
+
+/// The page as [`CAPTURE_SCRIPT`] read it.
+#[derive(Debug, Deserialize)]
+pub struct CapturedPage {
+    pub html: String,
+    /// What relative URLs in the page resolve against.
+    pub base: String,
+}
+
+/// How a page was saved.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ArchiveReport {
+    /// Resources put into the file.
+    pub inlined: usize,
+    /// Resources that didn't download, left to load from the site.
+    pub failed: usize,
+    /// Some resources were left to load from the site because the file
+    /// reached [`MAX_TOTAL_BYTES`].
+    pub capped: bool,
+}
+
+/// Saves `page`, shown at `url`, to `path` as one HTML file. `cookie` is
+/// the `Cookie` header of the page's host, sent along with requests to it
+/// through `proxy`. `progress` is told how many resources are done out of how many were
+/// found so far. Blocks while they download, so call it off the main
+/// thread.
+pub fn save(
+    page: &CapturedPage,
+    url: &Url,
+    cookie: Option<String>,
+    proxy: &ProxySettings,
+    path: &Path,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<ArchiveReport> {
+    let (html, report) = build(page, url, cookie, proxy, progress)?;
+    let tmp = path.with_extension("html.tmp");
+    fs::write(&tmp, html).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(report)
+}
+
+/// The file of `page`, shown at `url`; see [`save`].
+pub fn build(
+    page: &CapturedPage,
+    url: &Url,
+    cookie: Option<String>,
+    proxy: &ProxySettings,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<(String, ArchiveReport)> {
+    let base = Url::parse(&page.base).unwrap_or_else(|_| url.clone());
+    let client = proxy.blocking_client(FETCH_TIMEOUT)?;
+    let document = Html::parse_document(&page.html);
+    let mut archiver = Archiver {
+        client,
+        page: url,
+        cookie,
+        fetched: HashMap::new(),
+        found: 0,
+        done: 0,
+        bytes: 0,
+        report: ArchiveReport::default(),
+        progress,
+    };
+
+    let mut out = String::with_capacity(page.html.len());
+    for node in document.tree.root().children() {
+        if let Node::Doctype(doctype) = node.value() {
+            out.push_str("<!DOCTYPE ");
+            out.push_str(doctype.name());
+            if !doctype.public_id().is_empty() {
+                out.push_str(&format!(" PUBLIC \"{}\"", doctype.public_id()));
+            }
+            if !doctype.system_id().is_empty() {
+                let keyword = if doctype.public_id().is_empty() { " SYSTEM" } else { "" };
+                out.push_str(&format!("{} \"{}\"", keyword, doctype.system_id()));
+            }
+            out.push('>');
+        }
+    }
+    out.push_str(&format!("\n<!-- saved from {} -->\n", url.as_str().replace("--", "%2D%2D")));
+    archiver.write_element(document.root_element(), &base, &mut out);
+    Ok((out, archiver.report))
+}
+
+struct Archiver<'a> {
+    client: reqwest::blocking::Client,
+    page: &'a Url,
+    cookie: Option<String>,
+    /// Resources by URL as data URLs, or `None` if they stay on the site.
+    fetched: HashMap<Url, Option<String>>,
+    found: usize,
+    done: usize,
+    /// Size of what went into the file so far.
+    bytes: u64,
+    report: ArchiveReport,
+    progress: &'a mut dyn FnMut(usize, usize),
+}
+
+impl Archiver<'_> {
+    fn write_children(&mut self, element: ElementRef, base: &Url, out: &mut String) {
+        let raw = RAW_TEXT_TAGS.contains(&element.value().name());
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) if raw => out.push_str(text),
+                Node::Text(text) => out.push_str(&escape_html(text)),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        self.write_element(child, base, out);
+                    }
+                }
+                // Comments and processing instructions
+                _ => {}
+            }
+        }
+    }
+
+    fn write_element(&mut self, element: ElementRef, base: &Url, out: &mut String) {
+        let value = element.value();
+        let tag = value.name();
+        let rels: Vec<String> =
+            value.attr("rel").unwrap_or("").split_whitespace().map(|rel| rel.to_ascii_lowercase()).collect();
+        let has_rel = |name: &str| rels.iter().any(|rel| rel == name);
+        match tag {
+            "script" | "noscript" | "base" => return,
+            "meta" if is_dropped_meta(element) => return,
+            "link" if DROPPED_RELS.iter().any(|&rel| has_rel(rel)) => return,
+            "link" if has_rel("stylesheet") && !has_rel("alternate") => {
+                let css = value
+                    .attr("href")
+                    .and_then(|href| base.join(href.trim()).ok())
+                    .and_then(|url| self.stylesheet(&url, 0));
+                if let Some(css) = css {
+                    out.push_str("<style");
+                    if let Some(media) = value.attr("media") {
+                        push_attr(out, "media", media);
+                    }
+                    out.push('>');
+                    out.push_str(&css.replace("</style", "<\\/style"));
+                    out.push_str("</style>");
+                    return;
+                }
+            }
+            "style" => {
+                let css: String = element.text().collect();
+                out.push_str("<style");
+                for (name, value) in value.attrs() {
+                    push_attr(out, name, value);
+                }
+                out.push('>');
+                out.push_str(&self.css(&css, base, 0).replace("</style", "<\\/style"));
+                out.push_str("</style>");
+                return;
+            }
+            _ => {}
+        }
+
+        let inline_href = tag == "link" && rels.iter().any(|rel| rel.contains("icon"));
+        out.push('<');
+        out.push_str(tag);
+        for (name, value) in value.attrs() {
+            // Event handlers don't run without the page's scripts anyway
+            if (name.starts_with("on") && name.len() > 2) || name == "integrity" || name == "nonce" {
+                continue;
+            }
+            let value = match (tag, name) {
+                (_, "style") => self.css_urls(value, base),
+                ("img" | "input", "src")
+                | ("video", "poster")
+                | ("image", "href" | "xlink:href")
+                | (_, "background") => self.resource(value, base),
+                ("link", "href") if inline_href => self.resource(value, base),
+                ("img" | "source", "srcset") => absolute_srcset(value, base),
+                (_, "href" | "xlink:href" | "src" | "action" | "formaction" | "cite" | "data" | "poster") => {
+                    absolute(value, base)
+                }
+                _ => value.to_string(),
+            };
+            push_attr(out, name, &value);
+        }
+        out.push('>');
+        if tag == "head" {
+            out.push_str("<meta charset=\"utf-8\">");
+        }
+        if VOID_TAGS.contains(&tag) {
+            return;
+        }
+        self.write_children(element, base, out);
+        out.push_str("</");
+        out.push_str(tag);
+        out.push('>');
+    }
+
+    /// `value`, resolved against `base`, as a data URL, or else as an
+    /// absolute URL.
+    fn resource(&mut self, value: &str, base: &Url) -> String {
+        let Ok(url) = base.join(value.trim()) else {
+            return value.to_string();
+        };
+        self.data_url(&url).unwrap_or_else(|| url.into())
+    }
+
+    /// The resource at `url` as a data URL, or `None` if it stays on the site.
+    fn data_url(&mut self, url: &Url) -> Option<String> {
+        match url.scheme() {
+            "data" => return Some(url.to_string()),
+            "http" | "https" => {}
+            _ => return None,
+        }
+        if let Some(fetched) = self.fetched.get(url) {
+            return fetched.clone();
+        }
+        let data_url = self.fetch(url).map(|(bytes, content_type)| {
+            let content_type = content_type
+                .map(|t| t.split(';').next().unwrap_or("").trim().to_ascii_lowercase())
+                .filter(|t| !t.is_empty() && t != "application/octet-stream")
+                .or_else(|| favicon::image_type(&bytes).map(String::from))
+                .unwrap_or_else(|| "application/octet-stream".to_string());
+            format!("data:{};base64,{}", content_type, base64::encode(&bytes))
+        });
+        if data_url.is_some() {
+            self.report.inlined += 1;
+        }
+        self.fetched.insert(url.clone(), data_url.clone());
+        data_url
+    }
+
+    /// The stylesheet at `url` with what it uses inlined, or `None` if it
+    /// stays on the site.
+    fn stylesheet(&mut self, url: &Url, depth: usize) -> Option<String> {
+        if depth > MAX_IMPORT_DEPTH || !matches!(url.scheme(), "http" | "https") {
+            return None;
+        }
+        let (bytes, content_type) = self.fetch(url)?;
+        self.report.inlined += 1;
+        let css = charset::decode(&bytes, content_type.as_deref());
+        Some(self.css(&css, url, depth))
+    }
+
+    /// `css`, whose URLs are relative to `base`, with its `@import`s and
+    /// `url()`s inlined.
+    fn css(&mut self, css: &str, base: &Url, depth: usize) -> String {
+        let mut out = String::with_capacity(css.len());
+        let mut last = 0;
+        for caps in CSS_IMPORT.captures_iter(css) {
+            let whole = caps.get(0).expect("whole match");
+            out.push_str(&self.css_urls(&css[last..whole.start()], base));
+            last = whole.end();
+            let target = first_group(&caps, 1..=5);
+            let condition = caps.get(6).map_or("", |m| m.as_str().trim());
+            // Only plain media queries can wrap the imported rules
+            let lower = condition.to_ascii_lowercase();
+            let wrappable = !["layer", "supports"].iter().any(|prefix| lower.starts_with(prefix));
+            let imported = base
+                .join(target.trim())
+                .ok()
+                .filter(|_| wrappable)
+                .and_then(|url| self.stylesheet(&url, depth + 1));
+            match imported {
+                Some(imported) if condition.is_empty() => {
+                    out.push_str(&imported);
+                    out.push('\n');
+                }
+                Some(imported) => out.push_str(&format!("@media {} {{\n{}\n}}\n", condition, imported)),
+                None => {
+                    let url = base.join(target.trim()).map_or_else(|_| target.to_string(), String::from);
+                    let separator = if condition.is_empty() { "" } else { " " };
+                    out.push_str(&format!("@import url(\"{}\"){}{};", css_escape(&url), separator, condition));
+                }
+            }
+        }
+        out.push_str(&self.css_urls(&css[last..], base));
+        out
+    }
+
+    /// `css` with the resources of its `url()`s inlined.
+    fn css_urls(&mut self, css: &str, base: &Url) -> String {
+        let mut out = String::with_capacity(css.len());
+        let mut last = 0;
+        for caps in CSS_URL.captures_iter(css) {
+            let whole = caps.get(0).expect("whole match");
+            out.push_str(&css[last..whole.start()]);
+            last = whole.end();
+            let target = first_group(&caps, 1..=3).trim();
+            // Fragments point into the document, like SVG filters
+            if target.is_empty() || target.starts_with('#') {
+                out.push_str(whole.as_str());
+                continue;
+            }
+            let resource = self.resource(target, base);
+            out.push_str(&format!("url(\"{}\")", css_escape(&resource)));
+        }
+        out.push_str(&css[last..]);
+        out
+    }
+
+    /// Downloads `url`, with its `Content-Type` header, unless it fails or
+    /// would take the file over [`MAX_TOTAL_BYTES`].
+    fn fetch(&mut self, url: &Url) -> Option<(Vec<u8>, Option<String>)> {
+        self.found += 1;
+        (self.progress)(self.done, self.found);
+        let fetched = self.download(url);
+        self.done += 1;
+        (self.progress)(self.done, self.found);
+        fetched
+    }
+
+    fn download(&mut self, url: &Url) -> Option<(Vec<u8>, Option<String>)> {
+        let remaining = MAX_TOTAL_BYTES.saturating_sub(self.bytes);
+        if remaining == 0 {
+            self.report.capped = true;
+            return None;
+        }
+        let mut request = self.client.get(url.as_str()).header(REFERER, self.page.as_str());
+        if let Some(cookie) = self.cookie.as_deref().filter(|_| url.host_str() == self.page.host_str()) {
+            request = request.header(COOKIE, cookie);
+        }
+        let response = match request.send().and_then(|response| response.error_for_status()) {
+            Ok(response) => response,
+            Err(e) => {
+                log::info!("Leaving {} out of the saved page: {}", url, e);
+                self.report.failed += 1;
+                return None;
+            }
+        };
+        if response.content_length().is_some_and(|length| length > remaining) {
+            self.report.capped = true;
+            return None;
+        }
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+        let mut bytes = Vec::new();
+        if let Err(e) = response.take(remaining + 1).read_to_end(&mut bytes) {
+            log::info!("Leaving {} out of the saved page: {}", url, e);
+            self.report.failed += 1;
+            return None;
+        }
+        if bytes.len() as u64 > remaining {
+            self.report.capped = true;
+            return None;
+        }
+        self.bytes += bytes.len() as u64;
+        Some((bytes, content_type))
+    }
+}
+
+/// Whether `meta` would get in the way of the file: it reloads the page,
+/// restricts what it loads, or declares another encoding than the file's.
+fn is_dropped_meta(meta: ElementRef) -> bool {
+    let value = meta.value();
+    value.attr("charset").is_some()
+        || value.attr("http-equiv").is_some_and(|equiv| {
+            ["refresh", "content-security-policy", "content-type"].iter().any(|name| equiv.eq_ignore_ascii_case(name))
+        })
+}
+
+fn first_group<'t>(caps: &Captures<'t>, groups: std::ops::RangeInclusive<usize>) -> &'t str {
+    groups.filter_map(|n| caps.get(n)).map(|m| m.as_str()).next().unwrap_or("")
+}
+
+/// `value` resolved against `base`. Fragments of the page stay as they
+/// are, so they keep pointing into the file.
+fn absolute(value: &str, base: &Url) -> String {
+    let trimmed = value.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return value.to_string();
+    }
+    base.join(trimmed).map_or_else(|_| value.to_string(), String::from)
+}
+
+/// The candidates of `srcset` with absolute URLs.
+fn absolute_srcset(srcset: &str, base: &Url) -> String {
+    srcset
+        .split(',')
+        .map(|candidate| {
+            let candidate = candidate.trim();
+            match candidate.split_once(char::is_whitespace) {
+                Some((url, descriptor)) => format!("{} {}", absolute(url, base), descriptor.trim()),
+                None => absolute(candidate, base),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `url` for a double-quoted CSS string.
+fn css_escape(url: &str) -> String {
+    url.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\a ")
+}
+
+fn push_attr(out: &mut String, name: &str, value: &str) {
+    out.push_str(&format!(" {}=\"{}\"", name, escape_html(value)));
+}
@@ -0,0 +1,290 @@
+use crate::browser::tabs::TabId;
+use std::borrow::Cow;
+use std::path::PathBuf;
+use url::Url;
+use wry::http::{header::CONTENT_TYPE, Request, Response, StatusCode};
+
+/// Scheme of the custom protocol serving the app's own pages.
+pub const SCHEME: &str = "syncflo";
+
+/// Hosts of the internal pages, e.g. `history` for `syncflo://history/`.
+const HOSTS: &[&str] = &["app", "history", "bookmarks", "reading-list", "settings", "about", "reader", "error"];
+
+/// Env var pointing at an assets directory to serve from disk instead of the
+/// embedded copies, so HTML can be edited without rebuilding.
+pub const ASSETS_DIR_ENV: &str = "SYNCFLO_ASSETS_DIR";
+
+const NAV_HTML: &str = include_str!("../../assets/nav.html");
+const HOME_HTML: &str = include_str!("../../assets/home.html");
+const SIDEBAR_HTML: &str = include_str!("../../assets/sidebar.html");
+
+/// URL of an internal page, e.g. `url("app", "nav.html")` -> `syncflo://app/nav.html`.
+///
+/// Windows and Android only route custom protocols through `http://<scheme>.<host>`.
+pub fn url(host: &str, path: &str) -> String {
+    if cfg!(any(target_os = "windows", target_os = "android")) {
+        format!("http://{}.{}/{}", SCHEME, host, path)
+    } else {
+        format!("{}://{}/{}", SCHEME, host, path)
+    }
+}
+
+pub fn nav_url() -> String {
+    url("app", "nav.html")
+}
+
+pub fn home_url() -> String {
+    url("app", "home.html")
+}
+
+/// URL of the AI sidebar.
+pub fn sidebar_url() -> String {
+    url("app", "sidebar.html")
+}
+
+/// URL of the error page shown after `failed_url` didn't load.
+pub fn error_url(failed_url: &str, kind: &str) -> String {
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("url", failed_url)
+        .append_pair("kind", kind)
+        .finish();
+    url("error", &format!("?{}", query))
+}
+
+/// Whether `url` is the error page.
+pub fn is_error_url(url: &str) -> bool {
+    url.starts_with(&self::url("error", ""))
+}
+
+/// URL of the reader view of tab `tab_id`'s page.
+pub fn reader_url(tab_id: TabId) -> String {
+    url("reader", &format!("?tab={}", tab_id))
+}
+
+/// Whether `url` is a reader view.
+pub fn is_reader_url(url: &str) -> bool {
+    url.starts_with(&self::url("reader", ""))
+}
+
+/// URL of the reading list, or of the offline copy of its item `id`.
+pub fn reading_list_url(id: Option<&str>) -> String {
+    match id {
+        Some(id) => url("reading-list", &format!("{}/", id)),
+        None => url("reading-list", ""),
+    }
+}
+
+/// Assets directory set through [`ASSETS_DIR_ENV`], if any. A relative
+/// path is looked up next to the executable first, then in the working
+/// directory, since installed apps (on Windows especially) don't start in
+/// the directory they live in.
+pub fn dev_assets_dir() -> Option<PathBuf> {
+    let dir = PathBuf::from(std::env::var_os(ASSETS_DIR_ENV)?);
+    if dir.is_absolute() {
+        return Some(dir.canonicalize().unwrap_or(dir));
+    }
+    let exe_dir = std::env::current_exe().ok().and_then(|exe| Some(exe.parent()?.to_path_buf()));
+    exe_dir
+        .into_iter()
+        .chain(std::env::current_dir().ok())
+        .map(|base| base.join(&dir))
+        .find(|candidate| candidate.is_dir())
+        .map(|found| found.canonicalize().unwrap_or(found))
+        .or(Some(dir))
+}
+
+/// Contents of a bundled asset, read from the dev override directory if
+/// set. Pages the browser can't do without fall back to the embedded copy
+/// when the override directory lacks them.
+pub fn asset(name: &str) -> Option<Cow<'static, [u8]>> {
+    if name.split(['/', '\\']).any(|part| part == "..") {
+        return None;
+    }
+    if let Some(dir) = dev_assets_dir() {
+        match std::fs::read(dir.join(name)) {
+            Ok(bytes) => return Some(Cow::Owned(bytes)),
+            Err(e) => log::warn!("Failed to read {} from {}: {}", name, dir.display(), e),
+        }
+    }
+    embedded(name).map(|html| Cow::Borrowed(html.as_bytes()))
+}
+
+fn embedded(name: &str) -> Option<&'static str> {
+    match name {
+        "nav.html" => Some(NAV_HTML),
+        "home.html" => Some(HOME_HTML),
+        "sidebar.html" => Some(SIDEBAR_HTML),
+        _ => None,
+    }
+}
+
+/// Paths of the pages missing from the dev override directory, which are
+/// served from the embedded copies instead.
+pub fn missing_dev_assets() -> Vec<PathBuf> {
+    let Some(dir) = dev_assets_dir() else {
+        return Vec::new();
+    };
+    ["nav.html", "home.html", "sidebar.html"]
+        .into_iter()
+        .map(|name| dir.join(name))
+        .filter(|path| !path.is_file())
+        .collect()
+}
+
+fn mime_type(name: &str) -> &'static str {
+    match name.rsplit('.').next() {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Whether `url` points at an internal `syncflo://` page. Pages get
+/// privileged IPC by this, so anything short of the exact scheme, or on
+/// Windows and Android its exact `http://syncflo.<host>` form, is a web
+/// page, however it's named.
+pub fn is_internal_url(url: &str) -> bool {
+    is_internal(url, cfg!(any(target_os = "windows", target_os = "android")))
+}
+
+/// [`is_internal_url`], accepting the `http://syncflo.<host>` form if
+/// `mapped`.
+fn is_internal(url: &str, mapped: bool) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+    match url.scheme() {
+        SCHEME => true,
+        "http" if mapped => {
+            let host = url.host_str().and_then(|host| host.strip_prefix(SCHEME)?.strip_prefix('.'));
+            url.username().is_empty()
+                && url.password().is_none()
+                && url.port().is_none()
+                && host.is_some_and(|host| HOSTS.contains(&host))
+        }
+        _ => false,
+    }
+}
+
+/// Host part of an internal URL, accounting for the `http://syncflo.<host>` form.
+pub fn request_host(request: &Request<Vec<u8>>) -> &str {
+    let host = request.uri().host().unwrap_or("");
+    host.strip_prefix(SCHEME)
+        .and_then(|rest| rest.strip_prefix('.'))
+        .unwrap_or(host)
+}
+
+/// Whether a request comes from a web page trying to read an internal page
+/// (e.g. `fetch` or an iframe), as opposed to a navigation.
+pub fn is_cross_origin_request(request: &Request<Vec<u8>>) -> bool {
+    let header = |name: &str| request.headers().get(name).and_then(|v| v.to_str().ok());
+    if let Some(origin) = header("origin") {
+        if !origin.is_empty() && origin != "null" && !is_internal_url(origin) {
+            return true;
+        }
+    }
+    header("sec-fetch-site") == Some("cross-site") && header("sec-fetch-mode") != Some("navigate")
+}
+
+/// Serves a bundled asset from `syncflo://app/<path>`.
+pub fn serve_asset(path: &str) -> Response<Cow<'static, [u8]>> {
+    match asset(path) {
+        Some(body) => respond(StatusCode::OK, mime_type(path), body),
+        None => not_found(),
+    }
+}
+
+pub fn html_response(html: String) -> Response<Cow<'static, [u8]>> {
+    respond(StatusCode::OK, "text/html; charset=utf-8", Cow::Owned(html.into_bytes()))
+}
+
+/// A file of the app's own, typed by the extension of `name`.
+pub fn file_response(name: &str, body: Vec<u8>) -> Response<Cow<'static, [u8]>> {
+    respond(StatusCode::OK, mime_type(name), Cow::Owned(body))
+}
+
+pub fn not_found() -> Response<Cow<'static, [u8]>> {
+    respond(StatusCode::NOT_FOUND, "text/plain", Cow::Borrowed(b"Not found"))
+}
+
+pub fn forbidden() -> Response<Cow<'static, [u8]>> {
+    respond(StatusCode::FORBIDDEN, "text/plain", Cow::Borrowed(b"Forbidden"))
+}
+
+fn respond(status: StatusCode, content_type: &str, body: Cow<'static, [u8]>) -> Response<Cow<'static, [u8]>> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, content_type)
+        // Internal pages must never be embedded by web content
+        .header("X-Frame-Options", "DENY")
+        .header("Content-Security-Policy", "frame-ancestors 'none'")
+        .body(body)
+        .expect("static response parts are valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_pages_are_internal() {
+        for mapped in [false, true] {
+            assert!(is_internal("syncflo://app/nav.html", mapped));
+            assert!(is_internal("SYNCFLO://settings/", mapped));
+            assert!(is_internal("syncflo://history", mapped));
+        }
+        assert!(is_internal("http://syncflo.app/nav.html", true));
+        assert!(is_internal("http://syncflo.reading-list/abc/", true));
+        assert!(is_internal("http://SYNCFLO.SETTINGS", true));
+    }
+
+    #[test]
+    fn lookalike_hosts_are_web_pages() {
+        for url in [
+            "https://syncflo.com/",
+            "https://syncflo.app/",
+            "http://syncflo.com/",
+            "http://syncflo.evil.example/",
+            "http://syncflo.app.evil.example/",
+            "http://syncflo.app:8080/",
+            "http://user@syncflo.app/",
+            "http://syncfloapp/",
+            "http://app.syncflo/",
+            "https://example.com/?next=syncflo://app/",
+            "syncflo-evil://app/",
+            "",
+        ] {
+            assert!(!is_internal(url, true), "{} counts as internal", url);
+            assert!(!is_internal(url, false), "{} counts as internal", url);
+        }
+    }
+
+    #[test]
+    fn the_http_form_is_only_internal_where_it_is_used() {
+        assert!(!is_internal("http://syncflo.app/nav.html", false));
+    }
+
+    #[test]
+    fn page_urls_are_internal() {
+        for url in [nav_url(), home_url(), sidebar_url(), error_url("https://example.com/", "dns"), reader_url(3)] {
+            assert!(is_internal_url(&url), "{}", url);
+        }
+        assert!(is_internal_url(&reading_list_url(Some("abc"))));
+    }
+
+    #[test]
+    fn web_origins_are_cross_origin() {
+        let request = |origin: &str| Request::builder().header("origin", origin).body(Vec::new()).unwrap();
+        assert!(is_cross_origin_request(&request("https://syncflo.com")));
+        assert!(is_cross_origin_request(&request("https://example.com")));
+        assert!(!is_cross_origin_request(&request("syncflo://app")));
+        assert!(!is_cross_origin_request(&request("null")));
+    }
+}
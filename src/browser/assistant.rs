@@ -0,0 +1,320 @@
+//! Runs AI assistant requests on the background workers and streams their
+//! answers back to the event loop.
+
+use crate::ai::{
+    AIAssistant, AIResponse, ActionPage, AiError, Article, ContentProcessor, Conversation, ProposedAction,
+};
+use crate::browser::ipc::AppEvent;
+use crate::browser::pages;
+use crate::browser::tabs::TabId;
+use crate::browser::{worker, Navigation};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tao::event_loop::EventLoopProxy;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+/// Streamed text is forwarded at most this often, so the UI isn't sent a
+/// script per token.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Identifies an AI request, so text of a cancelled request that was
+/// already on its way isn't mixed into the next one's.
+pub type AiRequestId = u64;
+
+static NEXT_REQUEST: AtomicU64 = AtomicU64::new(1);
+
+/// A message to send in a tab's conversation with the assistant.
+#[derive(Debug, Clone)]
+pub struct ChatTurn {
+    pub tab: TabId,
+    pub request: AiRequestId,
+    /// A copy of the tab's conversation; the updated one comes back in
+    /// [`AppEvent::AiReply`].
+    pub conversation: Conversation,
+    /// URL and HTML of the page, to make the conversation about it.
+    pub page: Option<(Url, String)>,
+    pub message: String,
+}
+
+pub fn next_request_id() -> AiRequestId {
+    NEXT_REQUEST.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Streams the answer of `assistant` about `page`, a URL and its HTML, as
+/// [`AppEvent::AiText`] events, then sends [`AppEvent::AiFinished`].
+/// Nothing more is sent once `cancel` is cancelled.
+pub fn stream_page(
+    proxy: EventLoopProxy<AppEvent>,
+    assistant: AIAssistant,
+    tab: TabId,
+    request: AiRequestId,
+    page: (Url, String),
+    cancel: CancellationToken,
+) {
+    worker::spawn(async move {
+        let (url, html) = page;
+        let article = match ContentProcessor::new().extract_article(&html, &url) {
+            Ok(article) => article,
+            Err(e) => {
+                log::warn!("Can't ask about {}: {:#}", url, e);
+                let error = Some("The page has no text to ask about".to_string());
+                let _ = proxy.send_event(AppEvent::AiFinished { tab, request, error });
+                return;
+            }
+        };
+        let mut pending = String::new();
+        let mut last_flush = Instant::now();
+        let result = assistant
+            .process_page_stream(url.as_str(), &article, &cancel, |token| {
+                pending.push_str(token);
+                if last_flush.elapsed() >= FLUSH_INTERVAL {
+                    let text = std::mem::take(&mut pending);
+                    let _ = proxy.send_event(AppEvent::AiText { tab, request, text });
+                    last_flush = Instant::now();
+                }
+            })
+            .await;
+        let error = match result {
+            Ok(_) => None,
+            Err(AiError::Cancelled) => return,
+            Err(e) => {
+                log::warn!("AI request about {} failed: {}", url, e);
+                Some(e.to_string())
+            }
+        };
+        if !pending.is_empty() {
+            let _ = proxy.send_event(AppEvent::AiText { tab, request, text: pending });
+        }
+        let _ = proxy.send_event(AppEvent::AiFinished { tab, request, error });
+    });
+}
+
+/// Sends `turn` to `assistant`, then sends back the answer and the updated
+/// conversation as [`AppEvent::AiReply`]. Nothing is sent once `cancel` is
+/// cancelled.
+pub fn chat(proxy: EventLoopProxy<AppEvent>, assistant: AIAssistant, turn: ChatTurn, cancel: CancellationToken) {
+    worker::spawn(async move {
+        let ChatTurn { tab, request, mut conversation, page, message } = turn;
+        if let Some((url, html)) = page {
+            match ContentProcessor::new().extract_article(&html, &url) {
+                Ok(article) => {
+                    // Only fails when unconfigured, which the chat reports
+                    let _ = assistant.set_page(&mut conversation, url.as_str(), &article);
+                }
+                Err(e) => log::warn!("Chatting without the page, which couldn't be read: {:#}", e),
+            }
+        }
+        let result = until_cancelled(&cancel, assistant.chat(&mut conversation, &message)).await;
+        let response: Result<AIResponse, String> = match result {
+            Ok(response) => Ok(response),
+            Err(AiError::Cancelled) => return,
+            Err(e) => {
+                log::warn!("AI chat request failed: {}", e);
+                Err(e.to_string())
+            }
+        };
+        let conversation = Box::new(conversation);
+        let _ = proxy.send_event(AppEvent::AiReply { tab, request, conversation, response });
+    });
+}
+
+/// A one-off question to the assistant.
+#[derive(Debug, Clone)]
+pub enum Question {
+    /// Summarize the page at `url` with `html`.
+    Summary { url: Url, html: String },
+    /// Explain `text` selected on the page at `url` titled `title`.
+    Selection { url: String, title: String, text: String },
+}
+
+/// Asks `assistant` `question`, then sends the answer as
+/// [`AppEvent::AiAnswer`]. Nothing is sent once `cancel` is cancelled.
+pub fn ask(
+    proxy: EventLoopProxy<AppEvent>,
+    assistant: AIAssistant,
+    tab: TabId,
+    request: AiRequestId,
+    question: Question,
+    cancel: CancellationToken,
+) {
+    worker::spawn(async move {
+        let result = match &question {
+            Question::Summary { url, html } => match read_article(html, url) {
+                Ok((article, cut_off)) => until_cancelled(&cancel, assistant.summarize_page(url.as_str(), &article))
+                    .await
+                    .map(|mut response| {
+                        // The toast says only the start was summarized
+                        response.truncated |= cut_off;
+                        response
+                    }),
+                Err(e) => {
+                    log::warn!("Can't summarize {}: {:#}", url, e);
+                    let response = Err("The page has no text to summarize".to_string());
+                    let _ = proxy.send_event(AppEvent::AiAnswer { tab, request, response });
+                    return;
+                }
+            },
+            Question::Selection { url, title, text } => {
+                until_cancelled(&cancel, assistant.explain_selection(url, title, text)).await
+            }
+        };
+        let response = match result {
+            Ok(response) => Ok(response),
+            Err(AiError::Cancelled) => return,
+            Err(e) => {
+                log::warn!("AI request {} failed: {}", request, e);
+                Err(e.to_string())
+            }
+        };
+        let _ = proxy.send_event(AppEvent::AiAnswer { tab, request, response });
+    });
+}
+
+/// The article of the page at `url` with `html`, and whether the page was
+/// too long to read all of.
+fn read_article(html: &str, url: &Url) -> anyhow::Result<(Article, bool)> {
+    let processor = ContentProcessor::new();
+    let page = processor.parse(html);
+    let article = processor.extract_article_from(&page, url)?;
+    if page.is_truncated() {
+        log::warn!("Summarizing only the start of {}", url);
+    }
+    Ok((article, page.is_truncated()))
+}
+
+/// Asks `assistant` for an action doing what `instruction` says on `page`,
+/// a URL and its HTML, then sends it as [`AppEvent::AiAction`]. Nothing is
+/// sent once `cancel` is cancelled.
+pub fn suggest_action(
+    proxy: EventLoopProxy<AppEvent>,
+    assistant: AIAssistant,
+    tab: TabId,
+    request: AiRequestId,
+    page: (Url, String),
+    instruction: String,
+    cancel: CancellationToken,
+) {
+    worker::spawn(async move {
+        let (url, html) = page;
+        let page = ActionPage::from_html(&html, &url);
+        let action: Result<ProposedAction, String> =
+            match until_cancelled(&cancel, assistant.suggest_action(&instruction, &page)).await {
+                Ok(action) => Ok(action),
+                Err(AiError::Cancelled) => return,
+                Err(e) => {
+                    log::warn!("AI request {} for an action failed: {}", request, e);
+                    Err(e.to_string())
+                }
+            };
+        let _ = proxy.send_event(AppEvent::AiAction { tab, request, url, action });
+    });
+}
+
+/// Translates the article on `page`, a URL and its HTML, into `lang` with
+/// `assistant`, then sends its reader view as [`AppEvent::Translated`].
+/// Nothing is sent once `cancel` is cancelled.
+pub fn translate(
+    proxy: EventLoopProxy<AppEvent>,
+    assistant: AIAssistant,
+    tab: TabId,
+    request: AiRequestId,
+    page: (Url, String),
+    lang: String,
+    cancel: CancellationToken,
+) {
+    worker::spawn(async move {
+        let (url, html) = page;
+        let processor = ContentProcessor::new();
+        let article = match processor.extract_article(&html, &url) {
+            Ok(article) => article,
+            Err(e) => {
+                log::warn!("Can't translate {}: {:#}", url, e);
+                let page = Err("The page has no text to translate".to_string());
+                let _ = proxy.send_event(AppEvent::Translated { tab, request, url: url.into(), page });
+                return;
+            }
+        };
+        let page = match until_cancelled(&cancel, assistant.translate_article(&article, &lang)).await {
+            Ok(article) => Ok(pages::reader(&article, &processor.analyze(&article.text), url.as_str())),
+            Err(AiError::Cancelled) => return,
+            Err(e) => {
+                log::warn!("Translating {} failed: {}", url, e);
+                Err(e.to_string())
+            }
+        };
+        let _ = proxy.send_event(AppEvent::Translated { tab, request, url: url.into(), page });
+    });
+}
+
+/// Embeds `text`, the content of the page at `url`, with `assistant` and
+/// keeps the embedding with the page's entry in `history`. Pages embedded
+/// before aren't sent again.
+pub fn embed_page(assistant: AIAssistant, history: Arc<Mutex<Navigation>>, url: Url, text: String) {
+    if history.lock().map_or(true, |history| history.has_embedding(&url)) {
+        return;
+    }
+    worker::spawn(async move {
+        match assistant.embed(&text).await {
+            Ok(embedding) => {
+                if let Ok(mut history) = history.lock() {
+                    history.set_embedding(&url, embedding);
+                }
+            }
+            Err(e) => log::warn!("Failed to embed {}: {}", url, e),
+        }
+    });
+}
+
+/// Finds the `k` pages in `history` meaning the most like `query` with
+/// `assistant`, then sends them as [`AppEvent::HistoryMatches`].
+pub fn search_history(
+    proxy: EventLoopProxy<AppEvent>,
+    assistant: AIAssistant,
+    history: Arc<Mutex<Navigation>>,
+    tab: TabId,
+    query: String,
+    k: usize,
+) {
+    worker::spawn(async move {
+        let matches = assistant.embed(&query).await.map_err(|e| e.to_string());
+        // The history is only locked once the query is embedded
+        let matches = matches.and_then(|query| {
+            let history = history.lock().map_err(|e| e.to_string())?;
+            let matches = history.semantic_search(&query, k).into_iter().map(|(entry, score)| {
+                serde_json::json!({ "url": entry.url, "title": entry.title, "score": score })
+            });
+            Ok(matches.collect())
+        });
+        if let Err(e) = &matches {
+            log::warn!("Searching history by meaning failed: {}", e);
+        }
+        let _ = proxy.send_event(AppEvent::HistoryMatches { tab, matches });
+    });
+}
+
+/// Sends a test request with `assistant`, then sends what answered, or why
+/// nothing did, as [`AppEvent::AiValidated`].
+pub fn validate(proxy: EventLoopProxy<AppEvent>, assistant: AIAssistant, tab: TabId) {
+    worker::spawn(async move {
+        let result = assistant.validate().await.map_err(|e| e.to_string());
+        if let Err(e) = &result {
+            log::warn!("Testing the AI connection failed: {}", e);
+        }
+        let _ = proxy.send_event(AppEvent::AiValidated { tab, result });
+    });
+}
+
+/// Runs `request`, failing with [`AiError::Cancelled`] as soon as
+/// `cancel` is cancelled.
+async fn until_cancelled<T>(
+    cancel: &CancellationToken,
+    request: impl Future<Output = Result<T, AiError>>,
+) -> Result<T, AiError> {
+    tokio::select! {
+        _ = cancel.cancelled() => Err(AiError::Cancelled),
+        response = request => response,
+    }
+}
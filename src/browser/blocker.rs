@@ -0,0 +1,325 @@
+//! Ad and tracker blocking from hosts-file style filter lists.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Hosts-file entries that name the machine itself rather than a tracker.
+const LOCAL_NAMES: &[&str] = &[
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+    "0.0.0.0",
+];
+
+/// The `[blocking]` section of the config file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlockingSettings {
+    pub enabled: bool,
+    /// Sites where blocking was turned off with the nav bar toggle.
+    pub disabled_hosts: Vec<String>,
+}
+
+impl Default for BlockingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            disabled_hosts: Vec::new(),
+        }
+    }
+}
+
+impl BlockingSettings {
+    /// Whether requests made by pages on `host` are filtered.
+    pub fn is_enabled_for(&self, host: &str) -> bool {
+        self.enabled && !self.disabled_hosts.iter().any(|h| h.eq_ignore_ascii_case(host))
+    }
+
+    /// Turns blocking on `host` off, or back on. Returns whether it's now on.
+    pub fn toggle(&mut self, host: &str) -> bool {
+        let before = self.disabled_hosts.len();
+        self.disabled_hosts.retain(|h| !h.eq_ignore_ascii_case(host));
+        if self.disabled_hosts.len() == before {
+            self.disabled_hosts.push(host.to_ascii_lowercase());
+            return false;
+        }
+        true
+    }
+}
+
+/// Blocked domains. A domain also blocks all of its subdomains.
+#[derive(Debug, Default)]
+pub struct Blocklist {
+    domains: HashSet<String>,
+}
+
+impl Blocklist {
+    /// Loads every `*.txt` list in `dir`. A missing directory yields an
+    /// empty list; unreadable files are skipped with a warning.
+    pub fn load_dir(dir: &Path) -> Self {
+        let mut list = Self::default();
+        let Ok(entries) = fs::read_dir(dir) else {
+            return list;
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.extension().and_then(|ext| ext.to_str()) != Some("txt") {
+                continue;
+            }
+            match fs::read_to_string(&path) {
+                Ok(text) => list.extend_from_str(&text),
+                Err(e) => log::warn!("Skipping filter list {}: {}", path.display(), e),
+            }
+        }
+        list
+    }
+
+    /// Adds the domains of a hosts file (`0.0.0.0 ads.example.com`) or a
+    /// plain list with one domain per line. `#` starts a comment.
+    pub fn extend_from_str(&mut self, text: &str) {
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            let mut fields = line.split_whitespace();
+            let Some(first) = fields.next() else {
+                continue;
+            };
+            // Hosts files put an address first; plain lists don't
+            let names: Vec<&str> = if first.parse::<std::net::IpAddr>().is_ok() {
+                fields.collect()
+            } else {
+                vec![first]
+            };
+            for name in names {
+                let name = name.trim_end_matches('.').to_ascii_lowercase();
+                if is_domain(&name) && !LOCAL_NAMES.contains(&name.as_str()) {
+                    self.domains.insert(name);
+                }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.domains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domains.is_empty()
+    }
+
+    /// Whether requests to `host` are blocked, checking `host` itself and
+    /// each parent domain, so a lookup costs one hash per label.
+    pub fn is_blocked(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.');
+        if self.domains.is_empty() || host.is_empty() {
+            return false;
+        }
+        let host = host.to_ascii_lowercase();
+        let mut rest = host.as_str();
+        loop {
+            if self.domains.contains(rest) {
+                return true;
+            }
+            match rest.split_once('.') {
+                Some((_, parent)) => rest = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// The list as WebKit content blocker rules. Only third-party loads are
+    /// blocked, so typing a listed site into the address bar still works.
+    pub fn content_rules_json(&self) -> String {
+        let rules: Vec<serde_json::Value> = self
+            .domains
+            .iter()
+            .map(|domain| {
+                serde_json::json!({
+                    "trigger": {
+                        "url-filter": format!("^[^:]+://+([^:/]+\\.)?{}[:/]", domain.replace('.', "\\.")),
+                        "load-type": ["third-party"],
+                    },
+                    "action": { "type": "block" },
+                })
+            })
+            .collect();
+        serde_json::Value::Array(rules).to_string()
+    }
+}
+
+fn is_domain(name: &str) -> bool {
+    name.contains('.')
+        && name
+            .split('.')
+            .all(|label| !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+}
+
+#[cfg(target_os = "linux")]
+pub use self::webkit::ContentFilter;
+
+/// WebKitGTK content filters. The bindings don't wrap them, so this calls
+/// the C API directly.
+#[cfg(target_os = "linux")]
+mod webkit {
+    use super::Blocklist;
+    use gtk::glib::{self, translate::*};
+    use std::ffi::CString;
+    use std::path::Path;
+    use webkit2gtk::ffi;
+
+    type Callback = Box<dyn FnOnce(anyhow::Result<ContentFilter>)>;
+
+    /// A compiled filter list that can be attached to webviews.
+    pub struct ContentFilter(*mut ffi::WebKitUserContentFilter);
+
+    impl ContentFilter {
+        const IDENTIFIER: &'static str = "syncflo-blocklist";
+
+        /// Compiles `list` in the background, caching it in `store_dir`,
+        /// and calls `done` on the main thread when it's ready.
+        pub fn compile(
+            list: &Blocklist,
+            store_dir: &Path,
+            done: impl FnOnce(anyhow::Result<ContentFilter>) + 'static,
+        ) {
+            let (Ok(path), Ok(id)) = (
+                CString::new(store_dir.to_string_lossy().as_bytes()),
+                CString::new(Self::IDENTIFIER),
+            ) else {
+                done(Err(anyhow::anyhow!("Invalid filter store path {}", store_dir.display())));
+                return;
+            };
+            let source = glib::Bytes::from_owned(list.content_rules_json().into_bytes());
+            let done: *mut Callback = Box::into_raw(Box::new(Box::new(done)));
+            unsafe {
+                let store = ffi::webkit_user_content_filter_store_new(path.as_ptr());
+                ffi::webkit_user_content_filter_store_save(
+                    store,
+                    id.as_ptr(),
+                    source.to_glib_none().0,
+                    std::ptr::null_mut(),
+                    Some(save_finished),
+                    done.cast(),
+                );
+                // The pending save holds its own reference to the store
+                glib::gobject_ffi::g_object_unref(store.cast());
+            }
+        }
+
+        /// Enables or disables the filter for the next loads in `webview`.
+        pub fn apply(&self, webview: &webkit2gtk::WebView, enabled: bool) {
+            use webkit2gtk::{UserContentManagerExt, WebViewExt};
+
+            let Some(manager) = webview.user_content_manager() else {
+                return;
+            };
+            if enabled {
+                let manager: *mut ffi::WebKitUserContentManager = manager.to_glib_none().0;
+                unsafe { ffi::webkit_user_content_manager_add_filter(manager, self.0) };
+            } else {
+                manager.remove_filter_by_id(Self::IDENTIFIER);
+            }
+        }
+    }
+
+    impl Drop for ContentFilter {
+        fn drop(&mut self) {
+            unsafe { ffi::webkit_user_content_filter_unref(self.0) };
+        }
+    }
+
+    unsafe extern "C" fn save_finished(
+        store: *mut glib::gobject_ffi::GObject,
+        result: *mut gtk::gio::ffi::GAsyncResult,
+        done: glib::ffi::gpointer,
+    ) {
+        let done = Box::from_raw(done.cast::<Callback>());
+        let mut error = std::ptr::null_mut();
+        let filter = ffi::webkit_user_content_filter_store_save_finish(store.cast(), result, &mut error);
+        if filter.is_null() {
+            let error: glib::Error = from_glib_full(error);
+            done(Err(anyhow::anyhow!("Failed to compile filter list: {}", error)));
+        } else {
+            done(Ok(ContentFilter(filter)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(text: &str) -> Blocklist {
+        let mut list = Blocklist::default();
+        list.extend_from_str(text);
+        list
+    }
+
+    #[test]
+    fn hosts_files_and_plain_lists_are_parsed() {
+        let list = list(
+            "# Ad servers\n\
+             127.0.0.1 localhost\n\
+             ::1 ip6-localhost ip6-loopback\n\
+             0.0.0.0 ads.example.com tracker.example.net # two on a line\n\
+             \n\
+             Metrics.Example.org.\n",
+        );
+        assert_eq!(list.len(), 3);
+        assert!(list.is_blocked("ads.example.com"));
+        assert!(list.is_blocked("tracker.example.net"));
+        assert!(list.is_blocked("metrics.example.org"));
+        assert!(!list.is_blocked("localhost"));
+    }
+
+    #[test]
+    fn junk_lines_are_skipped() {
+        let list = list("0.0.0.0 0.0.0.0\nnot-a-domain\nbad..example.com\nhttps://example.com/ad\n");
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn subdomains_are_blocked_but_not_parents_or_lookalikes() {
+        let list = list("ads.example.com");
+        assert!(list.is_blocked("ads.example.com"));
+        assert!(list.is_blocked("cdn.eu.ads.example.com"));
+        assert!(list.is_blocked("ADS.Example.COM."));
+        assert!(!list.is_blocked("example.com"));
+        assert!(!list.is_blocked("badads.example.com"));
+        assert!(!list.is_blocked("ads.example.com.evil.test"));
+        assert!(!list.is_blocked(""));
+    }
+
+    #[test]
+    fn only_txt_lists_in_the_directory_are_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("ads.txt"), "0.0.0.0 ads.example.com\n").unwrap();
+        fs::write(dir.path().join("trackers.txt"), "tracker.example.net\n").unwrap();
+        fs::write(dir.path().join("README.md"), "readme.example.com\n").unwrap();
+        let list = Blocklist::load_dir(dir.path());
+        assert_eq!(list.len(), 2);
+        assert!(!list.is_blocked("readme.example.com"));
+        assert!(Blocklist::load_dir(&dir.path().join("missing")).is_empty());
+    }
+
+    #[test]
+    fn content_rules_escape_dots() {
+        let rules: serde_json::Value = serde_json::from_str(&list("ads.example.com").content_rules_json()).unwrap();
+        assert_eq!(rules[0]["trigger"]["url-filter"], "^[^:]+://+([^:/]+\\.)?ads\\.example\\.com[:/]");
+        assert_eq!(rules[0]["action"]["type"], "block");
+    }
+
+    #[test]
+    fn blocking_can_be_toggled_per_site() {
+        let mut settings = BlockingSettings::default();
+        assert!(settings.is_enabled_for("example.com"));
+        assert!(!settings.toggle("Example.com"));
+        assert!(!settings.is_enabled_for("example.com"));
+        assert!(settings.is_enabled_for("other.example"));
+        assert!(settings.toggle("example.com"));
+        assert!(settings.is_enabled_for("example.com"));
+    }
+}
@@ -0,0 +1,345 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+/// Microseconds between 1601-01-01 (Chrome's epoch) and 1970-01-01.
+const CHROME_EPOCH_OFFSET_MICROS: u64 = 11_644_473_600_000_000;
+
+/// A saved page. Each URL is bookmarked at most once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub url: Url,
+    pub title: String,
+    pub added_at: SystemTime,
+    /// Folder path, outermost first. Empty for top-level bookmarks.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub folder: Vec<String>,
+}
+
+/// Outcome of a bookmark import.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Already bookmarked, or repeated within the file.
+    pub skipped: usize,
+    /// Entries without a usable URL (e.g. bookmarklets).
+    pub invalid: usize,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct BookmarkStore {
+    /// Bookmarks in the order they were added.
+    bookmarks: Vec<Bookmark>,
+    #[serde(skip)]
+    store_path: Option<PathBuf>,
+}
+
+impl BookmarkStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads bookmarks from `path` and autosaves back to it on every change.
+    /// A missing or corrupt file yields an empty store instead of an error.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let mut store = Self::load_from(&path);
+        store.store_path = Some(path);
+        store
+    }
+
+    /// Loads bookmarks from `path` but keeps changes in memory, for
+    /// incognito windows.
+    pub fn load_from(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str::<BookmarkStore>(&text).unwrap_or_else(|e| {
+                log::warn!("Ignoring corrupt bookmarks file {}: {}", path.display(), e);
+                BookmarkStore::new()
+            }),
+            Err(_) => BookmarkStore::new(),
+        }
+    }
+
+    /// Writes the bookmarks to their store path, if any.
+    pub fn save(&self) -> Result<()> {
+        match &self.store_path {
+            Some(path) => self.save_to(path),
+            None => Ok(()),
+        }
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let json = serde_json::to_string(self).context("Failed to serialize bookmarks")?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(())
+    }
+
+    fn autosave(&self) {
+        if let Err(e) = self.save() {
+            log::warn!("Failed to save bookmarks: {:#}", e);
+        }
+    }
+
+    /// Bookmarks `url`. Bookmarking it again only updates the title.
+    pub fn add(&mut self, url: Url, title: impl Into<String>) {
+        let title = title.into();
+        match self.bookmarks.iter_mut().find(|b| b.url == url) {
+            Some(bookmark) => bookmark.title = title,
+            None => self.bookmarks.push(Bookmark {
+                url,
+                title,
+                added_at: SystemTime::now(),
+                folder: Vec::new(),
+            }),
+        }
+        self.autosave();
+    }
+
+    pub fn remove(&mut self, url: &Url) -> bool {
+        let before = self.bookmarks.len();
+        self.bookmarks.retain(|b| &b.url != url);
+        let removed = self.bookmarks.len() != before;
+        if removed {
+            self.autosave();
+        }
+        removed
+    }
+
+    pub fn is_bookmarked(&self, url: &Url) -> bool {
+        self.bookmarks.iter().any(|b| &b.url == url)
+    }
+
+    /// Up to `limit` bookmarks whose title or URL contains `query`, ignoring
+    /// case, newest first.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<&Bookmark> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        self.bookmarks
+            .iter()
+            .rev()
+            .filter(|b| b.title.to_lowercase().contains(&query) || b.url.as_str().to_lowercase().contains(&query))
+            .take(limit)
+            .collect()
+    }
+
+    /// All bookmarks, oldest first.
+    pub fn list(&self) -> &[Bookmark] {
+        &self.bookmarks
+    }
+
+    /// Writes the bookmarks in the Netscape HTML format that every browser
+    /// can import, nesting them by folder.
+    pub fn export_netscape_html(&self, mut writer: impl Write) -> Result<()> {
+        use crate::browser::pages::escape_html;
+
+        writer.write_all(
+            b"<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+<!-- This is an automatically generated file.\n     It will be read and overwritten.\n     DO NOT EDIT! -->\n\
+<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+<TITLE>Bookmarks</TITLE>\n<H1>Bookmarks</H1>\n<DL><p>\n",
+        )?;
+
+        // Group by folder; the sort is stable, so each folder keeps its order
+        let mut sorted: Vec<&Bookmark> = self.bookmarks.iter().collect();
+        sorted.sort_by(|a, b| a.folder.cmp(&b.folder));
+
+        let mut open: &[String] = &[];
+        for bookmark in sorted {
+            let common = open
+                .iter()
+                .zip(&bookmark.folder)
+                .take_while(|(a, b)| a == b)
+                .count();
+            for depth in (common..open.len()).rev() {
+                writeln!(writer, "{}</DL><p>", indent(depth + 1))?;
+            }
+            for (depth, name) in bookmark.folder.iter().enumerate().skip(common) {
+                writeln!(writer, "{}<DT><H3>{}</H3>", indent(depth + 1), escape_html(name))?;
+                writeln!(writer, "{}<DL><p>", indent(depth + 1))?;
+            }
+            open = &bookmark.folder;
+
+            let added = bookmark
+                .added_at
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            writeln!(
+                writer,
+                "{}<DT><A HREF=\"{}\" ADD_DATE=\"{}\">{}</A>",
+                indent(open.len() + 1),
+                escape_html(bookmark.url.as_str()),
+                added,
+                escape_html(&bookmark.title),
+            )?;
+        }
+        for depth in (0..open.len()).rev() {
+            writeln!(writer, "{}</DL><p>", indent(depth + 1))?;
+        }
+        writer.write_all(b"</DL><p>\n")?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Imports a bookmarks export, detecting whether it's Chrome's
+    /// `Bookmarks` JSON or the Netscape HTML format.
+    pub fn import_file(&mut self, path: &Path) -> Result<ImportReport> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if text.trim_start().starts_with('{') {
+            self.import_chrome_json(&text)
+        } else {
+            Ok(self.import_netscape_html(&text))
+        }
+    }
+
+    /// Imports the Netscape bookmarks HTML that every browser can export.
+    pub fn import_netscape_html(&mut self, html: &str) -> ImportReport {
+        let mut report = ImportReport::default();
+        for (href, title, added_at, folder) in parse_netscape_html(html) {
+            self.import_one(&href, title, added_at, folder, &mut report);
+        }
+        self.autosave();
+        report
+    }
+
+    /// Imports the JSON of Chrome's `Bookmarks` file, in its profile directory.
+    pub fn import_chrome_json(&mut self, json: &str) -> Result<ImportReport> {
+        let file: ChromeBookmarks =
+            serde_json::from_str(json).context("Not a Chrome bookmarks file")?;
+        let mut report = ImportReport::default();
+        for root in file.roots.values() {
+            self.import_chrome_node(root, &mut Vec::new(), &mut report);
+        }
+        self.autosave();
+        Ok(report)
+    }
+
+    fn import_chrome_node(&mut self, node: &ChromeNode, folder: &mut Vec<String>, report: &mut ImportReport) {
+        let added_at = node
+            .date_added
+            .as_deref()
+            .and_then(|micros| micros.parse::<u64>().ok())
+            .and_then(|micros| micros.checked_sub(CHROME_EPOCH_OFFSET_MICROS))
+            .map(|micros| UNIX_EPOCH + Duration::from_micros(micros));
+        match node.kind.as_str() {
+            "url" => {
+                let url = node.url.as_deref().unwrap_or("");
+                self.import_one(url, node.name.clone(), added_at, folder.clone(), report);
+            }
+            "folder" => {
+                folder.push(node.name.clone());
+                for child in &node.children {
+                    self.import_chrome_node(child, folder, report);
+                }
+                folder.pop();
+            }
+            _ => {}
+        }
+    }
+
+    fn import_one(
+        &mut self,
+        href: &str,
+        title: String,
+        added_at: Option<SystemTime>,
+        folder: Vec<String>,
+        report: &mut ImportReport,
+    ) {
+        let url = match Url::parse(href) {
+            Ok(url) if matches!(url.scheme(), "http" | "https" | "file" | "ftp") => url,
+            _ => {
+                report.invalid += 1;
+                return;
+            }
+        };
+        if self.is_bookmarked(&url) {
+            report.skipped += 1;
+            return;
+        }
+        let title = if title.trim().is_empty() { url.to_string() } else { title };
+        self.bookmarks.push(Bookmark {
+            url,
+            title,
+            added_at: added_at.unwrap_or_else(SystemTime::now),
+            folder,
+        });
+        report.imported += 1;
+    }
+}
+
+fn indent(depth: usize) -> String {
+    "    ".repeat(depth)
+}
+
+/// `(href, title, added_at, folder)` for every link in a Netscape bookmarks file.
+///
+/// Folders are `<DT><H3>name</H3><DL>...</DL>`; the HTML parser nests the
+/// `<DL>` inside the folder's `<DT>`, so a link's folder path is the `<H3>`
+/// of each enclosing `<DT>`.
+fn parse_netscape_html(html: &str) -> Vec<(String, String, Option<SystemTime>, Vec<String>)> {
+    use scraper::{ElementRef, Html, Selector};
+
+    let document = Html::parse_document(html);
+    let links = Selector::parse("a[href]").expect("valid selector");
+    let mut out = Vec::new();
+    for link in document.select(&links) {
+        let mut folder = Vec::new();
+        for ancestor in link.ancestors().filter_map(ElementRef::wrap) {
+            if ancestor.value().name() != "dt" {
+                continue;
+            }
+            let heading = ancestor
+                .children()
+                .filter_map(ElementRef::wrap)
+                .find(|child| child.value().name() == "h3");
+            if let Some(heading) = heading {
+                folder.push(heading.text().collect::<String>().trim().to_string());
+            }
+        }
+        folder.reverse();
+
+        let element = link.value();
+        let added_at = element
+            .attr("add_date")
+            .and_then(|secs| secs.trim().parse::<u64>().ok())
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+        out.push((
+            element.attr("href").unwrap_or("").to_string(),
+            link.text().collect::<String>().trim().to_string(),
+            added_at,
+            folder,
+        ));
+    }
+    out
+}
+
+/// The parts of Chrome's `Bookmarks` file that are imported.
+#[derive(Deserialize)]
+struct ChromeBookmarks {
+    roots: std::collections::BTreeMap<String, ChromeNode>,
+}
+
+#[derive(Deserialize)]
+struct ChromeNode {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    url: Option<String>,
+    date_added: Option<String>,
+    #[serde(default)]
+    children: Vec<ChromeNode>,
+}
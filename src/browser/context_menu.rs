@@ -0,0 +1,187 @@
+//! The right-click menu of content webviews. A page script reports what was
+//! clicked and Rust shows a native menu whose items run IPC commands.
+
+use crate::browser::ipc::{self, IpcCommand, IpcContext};
+use crate::browser::tabs::TabId;
+use muda::{Menu, MenuItem, PredefinedMenuItem};
+use serde::Deserialize;
+
+/// Prefix of context menu item ids, keeping them apart from the menu bar's.
+const ID_PREFIX: &str = "context:";
+/// Longer selections are cut off in item labels.
+const LABEL_CHARS: usize = 24;
+
+/// What was right-clicked, as reported by [`SCRIPT`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ContextTarget {
+    /// Where the click was, in CSS pixels from the page's top left.
+    pub x: f64,
+    pub y: f64,
+    #[serde(default)]
+    pub link: Option<String>,
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub selection: Option<String>,
+}
+
+/// The menu on screen and what it was opened on.
+pub struct OpenMenu {
+    // Dropping the menu would take it off the screen
+    _menu: Menu,
+    target: ContextTarget,
+}
+
+// Injected into every page of the content webviews. Editable fields and
+// pages with their own menu keep the default one.
+pub const SCRIPT: &str = r#"
+(function() {
+  if (window.top !== window || window.__syncfloContextMenu) return;
+  window.__syncfloContextMenu = true;
+  function editable(el) {
+    for (; el; el = el.parentElement) {
+      if (el.isContentEditable || el.tagName === 'INPUT' || el.tagName === 'TEXTAREA' || el.tagName === 'SELECT') return true;
+    }
+    return false;
+  }
+  window.addEventListener('contextmenu', function(e) {
+    if (e.defaultPrevented || editable(e.target)) return;
+    var link = e.target.closest && e.target.closest('a[href]');
+    var image = e.target.closest && e.target.closest('img');
+    var selection = String(window.getSelection() || '').trim();
+    e.preventDefault();
+    try {
+      window.ipc.postMessage(JSON.stringify({ op: 'context_menu', payload: {
+        x: e.clientX, y: e.clientY,
+        link: link ? link.href : null,
+        image: image ? (image.currentSrc || image.src) : null,
+        selection: selection || null
+      } }));
+    } catch (err) {}
+  });
+})();
+"#;
+
+/// Shows the menu for a right-click in tab `tab_id`.
+pub fn show(ctx: &IpcContext, tab_id: TabId, target: ContextTarget) {
+    let tab = ctx
+        .tabs
+        .borrow_mut()
+        .get_mut(tab_id)
+        .map(|tab| (tab.navigation.can_go_back(), tab.navigation.can_go_forward(), tab.zoom));
+    let Some((can_go_back, can_go_forward, zoom)) = tab else {
+        return;
+    };
+    let menu = match build(&target, can_go_back, can_go_forward, ctx.app.is_none()) {
+        Ok(menu) => menu,
+        Err(e) => {
+            log::warn!("Failed to build context menu: {}", e);
+            return;
+        }
+    };
+    (ctx.popup_menu)(&menu, target.x * zoom, target.y * zoom);
+    *ctx.context_menu.borrow_mut() = Some(OpenMenu { _menu: menu, target });
+}
+
+/// The menu for `target`. Without `tabs`, as in app windows, items that
+/// open tabs or navigate the page are left out.
+fn build(target: &ContextTarget, can_go_back: bool, can_go_forward: bool, tabs: bool) -> muda::Result<Menu> {
+    let item = |action: &str, text: &str, enabled: bool| {
+        MenuItem::with_id(format!("{}{}", ID_PREFIX, action), text, enabled, None)
+    };
+    let menu = Menu::new();
+    if target.link.is_some() {
+        if tabs {
+            menu.append(&item("open_link", "새 탭에서 링크 열기", true))?;
+            menu.append(&item("read_link_later", "링크를 읽기 목록에 저장", true))?;
+        }
+        menu.append(&item("copy_link", "링크 주소 복사", true))?;
+        menu.append(&PredefinedMenuItem::separator())?;
+    }
+    if target.image.is_some() {
+        menu.append(&item("save_image", "이미지 저장", true))?;
+        menu.append(&PredefinedMenuItem::separator())?;
+    }
+    if let Some(selection) = &target.selection {
+        let mut label: String = selection.chars().take(LABEL_CHARS).collect();
+        if label.len() < selection.len() {
+            label.push('…');
+        }
+        menu.append(&item("copy", "복사", true))?;
+        if tabs {
+            menu.append(&item("search_selection", &format!("'{}' 검색", label), true))?;
+        }
+        menu.append(&item("ask_selection", "AI에게 물어보기", true))?;
+        menu.append(&PredefinedMenuItem::separator())?;
+    }
+    if tabs {
+        menu.append(&item("back", "뒤로", can_go_back))?;
+        menu.append(&item("forward", "앞으로", can_go_forward))?;
+    }
+    menu.append(&item("reload", "새로고침", true))?;
+    if tabs {
+        menu.append(&item("save_page", "페이지 저장…", true))?;
+        menu.append(&item("save_pdf", "PDF로 저장…", true))?;
+        menu.append(&item("save_markdown", "Markdown으로 저장…", true))?;
+        menu.append(&PredefinedMenuItem::separator())?;
+        menu.append(&item("translate", "페이지 번역 (AI)", true))?;
+    }
+    Ok(menu)
+}
+
+/// Runs the context menu item with id `id`. Returns `false` if `id` isn't
+/// a context menu item.
+pub fn handle(ctx: &IpcContext, id: &str) -> bool {
+    let Some(action) = id.strip_prefix(ID_PREFIX) else {
+        return false;
+    };
+    let Some(OpenMenu { target, .. }) = ctx.context_menu.borrow_mut().take() else {
+        return true;
+    };
+    let command = match (action, target) {
+        ("back", _) => IpcCommand::Back,
+        ("forward", _) => IpcCommand::Forward,
+        ("reload", _) => IpcCommand::Refresh,
+        ("save_page", _) => IpcCommand::SavePage,
+        ("save_pdf", _) => IpcCommand::SavePdf { path: None },
+        ("save_markdown", _) => IpcCommand::SaveMarkdown,
+        ("translate", _) => IpcCommand::TranslatePage { lang: None, regenerate: false },
+        ("open_link", ContextTarget { link: Some(url), .. }) => IpcCommand::OpenTab { url },
+        ("read_link_later", ContextTarget { link: Some(url), .. }) => IpcCommand::ReadLinkLater { url },
+        ("copy_link", ContextTarget { link: Some(text), .. })
+        | ("copy", ContextTarget { selection: Some(text), .. }) => IpcCommand::CopyText { text },
+        ("save_image", ContextTarget { image: Some(url), .. }) => IpcCommand::Download { url },
+        ("search_selection", ContextTarget { selection: Some(query), .. }) => {
+            match ctx.search_url(&query) {
+                Ok(url) => IpcCommand::OpenTab { url: url.into() },
+                Err(e) => {
+                    log::warn!("Failed to search for the selection: {:#}", e);
+                    return true;
+                }
+            }
+        }
+        ("ask_selection", ContextTarget { selection: Some(text), .. }) => IpcCommand::AskSelection { text },
+        _ => {
+            log::warn!("Unknown context menu item {}", id);
+            return true;
+        }
+    };
+    if let Err(e) = ipc::dispatch(&command, ctx) {
+        log::warn!("Context menu item {} failed: {}", id, e);
+    }
+    true
+}
+
+/// Puts `text` on the system clipboard.
+#[cfg(target_os = "linux")]
+pub fn copy_to_clipboard(text: &str) -> anyhow::Result<()> {
+    gtk::Clipboard::get(&gtk::gdk::SELECTION_CLIPBOARD).set_text(text);
+    Ok(())
+}
+
+/// Puts `text` on the system clipboard.
+#[cfg(not(target_os = "linux"))]
+pub fn copy_to_clipboard(text: &str) -> anyhow::Result<()> {
+    arboard::Clipboard::new()?.set_text(text)?;
+    Ok(())
+}
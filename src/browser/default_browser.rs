@@ -0,0 +1,79 @@
+//! Making SyncFlo the system's default browser, for the button on the
+//! settings page. The bundle or desktop entry has to declare the `http`
+//! and `https` schemes first; see `packaging/`.
+
+use anyhow::Result;
+
+/// Name of the desktop entry in `packaging/`, once installed.
+#[cfg(all(unix, not(target_os = "macos")))]
+const DESKTOP_ENTRY: &str = "syncflo-browser.desktop";
+
+/// Asks the system to open web links with SyncFlo. On Windows, which
+/// leaves that to the user, opens the default apps settings instead.
+#[cfg(target_os = "macos")]
+pub fn set_as_default() -> Result<()> {
+    use std::ffi::{c_char, c_void, CString};
+
+    type CFStringRef = *const c_void;
+    const UTF8: u32 = 0x0800_0100;
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFBundleGetMainBundle() -> *const c_void;
+        fn CFBundleGetIdentifier(bundle: *const c_void) -> CFStringRef;
+        fn CFStringCreateWithCString(alloc: *const c_void, text: *const c_char, encoding: u32) -> CFStringRef;
+        fn CFRelease(object: *const c_void);
+    }
+    #[link(name = "CoreServices", kind = "framework")]
+    extern "C" {
+        fn LSSetDefaultHandlerForURLScheme(scheme: CFStringRef, handler: CFStringRef) -> i32;
+    }
+
+    // SAFETY: the main bundle and its identifier belong to the process and
+    // aren't released; the created strings are released after use
+    unsafe {
+        let bundle = CFBundleGetMainBundle();
+        let identifier = if bundle.is_null() { std::ptr::null() } else { CFBundleGetIdentifier(bundle) };
+        if identifier.is_null() {
+            anyhow::bail!("SyncFlo isn't running from its app bundle");
+        }
+        for scheme in ["http", "https"] {
+            let name = CString::new(scheme)?;
+            let scheme_ref = CFStringCreateWithCString(std::ptr::null(), name.as_ptr(), UTF8);
+            let status = LSSetDefaultHandlerForURLScheme(scheme_ref, identifier);
+            CFRelease(scheme_ref);
+            if status != 0 {
+                anyhow::bail!("Launch Services refused {} links (error {})", scheme, status);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Asks the system to open web links with SyncFlo. On Windows, which
+/// leaves that to the user, opens the default apps settings instead.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn set_as_default() -> Result<()> {
+    use anyhow::Context;
+
+    let output = std::process::Command::new("xdg-settings")
+        .args(["set", "default-web-browser", DESKTOP_ENTRY])
+        .output()
+        .context("Failed to run xdg-settings")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "xdg-settings failed; is {} installed? {}",
+            DESKTOP_ENTRY,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Asks the system to open web links with SyncFlo. On Windows, which
+/// leaves that to the user, opens the default apps settings instead.
+#[cfg(windows)]
+pub fn set_as_default() -> Result<()> {
+    let settings = url::Url::parse("ms-settings:defaultapps")?;
+    crate::browser::external::open(&settings)
+}
@@ -0,0 +1,420 @@
+//! Dialogs pages ask for, shown by the browser rather than the webview:
+//! `alert()`, `confirm()` and `prompt()`, and confirmation before leaving a
+//! page with a `beforeunload` handler. Also confirmation before quitting
+//! with downloads in progress, before handing a link to another program,
+//! whether a site may use the location, camera, microphone or
+//! notifications, which file to open, where to save a page, and errors
+//! that keep the browser from starting.
+
+use crate::browser::permissions::Permission;
+use std::path::{Path, PathBuf};
+
+pub const LEAVE_TITLE: &str = "이 사이트에서 나가시겠습니까?";
+pub const LEAVE_MESSAGE: &str = "변경사항이 저장되지 않을 수 있습니다.";
+pub const QUIT_TITLE: &str = "SyncFlo를 종료하시겠습니까?";
+pub const STARTUP_ERROR_TITLE: &str = "SyncFlo를 시작할 수 없습니다";
+pub const ASSETS_ERROR_TITLE: &str = "화면 파일을 찾을 수 없습니다";
+pub const EXTERNAL_MESSAGE: &str = "이 선택은 기억되며 설정에서 바꿀 수 있습니다.";
+pub const PERMISSION_INCOGNITO_MESSAGE: &str = "시크릿 창을 닫으면 이 선택은 잊혀집니다.";
+const OPEN_FILE_TITLE: &str = "파일 열기";
+
+fn external_title(scheme: &str) -> String {
+    format!("{}: 링크를 시스템에서 열까요?", scheme)
+}
+
+/// Like `meet.example.com에서 카메라, 마이크 사용을 요청합니다`.
+fn permission_title(origin: &str, permissions: &[Permission]) -> String {
+    let site = origin.split_once("://").map_or(origin, |(_, site)| site);
+    let labels: Vec<&str> = permissions.iter().map(|permission| permission.label()).collect();
+    format!("{}에서 {} 사용을 요청합니다", site, labels.join(", "))
+}
+
+fn permission_message(remembered: bool) -> &'static str {
+    if remembered {
+        EXTERNAL_MESSAGE
+    } else {
+        PERMISSION_INCOGNITO_MESSAGE
+    }
+}
+
+/// What a save dialog saves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveAs {
+    /// A page as one HTML file.
+    Html,
+    /// A page printed to a PDF.
+    Pdf,
+    /// A page converted to Markdown.
+    Markdown,
+}
+
+impl SaveAs {
+    fn title(self) -> &'static str {
+        match self {
+            Self::Html => "페이지 저장",
+            Self::Pdf => "PDF로 저장",
+            Self::Markdown => "Markdown으로 저장",
+        }
+    }
+
+    /// Name of the file type, and the extensions it's listed with.
+    fn filter(self) -> (&'static str, &'static [&'static str]) {
+        match self {
+            Self::Html => ("웹 페이지 (HTML)", &["html", "htm"]),
+            Self::Pdf => ("PDF 문서", &["pdf"]),
+            Self::Markdown => ("Markdown 문서", &["md", "markdown"]),
+        }
+    }
+}
+
+fn quit_message(downloads: usize) -> String {
+    format!("다운로드 {}개가 진행 중입니다. 종료하면 다운로드가 취소됩니다.", downloads)
+}
+
+/// What a page asked for.
+#[cfg(target_os = "linux")]
+pub enum DialogKind {
+    Alert,
+    Confirm,
+    Prompt { default: String },
+    /// Leaving a page whose `beforeunload` handler wants to stay.
+    Leave,
+    /// Quitting with downloads in progress.
+    Quit,
+    /// Opening a link in another program.
+    External,
+    /// A site asking to use the location, camera, microphone or
+    /// notifications; cancelling blocks it.
+    Permission,
+}
+
+/// Shows a dialog over `parent` without blocking the event loop. `done`
+/// gets `None` if the user cancelled, otherwise the entered text (empty
+/// for anything but prompts).
+#[cfg(target_os = "linux")]
+pub fn show(
+    parent: Option<&gtk::Window>,
+    kind: DialogKind,
+    title: &str,
+    message: &str,
+    done: impl FnOnce(Option<String>) + 'static,
+) {
+    use gtk::prelude::*;
+    use gtk::{ButtonsType, DialogFlags, MessageDialog, MessageType, ResponseType};
+    use std::cell::Cell;
+
+    let (message_type, ok_label, cancel_label) = match kind {
+        DialogKind::Alert => (MessageType::Info, "확인", None),
+        DialogKind::Confirm | DialogKind::Prompt { .. } => (MessageType::Question, "확인", Some("취소")),
+        DialogKind::Leave => (MessageType::Warning, "나가기", Some("취소")),
+        DialogKind::Quit => (MessageType::Warning, "종료", Some("취소")),
+        DialogKind::External => (MessageType::Question, "열기", Some("취소")),
+        DialogKind::Permission => (MessageType::Question, "허용", Some("차단")),
+    };
+    let dialog = MessageDialog::new(
+        parent,
+        DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+        message_type,
+        ButtonsType::None,
+        title,
+    );
+    dialog.set_secondary_text(Some(message).filter(|m| !m.is_empty()));
+    if let Some(cancel_label) = cancel_label {
+        dialog.add_button(cancel_label, ResponseType::Cancel);
+    }
+    dialog.add_button(ok_label, ResponseType::Ok);
+    dialog.set_default_response(ResponseType::Ok);
+
+    let entry = match kind {
+        DialogKind::Prompt { default } => {
+            let entry = gtk::Entry::new();
+            entry.set_text(&default);
+            entry.set_activates_default(true);
+            if let Ok(area) = dialog.message_area().downcast::<gtk::Box>() {
+                area.pack_end(&entry, false, false, 0);
+            }
+            Some(entry)
+        }
+        _ => None,
+    };
+
+    // Escape and the close button respond too, as a cancel
+    let done = Cell::new(Some(done));
+    dialog.connect_response(move |dialog, response| {
+        let answer = (response == ResponseType::Ok).then(|| {
+            entry.as_ref().map(|entry| entry.text().to_string()).unwrap_or_default()
+        });
+        if let Some(done) = done.take() {
+            done(answer);
+        }
+        // SAFETY: nothing uses the dialog after this
+        unsafe { dialog.destroy() };
+    });
+    dialog.show_all();
+}
+
+/// Asks whether to leave the page in `owner`. Calls `done` with `true` if
+/// the user chose to leave.
+#[cfg(target_os = "linux")]
+pub fn confirm_leave(owner: &wry::WebView, done: impl FnOnce(bool) + 'static) {
+    use gtk::prelude::*;
+    use wry::WebViewExtUnix;
+
+    let parent = owner.webview().toplevel().and_then(|w| w.downcast::<gtk::Window>().ok());
+    show(parent.as_ref(), DialogKind::Leave, LEAVE_TITLE, LEAVE_MESSAGE, move |answer| {
+        done(answer.is_some())
+    });
+}
+
+/// Asks whether to leave the page in `owner`. Calls `done` with `true` if
+/// the user chose to leave.
+#[cfg(not(target_os = "linux"))]
+pub fn confirm_leave(_owner: &wry::WebView, done: impl FnOnce(bool) + 'static) {
+    use rfd::{MessageButtons, MessageDialog, MessageDialogResult, MessageLevel};
+
+    let answer = MessageDialog::new()
+        .set_level(MessageLevel::Warning)
+        .set_title(LEAVE_TITLE)
+        .set_description(LEAVE_MESSAGE)
+        .set_buttons(MessageButtons::OkCancel)
+        .show();
+    done(answer == MessageDialogResult::Ok);
+}
+
+/// Asks over the window of `owner` whether to open `scheme:` links with
+/// the system. Calls `done` with `true` if the user chose to.
+#[cfg(target_os = "linux")]
+pub fn confirm_external(owner: &wry::WebView, scheme: &str, done: impl FnOnce(bool) + 'static) {
+    use gtk::prelude::*;
+    use wry::WebViewExtUnix;
+
+    let parent = owner.webview().toplevel().and_then(|w| w.downcast::<gtk::Window>().ok());
+    show(parent.as_ref(), DialogKind::External, &external_title(scheme), EXTERNAL_MESSAGE, move |answer| {
+        done(answer.is_some())
+    });
+}
+
+/// Asks over the window of `owner` whether to open `scheme:` links with
+/// the system. Calls `done` with `true` if the user chose to.
+#[cfg(not(target_os = "linux"))]
+pub fn confirm_external(_owner: &wry::WebView, scheme: &str, done: impl FnOnce(bool) + 'static) {
+    use rfd::{MessageButtons, MessageDialog, MessageDialogResult, MessageLevel};
+
+    let answer = MessageDialog::new()
+        .set_level(MessageLevel::Info)
+        .set_title(external_title(scheme))
+        .set_description(EXTERNAL_MESSAGE)
+        .set_buttons(MessageButtons::OkCancel)
+        .show();
+    done(answer == MessageDialogResult::Ok);
+}
+
+/// Asks over `parent` whether the site at `origin` may use `permissions`.
+/// Calls `done` with `true` if the user allowed it. `remembered` says
+/// whether the answer is kept after the window closes.
+#[cfg(target_os = "linux")]
+pub fn confirm_permission(
+    parent: Option<&gtk::Window>,
+    origin: &str,
+    permissions: &[Permission],
+    remembered: bool,
+    done: impl FnOnce(bool) + 'static,
+) {
+    let title = permission_title(origin, permissions);
+    show(parent, DialogKind::Permission, &title, permission_message(remembered), move |answer| {
+        done(answer.is_some())
+    });
+}
+
+/// Asks whether the site at `origin` may use `permissions`, and waits for
+/// the answer: `true` if the user allowed it. `remembered` says whether the
+/// answer is kept after the window closes.
+#[cfg(not(target_os = "linux"))]
+pub fn confirm_permission(origin: &str, permissions: &[Permission], remembered: bool) -> bool {
+    use rfd::{MessageButtons, MessageDialog, MessageDialogResult, MessageLevel};
+
+    let answer = MessageDialog::new()
+        .set_level(MessageLevel::Info)
+        .set_title(permission_title(origin, permissions))
+        .set_description(permission_message(remembered))
+        .set_buttons(MessageButtons::OkCancelCustom("허용".into(), "차단".into()))
+        .show();
+    // Depending on the platform, the chosen button comes back as Ok or by
+    // its label
+    match answer {
+        MessageDialogResult::Ok => true,
+        MessageDialogResult::Custom(label) => label == "허용",
+        _ => false,
+    }
+}
+
+/// Asks over the window of `owner` which file to open, starting in `dir`.
+/// Calls `done` with the chosen path, or `None` if the user cancelled.
+#[cfg(target_os = "linux")]
+pub fn choose_file(owner: &wry::WebView, dir: &Path, done: impl FnOnce(Option<PathBuf>) + 'static) {
+    use gtk::prelude::*;
+    use gtk::{FileChooserAction, FileChooserDialog, ResponseType};
+    use std::cell::Cell;
+    use wry::WebViewExtUnix;
+
+    let parent = owner.webview().toplevel().and_then(|w| w.downcast::<gtk::Window>().ok());
+    let dialog = FileChooserDialog::with_buttons(
+        Some(OPEN_FILE_TITLE),
+        parent.as_ref(),
+        FileChooserAction::Open,
+        &[("취소", ResponseType::Cancel), ("열기", ResponseType::Accept)],
+    );
+    dialog.set_modal(true);
+    dialog.set_current_folder(dir);
+    dialog.set_default_response(ResponseType::Accept);
+
+    let done = Cell::new(Some(done));
+    dialog.connect_response(move |dialog, response| {
+        let path = if response == ResponseType::Accept { dialog.filename() } else { None };
+        if let Some(done) = done.take() {
+            done(path);
+        }
+        // SAFETY: nothing uses the dialog after this
+        unsafe { dialog.destroy() };
+    });
+    dialog.show_all();
+}
+
+/// Asks which file to open, starting in `dir`. Calls `done` with the
+/// chosen path, or `None` if the user cancelled.
+#[cfg(not(target_os = "linux"))]
+pub fn choose_file(_owner: &wry::WebView, dir: &Path, done: impl FnOnce(Option<PathBuf>) + 'static) {
+    let path = rfd::FileDialog::new().set_title(OPEN_FILE_TITLE).set_directory(dir).pick_file();
+    done(path);
+}
+
+/// Asks over the window of `owner` where to save `kind`, starting in `dir`
+/// with `file_name`. Calls `done` with the chosen path, or `None` if the
+/// user cancelled. Replacing a file is confirmed first.
+#[cfg(target_os = "linux")]
+pub fn choose_save_path(
+    owner: &wry::WebView,
+    kind: SaveAs,
+    dir: &Path,
+    file_name: &str,
+    done: impl FnOnce(Option<PathBuf>) + 'static,
+) {
+    use gtk::prelude::*;
+    use gtk::{FileChooserAction, FileChooserDialog, FileFilter, ResponseType};
+    use std::cell::Cell;
+    use wry::WebViewExtUnix;
+
+    let parent = owner.webview().toplevel().and_then(|w| w.downcast::<gtk::Window>().ok());
+    let dialog = FileChooserDialog::with_buttons(
+        Some(kind.title()),
+        parent.as_ref(),
+        FileChooserAction::Save,
+        &[("취소", ResponseType::Cancel), ("저장", ResponseType::Accept)],
+    );
+    dialog.set_modal(true);
+    dialog.set_do_overwrite_confirmation(true);
+    dialog.set_current_folder(dir);
+    dialog.set_current_name(file_name);
+    let (label, extensions) = kind.filter();
+    let filter = FileFilter::new();
+    filter.set_name(Some(label));
+    for extension in extensions {
+        filter.add_pattern(&format!("*.{}", extension));
+    }
+    dialog.add_filter(filter);
+    dialog.set_default_response(ResponseType::Accept);
+
+    let done = Cell::new(Some(done));
+    dialog.connect_response(move |dialog, response| {
+        let path = if response == ResponseType::Accept { dialog.filename() } else { None };
+        if let Some(done) = done.take() {
+            done(path);
+        }
+        // SAFETY: nothing uses the dialog after this
+        unsafe { dialog.destroy() };
+    });
+    dialog.show_all();
+}
+
+/// Asks where to save `kind`, starting in `dir` with `file_name`. Calls
+/// `done` with the chosen path, or `None` if the user cancelled. Replacing
+/// a file is confirmed first.
+#[cfg(not(target_os = "linux"))]
+pub fn choose_save_path(
+    _owner: &wry::WebView,
+    kind: SaveAs,
+    dir: &Path,
+    file_name: &str,
+    done: impl FnOnce(Option<PathBuf>) + 'static,
+) {
+    let (label, extensions) = kind.filter();
+    let path = rfd::FileDialog::new()
+        .set_title(kind.title())
+        .set_directory(dir)
+        .set_file_name(file_name)
+        .add_filter(label, extensions)
+        .save_file();
+    done(path);
+}
+
+/// Asks over `window` whether to quit while `downloads` downloads are in
+/// progress. Calls `done` with `true` if the user chose to quit.
+#[cfg(target_os = "linux")]
+pub fn confirm_quit(window: &tao::window::Window, downloads: usize, done: impl FnOnce(bool) + 'static) {
+    use gtk::prelude::*;
+    use tao::platform::unix::WindowExtUnix;
+
+    let parent = window.gtk_window().upcast_ref::<gtk::Window>();
+    show(Some(parent), DialogKind::Quit, QUIT_TITLE, &quit_message(downloads), move |answer| {
+        done(answer.is_some())
+    });
+}
+
+/// Asks over `window` whether to quit while `downloads` downloads are in
+/// progress. Calls `done` with `true` if the user chose to quit.
+#[cfg(not(target_os = "linux"))]
+pub fn confirm_quit(_window: &tao::window::Window, downloads: usize, done: impl FnOnce(bool) + 'static) {
+    use rfd::{MessageButtons, MessageDialog, MessageDialogResult, MessageLevel};
+
+    let answer = MessageDialog::new()
+        .set_level(MessageLevel::Warning)
+        .set_title(QUIT_TITLE)
+        .set_description(quit_message(downloads))
+        .set_buttons(MessageButtons::OkCancel)
+        .show();
+    done(answer == MessageDialogResult::Ok);
+}
+
+/// Shows `message` as an error and waits until it's dismissed. Works
+/// before and after the event loop starts, so startup failures are seen
+/// even without a terminal.
+#[cfg(target_os = "linux")]
+pub fn show_error(title: &str, message: &str) {
+    use gtk::prelude::*;
+    use gtk::{ButtonsType, DialogFlags, MessageDialog, MessageType};
+
+    if !gtk::is_initialized() && gtk::init().is_err() {
+        log::error!("{}: {}", title, message);
+        return;
+    }
+    let dialog = MessageDialog::new(None::<&gtk::Window>, DialogFlags::MODAL, MessageType::Error, ButtonsType::Ok, title);
+    dialog.set_secondary_text(Some(message));
+    dialog.run();
+    // SAFETY: nothing uses the dialog after this
+    unsafe { dialog.destroy() };
+}
+
+/// Shows `message` as an error and waits until it's dismissed. Works
+/// before and after the event loop starts, so startup failures are seen
+/// even without a terminal.
+#[cfg(not(target_os = "linux"))]
+pub fn show_error(title: &str, message: &str) {
+    use rfd::{MessageButtons, MessageDialog, MessageLevel};
+
+    MessageDialog::new()
+        .set_level(MessageLevel::Error)
+        .set_title(title)
+        .set_description(message)
+        .set_buttons(MessageButtons::Ok)
+        .show();
+}
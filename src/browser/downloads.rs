@@ -0,0 +1,238 @@
+//! Downloads started by pages, with their progress for the nav bar.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Characters of a page's title kept in the file name it's saved under.
+const PAGE_FILE_NAME_CHARS: usize = 100;
+
+pub type DownloadId = u64;
+
+/// Progress isn't pushed to the nav bar more often than this.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadState {
+    InProgress,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// A download, as shown in the nav bar's list.
+#[derive(Serialize)]
+pub struct Download {
+    pub id: DownloadId,
+    pub url: String,
+    pub path: PathBuf,
+    pub file_name: String,
+    pub received: u64,
+    /// Size from the response, if the server sent one.
+    pub total: Option<u64>,
+    pub state: DownloadState,
+    /// Whether it can be cancelled while in progress.
+    pub cancellable: bool,
+    #[serde(skip)]
+    cancel: Option<Box<dyn Fn()>>,
+    #[serde(skip)]
+    last_reported: Option<Instant>,
+}
+
+/// Downloads of this session, oldest first.
+#[derive(Default)]
+pub struct DownloadManager {
+    downloads: Vec<Download>,
+    next_id: DownloadId,
+}
+
+impl DownloadManager {
+    /// Records a download saving to `path`. `cancel` stops it, where the
+    /// platform allows that.
+    pub fn start(&mut self, url: &str, path: PathBuf, cancel: Option<Box<dyn Fn()>>) -> DownloadId {
+        self.next_id += 1;
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| url.to_string());
+        self.downloads.push(Download {
+            id: self.next_id,
+            url: url.to_string(),
+            path,
+            file_name,
+            received: 0,
+            total: None,
+            state: DownloadState::InProgress,
+            cancellable: cancel.is_some(),
+            cancel,
+            last_reported: None,
+        });
+        self.next_id
+    }
+
+    /// Updates the byte counts. Returns whether enough time has passed since
+    /// the last update to report this one.
+    pub fn progress(&mut self, id: DownloadId, received: u64, total: Option<u64>) -> bool {
+        let Some(download) = self.get_mut(id) else {
+            return false;
+        };
+        download.received = received;
+        download.total = total.filter(|&total| total > 0);
+        let due = download.last_reported.is_none_or(|at| at.elapsed() >= PROGRESS_INTERVAL);
+        if due {
+            download.last_reported = Some(Instant::now());
+        }
+        due
+    }
+
+    /// Marks a download as finished. A cancelled download stays cancelled.
+    pub fn finish(&mut self, id: DownloadId, success: bool) {
+        if let Some(download) = self.get_mut(id).filter(|d| d.state == DownloadState::InProgress) {
+            download.state = if success { DownloadState::Completed } else { DownloadState::Failed };
+            download.cancel = None;
+        }
+    }
+
+    /// Finishes the oldest download in progress from `url`, for platforms
+    /// that only report completion by URL. A known `path` replaces the
+    /// planned one.
+    #[cfg(not(target_os = "linux"))]
+    pub fn finish_url(&mut self, url: &str, path: Option<PathBuf>, success: bool) -> Option<DownloadId> {
+        let download = self
+            .downloads
+            .iter_mut()
+            .find(|d| d.url == url && d.state == DownloadState::InProgress)?;
+        if let Some(path) = path.filter(|p| !p.as_os_str().is_empty()) {
+            download.path = path;
+        }
+        let id = download.id;
+        self.finish(id, success);
+        Some(id)
+    }
+
+    /// Stops a download in progress. Returns `false` if it can't be cancelled.
+    pub fn cancel(&mut self, id: DownloadId) -> bool {
+        let Some(download) = self.get_mut(id).filter(|d| d.state == DownloadState::InProgress) else {
+            return false;
+        };
+        let Some(cancel) = download.cancel.take() else {
+            return false;
+        };
+        download.state = DownloadState::Cancelled;
+        cancel();
+        true
+    }
+
+    /// Stops every download in progress that can be, e.g. on quit.
+    /// Returns how many were stopped.
+    pub fn cancel_all(&mut self) -> usize {
+        let ids: Vec<DownloadId> =
+            self.downloads.iter().filter(|d| d.state == DownloadState::InProgress).map(|d| d.id).collect();
+        ids.into_iter().filter(|&id| self.cancel(id)).count()
+    }
+
+    /// How many downloads are in progress.
+    pub fn active(&self) -> usize {
+        self.downloads.iter().filter(|d| d.state == DownloadState::InProgress).count()
+    }
+
+    pub fn get(&self, id: DownloadId) -> Option<&Download> {
+        self.downloads.iter().find(|d| d.id == id)
+    }
+
+    fn get_mut(&mut self, id: DownloadId) -> Option<&mut Download> {
+        self.downloads.iter_mut().find(|d| d.id == id)
+    }
+
+    pub fn list(&self) -> &[Download] {
+        &self.downloads
+    }
+}
+
+/// The user's Downloads folder, falling back to the home directory.
+pub fn default_dir() -> PathBuf {
+    directories::UserDirs::new()
+        .and_then(|dirs| dirs.download_dir().map(Path::to_path_buf).or_else(|| Some(dirs.home_dir().to_path_buf())))
+        .unwrap_or_else(|| std::env::current_dir().unwrap_or_default())
+}
+
+/// File name with `extension` to suggest for saving the page titled
+/// `title` at `url`.
+pub fn page_file_name(title: &str, url: &Url, extension: &str) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .take(PAGE_FILE_NAME_CHARS)
+        .collect();
+    let name = name.trim().trim_matches('.');
+    let name = if name.is_empty() { url.host_str().unwrap_or("page") } else { name };
+    format!("{}.{}", name, extension)
+}
+
+/// A path in `dir` for `file_name` that doesn't exist yet, numbering
+/// duplicates like `file (1).zip`. Directory parts of `file_name` are dropped.
+pub fn unique_path(dir: &Path, file_name: &str) -> PathBuf {
+    let name = file_name
+        .rsplit(['/', '\\'])
+        .next()
+        .map(str::trim)
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+        .unwrap_or("download");
+    // Number before the first dot so `a.tar.gz` becomes `a (1).tar.gz`
+    let (stem, ext) = match name.char_indices().skip(1).find(|&(_, c)| c == '.') {
+        Some((dot, _)) => name.split_at(dot),
+        None => (name, ""),
+    };
+    let mut path = dir.join(name);
+    let mut counter = 1;
+    while path.exists() {
+        path = dir.join(format!("{} ({}){}", stem, counter, ext));
+        counter += 1;
+    }
+    path
+}
+
+/// Opens a downloaded file with its default application.
+pub fn open(path: &Path) -> anyhow::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    spawn(command.arg(path))
+}
+
+/// Shows a downloaded file in the file manager.
+pub fn reveal(path: &Path) -> anyhow::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+        command
+    } else {
+        // There's no portable way to select the file, so open its folder
+        let mut command = Command::new("xdg-open");
+        command.arg(path.parent().unwrap_or(path));
+        command
+    };
+    spawn(&mut command)
+}
+
+fn spawn(command: &mut Command) -> anyhow::Result<()> {
+    let mut child = command
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to run {:?}: {}", command.get_program(), e))?;
+    // Reap it so it doesn't linger as a zombie
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
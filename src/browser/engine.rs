@@ -1,639 +1,1491 @@
 use anyhow::{Context, Result};
-use crate::browser::Navigation;
+use crate::ai::{ResponseCache, UsageLog};
+use crate::browser::app_mode::AppMode;
+use crate::browser::single_instance::{self, Claim, Instance};
+use crate::browser::{assets, context_menu, pages, shortcuts};
+use crate::browser::blocker::Blocklist;
+#[cfg(target_os = "linux")]
+use crate::browser::blocker::ContentFilter;
+use crate::browser::bookmarks::BookmarkStore;
+use crate::browser::dialogs;
+#[cfg(target_os = "linux")]
+use crate::browser::dialogs::DialogKind;
+use crate::browser::downloads::DownloadManager;
+use crate::browser::ipc::{self, AppEvent, Chrome, IpcContext, TabFactory, WindowLayout};
+use crate::browser::permissions::{self, PermissionBroker};
+use crate::browser::profile::Profile;
+use crate::browser::reading_list::ReadingList;
+use crate::browser::recovery;
+use crate::browser::search::{self, SearchEngine};
+use crate::browser::session::{Session, StartupMode};
+use crate::browser::settings::Settings;
+use crate::browser::tabs::{TabId, TabManager, TabView};
+use crate::browser::window_state;
+use crate::browser::{worker, Navigation};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
 
-pub struct Browser {
+/// Height of the nav bar webview, in logical pixels.
+const NAV_HEIGHT: f64 = 92.0;
+/// Width of the AI sidebar webview, in logical pixels.
+const SIDEBAR_WIDTH: f64 = 360.0;
+/// How long the window has to stay put after moving or resizing before
+/// its state is saved.
+const WINDOW_SAVE_DELAY: Duration = Duration::from_millis(500);
+/// Quitting again this soon after being asked about running downloads
+/// quits without asking.
+const QUIT_AGAIN_WINDOW: Duration = Duration::from_secs(2);
+
+/// State behind the `syncflo://` pages, shared with the protocol handlers.
+#[derive(Clone)]
+struct InternalPages {
+    profile: Profile,
+    history: Arc<Mutex<Navigation>>,
+    bookmarks: Arc<Mutex<BookmarkStore>>,
+    reading_list: Arc<Mutex<ReadingList>>,
+    settings: Arc<Mutex<Settings>>,
+    recovered_session: Arc<Mutex<Option<Session>>>,
+    reader_pages: Arc<Mutex<HashMap<TabId, String>>>,
+    ai_usage: Arc<UsageLog>,
+}
+
+impl InternalPages {
+    /// Routes `syncflo://` requests to bundled assets and generated pages.
+    fn handle(
+        &self,
+        request: &wry::http::Request<Vec<u8>>,
+    ) -> wry::http::Response<std::borrow::Cow<'static, [u8]>> {
+        if assets::is_cross_origin_request(request) {
+            log::warn!("Blocked cross-origin request for {}", request.uri());
+            return assets::forbidden();
+        }
+        match assets::request_host(request) {
+            "app" => {
+                let path = request.uri().path().trim_start_matches('/');
+                if path == "home.html" {
+                    if let Some(response) = self.home_with_recovery_banner() {
+                        return response;
+                    }
+                }
+                assets::serve_asset(path)
+            }
+            "history" => {
+                let semantic = self.settings.lock().is_ok_and(|settings| settings.ai.semantic_history);
+                match self.history.lock() {
+                    Ok(nav) => assets::html_response(pages::history(&nav, semantic)),
+                    Err(_) => assets::not_found(),
+                }
+            }
+            "bookmarks" => match self.bookmarks.lock() {
+                Ok(bookmarks) => assets::html_response(pages::bookmarks(&bookmarks)),
+                Err(_) => assets::not_found(),
+            },
+            "reading-list" => self.reading_list_page(request.uri().path()),
+            "settings" => assets::html_response(pages::settings(&self.profile, &self.ai_usage)),
+            "about" => {
+                let user_agent = self.settings.lock().ok().and_then(|settings| settings.user_agent.default.clone());
+                assets::html_response(pages::about(user_agent.as_deref()))
+            }
+            "reader" => {
+                let tab = request
+                    .uri()
+                    .query()
+                    .and_then(|query| query.strip_prefix("tab="))
+                    .and_then(|id| id.parse::<TabId>().ok());
+                let page = tab.and_then(|tab| self.reader_pages.lock().ok()?.get(&tab).cloned());
+                match page {
+                    Some(page) => assets::html_response(page),
+                    None => assets::not_found(),
+                }
+            }
+            "error" => {
+                let query = request.uri().query().unwrap_or("");
+                let param = |name: &str| {
+                    url::form_urlencoded::parse(query.as_bytes())
+                        .find(|(key, _)| key == name)
+                        .map(|(_, value)| value.into_owned())
+                        .unwrap_or_default()
+                };
+                let kind = pages::LoadErrorKind::parse(&param("kind"));
+                assets::html_response(pages::load_error(&param("url"), kind))
+            }
+            _ => {
+                log::warn!("No internal page for {}", request.uri());
+                assets::not_found()
+            }
+        }
+    }
+
+    /// `syncflo://reading-list` at `path`: the list, the offline copy of an
+    /// item at `/<id>/`, or one of its images.
+    fn reading_list_page(&self, path: &str) -> wry::http::Response<std::borrow::Cow<'static, [u8]>> {
+        let Ok(list) = self.reading_list.lock() else {
+            return assets::not_found();
+        };
+        let path = path.trim_start_matches('/');
+        if path.is_empty() {
+            return assets::html_response(pages::reading_list(&list));
+        }
+        let Some((id, name)) = path.split_once('/') else {
+            return assets::not_found();
+        };
+        let Some(file) = list.snapshot_file(id, name) else {
+            return assets::not_found();
+        };
+        drop(list);
+        match std::fs::read(&file) {
+            Ok(bytes) if name.is_empty() => assets::html_response(String::from_utf8_lossy(&bytes).into_owned()),
+            Ok(bytes) => assets::file_response(name, bytes),
+            Err(e) => {
+                log::warn!("Failed to read {}: {}", file.display(), e);
+                assets::not_found()
+            }
+        }
+    }
+
+    /// The home page with the crash recovery banner, while there's a session to restore.
+    fn home_with_recovery_banner(&self) -> Option<wry::http::Response<std::borrow::Cow<'static, [u8]>>> {
+        let tab_count = self.recovered_session.lock().ok()?.as_ref()?.tabs.len();
+        let html = assets::asset("home.html")?;
+        let html = String::from_utf8_lossy(&html);
+        Some(assets::html_response(pages::with_recovery_banner(&html, tab_count)))
+    }
+}
+
+/// How to start the browser, usually from the command line.
+#[derive(Debug, Clone, Default)]
+pub struct LaunchOptions {
+    /// URLs or local files to open as tabs in place of the startup tabs.
+    pub urls: Vec<String>,
+    /// Overrides the startup setting.
+    pub startup: Option<StartupMode>,
+    /// Named profile to use instead of the default one.
+    pub profile: Option<String>,
+    /// Directory to keep the profiles in instead of the platform's.
+    pub user_data_dir: Option<PathBuf>,
+    /// Open an incognito window, which writes nothing to the profile.
+    pub incognito: bool,
+    /// Inner size of the window in logical pixels; the size it was left at
+    /// if unset.
+    pub window_size: Option<(u32, u32)>,
+    /// Open only this URL or file, in app mode.
+    pub app: Option<String>,
+    /// Keep the app mode window fullscreen and open.
+    pub kiosk: bool,
+}
+
+impl LaunchOptions {
+    /// The profile these options pick.
+    pub fn profile(&self) -> Result<Profile> {
+        Profile::open(self.profile.as_deref(), self.user_data_dir.as_deref())
+    }
+}
+
+/// What a window keeps of its profile. Incognito windows read the
+/// profile's settings and bookmarks but keep changes in memory, and keep
+/// their reading list and compiled content filters in a temporary directory.
+struct ProfileData {
     navigation: Navigation,
+    bookmarks: BookmarkStore,
+    settings: Settings,
+    reading_list: ReadingList,
+    permissions: PermissionBroker,
+    ai_usage: UsageLog,
+    /// Where WebKit caches the compiled content filters.
+    #[cfg(target_os = "linux")]
+    content_filters_dir: PathBuf,
+    /// An incognito window's temporary directory, removed when it closes.
+    scratch: Option<tempfile::TempDir>,
 }
 
-impl Browser {
-    pub fn new() -> Result<Self> {
+impl ProfileData {
+    /// Loads `profile`'s data for an incognito window, an app window, or
+    /// otherwise a normal one.
+    fn load(profile: &Profile, incognito: bool, app: bool) -> Result<Self> {
+        if !incognito {
+            return Ok(Self {
+                // Restore saved history so it carries across restarts. App
+                // windows start without any and keep theirs in memory.
+                navigation: if app { Navigation::new() } else { Navigation::load(profile.history_path()) },
+                bookmarks: BookmarkStore::load(profile.bookmarks_path()),
+                settings: Settings::load(&profile.settings_path()),
+                reading_list: ReadingList::load(profile.reading_list_dir()),
+                permissions: PermissionBroker::load(profile.permissions_path()),
+                ai_usage: UsageLog::load(UsageLog::store_path_in(profile.data_dir())),
+                #[cfg(target_os = "linux")]
+                content_filters_dir: profile.content_filters_dir(),
+                scratch: None,
+            });
+        }
+        let scratch = tempfile::Builder::new()
+            .prefix("syncflo-incognito-")
+            .tempdir()
+            .context("Failed to create the incognito window's temporary directory")?;
         Ok(Self {
             navigation: Navigation::new(),
+            bookmarks: BookmarkStore::load_from(&profile.bookmarks_path()),
+            settings: Settings::load_from(&profile.settings_path()),
+            reading_list: ReadingList::load(scratch.path().join("reading-list")),
+            permissions: PermissionBroker::new(),
+            ai_usage: UsageLog::default(),
+            #[cfg(target_os = "linux")]
+            content_filters_dir: scratch.path().join("content-filters"),
+            scratch: Some(scratch),
         })
     }
+}
+
+pub struct Browser {
+    profile: Profile,
+    data: ProfileData,
+    startup: StartupMode,
+    options: LaunchOptions,
+    /// Endpoint later launches with this profile send their URLs to; not
+    /// claimed by incognito and app windows.
+    instance: Option<Instance>,
+    /// Runs the background work for as long as the browser runs.
+    _workers: tokio::runtime::Runtime,
+}
+
+impl Browser {
+    /// Loads the stored data of the profile `options` pick. Returns `None`
+    /// if another process already runs the profile; it was handed the URLs.
+    pub fn new(options: LaunchOptions) -> Result<Option<Self>> {
+        let profile = options.profile()?;
+        // Incognito and app windows are processes of their own
+        let instance = if options.incognito || options.app.is_some() {
+            None
+        } else {
+            match single_instance::claim(&profile.instance_path(), &options.urls) {
+                Ok(Claim::Forwarded) => return Ok(None),
+                Ok(Claim::Primary(instance)) => Some(instance),
+                Err(e) => {
+                    log::warn!("Running without the single-instance check: {:#}", e);
+                    None
+                }
+            }
+        };
+        let data = ProfileData::load(&profile, options.incognito, options.app.is_some())?;
+        let startup = options.startup.unwrap_or(data.settings.startup);
+        let workers = worker::start()?;
+        Ok(Some(Self { profile, data, startup, options, instance, _workers: workers }))
+    }
 
-    pub fn run(&mut self) -> Result<()> {
+    pub fn run(self) -> Result<()> {
         // EventLoop must be created on the main thread (macOS requirement)
-        let navigation = Arc::new(Mutex::new(std::mem::take(&mut self.navigation)));
-        Self::run_event_loop(navigation)
+        Self::run_event_loop(self.profile, self.data, self.startup, &self.options, self.instance)
     }
 
-    fn run_event_loop(navigation: Arc<Mutex<Navigation>>) -> Result<()> {
-        use wry::{
-            application::{
-                event::{Event, StartCause, WindowEvent},
-                event_loop::{ControlFlow, EventLoop},
-                window::WindowBuilder,
-            },
-            webview::WebViewBuilder,
+    fn run_event_loop(
+        profile: Profile,
+        data: ProfileData,
+        startup: StartupMode,
+        options: &LaunchOptions,
+        instance: Option<Instance>,
+    ) -> Result<()> {
+        use tao::{
+            event::{Event, StartCause, WindowEvent},
+            event_loop::{ControlFlow, EventLoopBuilder},
+            window::{Fullscreen, WindowBuilder},
         };
+        use wry::{PageLoadEvent, WebContext, WebViewBuilder};
+        use std::cell::Cell;
 
-        let event_loop = EventLoop::new();
-        
-        let window = WindowBuilder::new()
-            .with_title("SyncFlo Browser")
-            .with_inner_size(wry::application::dpi::LogicalSize::new(1280.0, 800.0))
-            .build(&event_loop)
-            .context("Failed to create window")?;
+        let event_loop = EventLoopBuilder::<AppEvent>::with_user_event().build();
+        let navigation = Arc::new(Mutex::new(data.navigation));
+        let bookmarks = Arc::new(Mutex::new(data.bookmarks));
+        let settings = Arc::new(Mutex::new(data.settings));
 
-        let nav_clone = navigation.clone();
-        
-        // Create two windows: nav bar (top, 56px) and main content
-        use wry::application::dpi::{LogicalPosition, LogicalSize};
-        use std::rc::Rc;
-        use std::cell::RefCell;
-        
-        // Get window position and size for alignment
-        let window_size = window.outer_size();
-        let nav_height = 56.0;
-        let content_window_id = window.id(); // Store ID before moving window
-        
-        // Adjust main window size to account for nav bar (height only)
-        window.set_inner_size(LogicalSize::new(window_size.width as f64, (window_size.height as f64) - nav_height));
-        
-        // Nav bar window (top, 56px, no decorations, always on top, clickable)
-        // Position will be at same location as main window, but will stay on top
-        let nav_window = WindowBuilder::new()
-            .with_title("")
-            .with_decorations(false) // No title bar, no borders
-            .with_inner_size(LogicalSize::new(window_size.width as f64, nav_height))
-            .with_always_on_top(true) // Keep nav always on top
-            .build(&event_loop)
-            .context("Failed to create nav window")?;
-        let nav_window_id = nav_window.id(); // Store ID before moving nav_window
-        
-        // Create content webview in original window
-        let content_webview = WebViewBuilder::new(window)?
-            .with_url("about:blank")?
-            .with_devtools(true)
-            .build()?;
-        let content_wv_rc = Rc::new(RefCell::new(content_webview));
-        
-        // Create nav webview with IPC handler
-        let nav_url = Self::local_nav_file_url()?;
-        let content_for_ipc = content_wv_rc.clone();
-        let _nav_webview = WebViewBuilder::new(nav_window)?
-            .with_url(&nav_url)?
-            .with_ipc_handler(move |_, msg| {
-                let text = msg;
-                if let Ok(v) = serde_json::from_str::<serde_json::Value>(&text) {
-                    let op = v.get("op").and_then(|x| x.as_str()).unwrap_or("");
-                    match op {
-                        "back" => { let _ = content_for_ipc.borrow().evaluate_script("window.history.back()"); },
-                        "forward" => { let _ = content_for_ipc.borrow().evaluate_script("window.history.forward()"); },
-                        "refresh" => { let _ = content_for_ipc.borrow().evaluate_script("window.location.reload()"); },
-                        "home" => {
-                            if let Ok(url) = Self::local_home_file_url() { 
-                                let _ = content_for_ipc.borrow().load_url(&url); 
+        let incognito = options.incognito;
+        let engine = settings.lock().map(|settings| settings.search_engine.clone()).unwrap_or_default();
+        let app = match &options.app {
+            Some(arg) => {
+                let url = Url::parse(&Self::launch_url(arg, &engine)).context("Invalid app URL")?;
+                let exit = match settings.lock() {
+                    Ok(settings) => settings.kiosk_exit.clone(),
+                    Err(_) => Settings::default().kiosk_exit,
+                };
+                Some(AppMode::new(&url, options.kiosk, exit))
+            }
+            None => None,
+        };
+        let kiosk = app.as_ref().is_some_and(|app| app.kiosk);
+        // Neither incognito nor app windows leave history, a session or a
+        // running marker behind
+        let persistent = !incognito && app.is_none();
+        let window_state = settings.lock().map(|settings| settings.window).unwrap_or_default();
+        let mut window_builder = window_state
+            .restore(WindowBuilder::new(), &event_loop, options.window_size)
+            .with_title(if incognito { "SyncFlo Browser (시크릿)" } else { "SyncFlo Browser" });
+        if kiosk {
+            window_builder = window_builder.with_fullscreen(Some(Fullscreen::Borderless(None)));
+        }
+        let window = Rc::new(window_builder.build(&event_loop).context("Failed to create window")?);
+
+        // A dev override directory missing pages is easy to miss when
+        // launched without a terminal; the embedded copies stand in
+        let missing = assets::missing_dev_assets();
+        if !missing.is_empty() {
+            let paths: Vec<String> = missing.iter().map(|path| path.display().to_string()).collect();
+            log::error!("Missing assets: {}", paths.join(", "));
+            dialogs::show_error(
+                dialogs::ASSETS_ERROR_TITLE,
+                &format!(
+                    "{}에 설정된 폴더에 다음 파일이 없어 기본 화면을 대신 사용합니다:\n{}",
+                    assets::ASSETS_DIR_ENV,
+                    paths.join("\n")
+                ),
+            );
+        }
+
+        // One window hosts the nav bar on top and the tabs' content webviews
+        // below it. On Linux child webviews live in a GTK container.
+        #[cfg(target_os = "linux")]
+        let container = {
+            use gtk::prelude::*;
+            use tao::platform::unix::WindowExtUnix;
+            let fixed = gtk::Fixed::new();
+            let vbox = window
+                .default_vbox()
+                .context("Window has no GTK container")?;
+            vbox.pack_start(&fixed, true, true, 0);
+            fixed.show_all();
+            fixed
+        };
+        let build_child = {
+            #[cfg(not(target_os = "linux"))]
+            let window = window.clone();
+            move |builder: WebViewBuilder<'_>| -> wry::Result<wry::WebView> {
+                #[cfg(target_os = "linux")]
+                {
+                    use wry::WebViewBuilderExtUnix;
+                    builder.build_gtk(&container)
+                }
+                #[cfg(not(target_os = "linux"))]
+                {
+                    builder.build_as_child(window.as_ref())
+                }
+            }
+        };
+
+        let sidebar_open = settings.lock().is_ok_and(|settings| settings.sidebar_open);
+        let launch_urls: Vec<String> = match &app {
+            Some(app) => vec![app.url.to_string()],
+            None => options.urls.iter().map(|arg| Self::launch_url(arg, &engine)).collect(),
+        };
+        // App windows show only the content
+        let nav_shown = app.is_none();
+        let sidebar_shown = sidebar_open && app.is_none();
+        let layout = Rc::new(Cell::new(Self::layout_bounds(&window, nav_shown, 0.0, sidebar_shown)));
+
+        let home_url = match &app {
+            Some(app) => app.url.to_string(),
+            None => assets::home_url(),
+        };
+        let session_path = profile.session_path();
+
+        // A leftover running marker means the last run crashed or was killed.
+        // Its tabs are offered on the home page rather than reopened blindly.
+        // Incognito windows leave both to the profile's normal windows.
+        let crashed = persistent && recovery::mark_running(&profile.running_marker_path());
+        if persistent {
+            recovery::track_history(navigation.clone());
+        }
+        let recovered = if crashed {
+            log::warn!("Previous session didn't shut down cleanly");
+            Session::load(&session_path).filter(|session| !session.tabs.is_empty())
+        } else {
+            None
+        };
+        let recovered_session = Arc::new(Mutex::new(recovered));
+        let reader_pages = Arc::new(Mutex::new(HashMap::new()));
+        let ai_usage = Arc::new(data.ai_usage);
+        let permissions = Arc::new(Mutex::new(data.permissions));
+        let reading_list = Arc::new(Mutex::new(data.reading_list));
+        let mut scratch = data.scratch;
+        let pages = InternalPages {
+            profile: profile.clone(),
+            history: navigation.clone(),
+            bookmarks: bookmarks.clone(),
+            reading_list: reading_list.clone(),
+            settings: settings.clone(),
+            recovered_session: recovered_session.clone(),
+            reader_pages: reader_pages.clone(),
+            ai_usage: ai_usage.clone(),
+        };
+        let blocklist = Arc::new(Blocklist::load_dir(&profile.filters_dir()));
+        if !blocklist.is_empty() {
+            log::info!("Blocking {} domains", blocklist.len());
+        }
+        #[cfg(target_os = "linux")]
+        let content_filter = Self::compile_content_filter(&blocklist, &data.content_filters_dir);
+
+        // Kiosk windows have to hear their exit keys from every webview
+        let extra_keys: Vec<shortcuts::KeyPress> =
+            app.iter().filter(|app| app.kiosk).map(|app| app.exit.clone()).collect();
+
+        let nav_wv_rc = Rc::new(RefCell::new(None));
+        let sidebar_wv_rc = Rc::new(RefCell::new(None));
+        let ipc_ctx = Rc::new(IpcContext {
+            tabs: RefCell::new(TabManager::new()),
+            nav: nav_wv_rc.clone(),
+            sidebar: sidebar_wv_rc.clone(),
+            layout: Box::new({
+                let window = Rc::downgrade(&window);
+                let nav = nav_wv_rc.clone();
+                let sidebar = sidebar_wv_rc.clone();
+                let layout = layout.clone();
+                move |nav_shown, popover, sidebar_open| {
+                    if let Some(window) = window.upgrade() {
+                        let bounds = Self::layout_bounds(&window, nav_shown, popover, sidebar_open);
+                        if let Some(nav) = nav.borrow().as_ref() {
+                            let _ = nav.set_bounds(bounds.nav);
+                            let _ = nav.set_visible(nav_shown);
+                        }
+                        if let Some(sidebar) = sidebar.borrow().as_ref() {
+                            let _ = sidebar.set_bounds(bounds.sidebar);
+                            let _ = sidebar.set_visible(sidebar_open);
+                        }
+                        layout.set(bounds);
+                    }
+                    layout.get()
+                }
+            }),
+            layout_overlay: Cell::new(false),
+            popover_height: Cell::new(0.0),
+            suggest_cancel: RefCell::new(None),
+            http: RefCell::new(None),
+            chrome: Cell::new(if window.fullscreen().is_some() {
+                Chrome::Fullscreen { revealed: false }
+            } else {
+                Chrome::Normal
+            }),
+            set_fullscreen: Box::new({
+                let window = Rc::downgrade(&window);
+                move |fullscreen| {
+                    if let Some(window) = window.upgrade() {
+                        window.set_fullscreen(fullscreen.then_some(Fullscreen::Borderless(None)));
+                    }
+                }
+            }),
+            history: navigation.clone(),
+            bookmarks,
+            reading_list,
+            settings,
+            permissions,
+            blocklist,
+            home_url: home_url.clone(),
+            profile: profile.clone(),
+            incognito,
+            app: app.clone(),
+            recovered_session: recovered_session.clone(),
+            tab_factory: RefCell::new(None),
+            set_window_title: Box::new({
+                let window = Rc::downgrade(&window);
+                move |title| {
+                    if let Some(window) = window.upgrade() {
+                        if incognito {
+                            window.set_title(&format!("{} (시크릿)", title));
+                        } else {
+                            window.set_title(title);
+                        }
+                    }
+                }
+            }),
+            popup_menu: Box::new({
+                let window = Rc::downgrade(&window);
+                let layout = layout.clone();
+                move |menu, x, y| {
+                    if let Some(window) = window.upgrade() {
+                        Self::popup_menu(&window, menu, layout.get().content, x, y);
+                    }
+                }
+            }),
+            context_menu: RefCell::new(None),
+            reader_pages,
+            ai_requests: RefCell::new(HashMap::new()),
+            ai_actions: RefCell::new(HashMap::new()),
+            ai_cache: (!incognito).then(|| Arc::new(ResponseCache::new(ResponseCache::dir_in(profile.data_dir())))),
+            ai_usage,
+            proxy: event_loop.create_proxy(),
+            favicon_fetches: RefCell::new(HashSet::new()),
+            chosen_files: RefCell::new(HashSet::new()),
+            downloads: RefCell::new(DownloadManager::default()),
+            #[cfg(target_os = "macos")]
+            menu: RefCell::new(None),
+        });
+        muda::MenuEvent::set_event_handler(Some({
+            let proxy = event_loop.create_proxy();
+            move |event: muda::MenuEvent| {
+                let _ = proxy.send_event(AppEvent::Menu(event.id.0));
+            }
+        }));
+        #[cfg(target_os = "macos")]
+        match crate::browser::menu::AppMenu::install() {
+            Ok(menu) => *ipc_ctx.menu.borrow_mut() = Some(menu),
+            Err(e) => log::warn!("Failed to create the menu bar: {:#}", e),
+        }
+
+        // Each tab gets its own content webview. Committed page loads are
+        // reported back over IPC so the Rust history sees link clicks too.
+        let tab_factory: TabFactory = Box::new({
+            let ctx = Rc::downgrade(&ipc_ctx);
+            let layout = layout.clone();
+            let pages = pages.clone();
+            let build_child = build_child.clone();
+            let shortcuts_script = shortcuts::script(&extra_keys);
+            let webview_dir = profile.webview_dir().filter(|_| !incognito);
+            // Read once: WebView2 needs the same for every webview sharing a
+            // data directory, and only WebKitGTK can change it later
+            let proxy_config = ipc_ctx.settings.lock().ok().and_then(|settings| settings.proxy.webview_config());
+            #[cfg(target_os = "linux")]
+            let content_filter = content_filter.clone();
+            move |id, url| {
+                let ipc_ctx = ctx.clone();
+                let nav_ctx = ctx.clone();
+                let title_ctx = ctx.clone();
+                let load_ctx = ctx.clone();
+                // A context of its own, as wry would make, keeping cookies
+                // and storage in the profile's directory. Incognito tabs get
+                // an ephemeral one instead.
+                let mut web_context = WebContext::new(webview_dir.clone());
+                let builder = WebViewBuilder::new_with_web_context(&mut web_context).with_incognito(incognito);
+                let builder = Self::with_download_handlers(builder, ctx.clone());
+                let builder = Self::with_new_window_handler(builder, id, ctx.clone());
+                let page_origin = Arc::new(Mutex::new(None));
+                let builder = Self::with_permission_handler(builder, page_origin.clone(), ctx.clone());
+                // Later navigations change it where the webview can
+                let builder = match ctx.upgrade().and_then(|ctx| ctx.user_agent(url)) {
+                    Some(user_agent) => builder.with_user_agent(user_agent),
+                    None => builder,
+                };
+                let builder = match proxy_config.clone() {
+                    Some(proxy) => builder.with_proxy_config(proxy),
+                    None => builder,
+                };
+                // WebKitGTK's own notifications are reported to watch_notifications
+                #[cfg(not(target_os = "linux"))]
+                let builder = builder.with_initialization_script(crate::browser::notifications::SCRIPT);
+                let webview = build_child(
+                    builder
+                        .with_bounds(layout.get().content)
+                        .with_url(url)
+                        .with_devtools(true)
+                        .with_custom_protocol(assets::SCHEME.into(), {
+                            let pages = pages.clone();
+                            move |_, req| pages.handle(&req)
+                        })
+                        .with_initialization_script(ipc::COMMIT_REPORTER_SCRIPT)
+                        .with_initialization_script(&shortcuts_script)
+                        .with_initialization_script(context_menu::SCRIPT)
+                        .with_on_page_load_handler(move |event, url| {
+                            if matches!(event, PageLoadEvent::Started) {
+                                if let Ok(mut origin) = page_origin.lock() {
+                                    *origin = permissions::origin(&url);
+                                }
+                            }
+                            if let Some(ctx) = load_ctx.upgrade() {
+                                ctx.load_progress(id, matches!(event, PageLoadEvent::Started), &url);
                             }
-                        },
-                        "navigate" => {
-                            if let Some(u) = v.get("payload").and_then(|p| p.get("url")).and_then(|x| x.as_str()) {
-                                let target = if u.starts_with("http://") || u.starts_with("https://") { 
-                                    u.to_string() 
-                                } else if u.contains('.') && !u.contains(' ') { 
-                                    format!("https://{}", u) 
-                                } else { 
-                                    format!("https://www.google.com/search?q={}", urlencoding::encode(u)) 
-                                };
-                                let _ = content_for_ipc.borrow().load_url(&target);
+                        })
+                        .with_document_title_changed_handler(move |title| {
+                            if let Some(ctx) = title_ctx.upgrade() {
+                                ctx.title_changed(id, &title);
                             }
-                        },
-                        _ => {}
+                        })
+                        .with_navigation_handler(move |url| {
+                            nav_ctx.upgrade().is_none_or(|ctx| ctx.allow_navigation(id, &url))
+                        })
+                        .with_ipc_handler(move |req| {
+                            if let Some(ctx) = ipc_ctx.upgrade() {
+                                ipc::handle_page_message(req.body(), &req.uri().to_string(), id, &ctx)
+                            }
+                        }),
+                )
+                .context("Failed to create tab webview")?;
+                Self::watch_load_failures(&webview, id, ctx.clone());
+                // With the credentials and bypass list wry leaves out
+                #[cfg(target_os = "linux")]
+                if let Some(ctx) = ctx.upgrade() {
+                    if let Ok(settings) = ctx.settings.lock() {
+                        TabView::set_proxy(&webview, &settings.proxy);
                     }
                 }
-            })
-            .build()?;
+                #[cfg(target_os = "linux")]
+                Self::watch_security(&webview, id, ctx.clone());
+                #[cfg(target_os = "linux")]
+                Self::apply_site_settings(&webview, ctx.clone());
+                #[cfg(target_os = "linux")]
+                Self::watch_downloads(&webview, ctx.clone());
+                #[cfg(target_os = "linux")]
+                Self::watch_new_windows(&webview, id, ctx.clone());
+                #[cfg(target_os = "linux")]
+                Self::watch_script_dialogs(&webview);
+                #[cfg(target_os = "linux")]
+                Self::watch_permissions(&webview, ctx.clone());
+                #[cfg(target_os = "linux")]
+                Self::watch_notifications(&webview, id, ctx.clone());
+                #[cfg(target_os = "linux")]
+                Self::watch_element_fullscreen(&webview, ctx.clone());
+                #[cfg(target_os = "linux")]
+                Self::attach_content_blocker(&webview, id, ctx.clone(), content_filter.clone());
+                Ok(Rc::new(webview) as Rc<dyn TabView>)
+            }
+        });
+        *ipc_ctx.tab_factory.borrow_mut() = Some(tab_factory);
+
+        // Create nav webview with IPC handler
+        let nav_webview = build_child(
+            WebViewBuilder::new()
+                .with_incognito(incognito)
+                .with_bounds(layout.get().nav)
+                .with_visible(nav_shown)
+                .with_url(assets::nav_url())
+                .with_custom_protocol(assets::SCHEME.into(), {
+                    let pages = pages.clone();
+                    move |_, req| pages.handle(&req)
+                })
+                .with_initialization_script(ipc::NAV_PRELOAD_SCRIPT)
+                .with_initialization_script(shortcuts::script(&extra_keys))
+                .with_ipc_handler({
+                    let ctx = ipc_ctx.clone();
+                    move |req| ipc::handle_nav_message(req.body(), &ctx)
+                }),
+        )
+        .context("Failed to create nav webview")?;
+        *nav_wv_rc.borrow_mut() = Some(nav_webview);
+
+        // The AI sidebar right of the content, hidden while closed
+        let sidebar_webview = build_child(
+            WebViewBuilder::new()
+                .with_incognito(incognito)
+                .with_bounds(layout.get().sidebar)
+                .with_visible(sidebar_shown)
+                .with_url(assets::sidebar_url())
+                .with_custom_protocol(assets::SCHEME.into(), {
+                    let pages = pages.clone();
+                    move |_, req| pages.handle(&req)
+                })
+                .with_initialization_script(ipc::NAV_PRELOAD_SCRIPT)
+                .with_initialization_script(shortcuts::script(&extra_keys))
+                .with_ipc_handler({
+                    let ctx = ipc_ctx.clone();
+                    move |req| ipc::handle_sidebar_message(req.body(), &ctx)
+                }),
+        )
+        .context("Failed to create sidebar webview")?;
+        *sidebar_wv_rc.borrow_mut() = Some(sidebar_webview);
         
-        // Load home page initially
-        if let Ok(u) = Self::local_home_file_url() { 
-            let _ = content_wv_rc.borrow().load_url(&u); 
-        }
+        // Open the URLs given on the command line, else reopen the last
+        // session's tabs, or start with home / a blank tab. Without
+        // URLs, incognito windows start with home.
+        let has_recovery = recovered_session.lock().map(|s| s.is_some()).unwrap_or(false);
+        let (urls, active) = if !launch_urls.is_empty() {
+            (launch_urls, 0)
+        } else if has_recovery || incognito {
+            (vec![home_url.clone()], 0)
+        } else {
+            Session::startup_tabs(startup, &session_path, &ipc_ctx.home_page())
+        };
+        ipc_ctx.open_tabs(&urls, active)?;
 
-        let nav_for_keys = navigation.clone();
+        // Later launches with this profile open their URLs here
+        let instance_path = instance.map(|instance| instance.serve(event_loop.create_proxy()));
 
+        // Moving or resizing saves the window state once it settles
+        let mut save_window_at: Option<Instant> = None;
+        let mut quit_asked_at: Option<Instant> = None;
+        // Links the system opened with the browser (macOS) before the loop
+        // got going, like the one that launched it; opened at init
+        let mut opened_early: Option<Vec<String>> = Some(Vec::new());
         event_loop.run(move |event, _, control_flow| {
             *control_flow = ControlFlow::Wait;
 
             match event {
                 Event::NewEvents(StartCause::Init) => {
                     log::info!("SyncFlo Browser initialized");
-                    // Initialize navigation with start page
-                    if let Ok(mut nav) = nav_for_keys.lock() {
-                        let _ = nav.navigate("data:text/html,start".to_string());
+                    if let Some(urls) = opened_early.take().filter(|urls| !urls.is_empty()) {
+                        Self::open_launched(&ipc_ctx, &window, urls);
+                    }
+                }
+                Event::Opened { urls } => {
+                    let urls: Vec<String> = urls.into_iter().map(String::from).collect();
+                    match &mut opened_early {
+                        Some(early) => early.extend(urls),
+                        None => Self::open_launched(&ipc_ctx, &window, urls),
                     }
                 }
+                Event::UserEvent(AppEvent::Launched { urls }) => Self::open_launched(&ipc_ctx, &window, urls),
+                Event::UserEvent(AppEvent::NotificationClicked { tab, url }) => {
+                    Self::notification_clicked(&ipc_ctx, &window, tab, &url)
+                }
+                Event::UserEvent(AppEvent::Quit { confirmed })
+                    if confirmed || Self::may_quit(&ipc_ctx, &window, &mut quit_asked_at) =>
+                {
+                    *control_flow = ControlFlow::Exit;
+                }
                 Event::WindowEvent {
-                    window_id,
-                    event: WindowEvent::CloseRequested { .. },
+                    event: WindowEvent::CloseRequested,
                     ..
-                } => {
-                    // Close both windows when one is closed
-                    if window_id == content_window_id || window_id == nav_window_id {
-                        *control_flow = ControlFlow::Exit;
+                } if kiosk => log::info!("Kiosk windows close only with the exit keys"),
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    ..
+                } if Self::may_quit(&ipc_ctx, &window, &mut quit_asked_at) => {
+                    *control_flow = ControlFlow::Exit;
+                }
+                // Every way out ends here, including quitting from the
+                // Dock or the OS logging out
+                Event::LoopDestroyed => {
+                    Self::save_window_state(&ipc_ctx, &window);
+                    ipc_ctx.shut_down();
+                    if persistent {
+                        recovery::clear_running(&ipc_ctx.profile.running_marker_path());
                     }
+                    if let Some(path) = &instance_path {
+                        single_instance::release(path);
+                    }
+                    if let Some(Err(e)) = scratch.take().map(tempfile::TempDir::close) {
+                        log::warn!("Failed to remove the incognito window's temporary directory: {}", e);
+                    }
+                    log::info!("SyncFlo Browser shut down");
+                }
+                Event::UserEvent(event) => ipc::handle_app_event(&ipc_ctx, event),
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(_),
+                    ..
+                } => {
+                    ipc_ctx.fullscreen_changed(window.fullscreen().is_some());
+                    ipc_ctx.relayout();
+                    save_window_at = Some(Instant::now() + WINDOW_SAVE_DELAY);
                 }
+                // Moved to a display with another scale factor: the same
+                // logical layout takes a different number of pixels
+                Event::WindowEvent {
+                    event: WindowEvent::ScaleFactorChanged { .. },
+                    ..
+                } => ipc_ctx.relayout(),
+                Event::WindowEvent {
+                    event: WindowEvent::Moved(_),
+                    ..
+                } => save_window_at = Some(Instant::now() + WINDOW_SAVE_DELAY),
                 _ => {}
             }
+            if let Some(at) = save_window_at {
+                if Instant::now() >= at {
+                    save_window_at = None;
+                    window_state::keep_on_screen(&window);
+                    Self::save_window_state(&ipc_ctx, &window);
+                } else if *control_flow == ControlFlow::Wait {
+                    *control_flow = ControlFlow::WaitUntil(at);
+                }
+            }
         });
-        
-        // This line is unreachable because event_loop.run() blocks until exit
-        // but we keep it for clarity and potential future changes
-        #[allow(unreachable_code)]
-        Ok(())
-    }
-
-    fn local_app_file_url() -> Result<String> {
-        use std::path::{Path, PathBuf};
-        // During development, assets/home.html is relative to project root.
-        // We resolve it from current working directory.
-        let cwd = std::env::current_dir().context("Failed to get current_dir")?;
-        let path: PathBuf = cwd.join("assets").join("app.html");
-        if !path.exists() {
-            return Err(anyhow::anyhow!(
-                "Home file not found: {}",
-                path.to_string_lossy()
-            ));
-        }
-        let url = format!("file://{}", path.to_string_lossy());
-        Ok(url)
-    }
-
-    fn local_nav_file_url() -> Result<String> {
-        use std::path::PathBuf;
-        let cwd = std::env::current_dir().context("Failed to get current_dir")?;
-        let path: PathBuf = cwd.join("assets").join("nav.html");
-        if !path.exists() {
-            return Err(anyhow::anyhow!(
-                "Nav file not found: {}",
-                path.to_string_lossy()
-            ));
-        }
-        Ok(format!("file://{}", path.to_string_lossy()))
-    }
-
-    fn local_home_file_url() -> Result<String> {
-        use std::path::PathBuf;
-        let cwd = std::env::current_dir().context("Failed to get current_dir")?;
-        let path: PathBuf = cwd.join("assets").join("home.html");
-        if !path.exists() {
-            return Err(anyhow::anyhow!(
-                "Home file not found: {}",
-                path.to_string_lossy()
-            ));
-        }
-        Ok(format!("file://{}", path.to_string_lossy()))
-    }
-
-    fn build_start_page_html() -> String {
-        // Start page with navigation bar and centered search box
-        r#"<!DOCTYPE html>
-<html lang="ko">
-<head>
-  <meta charset="utf-8" />
-  <meta name="viewport" content="width=device-width, initial-scale=1" />
-  <title>SyncFlo Start</title>
-  <style>
-    * { box-sizing: border-box; }
-    html, body { height: 100%; margin: 0; font-family: -apple-system, BlinkMacSystemFont, Segoe UI, Roboto, Helvetica, Arial, sans-serif; background: #121212; color: #e6e6e6; }
-    
-    /* Navigation Bar */
-    .navbar { position: fixed; top: 0; left: 0; right: 0; height: 56px; background: #1e1e1e; border-bottom: 1px solid #2a2a2a; display: flex; align-items: center; gap: 8px; padding: 0 12px; z-index: 1000; }
-    .nav-btn { width: 36px; height: 36px; border: none; background: #2a2a2a; color: #e6e6e6; border-radius: 6px; cursor: pointer; display: flex; align-items: center; justify-content: center; font-size: 16px; transition: background 0.2s; }
-    .nav-btn:hover { background: #3a3a3a; }
-    .nav-btn:active { background: #1a1a1a; }
-    .nav-btn:disabled { opacity: 0.4; cursor: not-allowed; }
-    .address-bar { flex: 1; height: 36px; padding: 0 12px; border-radius: 6px; border: 1px solid #2a2a2a; background: #1b1b1b; color: #e6e6e6; outline: none; font-size: 14px; }
-    .address-bar:focus { border-color: #3a83f7; box-shadow: 0 0 0 2px rgba(58,131,247,0.25); }
-    .refresh-btn { width: 36px; height: 36px; }
-    
-    /* Content Area */
-    .content { padding-top: 56px; min-height: 100%; display: flex; align-items: center; justify-content: center; }
-    .wrap { width: min(720px, 92vw); text-align: center; }
-    h1 { font-size: 28px; font-weight: 700; margin-bottom: 20px; color: #fafafa; }
-    form { display: flex; gap: 8px; }
-    input[type=text] { flex: 1; height: 48px; padding: 0 16px; border-radius: 12px; border: 1px solid #2a2a2a; background: #1b1b1b; color: #e6e6e6; outline: none; font-size: 16px; }
-    input[type=text]:focus { border-color: #3a83f7; box-shadow: 0 0 0 3px rgba(58,131,247,0.25); }
-    button { height: 48px; padding: 0 18px; border-radius: 12px; border: 0; background: #3a83f7; color: white; font-size: 16px; cursor: pointer; }
-    button:hover { background: #2f73e1; }
-    .hint { margin-top: 12px; color: #a7a7a7; font-size: 13px; }
-    
-    /* Start page only; no global injection on external pages */
-  </style>
-  <script>
-    (function() {
-      function isUrl(str) {
-        try {
-          var url = new URL(str);
-          return url.protocol === 'http:' || url.protocol === 'https:';
-        } catch(e) {
-          if (str.indexOf('.') > 0 && (str.indexOf(' ') === -1)) {
-            return true;
-          }
-          return false;
-        }
-      }
+    }
 
-      function handleSearch(query) {
-        query = query.trim();
-        if (!query) return;
-        
-        var url;
-        if (isUrl(query)) {
-          if (!query.startsWith('http://') && !query.startsWith('https://')) {
-            url = 'https://' + query;
-          } else {
-            url = query;
-          }
-        } else {
-          url = 'https://www.google.com/search?q=' + encodeURIComponent(query);
+    /// Whether to quit now. With downloads in progress, asks first and
+    /// sends `AppEvent::Quit { confirmed: true }` if the user agrees;
+    /// quitting again within [`QUIT_AGAIN_WINDOW`] of being asked doesn't
+    /// ask again.
+    fn may_quit(ctx: &IpcContext, window: &tao::window::Window, asked_at: &mut Option<Instant>) -> bool {
+        let downloads = ctx.downloads.borrow().active();
+        if downloads == 0 || asked_at.is_some_and(|at| at.elapsed() < QUIT_AGAIN_WINDOW) {
+            return true;
         }
-        
-        window.location.href = url;
-      }
-
-      function onSubmit(e) {
-        e.preventDefault();
-        var input = document.getElementById('q');
-        handleSearch(input.value);
-        return false;
-      }
-
-      function onButtonClick() {
-        var input = document.getElementById('q');
-        handleSearch(input.value);
-      }
-
-
-      // Navigation bar handlers
-      function initNavBar() {
-        var backBtn = document.getElementById('navBack');
-        var homeBtn = document.getElementById('navHome');
-        var forwardBtn = document.getElementById('navForward');
-        var refreshBtn = document.getElementById('navRefresh');
-        var addressBar = document.getElementById('addressBar');
-        
-        if (backBtn) {
-          backBtn.addEventListener('click', function() {
-            var before = window.location.href;
-            if (window.history.length > 1) {
-              window.history.back();
-              setTimeout(function(){
-                if (window.location.href === before) {
-                  // fallback to home if no back navigation happened
-                  window.location.href = window.SYNCFLO_HOME || '#';
-                }
-              }, 400);
-            } else {
-              window.location.href = window.SYNCFLO_HOME || '#';
+        *asked_at = Some(Instant::now());
+        let proxy = ctx.proxy.clone();
+        dialogs::confirm_quit(window, downloads, move |quit| {
+            if quit && proxy.send_event(AppEvent::Quit { confirmed: true }).is_err() {
+                log::warn!("Event loop is gone; can't quit");
             }
-          });
-        }
+        });
+        false
+    }
 
-        if (homeBtn) {
-          homeBtn.addEventListener('click', function(){
-            window.location.href = window.SYNCFLO_HOME || '#';
-          });
+    /// Opens `urls`, which another launch or the system sent, as tabs and
+    /// brings the window to the front.
+    fn open_launched(ctx: &IpcContext, window: &tao::window::Window, urls: Vec<String>) {
+        window.set_minimized(false);
+        window.set_focus();
+        for url in urls {
+            ctx.open_in_new_tab(&url);
         }
-        
-        if (forwardBtn) {
-          forwardBtn.addEventListener('click', function() {
-            window.history.forward();
-          });
+    }
+
+    /// Brings the window to the front with `tab`, whose page at `url`
+    /// showed a notification the user clicked, or `url` in a new tab if
+    /// that was closed.
+    fn notification_clicked(ctx: &IpcContext, window: &tao::window::Window, tab: TabId, url: &str) {
+        window.set_minimized(false);
+        window.set_focus();
+        if ctx.switch_tab(tab).is_err() {
+            ctx.open_in_new_tab(url);
         }
-        
-        if (refreshBtn) {
-          refreshBtn.addEventListener('click', function() {
-            window.location.reload();
-          });
+    }
+
+    /// Saves the window's size, position and maximized state to the
+    /// settings, except from incognito windows.
+    fn save_window_state(ctx: &IpcContext, window: &tao::window::Window) {
+        // Kiosk windows are always fullscreen, which isn't how the window
+        // was left
+        if ctx.incognito || ctx.app.as_ref().is_some_and(|app| app.kiosk) {
+            return;
         }
-        
-        if (addressBar) {
-          addressBar.addEventListener('keydown', function(e) {
-            if (e.key === 'Enter') {
-              e.preventDefault();
-              handleSearch(addressBar.value);
-            }
-          });
-          
-          // Update address bar on navigation
-          window.addEventListener('popstate', function() {
-            addressBar.value = window.location.href;
-          });
-          
-          // Update on page load
-          addressBar.value = window.location.href;
+        let Ok(before) = ctx.settings.lock().map(|settings| settings.window) else {
+            return;
+        };
+        let after = before.capture(window);
+        if after == before {
+            return;
         }
-        
-        // Update navigation buttons state
-        function updateNavButtons() {
-          if (backBtn) {
-            backBtn.disabled = window.history.length <= 1;
-          }
-          if (forwardBtn) {
-            // Forward state is harder to detect, keep it enabled
-            forwardBtn.disabled = false;
-          }
+        if let Err(e) = ctx.update_settings(|settings| settings.window = after) {
+            log::warn!("Failed to save the window state: {:#}", e);
         }
-        
-        updateNavButtons();
-        setInterval(updateNavButtons, 500);
-      }
+    }
 
-      window.addEventListener('DOMContentLoaded', function() {
-        document.body.classList.add('start-page');
-        
-        var form = document.getElementById('searchForm');
-        var input = document.getElementById('q');
-        var button = document.getElementById('searchBtn');
-        
-        if (form) form.addEventListener('submit', onSubmit);
-        if (button) button.addEventListener('click', onButtonClick);
-        
-        // Note: Global keyboard shortcuts are disabled to avoid WebKit crashes
-        
-        // Initialize navigation bar
-        initNavBar();
-        
-        if (input) input.focus();
-        
-        // Update address bar when page changes
-        var observer = new MutationObserver(function() {
-          var addressBar = document.getElementById('addressBar');
-          if (addressBar) {
-            addressBar.value = window.location.href;
-          }
+    /// Replaces the platform's error view with HTTPS-first fallback or the
+    /// `syncflo://error` page. Only WebKitGTK reports load failures;
+    /// elsewhere the platform's own error view is shown.
+    #[cfg(target_os = "linux")]
+    fn watch_load_failures(webview: &wry::WebView, id: TabId, ctx: Weak<IpcContext>) {
+        use crate::browser::pages::LoadErrorKind;
+        use gtk::gio::{IOErrorEnum, ResolverError, TlsError};
+        use webkit2gtk::{NetworkError, PolicyError, WebViewExt};
+        use wry::WebViewExtUnix;
+
+        let webview = webview.webview();
+        webview.connect_load_failed({
+            let ctx = ctx.clone();
+            move |_, _, uri, error| {
+                // Cancelled loads were replaced by another navigation
+                if error.matches(NetworkError::Cancelled)
+                    || error.matches(PolicyError::FrameLoadInterruptedByPolicyChange)
+                {
+                    return false;
+                }
+                let kind = if error.kind::<ResolverError>().is_some() {
+                    LoadErrorKind::Dns
+                } else if error.matches(IOErrorEnum::TimedOut) {
+                    LoadErrorKind::Timeout
+                } else if error.kind::<TlsError>().is_some() {
+                    LoadErrorKind::Tls
+                } else {
+                    LoadErrorKind::Network
+                };
+                ctx.upgrade().is_some_and(|ctx| ctx.load_failed(id, uri, kind))
+            }
         });
-        
-        observer.observe(document.body, { childList: true, subtree: true });
-      });
-      
-      // Update address bar on navigation
-      window.addEventListener('load', function() {
-        document.body.classList.remove('start-page');
-        var addressBar = document.getElementById('addressBar');
-        if (addressBar) {
-          addressBar.value = window.location.href;
-        }
-        
-        // Inject navigation bar on all pages (including external pages)
-        if (!document.getElementById('syncflo-navbar')) {
-          injectNavBar();
+        webview.connect_load_failed_with_tls_errors(move |_, uri, _, _| {
+            ctx.upgrade().is_some_and(|ctx| ctx.load_failed(id, uri, LoadErrorKind::Tls))
+        });
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn watch_load_failures(_webview: &wry::WebView, _id: TabId, _ctx: Weak<IpcContext>) {}
+
+    /// Records the certificate of each page that commits and whether it
+    /// loads content over plain HTTP, for the address bar's security chip.
+    #[cfg(target_os = "linux")]
+    fn watch_security(webview: &wry::WebView, id: TabId, ctx: Weak<IpcContext>) {
+        use crate::browser::site_info::{self, PageSecurity};
+        use gtk::gio::prelude::*;
+        use webkit2gtk::{LoadEvent, WebViewExt};
+        use wry::WebViewExtUnix;
+
+        let webview = webview.webview();
+        webview.connect_load_changed({
+            let ctx = ctx.clone();
+            move |webview, event| {
+                if event != LoadEvent::Committed {
+                    return;
+                }
+                let security = match webview.tls_info() {
+                    Some((certificate, errors)) => PageSecurity {
+                        mixed_content: false,
+                        certificate_errors: site_info::certificate_errors(errors),
+                        certificate_pem: certificate.certificate_pem().map(String::from),
+                    },
+                    None => PageSecurity::default(),
+                };
+                if let Some(ctx) = ctx.upgrade() {
+                    ctx.security_committed(id, security);
+                }
+            }
+        });
+        webview.connect_insecure_content_detected(move |_, _| {
+            if let Some(ctx) = ctx.upgrade() {
+                ctx.mixed_content(id);
+            }
+        });
+    }
+
+    /// Turns the page's scripts and images on or off by its site's settings
+    /// as each page starts loading, and again if it's redirected to another
+    /// site. `enable-javascript-markup` leaves the browser's own scripts
+    /// running, so history and shortcuts keep working.
+    #[cfg(target_os = "linux")]
+    fn apply_site_settings(webview: &wry::WebView, ctx: Weak<IpcContext>) {
+        use webkit2gtk::{LoadEvent, SettingsExt, WebViewExt};
+        use wry::WebViewExtUnix;
+
+        webview.webview().connect_load_changed(move |webview, event| {
+            if !matches!(event, LoadEvent::Started | LoadEvent::Redirected) {
+                return;
+            }
+            let (Some(ctx), Some(url), Some(settings)) = (ctx.upgrade(), webview.uri(), WebViewExt::settings(webview))
+            else {
+                return;
+            };
+            let site = ctx.site_settings(&url);
+            settings.set_enable_javascript_markup(site.javascript);
+            settings.set_auto_load_images(site.images);
+        });
+    }
+
+    /// Compiles the blocklist into a WebKit content filter in the background.
+    /// Tabs pick it up on their next page load once it's ready.
+    #[cfg(target_os = "linux")]
+    fn compile_content_filter(blocklist: &Blocklist, store_dir: &Path) -> Rc<RefCell<Option<ContentFilter>>> {
+        let filter = Rc::new(RefCell::new(None));
+        if !blocklist.is_empty() {
+            let slot = filter.clone();
+            ContentFilter::compile(blocklist, store_dir, move |result| match result {
+                Ok(compiled) => *slot.borrow_mut() = Some(compiled),
+                Err(e) => log::warn!("Ad blocking disabled: {:#}", e),
+            });
         }
-      });
-      
-      // Inject navigation bar function - runs on all pages
-      function injectNavBar() {
-        // Only inject if navbar doesn't exist
-        if (document.getElementById('syncflo-navbar')) return;
-        
-        // Navigation bar HTML
-        var navbarHTML = `
-          <div id="syncflo-navbar" style="position: fixed; top: 0; left: 0; right: 0; height: 56px; background: #1e1e1e; border-bottom: 1px solid #2a2a2a; display: flex; align-items: center; gap: 8px; padding: 0 12px; z-index: 999999; font-family: -apple-system, BlinkMacSystemFont, Segoe UI, Roboto, Helvetica, Arial, sans-serif;">
-            <button id="syncflo-navBack" style="width: 36px; height: 36px; border: none; background: #2a2a2a; color: #e6e6e6; border-radius: 6px; cursor: pointer; display: flex; align-items: center; justify-content: center; font-size: 16px; transition: background 0.2s;" title="뒤로가기 (Cmd+[)">←</button>
-            <button id="syncflo-navForward" style="width: 36px; height: 36px; border: none; background: #2a2a2a; color: #e6e6e6; border-radius: 6px; cursor: pointer; display: flex; align-items: center; justify-content: center; font-size: 16px; transition: background 0.2s;" title="앞으로가기 (Cmd+])">→</button>
-            <button id="syncflo-navRefresh" style="width: 36px; height: 36px; border: none; background: #2a2a2a; color: #e6e6e6; border-radius: 6px; cursor: pointer; display: flex; align-items: center; justify-content: center; font-size: 16px; transition: background 0.2s;" title="새로고침 (Cmd+R)">⟳</button>
-            <input id="syncflo-addressBar" type="text" style="flex: 1; height: 36px; padding: 0 12px; border-radius: 6px; border: 1px solid #2a2a2a; background: #1b1b1b; color: #e6e6e6; outline: none; font-size: 14px;" placeholder="주소 또는 검색어 입력" />
-          </div>
-        `;
-        
-        // Inject navbar at the beginning of body
-        if (document.body) {
-          document.body.insertAdjacentHTML('afterbegin', navbarHTML);
-          
-          // Add padding to body to prevent content from being hidden under navbar
-          if (!document.body.style.paddingTop) {
-            document.body.style.paddingTop = '56px';
-          }
-          
-          // Initialize navigation handlers
-          var backBtn = document.getElementById('syncflo-navBack');
-          var forwardBtn = document.getElementById('syncflo-navForward');
-          var refreshBtn = document.getElementById('syncflo-navRefresh');
-          var addressBar = document.getElementById('syncflo-addressBar');
-          
-          if (backBtn) {
-            backBtn.addEventListener('click', function() {
-              window.history.back();
+        filter
+    }
+
+    /// Filters a tab's requests through the content filter and counts the
+    /// blocked ones for the nav bar badge.
+    #[cfg(target_os = "linux")]
+    fn attach_content_blocker(
+        webview: &wry::WebView,
+        id: TabId,
+        ctx: Weak<IpcContext>,
+        filter: Rc<RefCell<Option<ContentFilter>>>,
+    ) {
+        use webkit2gtk::{LoadEvent, URIRequestExt, WebViewExt};
+        use wry::WebViewExtUnix;
+
+        let webview = webview.webview();
+        webview.connect_load_changed({
+            let ctx = ctx.clone();
+            move |webview, event| {
+                if event != LoadEvent::Committed {
+                    return;
+                }
+                let filter = filter.borrow();
+                let (Some(ctx), Some(filter)) = (ctx.upgrade(), filter.as_ref()) else {
+                    return;
+                };
+                let url = webview.uri().unwrap_or_default();
+                filter.apply(webview, ctx.page_committed(id, &url));
+            }
+        });
+        webview.connect_resource_load_started(move |_, _, request| {
+            if let (Some(ctx), Some(url)) = (ctx.upgrade(), request.uri()) {
+                ctx.request_started(id, &url);
+            }
+        });
+    }
+
+    /// Saves downloads to the Downloads folder and tracks their progress.
+    /// WebKitGTK's download objects report progress and can be cancelled,
+    /// so Linux uses them directly instead of wry's handlers.
+    #[cfg(target_os = "linux")]
+    fn watch_downloads(webview: &wry::WebView, ctx: Weak<IpcContext>) {
+        use crate::browser::downloads;
+        use std::cell::Cell;
+        use webkit2gtk::{DownloadExt, URIRequestExt, URIResponseExt, WebContextExt, WebViewExt};
+        use wry::WebViewExtUnix;
+
+        let Some(context) = webview.webview().context() else {
+            return;
+        };
+        context.connect_download_started(move |_, download| {
+            let id = Rc::new(Cell::new(None));
+            let failed = Rc::new(Cell::new(false));
+            download.connect_decide_destination({
+                let (ctx, id) = (ctx.clone(), id.clone());
+                move |download, suggested_name| {
+                    let Some(ctx) = ctx.upgrade() else {
+                        return false;
+                    };
+                    let url = download.request().and_then(|r| r.uri()).unwrap_or_default();
+                    let path = downloads::unique_path(&downloads::default_dir(), suggested_name);
+                    download.set_destination(&path.to_string_lossy());
+                    let cancel = {
+                        let download = download.clone();
+                        Box::new(move || download.cancel())
+                    };
+                    id.set(Some(ctx.download_started(&url, path, Some(cancel))));
+                    true
+                }
             });
-            backBtn.addEventListener('mouseenter', function() { this.style.background = '#3a3a3a'; });
-            backBtn.addEventListener('mouseleave', function() { this.style.background = '#2a2a2a'; });
-          }
-          
-          if (forwardBtn) {
-            forwardBtn.addEventListener('click', function() {
-              window.history.forward();
+            download.connect_received_data({
+                let (ctx, id) = (ctx.clone(), id.clone());
+                move |download, _| {
+                    if let (Some(ctx), Some(id)) = (ctx.upgrade(), id.get()) {
+                        let total = download.response().map(|r| r.content_length());
+                        ctx.download_progress(id, download.received_data_length(), total);
+                    }
+                }
             });
-            forwardBtn.addEventListener('mouseenter', function() { this.style.background = '#3a3a3a'; });
-            forwardBtn.addEventListener('mouseleave', function() { this.style.background = '#2a2a2a'; });
-          }
-          
-          if (refreshBtn) {
-            refreshBtn.addEventListener('click', function() {
-              window.location.reload();
+            download.connect_failed({
+                let failed = failed.clone();
+                move |_, error| {
+                    log::warn!("Download failed: {}", error);
+                    failed.set(true);
+                }
             });
-            refreshBtn.addEventListener('mouseenter', function() { this.style.background = '#3a3a3a'; });
-            refreshBtn.addEventListener('mouseleave', function() { this.style.background = '#2a2a2a'; });
-          }
-          
-          if (addressBar) {
-            addressBar.value = window.location.href;
-            
-            addressBar.addEventListener('keydown', function(e) {
-              if (e.key === 'Enter') {
-                e.preventDefault();
-                var url = addressBar.value.trim();
-                if (!url) return;
-                
-                // Check if URL or search query
-                var isUrl = false;
-                try {
-                  var testUrl = new URL(url);
-                  isUrl = testUrl.protocol === 'http:' || testUrl.protocol === 'https:';
-                } catch(e) {
-                  if (url.indexOf('.') > 0 && url.indexOf(' ') === -1) {
-                    isUrl = true;
-                    url = 'https://' + url;
-                  }
-                }
-                
-                if (isUrl) {
-                  window.location.href = url;
-                } else {
-                  window.location.href = 'https://www.google.com/search?q=' + encodeURIComponent(url);
+            // Emitted after `failed` too, including for cancelled downloads
+            download.connect_finished({
+                let ctx = ctx.clone();
+                move |_| {
+                    if let (Some(ctx), Some(id)) = (ctx.upgrade(), id.get()) {
+                        ctx.download_finished(id, !failed.get());
+                    }
                 }
-              }
             });
-            
-            // Update address bar on navigation
-            window.addEventListener('popstate', function() {
-              if (addressBar) addressBar.value = window.location.href;
+        });
+    }
+
+    /// Downloads through wry, which reports only start and completion.
+    #[cfg(not(target_os = "linux"))]
+    fn with_download_handlers<'a>(
+        builder: wry::WebViewBuilder<'a>,
+        ctx: Weak<IpcContext>,
+    ) -> wry::WebViewBuilder<'a> {
+        use crate::browser::downloads;
+
+        let completed_ctx = ctx.clone();
+        builder
+            .with_download_started_handler(move |url, path| {
+                let Some(ctx) = ctx.upgrade() else {
+                    return false;
+                };
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .or_else(|| url::Url::parse(&url).ok()?.path_segments()?.next_back().map(String::from))
+                    .unwrap_or_default();
+                *path = downloads::unique_path(&downloads::default_dir(), &name);
+                ctx.download_started(&url, path.clone(), None);
+                true
+            })
+            .with_download_completed_handler(move |url, path, success| {
+                if let Some(ctx) = completed_ctx.upgrade() {
+                    ctx.download_finished_url(&url, path, success);
+                }
+            })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn with_download_handlers<'a>(
+        builder: wry::WebViewBuilder<'a>,
+        _ctx: Weak<IpcContext>,
+    ) -> wry::WebViewBuilder<'a> {
+        builder
+    }
+
+    /// Opens `target=_blank` links and `window.open` in a new tab. WebKitGTK
+    /// says whether the user clicked; other popups are blocked unless the
+    /// page reported a click just before or its site is allowed them.
+    #[cfg(target_os = "linux")]
+    fn watch_new_windows(webview: &wry::WebView, id: TabId, ctx: Weak<IpcContext>) {
+        use webkit2gtk::{URIRequestExt, WebViewExt};
+        use wry::WebViewExtUnix;
+
+        webview.webview().connect_create(move |_, action| {
+            let url = action.request().and_then(|request| request.uri());
+            if let (Some(ctx), Some(url)) = (ctx.upgrade(), url) {
+                ctx.new_window_requested(id, &url, action.is_user_gesture());
+            }
+            // The page never gets a window object; the tab is separate
+            None
+        });
+    }
+
+    /// Shows `alert()`, `confirm()`, `prompt()` and `beforeunload` prompts
+    /// as GTK dialogs instead of WebKitGTK's in-page overlays. The page
+    /// waits until the dialog is answered. Other platforms keep the
+    /// webview's own dialogs.
+    #[cfg(target_os = "linux")]
+    fn watch_script_dialogs(webview: &wry::WebView) {
+        use gtk::prelude::*;
+        use webkit2gtk::{ScriptDialogType, WebViewExt};
+        use wry::WebViewExtUnix;
+
+        webview.webview().connect_script_dialog(|view, dialog| {
+            let dialog_type = dialog.dialog_type();
+            let kind = match dialog_type {
+                ScriptDialogType::Alert => DialogKind::Alert,
+                ScriptDialogType::Confirm => DialogKind::Confirm,
+                ScriptDialogType::Prompt => DialogKind::Prompt {
+                    default: dialog.prompt_get_default_text().map(String::from).unwrap_or_default(),
+                },
+                ScriptDialogType::BeforeUnloadConfirm => DialogKind::Leave,
+                _ => return false,
+            };
+            let (title, message) = if matches!(kind, DialogKind::Leave) {
+                (dialogs::LEAVE_TITLE.to_string(), dialogs::LEAVE_MESSAGE.to_string())
+            } else {
+                // Name the site that's asking, as other browsers do
+                let host = view.uri().and_then(|uri| Some(url::Url::parse(&uri).ok()?.host_str()?.to_string()));
+                let title = match host {
+                    Some(host) => format!("{} 내용:", host),
+                    None => "페이지 내용:".to_string(),
+                };
+                (title, dialog.message().map(String::from).unwrap_or_default())
+            };
+            let parent = view.toplevel().and_then(|w| w.downcast::<gtk::Window>().ok());
+            let dialog = dialog.clone();
+            dialogs::show(parent.as_ref(), kind, &title, &message, move |answer| {
+                match (dialog_type, answer) {
+                    (ScriptDialogType::Alert, _) => {}
+                    // A cancelled prompt returns null to the page
+                    (ScriptDialogType::Prompt, Some(text)) => dialog.prompt_set_text(&text),
+                    (ScriptDialogType::Prompt, None) => {}
+                    (_, answer) => dialog.confirm_set_confirmed(answer.is_some()),
+                }
+                dialog.close();
             });
-          }
-          
-          // Update address bar periodically
-          setInterval(function() {
-            if (addressBar && addressBar !== document.activeElement) {
-              addressBar.value = window.location.href;
+            true
+        });
+    }
+
+    /// Asks before pages use the location, camera, microphone or
+    /// notifications, unless their site was answered before. Anything else
+    /// pages ask for, like sharing the screen, is denied.
+    #[cfg(target_os = "linux")]
+    fn watch_permissions(webview: &wry::WebView, ctx: Weak<IpcContext>) {
+        use crate::browser::permissions::Permission;
+        use gtk::prelude::*;
+        use webkit2gtk::{
+            GeolocationPermissionRequest, NotificationPermissionRequest, PermissionRequestExt,
+            UserMediaPermissionRequest, UserMediaPermissionRequestExt, WebViewExt,
+        };
+        use wry::WebViewExtUnix;
+
+        webview.webview().connect_permission_request(move |view, request| {
+            let mut asked = Vec::new();
+            if let Some(media) = request.downcast_ref::<UserMediaPermissionRequest>() {
+                if media.is_for_video_device() {
+                    asked.push(Permission::Camera);
+                }
+                if media.is_for_audio_device() {
+                    asked.push(Permission::Microphone);
+                }
+            } else if request.is::<GeolocationPermissionRequest>() {
+                asked.push(Permission::Geolocation);
+            } else if request.is::<NotificationPermissionRequest>() {
+                asked.push(Permission::Notifications);
+            }
+            let origin = view.uri().and_then(|uri| permissions::origin(&uri));
+            let (Some(ctx), Some(origin)) = (ctx.upgrade(), origin) else {
+                request.deny();
+                return true;
+            };
+            if asked.is_empty() {
+                log::info!("Denied a permission request from {}", origin);
+                request.deny();
+                return true;
+            }
+            match ctx.permissions.lock().ok().and_then(|permissions| permissions.check(&origin, &asked)) {
+                Some(true) => request.allow(),
+                Some(false) => request.deny(),
+                None => {
+                    let parent = view.toplevel().and_then(|w| w.downcast::<gtk::Window>().ok());
+                    let done = {
+                        let permissions = ctx.permissions.clone();
+                        let request = request.clone();
+                        let (origin, asked) = (origin.clone(), asked.clone());
+                        move |allowed| {
+                            if let Ok(mut permissions) = permissions.lock() {
+                                permissions.decide(&origin, &asked, allowed);
+                            }
+                            if allowed {
+                                request.allow();
+                            } else {
+                                request.deny();
+                            }
+                        }
+                    };
+                    dialogs::confirm_permission(parent.as_ref(), &origin, &asked, !ctx.incognito, done);
+                }
             }
-            if (backBtn) {
-              backBtn.disabled = window.history.length <= 1;
+            true
+        });
+    }
+
+    /// Shows the notifications pages show with the system instead of
+    /// WebKitGTK's own. WebKitGTK doesn't pass on their icons, so they get
+    /// the site's.
+    #[cfg(target_os = "linux")]
+    fn watch_notifications(webview: &wry::WebView, id: TabId, ctx: Weak<IpcContext>) {
+        use webkit2gtk::{NotificationExt, WebViewExt};
+        use wry::WebViewExtUnix;
+
+        webview.webview().connect_show_notification(move |view, notification| {
+            if let (Some(ctx), Some(url)) = (ctx.upgrade(), view.uri()) {
+                let title = notification.title().map(String::from).unwrap_or_default();
+                let body = notification.body().map(String::from).unwrap_or_default();
+                ctx.web_notification(id, &url, title, body, None);
+            }
+            true
+        });
+    }
+
+    /// Asks before pages use the location, camera, microphone or
+    /// notifications, as far as the platform's webview reports them, each
+    /// on its own. It doesn't say which page asks, so this goes by
+    /// `page_origin`, the origin of the last page the tab started loading.
+    #[cfg(not(target_os = "linux"))]
+    fn with_permission_handler(
+        builder: wry::WebViewBuilder<'_>,
+        page_origin: Arc<Mutex<Option<String>>>,
+        ctx: Weak<IpcContext>,
+    ) -> wry::WebViewBuilder<'_> {
+        use crate::browser::permissions::Permission;
+        use wry::{PermissionKind, PermissionResponse};
+
+        let Some(ctx) = ctx.upgrade() else {
+            return builder;
+        };
+        let permissions = ctx.permissions.clone();
+        let remembered = !ctx.incognito;
+        builder.with_permission_handler(move |kind| {
+            let permission = match kind {
+                PermissionKind::Geolocation => Permission::Geolocation,
+                PermissionKind::Camera => Permission::Camera,
+                PermissionKind::Microphone => Permission::Microphone,
+                PermissionKind::Notifications => Permission::Notifications,
+                _ => return PermissionResponse::Deny,
+            };
+            let Some(origin) = page_origin.lock().ok().and_then(|origin| origin.clone()) else {
+                return PermissionResponse::Deny;
+            };
+            let decided = permissions.lock().ok().and_then(|permissions| permissions.check(&origin, &[permission]));
+            let allowed = decided.unwrap_or_else(|| {
+                let allowed = dialogs::confirm_permission(&origin, &[permission], remembered);
+                if let Ok(mut permissions) = permissions.lock() {
+                    permissions.decide(&origin, &[permission], allowed);
+                }
+                allowed
+            });
+            if allowed {
+                PermissionResponse::Allow
+            } else {
+                PermissionResponse::Deny
             }
-          }, 500);
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn with_permission_handler(
+        builder: wry::WebViewBuilder<'_>,
+        _page_origin: Arc<Mutex<Option<String>>>,
+        _ctx: Weak<IpcContext>,
+    ) -> wry::WebViewBuilder<'_> {
+        builder
+    }
+
+    /// Hides the nav bar and the sidebar while an element of the page, like
+    /// a video, is fullscreen. WebKitGTK makes the window fullscreen and
+    /// back itself. Elsewhere the platform's webview doesn't say, and the
+    /// element only fills the content area.
+    #[cfg(target_os = "linux")]
+    fn watch_element_fullscreen(webview: &wry::WebView, ctx: Weak<IpcContext>) {
+        use webkit2gtk::WebViewExt;
+        use wry::WebViewExtUnix;
+
+        let webview = webview.webview();
+        webview.connect_enter_fullscreen({
+            let ctx = ctx.clone();
+            move |_| {
+                if let Some(ctx) = ctx.upgrade() {
+                    ctx.element_fullscreen(true);
+                }
+                false
+            }
+        });
+        webview.connect_leave_fullscreen(move |_| {
+            if let Some(ctx) = ctx.upgrade() {
+                ctx.element_fullscreen(false);
+            }
+            false
+        });
+    }
+
+    /// wry doesn't say whether the user clicked, so elsewhere popups go by
+    /// the clicks and key presses the page script reports.
+    #[cfg(not(target_os = "linux"))]
+    fn with_new_window_handler(
+        builder: wry::WebViewBuilder<'_>,
+        id: TabId,
+        ctx: Weak<IpcContext>,
+    ) -> wry::WebViewBuilder<'_> {
+        builder.with_new_window_req_handler(move |url, _| {
+            if let Some(ctx) = ctx.upgrade() {
+                ctx.new_window_requested(id, &url, false);
+            }
+            wry::NewWindowResponse::Deny
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn with_new_window_handler(
+        builder: wry::WebViewBuilder<'_>,
+        _id: TabId,
+        _ctx: Weak<IpcContext>,
+    ) -> wry::WebViewBuilder<'_> {
+        builder
+    }
+
+    /// Shows `menu` at (`x`, `y`) in the content area, which is at `content`
+    /// in the window.
+    fn popup_menu(window: &tao::window::Window, menu: &muda::Menu, content: wry::Rect, x: f64, y: f64) {
+        use muda::ContextMenu;
+
+        let scale = window.scale_factor();
+        let origin = content.position.to_logical::<f64>(scale);
+        let position = Some(muda::dpi::LogicalPosition::new(origin.x + x, origin.y + y).into());
+        #[cfg(target_os = "linux")]
+        {
+            use gtk::prelude::*;
+            use tao::platform::unix::WindowExtUnix;
+            menu.show_context_menu_for_gtk_window(window.gtk_window().upcast_ref::<gtk::Window>(), position);
         }
-      }
-      
-      // Inject navbar on DOMContentLoaded as well (for pages that load quickly)
-      if (document.readyState === 'loading') {
-        document.addEventListener('DOMContentLoaded', injectNavBar);
-      } else {
-        injectNavBar();
-      }
-    })();
-  </script>
-</head>
-<body>
-  <!-- Navigation Bar -->
-  <div class="navbar">
-    <button id="navBack" class="nav-btn" title="뒤로가기 (Cmd+[)">←</button>
-    <button id="navHome" class="nav-btn" title="홈으로">⌂</button>
-    <button id="navForward" class="nav-btn" title="앞으로가기 (Cmd+])">→</button>
-    <button id="navRefresh" class="nav-btn refresh-btn" title="새로고침 (Cmd+R)">⟳</button>
-    <input id="addressBar" type="text" class="address-bar" placeholder="주소 또는 검색어 입력" />
-  </div>
-  
-  <!-- Content Area -->
-  <div class="content">
-    <div class="wrap">
-      <div class="box">
-        <h1>SyncFlo Browser</h1>
-        <form id="searchForm" autocomplete="on">
-          <input id="q" type="text" placeholder="브라우저 검색" />
-          <button id="searchBtn" type="button">검색</button>
-        </form>
-        <div class="hint">Enter로 검색 · URL을 입력하면 해당 사이트로 이동합니다</div>
-      </div>
-    </div>
-  </div>
-</body>
-</html>"#.to_string()
-    }
-
-    // No converter needed when using with_html()
-
-    // Build data URL for the start page so JS can navigate "home"
-    fn build_start_page_data_url() -> String {
-        let html = Self::build_start_page_html();
-        let encoded = base64::encode(html);
-        format!("data:text/html;base64,{}", encoded)
-    }
-
-    // Global script injected on every page load to render a minimal navigation bar
-    fn build_global_nav_script(home_data_url: &str) -> String {
-        let template = r#"
-(function() {
-  try {
-    function ensureNavbar() {
-      if (document.getElementById('syncflo-navbar')) return;
-
-      var navbar = document.createElement('div');
-      navbar.id = 'syncflo-navbar';
-      navbar.style.cssText = 'position:fixed;top:0;left:0;right:0;height:44px;background:rgba(30,30,30,0.95);backdrop-filter:saturate(150%) blur(6px);border-bottom:1px solid #2a2a2a;display:flex;align-items:center;gap:8px;padding:0 8px;z-index:2147483647;pointer-events:auto;box-shadow:0 2px 8px rgba(0,0,0,0.35);font-family:-apple-system,BlinkMacSystemFont,Segoe UI,Roboto,Helvetica,Arial,sans-serif;';
-
-      function mkBtn(id, text, title) {
-        var b = document.createElement('button');
-        b.id = id; b.textContent = text; b.title = title;
-        b.style.cssText = 'width:32px;height:32px;border:none;background:#2a2a2a;color:#e6e6e6;border-radius:6px;cursor:pointer;display:flex;align-items:center;justify-content:center;font-size:14px;';
-        b.addEventListener('mouseenter', function(){ this.style.background = '#3a3a3a'; });
-        b.addEventListener('mouseleave', function(){ this.style.background = '#2a2a2a'; });
-        return b;
-      }
-
-      var back = mkBtn('syncflo-navBack', '←', '뒤로가기');
-      var home = mkBtn('syncflo-navHome', '⌂', '홈으로 (Cmd+H)');
-      var fwd  = mkBtn('syncflo-navForward', '→', '앞으로가기');
-      var ref  = mkBtn('syncflo-navRefresh', '⟳', '새로고침');
-      var devtools = mkBtn('syncflo-devTools', '⚙', '개발자 도구 (F12)');
-      var addr = document.createElement('input');
-      addr.id = 'syncflo-addressBar';
-      addr.placeholder = '주소 또는 검색어 입력';
-      addr.style.cssText = 'flex:1;height:32px;padding:0 10px;border-radius:6px;border:1px solid #2a2a2a;background:#1b1b1b;color:#e6e6e6;outline:none;font-size:13px;';
-
-      navbar.appendChild(back); navbar.appendChild(home); navbar.appendChild(fwd); navbar.appendChild(ref); navbar.appendChild(devtools); navbar.appendChild(addr);
-
-      document.documentElement.appendChild(navbar);
-      var body = document.body || document.documentElement;
-      if (body) {
-        // Reserve space so page elements aren't interactable behind the navbar
-        var current = parseInt((body.style.paddingTop||'0').replace('px','')) || 0;
-        if (current < 60) body.style.paddingTop = '60px';
-      }
-
-      function handleEnter(url) {
-        url = (url || '').trim(); if (!url) return;
-        var isUrl = false;
-        try { var u = new URL(url); isUrl = (u.protocol === 'http:' || u.protocol === 'https:'); } catch(e) {
-          if (url.indexOf('.')>0 && url.indexOf(' ')===-1) { isUrl = true; url = 'https://' + url; }
+        #[cfg(target_os = "macos")]
+        {
+            use tao::platform::macos::WindowExtMacOS;
+            // SAFETY: the view belongs to a live window
+            unsafe { menu.show_context_menu_for_nsview(window.ns_view(), position) };
         }
-        window.location.href = isUrl ? url : ('https://www.google.com/search?q=' + encodeURIComponent(url));
-      }
-
-      var homeUrl = window.SYNCFLO_HOME || '{HOME}';
-      
-      // Enhanced back button - go to home if can't go back
-      back.onclick = function(){
-        try {
-          // Try history.back() first
-          if (history.length > 1 && document.referrer) {
-            var before = location.href;
-            history.back();
-            // Check after a delay if we actually navigated back
-            setTimeout(function(){ 
-              if (location.href === before || location.href === document.referrer) {
-                // Didn't navigate, go to home instead
-                window.location.replace(homeUrl);
-              }
-            }, 100);
-          } else {
-            // No history, go directly to home
-            window.location.replace(homeUrl);
-          }
-        } catch(e) {
-          // Fallback to home on any error
-          window.location.replace(homeUrl);
+        #[cfg(target_os = "windows")]
+        {
+            use tao::platform::windows::WindowExtWindows;
+            // SAFETY: the handle belongs to a live window
+            unsafe { menu.show_context_menu_for_hwnd(window.hwnd(), position) };
         }
-      };
-      
-      home.onclick = function(){ window.location.replace(homeUrl); };
-      fwd.onclick  = function(){ history.forward(); };
-      ref.onclick  = function(){ location.reload(); };
-      devtools.onclick = function(){ console.log('DevTools: macOS에서는 Cmd+Option+I를 사용하세요.'); alert('DevTools: macOS에서는 Cmd+Option+I를 사용하세요.'); };
-      addr.onkeydown = function(e){ if (e.key === 'Enter') { e.preventDefault(); handleEnter(addr.value); } };
-
-      function syncAddr(){ if (document.activeElement !== addr) addr.value = location.href; }
-      syncAddr(); setInterval(syncAddr, 700);
-      
-      // Add keyboard shortcut for home (Cmd+H or Ctrl+H)
-      document.addEventListener('keydown', function(e) {
-        if ((e.metaKey || e.ctrlKey) && e.key === 'h') {
-          e.preventDefault();
-          window.location.replace(homeUrl);
+    }
+
+    /// The URL to open for `arg` from the command line: the local file at
+    /// that path if there is one, else what typing it in the nav bar opens.
+    fn launch_url(arg: &str, engine: &SearchEngine) -> String {
+        let path = Path::new(arg);
+        if path.exists() {
+            if let Some(url) = path.canonicalize().ok().and_then(|path| Url::from_file_path(path).ok()) {
+                return url.into();
+            }
         }
-      });
+        search::resolve_input(arg, engine).into()
     }
 
-    if (document.readyState === 'loading') {
-      document.addEventListener('DOMContentLoaded', ensureNavbar, { once:true });
-    } else {
-      ensureNavbar();
+    /// Bounds of the nav, content and sidebar webviews in `window`. The nav
+    /// bar takes its height, and that of its `popover`, from the content
+    /// only while it's shown, and the sidebar its width
+    /// only while it's open.
+    ///
+    /// Everything is in logical pixels: the window's physical size is
+    /// converted with its current scale factor, and the webviews convert
+    /// back with the same one, so the nav bar keeps its height on HiDPI
+    /// displays.
+    fn layout_bounds(window: &tao::window::Window, nav_shown: bool, popover: f64, sidebar_open: bool) -> WindowLayout {
+        use wry::dpi::{LogicalPosition, LogicalSize};
+
+        let scale_factor = window.scale_factor();
+        let size = window.inner_size().to_logical::<f64>(scale_factor);
+        let nav_height = if nav_shown { (NAV_HEIGHT + popover).min(size.height) } else { 0.0 };
+        let sidebar_width = SIDEBAR_WIDTH.min(size.width / 2.0);
+        let content_width = if sidebar_open { size.width - sidebar_width } else { size.width };
+        let nav = wry::Rect {
+            position: LogicalPosition::new(0.0, 0.0).into(),
+            size: LogicalSize::new(size.width, nav_height).into(),
+        };
+        let content = wry::Rect {
+            position: LogicalPosition::new(0.0, nav_height).into(),
+            size: LogicalSize::new(content_width, size.height - nav_height).into(),
+        };
+        let sidebar = wry::Rect {
+            position: LogicalPosition::new(content_width, nav_height).into(),
+            size: LogicalSize::new(sidebar_width, size.height - nav_height).into(),
+        };
+        WindowLayout { nav, content, sidebar, scale_factor }
     }
-    window.addEventListener('load', ensureNavbar);
-    // expose home URL for all pages
-    try { window.SYNCFLO_HOME = window.SYNCFLO_HOME || '{HOME}'; } catch(_) {}
-  } catch (e) { /* ignore */ }
-})();
-"#;
-        template.replace("{HOME}", home_data_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::permissions::Permission;
+    use std::fs;
+
+    /// Every file and directory under `dir` with the files' contents,
+    /// sorted by path.
+    fn tree(dir: &Path) -> Vec<(PathBuf, Option<Vec<u8>>)> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                entries.extend(tree(&path));
+                entries.push((path, None));
+            } else {
+                entries.push((path.clone(), Some(fs::read(&path).unwrap())));
+            }
+        }
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn incognito_leaves_nothing_in_the_data_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile = Profile::open(None, Some(dir.path())).unwrap();
+        // A profile that was used before
+        let mut normal = ProfileData::load(&profile, false, false).unwrap();
+        normal.bookmarks.add(Url::parse("https://example.com/saved").unwrap(), "Saved");
+        normal.reading_list.save().unwrap();
+        normal.settings.set("https_first", true.into()).unwrap();
+        normal.settings.save_to(&profile.settings_path()).unwrap();
+        normal.permissions.decide("https://example.com", &[Permission::Camera], false);
+        drop(normal);
+        let before = tree(dir.path());
+
+        // What an incognito window changes while it's open, in what `run`
+        // gets to work with
+        let options = LaunchOptions { incognito: true, user_data_dir: Some(dir.path().into()), ..Default::default() };
+        let mut browser = Browser::new(options).unwrap().expect("incognito windows don't forward");
+        let data = &mut browser.data;
+        assert_eq!(data.bookmarks.list().len(), 1);
+        let url = data.navigation.navigate("https://example.com/private".into()).unwrap();
+        data.navigation.set_current_title("Private");
+        data.navigation.visit(&url);
+        data.navigation.save().unwrap();
+        data.bookmarks.add(Url::parse("https://example.com/private").unwrap(), "Private");
+        data.reading_list.save().unwrap();
+        data.settings.set("https_first", false.into()).unwrap();
+        data.permissions.decide("https://example.com", &[Permission::Camera], true);
+        data.permissions.save().unwrap();
+        data.ai_usage.reset();
+        #[cfg(target_os = "linux")]
+        {
+            assert!(!data.content_filters_dir.starts_with(dir.path()));
+            fs::create_dir_all(&data.content_filters_dir).unwrap();
+            fs::write(data.content_filters_dir.join("filter"), "compiled").unwrap();
+        }
+        let scratch = data.scratch.as_ref().unwrap().path().to_path_buf();
+        assert!(!scratch.starts_with(dir.path()));
+        drop(browser);
+
+        assert_eq!(tree(dir.path()), before);
+        assert!(!scratch.exists());
     }
 }
@@ -0,0 +1,42 @@
+//! Handing URLs the browser doesn't open itself to the system, like
+//! `mailto:` or `zoommtg:` links.
+
+use crate::browser::assets;
+use std::process::Command;
+use url::Url;
+
+/// Schemes the webviews load themselves.
+const BROWSER_SCHEMES: &[&str] = &["http", "https", "file", "about", "blob", "ws", "wss", assets::SCHEME];
+/// Schemes whose URLs carry their own script or content. Another program
+/// would run or show it outside the page's sandbox, so they're never
+/// handed on.
+const NEVER_FORWARDED: &[&str] = &["javascript", "data", "vbscript"];
+
+/// Whether links to `scheme` may be handed to the system.
+pub fn is_forwardable(scheme: &str) -> bool {
+    let scheme = scheme.to_ascii_lowercase();
+    !BROWSER_SCHEMES.contains(&scheme.as_str()) && !NEVER_FORWARDED.contains(&scheme.as_str())
+}
+
+/// Opens `url` with the system's handler for its scheme, like the default
+/// browser for web pages.
+pub fn open(url: &Url) -> anyhow::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        // Unlike `cmd /C start`, this doesn't read `&` in the URL as a
+        // command separator
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = command
+        .arg(url.as_str())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to run {:?}: {}", command.get_program(), e))?;
+    // Reap it so it doesn't linger as a zombie
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,95 @@
+//! Site icons for the nav bar and notifications, cached on disk by host.
+
+use crate::browser::proxy::ProxySettings;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use url::Url;
+
+/// Larger icons are skipped; the nav bar shows them at 16px anyway, and
+/// notifications not much larger.
+const MAX_ICON_BYTES: u64 = 256 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where the icon of `host` is cached in `dir`.
+fn cache_path(dir: &Path, host: &str) -> PathBuf {
+    // Hosts are already lowercase ASCII; `:` from IPv6 isn't valid everywhere
+    let name: String = host
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect();
+    dir.join(name)
+}
+
+/// The icon for `host` cached in `dir` as a data URL, if there is one.
+pub fn cached(dir: &Path, host: &str) -> Option<String> {
+    let bytes = fs::read(cache_path(dir, host)).ok()?;
+    data_url(&bytes)
+}
+
+/// Where the icon of `host` is cached in `dir`, if it is.
+pub fn cached_path(dir: &Path, host: &str) -> Option<PathBuf> {
+    Some(cache_path(dir, host)).filter(|path| path.is_file())
+}
+
+/// Downloads `icon_url` through `proxy`, caches it for `host` in `dir` and
+/// returns it as a data URL. Blocks, so call it off the main thread.
+pub fn fetch(icon_url: &Url, host: &str, dir: &Path, proxy: &ProxySettings) -> Result<String> {
+    let bytes = download(icon_url, proxy)?;
+    let data_url = data_url(&bytes).with_context(|| format!("{} isn't an image", icon_url))?;
+
+    let written = fs::create_dir_all(dir).and_then(|_| fs::write(cache_path(dir, host), &bytes));
+    if let Err(e) = written {
+        log::warn!("Failed to cache icon for {}: {}", host, e);
+    }
+    Ok(data_url)
+}
+
+/// Downloads the image at `icon_url` through `proxy`, up to
+/// [`MAX_ICON_BYTES`]. Blocks.
+pub fn download(icon_url: &Url, proxy: &ProxySettings) -> Result<Vec<u8>> {
+    if !matches!(icon_url.scheme(), "http" | "https") {
+        bail!("Not fetching icon {}", icon_url);
+    }
+    let response = proxy
+        .blocking_client(FETCH_TIMEOUT)?
+        .get(icon_url.as_str())
+        .send()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch {}", icon_url))?;
+    let mut bytes = Vec::new();
+    response.take(MAX_ICON_BYTES + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > MAX_ICON_BYTES {
+        bail!("Icon {} is too large", icon_url);
+    }
+    if image_type(&bytes).is_none() {
+        bail!("{} isn't an image", icon_url);
+    }
+    Ok(bytes)
+}
+
+fn data_url(bytes: &[u8]) -> Option<String> {
+    Some(format!("data:{};base64,{}", image_type(bytes)?, base64::encode(bytes)))
+}
+
+/// MIME type of an image, sniffed from its first bytes.
+pub fn image_type(bytes: &[u8]) -> Option<&'static str> {
+    let head = &bytes[..bytes.len().min(256)];
+    if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if head.starts_with(&[0, 0, 1, 0]) {
+        Some("image/x-icon")
+    } else if head.starts_with(b"GIF8") {
+        Some("image/gif")
+    } else if head.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP") {
+        Some("image/webp")
+    } else if String::from_utf8_lossy(head).contains("<svg") {
+        Some("image/svg+xml")
+    } else {
+        None
+    }
+}
@@ -0,0 +1,63 @@
+//! HTTPS-first mode: `http://` navigations are tried over HTTPS and fall
+//! back to HTTP once if the secure load fails.
+
+use url::{Host, Url};
+
+/// Upgrade state of one tab's current navigation.
+///
+/// A navigation is everything from the first request until a page commits,
+/// so a server redirecting HTTPS back to HTTP gets one downgrade and can't
+/// bounce between the two schemes forever.
+#[derive(Debug, Default)]
+pub struct HttpsFirst {
+    /// The `http://` URL that was upgraded, until a page commits.
+    upgraded: Option<Url>,
+    /// Whether the current navigation already fell back to HTTP.
+    downgraded: bool,
+}
+
+impl HttpsFirst {
+    /// The `https://` URL to load instead of `url`, if it should be upgraded.
+    pub fn upgrade(&mut self, url: &Url) -> Option<Url> {
+        if url.scheme() != "http" || is_local(url) || self.downgraded {
+            return None;
+        }
+        if self.upgraded.as_ref().is_some_and(|prev| prev.host() == url.host()) {
+            // The HTTPS site sent us back to HTTP; don't try again
+            self.downgraded = true;
+            return None;
+        }
+        let mut secure = url.clone();
+        secure.set_scheme("https").ok()?;
+        if url.port() == Some(80) {
+            secure.set_port(None).ok()?;
+        }
+        self.upgraded = Some(url.clone());
+        Some(secure)
+    }
+
+    /// The original `http://` URL to retry after `failed` didn't load, at
+    /// most once per navigation.
+    pub fn fallback(&mut self, failed: &Url) -> Option<Url> {
+        if self.downgraded || failed.scheme() != "https" {
+            return None;
+        }
+        let original = self.upgraded.as_ref().filter(|url| url.host() == failed.host())?;
+        self.downgraded = true;
+        Some(original.clone())
+    }
+
+    /// Ends the current navigation.
+    pub fn committed(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Localhost, IP addresses and single-label intranet hosts rarely have
+/// certificates, so they're left on HTTP.
+fn is_local(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Domain(domain)) => domain == "localhost" || !domain.contains('.'),
+        Some(Host::Ipv4(_)) | Some(Host::Ipv6(_)) | None => true,
+    }
+}
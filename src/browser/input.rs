@@ -0,0 +1,262 @@
+//! Deciding whether address-bar input is a URL or a search query.
+
+use crate::browser::assets;
+use std::collections::HashSet;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use url::Url;
+
+/// What the user meant by address-bar input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputKind {
+    Url(Url),
+    Search(String),
+}
+
+/// Schemes that are URLs even without `//`, e.g. `about:blank`.
+const OPAQUE_SCHEMES: &[&str] = &["about", "data", "javascript", "mailto", "vbscript", "view-source"];
+
+/// Punctuation dropped from the end of a pasted address, e.g. `example.com.`
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?'];
+
+/// Classifies address-bar input.
+///
+/// Explicit URLs (`scheme://...`, `about:blank`) are kept as typed. Bare
+/// addresses get a scheme: `http` for localhost, IP addresses and intranet
+/// hosts, `https` for everything else. Anything containing whitespace, and
+/// single words without a port or path, are searches. Absolute paths of
+/// existing files open the file.
+pub fn classify(input: &str) -> InputKind {
+    let input = input.trim();
+    let search = || InputKind::Search(input.to_string());
+    // Before anything else: `C:\Users` looks like a URL scheme, and paths
+    // may have spaces
+    if let Some(url) = local_file(input) {
+        return InputKind::Url(url);
+    }
+    if input.is_empty() || input.contains(char::is_whitespace) {
+        return search();
+    }
+
+    if let Some(url) = explicit_url(input) {
+        return InputKind::Url(url);
+    }
+
+    let candidate = input.trim_end_matches(TRAILING_PUNCTUATION);
+    match bare_address(candidate) {
+        Some(url) => InputKind::Url(url),
+        None => search(),
+    }
+}
+
+/// A `file://` URL for input that's the absolute path of an existing file
+/// or directory. Built with [`Url::from_file_path`], which gets drive
+/// letters and backslashes right on Windows.
+fn local_file(input: &str) -> Option<Url> {
+    let path = std::path::Path::new(input);
+    if !path.is_absolute() || !path.exists() {
+        return None;
+    }
+    Url::from_file_path(path.canonicalize().ok()?).ok()
+}
+
+/// Input that already names its scheme.
+fn explicit_url(input: &str) -> Option<Url> {
+    if let Some(rest) = input.strip_prefix("syncflo://") {
+        let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+        return Url::parse(&assets::url(host, path)).ok();
+    }
+    let (scheme, rest) = input.split_once(':')?;
+    let valid_scheme = scheme
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    let lower = scheme.to_ascii_lowercase();
+    if valid_scheme && (rest.starts_with("//") || OPAQUE_SCHEMES.contains(&lower.as_str())) {
+        Url::parse(input).ok()
+    } else {
+        None
+    }
+}
+
+/// `host[:port][/path]` without a scheme.
+fn bare_address(input: &str) -> Option<Url> {
+    // Bare IPv6 addresses can't take a port or path without brackets
+    if let Ok(ip) = input.parse::<Ipv6Addr>() {
+        return Url::parse(&format!("http://[{}]/", ip)).ok();
+    }
+
+    let authority_end = input.find(['/', '?', '#']).unwrap_or(input.len());
+    let (authority, rest) = input.split_at(authority_end);
+    if authority.contains('@') {
+        return None;
+    }
+    let (host, port) = split_port(authority)?;
+
+    let local = if host.starts_with('[') && host.ends_with(']') {
+        host[1..host.len() - 1].parse::<Ipv6Addr>().ok()?;
+        true
+    } else if looks_numeric(host) {
+        // Only dotted quads; the URL parser would also accept `1.5` or `123`
+        host.parse::<Ipv4Addr>().ok()?;
+        true
+    } else if host.eq_ignore_ascii_case("localhost") {
+        true
+    } else if !host.split('.').all(is_dns_label) {
+        return None;
+    } else if host.contains('.') {
+        let tld = host.rsplit('.').next().unwrap_or("");
+        if tld.len() < 2 || !(tld.chars().all(char::is_alphabetic) || tld.starts_with("xn--")) {
+            return None;
+        }
+        false
+    } else {
+        // A single word is a search unless it's clearly an address
+        if port.is_none() && rest.is_empty() {
+            return None;
+        }
+        true
+    };
+
+    let scheme = if local { "http" } else { "https" };
+    Url::parse(&format!("{}://{}", scheme, input)).ok()
+}
+
+/// Splits `host:port`. Returns `None` if the port isn't a number.
+fn split_port(authority: &str) -> Option<(&str, Option<u16>)> {
+    let port_start = match authority.rfind(':') {
+        Some(i) if !authority[i..].contains(']') => i,
+        _ => return Some((authority, None)),
+    };
+    let port = authority[port_start + 1..].parse::<u16>().ok()?;
+    Some((&authority[..port_start], Some(port)))
+}
+
+fn looks_numeric(host: &str) -> bool {
+    host.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+fn is_dns_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= 63
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+}
+
+/// Why a typed URL may not be opened, as a message for the user, or `None`
+/// if it's allowed.
+///
+/// Script and `data:` URLs would run in whatever page is open, and `file:`
+/// URLs are only allowed inside the app's assets directory and for `chosen`
+/// files, which the user picked in the open-file dialog (canonicalized).
+pub fn blocked_reason(url: &Url, chosen: &HashSet<PathBuf>) -> Option<&'static str> {
+    match url.scheme() {
+        "javascript" | "vbscript" => Some("스크립트 주소는 주소창에서 열 수 없습니다."),
+        "data" => Some("data: 주소는 주소창에서 열 수 없습니다."),
+        "file" if !is_allowed_file(url, chosen) => Some("허용되지 않은 위치의 파일입니다."),
+        _ => None,
+    }
+}
+
+fn is_allowed_file(url: &Url, chosen: &HashSet<PathBuf>) -> bool {
+    // Canonicalize so `..` and symlinks can't escape the directory
+    let Ok(path) = url.to_file_path().and_then(|path| path.canonicalize().map_err(|_| ())) else {
+        return false;
+    };
+    if chosen.contains(&path) {
+        return true;
+    }
+    assets::dev_assets_dir()
+        .and_then(|dir| dir.canonicalize().ok())
+        .is_some_and(|dir| path.starts_with(dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(input: &str) -> String {
+        match classify(input) {
+            InputKind::Url(url) => url.to_string(),
+            InputKind::Search(query) => panic!("{:?} was taken as the search {:?}", input, query),
+        }
+    }
+
+    fn is_search(input: &str) -> bool {
+        matches!(classify(input), InputKind::Search(_))
+    }
+
+    #[test]
+    fn localhost_and_ports_are_http() {
+        assert_eq!(url("localhost"), "http://localhost/");
+        assert_eq!(url("localhost:8080"), "http://localhost:8080/");
+        assert_eq!(url("LOCALHOST:3000/api"), "http://localhost:3000/api");
+    }
+
+    #[test]
+    fn ip_addresses_are_http() {
+        assert_eq!(url("192.168.1.1"), "http://192.168.1.1/");
+        assert_eq!(url("http://192.168.1.1"), "http://192.168.1.1/");
+        assert_eq!(url("10.0.0.2:8443/admin"), "http://10.0.0.2:8443/admin");
+        assert_eq!(url("::1"), "http://[::1]/");
+        assert_eq!(url("[fe80::1]:8080"), "http://[fe80::1]:8080/");
+        assert!(is_search("1.5"));
+        assert!(is_search("256.1.1.1"));
+    }
+
+    #[test]
+    fn intranet_hosts_need_a_port_or_path() {
+        assert_eq!(url("router:80"), "http://router/");
+        assert_eq!(url("wiki/Main_Page"), "http://wiki/Main_Page");
+        assert!(is_search("wiki"));
+    }
+
+    #[test]
+    fn domains_get_https() {
+        assert_eq!(url("example.com"), "https://example.com/");
+        assert_eq!(url("docs.rs/url/latest"), "https://docs.rs/url/latest");
+        assert_eq!(url("한국.xn--3e0b707e"), "https://xn--3e0b707e.xn--3e0b707e/");
+        assert!(is_search("file.txt1"));
+        assert!(is_search("a.b"));
+    }
+
+    #[test]
+    fn explicit_schemes_are_kept() {
+        assert_eq!(url("about:blank"), "about:blank");
+        assert_eq!(url("ftp://files.example.com/pub"), "ftp://files.example.com/pub");
+        assert_eq!(url("mailto:someone@example.com"), "mailto:someone@example.com");
+        assert_eq!(url("HTTPS://Example.com"), "https://example.com/");
+    }
+
+    #[test]
+    fn trailing_punctuation_is_dropped() {
+        assert_eq!(url("example.com."), "https://example.com/");
+        assert_eq!(url("example.com,"), "https://example.com/");
+        assert_eq!(url("localhost:8080!"), "http://localhost:8080/");
+    }
+
+    #[test]
+    fn sentences_and_words_are_searches() {
+        assert_eq!(classify("how to use vec.push"), InputKind::Search("how to use vec.push".into()));
+        assert_eq!(classify("  rust  "), InputKind::Search("rust".into()));
+        assert!(is_search(""));
+        assert!(is_search("user@example.com"));
+        assert!(is_search("c++"));
+    }
+
+    #[test]
+    fn scripts_and_data_are_blocked() {
+        let chosen = HashSet::new();
+        for input in ["javascript:alert(1)", "data:text/html,<p>hi</p>"] {
+            let InputKind::Url(url) = classify(input) else {
+                panic!("{:?} should be a URL", input);
+            };
+            assert!(blocked_reason(&url, &chosen).is_some());
+        }
+        assert!(blocked_reason(&Url::parse("https://example.com/").unwrap(), &chosen).is_none());
+    }
+}
@@ -0,0 +1,3778 @@
+use crate::browser::app_mode::AppMode;
+use crate::browser::archive::{self, ArchiveReport, CapturedPage};
+use crate::browser::blocker::Blocklist;
+use crate::browser::bookmarks::BookmarkStore;
+use crate::browser::context_menu::{self, ContextTarget, OpenMenu};
+use crate::browser::downloads::{self, DownloadId, DownloadManager, DownloadState};
+use crate::ai::{
+    AIAssistant, AIResponse, AiAction, AiError, ContentProcessor, Conversation, ImageMode, ProposedAction,
+    ProviderInfo, ResponseCache, UsageLog,
+};
+use crate::browser::assistant::{self, AiRequestId, ChatTurn, Question};
+use crate::browser::default_browser;
+#[cfg(not(target_os = "linux"))]
+use crate::browser::dialogs;
+use crate::browser::dialogs::SaveAs;
+use crate::browser::external;
+use crate::browser::favicon;
+use crate::browser::input;
+use crate::browser::keychain;
+use crate::browser::notifications::{self, WebNotification};
+use crate::browser::pages::{self, LoadErrorKind};
+use crate::browser::permissions::{self, Permission, PermissionBroker};
+use crate::browser::privacy;
+use crate::browser::profile::Profile;
+use crate::browser::proxy::{self, ProxySettings};
+use crate::browser::reading_list::{self, ReadingItem, ReadingList};
+use crate::browser::recovery;
+use crate::browser::search;
+use crate::browser::session::Session;
+use crate::browser::settings::Settings;
+use crate::browser::shortcuts::{KeyPress, Shortcut};
+use crate::browser::site_info::{self, PageSecurity, Security, SiteInfo, SiteSettings};
+use crate::browser::suggest::{self, Suggestion};
+use crate::browser::tabs::{TabId, TabManager, TabView};
+use crate::browser::{assets, worker, Navigation};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+use tao::event_loop::EventLoopProxy;
+use tokio_util::sync::CancellationToken;
+use wry::{Rect, WebView};
+
+// Injected into every page of the content webviews. Runs at document start, so
+// `location.href` is already the final URL after any HTTP redirects.
+pub const COMMIT_REPORTER_SCRIPT: &str = r#"
+(function() {
+  if (window.top !== window) return;
+  function report() {
+    try { window.ipc.postMessage(JSON.stringify({ op: 'committed', payload: { url: location.href } })); } catch (e) {}
+  }
+  function reportIcons() {
+    var links = Array.prototype.map.call(document.querySelectorAll('link[rel~="icon" i]'), function(l) { return l.outerHTML; });
+    try { window.ipc.postMessage(JSON.stringify({ op: 'page_icons', payload: { links: links.join('') } })); } catch (e) {}
+  }
+  report();
+  if (document.readyState === 'loading') document.addEventListener('DOMContentLoaded', reportIcons);
+  else reportIcons();
+  // Popups need a click or key press shortly before; reported as they
+  // happen, at most every 250ms
+  var lastGesture = -1000;
+  function gesture(e) {
+    if (!e.isTrusted || e.timeStamp - lastGesture < 250) return;
+    lastGesture = e.timeStamp;
+    try { window.ipc.postMessage(JSON.stringify({ op: 'user_gesture' })); } catch (err) {}
+  }
+  ['pointerdown', 'keydown', 'touchend', 'click'].forEach(function(type) {
+    window.addEventListener(type, gesture, { capture: true, passive: true });
+  });
+  // Reveals the hidden nav bar in fullscreen when the pointer reaches the
+  // top edge; sent only when that changes
+  var atTop = false;
+  window.addEventListener('mousemove', function(e) {
+    if ((e.clientY <= 2) === atTop) return;
+    atTop = !atTop;
+    try { window.ipc.postMessage(JSON.stringify({ op: 'pointer_at_top', payload: { at_top: atTop } })); } catch (err) {}
+  }, { passive: true });
+  var push = history.pushState;
+  history.pushState = function() { var r = push.apply(this, arguments); report(); return r; };
+  window.addEventListener('hashchange', report);
+})();
+"#;
+
+// Injected into the nav webview. `window.syncflo.invoke(op, payload)` returns a
+// promise settled when Rust replies through `window.syncflo._resolve`, and
+// `window.syncflo.on(event, cb)` subscribes to events pushed with `_emit`.
+pub const NAV_PRELOAD_SCRIPT: &str = r#"
+(function() {
+  var nextId = 1;
+  var pending = {};
+  var listeners = {};
+  window.syncflo = {
+    invoke: function(op, payload) {
+      var id = nextId++;
+      return new Promise(function(resolve, reject) {
+        pending[id] = { resolve: resolve, reject: reject };
+        window.ipc.postMessage(JSON.stringify({ id: id, op: op, payload: payload }));
+      });
+    },
+    send: function(op, payload) {
+      window.ipc.postMessage(JSON.stringify({ op: op, payload: payload }));
+    },
+    on: function(event, cb) {
+      (listeners[event] = listeners[event] || []).push(cb);
+    },
+    _resolve: function(id, result, error) {
+      var p = pending[id];
+      if (!p) return;
+      delete pending[id];
+      if (error) p.reject(new Error(error)); else p.resolve(result);
+    },
+    _emit: function(event, data) {
+      (listeners[event] || []).forEach(function(cb) {
+        try { cb(data); } catch (e) { console.log(e); }
+      });
+    }
+  };
+})();
+"#;
+
+// Evaluated into the active tab before each find command; defines
+// `window.__syncfloFind` once per page.
+const FIND_SCRIPT: &str = include_str!("../../assets/find.js");
+
+// Evaluated into the active tab before the nav bar leaves the page. Runs the
+// page's `beforeunload` handlers and returns whether one of them objects.
+// Browsers only ask once the user has interacted with the page.
+const BEFOREUNLOAD_CHECK: &str = r#"
+(function() {
+  if (navigator.userActivation && !navigator.userActivation.hasBeenActive) return false;
+  var e = document.createEvent('BeforeUnloadEvent');
+  e.initEvent('beforeunload', false, true);
+  return !window.dispatchEvent(e) || !!e.returnValue;
+})()
+"#;
+
+// Evaluated into a tab to read its selected text. A selection inside a
+// frame is only readable if the frame is same-origin; otherwise the result
+// is empty.
+const SELECTION_SCRIPT: &str = r#"
+(function() {
+  var text = String(window.getSelection() || '').trim();
+  var el = document.activeElement;
+  if (!text && el && (el.tagName === 'IFRAME' || el.tagName === 'FRAME')) {
+    try { text = String(el.contentWindow.getSelection() || '').trim(); } catch (e) {}
+  }
+  return text || null;
+})()
+"#;
+
+// Evaluated into a tab to scroll to the heading whose text is the argument
+// appended in parentheses, compared like the assistant compared it.
+const SCROLL_TO_HEADING_SCRIPT: &str = r#"
+(function(text) {
+  var wanted = text.replace(/\s+/g, ' ').trim().toLowerCase();
+  var headings = document.querySelectorAll('h1, h2, h3, h4, h5, h6');
+  for (var i = 0; i < headings.length; i++) {
+    if (headings[i].innerText.replace(/\s+/g, ' ').trim().toLowerCase() === wanted) {
+      headings[i].scrollIntoView({ behavior: 'smooth', block: 'start' });
+      return true;
+    }
+  }
+  return false;
+})"#;
+
+// Evaluated into the nav bar, the sidebar and the tabs to outline the
+// viewport with the argument appended in parentheses, the bounds Rust gave
+// the webview, along with the viewport the page got. `null` removes it.
+const LAYOUT_OVERLAY_SCRIPT: &str = r#"
+(function(label) {
+  var old = document.getElementById('__syncfloLayout');
+  if (old) old.remove();
+  if (label === null) return;
+  var box = document.createElement('div');
+  box.id = '__syncfloLayout';
+  box.style.cssText = 'position:fixed;inset:0;box-sizing:border-box;border:2px dashed #e11;' +
+    'pointer-events:none;z-index:2147483647;font:11px/1.4 monospace;color:#e11;padding:2px 4px';
+  box.textContent = label + ' | viewport ' + innerWidth + 'x' + innerHeight + ' @' + devicePixelRatio + 'x';
+  (document.body || document.documentElement).appendChild(box);
+})"#;
+
+/// Commands sent by the nav webview, e.g. `{"op":"navigate","payload":{"url":"..."}}`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "op", content = "payload", rename_all = "snake_case")]
+pub enum IpcCommand {
+    Back,
+    Forward,
+    Refresh,
+    Home,
+    Navigate { url: String },
+    GetState,
+    /// Current tab list, for rendering the strip before any `tabs_changed`.
+    GetTabs,
+    NewTab,
+    CloseTab { id: TabId },
+    SwitchTab { id: TabId },
+    /// Bookmarks the active tab's page, or removes its bookmark.
+    BookmarkCurrent,
+    ListBookmarks,
+    /// Saves the active tab's page to the reading list with an offline
+    /// copy.
+    ReadLater,
+    /// Saves the page `url` links to to the reading list without opening
+    /// it.
+    ReadLinkLater { url: String },
+    /// Asks which local file to open, then opens it in the active tab.
+    OpenFile,
+    /// Saves the active tab's page as one HTML file, asking where first.
+    SavePage,
+    /// Prints the active tab's whole page to a PDF at `path`, asking where
+    /// first without one. Where pages can't be printed to a file, opens the
+    /// print dialog instead.
+    SavePdf {
+        #[serde(default)]
+        path: Option<PathBuf>,
+    },
+    /// Saves the active tab's page as a Markdown file, asking where first.
+    SaveMarkdown,
+    /// Shows `path` in the file manager.
+    RevealFile { path: PathBuf },
+    /// Stops loading the active tab's page.
+    Stop,
+    /// Cancels the AI request of the active tab.
+    StopAi,
+    /// Summarizes the active tab's page with the AI assistant. The summary
+    /// arrives as an `ai_reply` event. With `regenerate`, a cached summary
+    /// isn't used.
+    Summarize {
+        #[serde(default)]
+        regenerate: bool,
+    },
+    /// Shows the active tab's page in the reader view translated into
+    /// `lang`, by default the UI's language. With `regenerate`, cached
+    /// translations aren't used.
+    TranslatePage {
+        #[serde(default)]
+        lang: Option<String>,
+        #[serde(default)]
+        regenerate: bool,
+    },
+    /// Sends `message` in the active tab's conversation with the AI
+    /// assistant. The answer arrives as an `ai_reply` event.
+    Chat { message: String },
+    /// Has the AI assistant explain the active tab's page. The answer
+    /// streams in as `ai_text` events and ends with `ai_finished`.
+    AskAi,
+    /// Keeps the active tab's conversation when it navigates elsewhere, or
+    /// stops doing so.
+    PinConversation { pinned: bool },
+    /// Turns ad blocking on `host` off, or back on, and reloads the page.
+    ToggleBlocking { host: String },
+    /// Highlights `query` in the active tab's page and moves to the next
+    /// (or previous) match. Results arrive as `find_result` events.
+    Find {
+        query: String,
+        #[serde(default = "default_true")]
+        forward: bool,
+        #[serde(default)]
+        case_sensitive: bool,
+    },
+    /// Removes the find highlights from the active tab's page.
+    FindClear,
+    /// A keyboard shortcut pressed in the nav bar.
+    Shortcut(KeyPress),
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+    /// Opens or closes the web inspector for the active tab.
+    Devtools,
+    /// Downloads of this session; also pushed as `downloads` events.
+    ListDownloads,
+    /// Opens a downloaded file with its default application.
+    OpenDownload { id: DownloadId },
+    /// Shows a downloaded file in the file manager.
+    RevealDownload { id: DownloadId },
+    CancelDownload { id: DownloadId },
+    /// Shows the directory of the log files in the file manager, for
+    /// attaching them to bug reports.
+    OpenLogs,
+    /// Opens a blocked popup of the active tab after all, in a new tab.
+    OpenPopup { url: String },
+    /// Opens all blocked popups of the active tab; with `always`, its site
+    /// may open popups from now on.
+    AllowPopups { always: bool },
+    /// Opens `url` in a new tab after the active one.
+    OpenTab { url: String },
+    CopyText { text: String },
+    /// Downloads `url` through the active tab, like a link with `download`.
+    Download { url: String },
+    /// Asks the AI assistant about text selected in the active tab's page.
+    /// The answer arrives as an `ai_reply` event.
+    AskSelection { text: String },
+    /// Asks the AI assistant for an action doing what `instruction` says on
+    /// the active tab's page. The action arrives as an `ai_action` event
+    /// and is only done once confirmed with `run_action`.
+    SuggestAction { instruction: String },
+    /// Does the action the assistant proposed in AI request `request` of
+    /// the active tab.
+    RunAction { request: AiRequestId },
+    /// Drops the action the assistant proposed for the active tab.
+    DismissAction,
+    /// Shows the active tab's page in the reader view, or the original
+    /// page again.
+    ToggleReader,
+    /// Opens or closes the AI sidebar. Replies `{open}`; also pushed as a
+    /// `sidebar` event.
+    ToggleSidebar,
+    /// Outlines each webview with the bounds it was laid out at, or stops.
+    ToggleLayoutOverlay,
+    /// Makes the window fullscreen with the nav bar hidden, or back.
+    ToggleFullscreen,
+    /// History, bookmark and search suggestions for `input` typed into the
+    /// address bar. Replies with the first two; the search engine's arrive
+    /// as a `search_suggestions` event.
+    Suggest { input: String },
+    /// A popover below the nav bar, like the address bar's suggestions or
+    /// the site info panel, is shown `height` tall, or hidden at 0.
+    ShowPopover { height: f64 },
+    /// Details of the active tab's connection for the site info panel.
+    SiteInfo,
+    /// Turns the page scripts or images of `host` on or off. Pushed back
+    /// as a `site_settings` event; the page reloads to apply them.
+    SetSiteSettings {
+        host: String,
+        #[serde(flatten)]
+        settings: SiteSettings,
+    },
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// A command with an optional request id. Requests with an id get a reply.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct IpcRequest {
+    #[serde(default)]
+    pub id: Option<u64>,
+    #[serde(flatten)]
+    pub command: IpcCommand,
+}
+
+/// Reply to `get_state`, used by the nav bar to render button states.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NavState {
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub can_go_back: bool,
+    pub can_go_forward: bool,
+    pub bookmarked: bool,
+    /// How secure the connection to the page is.
+    pub security: Security,
+    /// Whether ad blocking is on for this site. `None` without filter lists.
+    pub blocking: Option<bool>,
+    /// Requests blocked on this page.
+    pub blocked: usize,
+    /// Popups the page opened without a click, which were blocked.
+    pub blocked_popups: Vec<String>,
+    pub loading: bool,
+    /// The page is shown in the reader view.
+    pub reader: bool,
+    /// The AI sidebar is open.
+    pub sidebar: bool,
+    pub incognito: bool,
+}
+
+/// Events reported by pages in the content webviews.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "op", content = "payload", rename_all = "snake_case")]
+pub enum PageEvent {
+    Committed { url: String },
+    /// `<link rel="icon">` tags of the page, sent once it's parsed.
+    PageIcons { links: String },
+    /// Match count from the find script; `current` is 1-based, 0 if none.
+    FindResult { query: String, current: usize, total: usize },
+    /// A keyboard shortcut pressed in the page.
+    Shortcut(KeyPress),
+    /// A click or key press in the page, after which it may open popups
+    /// for a while.
+    UserGesture,
+    /// The pointer moved onto the top edge of the page, or off it.
+    PointerAtTop { at_top: bool },
+    /// Right-click in the page, answered with the context menu.
+    ContextMenu(ContextTarget),
+    /// A page showed a notification through [`notifications::SCRIPT`];
+    /// `icon` is its URL.
+    ShowNotification { title: String, body: String, icon: Option<String> },
+    /// Sent by [`notifications::SCRIPT`] as the page loads; answered with
+    /// whether the page's site may show notifications.
+    NotificationPermission,
+    /// Like `NotificationPermission`, but asks the user if the site wasn't
+    /// answered before.
+    RequestNotificationPermission,
+    /// Address or search input typed into the home page.
+    Navigate { input: String },
+    /// Sent by `syncflo://history`; ignored from any other page.
+    DeleteHistory { url: String },
+    /// Sent by `syncflo://history`; answered with a
+    /// `syncflo:history_matches` event.
+    SearchHistory { query: String },
+    /// Sent by the home page; answered with a `syncflo:top_sites` event.
+    GetTopSites,
+    /// Sent by the crash recovery banner on the home page.
+    RestoreSession,
+    DismissRecovery,
+    /// Sent by `syncflo://bookmarks`.
+    DeleteBookmark { url: String },
+    /// Sent by `syncflo://reading-list`.
+    SetReadingItemRead { id: String, read: bool },
+    DeleteReadingItems { ids: Vec<String> },
+    /// Sent by `syncflo://settings`. The result is dispatched back to the
+    /// page as a `syncflo:import_bookmarks` event.
+    ImportBookmarks { path: String },
+    /// Sent by `syncflo://settings`; answered with a `syncflo:export` event.
+    ExportHistory { path: String },
+    ExportBookmarks { path: String },
+    /// Sent by `syncflo://settings`; answered with a `syncflo:clear_data` event.
+    ClearData(ClearDataRequest),
+    /// Sent by `syncflo://settings`; answered with a `syncflo:reset_usage` event.
+    ResetUsage,
+    /// Sent by `syncflo://settings`; answered with a `syncflo:ai_connection`
+    /// event once a test request to the AI API is done.
+    TestAiConnection,
+    /// Sent by `syncflo://settings`; answered with a `syncflo:proxy_test`
+    /// event once a page was fetched through the saved proxy, or not.
+    TestProxy,
+    /// Sent by `syncflo://settings`; answered with a `syncflo:permissions`
+    /// event.
+    GetPermissions,
+    /// Forgets the answer to `origin` asking for `permission`, so the site
+    /// is asked again. Answered with a `syncflo:permissions` event.
+    RevokePermission { origin: String, permission: Permission },
+    /// Sent by `syncflo://settings`; answered with a `syncflo:default_browser`
+    /// event.
+    SetDefaultBrowser,
+    /// Sent by `syncflo://settings`; answered with a `syncflo:settings` event.
+    GetSettings,
+    /// Sent by `syncflo://settings`; answered with a `syncflo:setting` event
+    /// with the new settings, or why `value` was refused.
+    SetSetting { key: String, value: Value },
+    /// Sent by the start page and `syncflo://settings`; answered with a
+    /// `syncflo:profiles` event.
+    GetProfiles,
+    /// Opens the profile `name`, or the default one for `None`, in a new
+    /// window unless it's this one.
+    OpenProfile { name: Option<String> },
+    /// Creates the profile `name` and opens it. Both answered with a
+    /// `syncflo:profiles` event.
+    CreateProfile { name: String },
+    DeleteProfile { name: String },
+}
+
+/// Time range for clearing browsing data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClearRange {
+    LastHour,
+    LastDay,
+    Everything,
+}
+
+impl ClearRange {
+    /// How far back to clear; `None` means everything.
+    pub fn duration(self) -> Option<Duration> {
+        match self {
+            ClearRange::LastHour => Some(Duration::from_secs(60 * 60)),
+            ClearRange::LastDay => Some(Duration::from_secs(24 * 60 * 60)),
+            ClearRange::Everything => None,
+        }
+    }
+}
+
+/// What to clear, and from how far back.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ClearDataRequest {
+    pub range: ClearRange,
+    #[serde(default)]
+    pub history: bool,
+    #[serde(default)]
+    pub cookies: bool,
+    #[serde(default)]
+    pub cache: bool,
+}
+
+/// The parts of a webview that IPC commands act on.
+pub trait WebViewHandle {
+    fn load_url(&self, url: &str);
+    fn evaluate_script(&self, js: &str);
+    fn focus(&self);
+}
+
+impl WebViewHandle for WebView {
+    fn load_url(&self, url: &str) {
+        if let Err(e) = WebView::load_url(self, url) {
+            log::warn!("Failed to load {}: {}", url, e);
+        }
+    }
+
+    fn evaluate_script(&self, js: &str) {
+        if let Err(e) = WebView::evaluate_script(self, js) {
+            log::warn!("Failed to evaluate script: {}", e);
+        }
+    }
+
+    fn focus(&self) {
+        if let Err(e) = WebView::focus(self) {
+            log::warn!("Failed to focus webview: {}", e);
+        }
+    }
+}
+
+/// A webview that's built after the handlers referring to it.
+impl WebViewHandle for RefCell<Option<WebView>> {
+    fn load_url(&self, url: &str) {
+        if let Some(webview) = self.borrow().as_ref() {
+            WebViewHandle::load_url(webview, url);
+        }
+    }
+
+    fn evaluate_script(&self, js: &str) {
+        if let Some(webview) = self.borrow().as_ref() {
+            WebViewHandle::evaluate_script(webview, js);
+        }
+    }
+
+    fn focus(&self) {
+        if let Some(webview) = self.borrow().as_ref() {
+            WebViewHandle::focus(webview);
+        }
+    }
+}
+
+/// Creates the content webview for a new tab with the given id and URL.
+pub type TabFactory = Box<dyn Fn(TabId, &str) -> anyhow::Result<Rc<dyn TabView>>>;
+
+/// Shows a context menu at a position in the content area, in logical pixels.
+pub type PopupMenu = Box<dyn Fn(&muda::Menu, f64, f64)>;
+
+/// Lays the window out with the nav bar shown or hidden, then the height
+/// a popover of the nav bar takes below it, then the AI sidebar shown or
+/// hidden. Returns the bounds it gave the webviews; the content
+/// webviews get `content`.
+pub type Layout = Box<dyn Fn(bool, f64, bool) -> WindowLayout>;
+
+/// How much of the browser's own UI the window shows around the page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Chrome {
+    #[default]
+    Normal,
+    /// The window is fullscreen and the nav bar hidden, unless the pointer
+    /// is at the top edge.
+    Fullscreen { revealed: bool },
+    /// An element of the page, like a video, is fullscreen; only the
+    /// content is shown.
+    Element,
+}
+
+impl Chrome {
+    fn shows_nav(self) -> bool {
+        matches!(self, Chrome::Normal | Chrome::Fullscreen { revealed: true })
+    }
+}
+
+/// Bounds of the webviews in the window, in logical pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct WindowLayout {
+    pub nav: Rect,
+    pub content: Rect,
+    pub sidebar: Rect,
+    /// Physical pixels per logical pixel of the display the window is on.
+    pub scale_factor: f64,
+}
+
+/// State shared by the IPC handlers.
+pub struct IpcContext {
+    pub tabs: RefCell<TabManager>,
+    pub nav: Rc<dyn WebViewHandle>,
+    /// The AI sidebar, which gets the same events as the nav bar.
+    pub sidebar: Rc<dyn WebViewHandle>,
+    pub layout: Layout,
+    /// Outlines each webview with the bounds it was given, to check the
+    /// layout on HiDPI displays.
+    pub layout_overlay: Cell<bool>,
+    /// What the window shows besides the page.
+    pub chrome: Cell<Chrome>,
+    /// Height of the nav bar's popover, like the address bar's suggestions,
+    /// which the nav bar grows by while it's shown.
+    pub popover_height: Cell<f64>,
+    /// Cancels the search engine request for the last address bar input.
+    pub suggest_cancel: RefCell<Option<CancellationToken>>,
+    /// Client of the browser's own async requests and the proxy it goes
+    /// through; made again when the proxy setting changes.
+    pub http: RefCell<Option<(ProxySettings, reqwest::Client)>>,
+    /// Makes the window fullscreen, or not.
+    pub set_fullscreen: Box<dyn Fn(bool)>,
+    /// Persisted history shared by all tabs.
+    pub history: Arc<Mutex<Navigation>>,
+    pub bookmarks: Arc<Mutex<BookmarkStore>>,
+    /// Pages saved to read later, shared with `syncflo://reading-list`.
+    pub reading_list: Arc<Mutex<ReadingList>>,
+    pub settings: Arc<Mutex<Settings>>,
+    /// What sites may use, answered when they first asked; in memory in
+    /// incognito windows.
+    pub permissions: Arc<Mutex<PermissionBroker>>,
+    /// Ad and tracker domains loaded from the filter lists.
+    pub blocklist: Arc<Blocklist>,
+    pub home_url: String,
+    /// Where the settings and data are kept, including the open tabs,
+    /// which are saved after every change.
+    pub profile: Profile,
+    /// This is an incognito window: history, the open tabs, icons and AI
+    /// answers stay in memory, and the webviews keep cookies and cache
+    /// there too.
+    pub incognito: bool,
+    /// This is an app window, locked to one site and without the browser's
+    /// UI; `home_url` is the site's page. Like incognito windows, it keeps
+    /// history and the open tabs in memory.
+    pub app: Option<AppMode>,
+    /// Tabs from a run that didn't shut down cleanly, offered on the home page.
+    pub recovered_session: Arc<Mutex<Option<Session>>>,
+    /// Set once the window exists; see [`TabFactory`].
+    pub tab_factory: RefCell<Option<TabFactory>>,
+    pub set_window_title: Box<dyn Fn(&str)>,
+    /// Wakes the event loop with results of background work.
+    pub proxy: EventLoopProxy<AppEvent>,
+    /// Hosts whose icon was already fetched (or tried) this run.
+    pub favicon_fetches: RefCell<HashSet<String>>,
+    /// Files the user picked in the open-file dialog, canonicalized. The
+    /// address bar may open these besides the assets directory.
+    pub chosen_files: RefCell<HashSet<PathBuf>>,
+    pub downloads: RefCell<DownloadManager>,
+    pub popup_menu: PopupMenu,
+    /// The context menu on screen, if any.
+    pub context_menu: RefCell<Option<OpenMenu>>,
+    /// Rendered reader views by tab, shared with the `syncflo://reader` handler.
+    pub reader_pages: Arc<Mutex<HashMap<TabId, String>>>,
+    /// The running AI request of each tab.
+    pub ai_requests: RefCell<HashMap<TabId, (AiRequestId, CancellationToken)>>,
+    /// The action the assistant proposed for each tab, waiting to be
+    /// confirmed: the request it came from, the page it's about, and the
+    /// action.
+    pub ai_actions: RefCell<HashMap<TabId, (AiRequestId, Url, AiAction)>>,
+    /// Answers of the AI assistant kept on disk; `None` in incognito windows.
+    pub ai_cache: Option<Arc<ResponseCache>>,
+    /// Tokens and cost of AI requests, shared with `syncflo://settings`.
+    pub ai_usage: Arc<UsageLog>,
+    /// Set once the menu bar is installed.
+    #[cfg(target_os = "macos")]
+    pub menu: RefCell<Option<crate::browser::menu::AppMenu>>,
+}
+
+impl IpcContext {
+    /// Opens `url` in a new tab after the active one and switches to it.
+    pub fn open_tab(&self, url: &str) -> anyhow::Result<TabId> {
+        let id = self.insert_tab(url)?;
+        self.tabs_changed();
+        Ok(id)
+    }
+
+    /// Opens `urls` as tabs and activates the one at `active`, saving the
+    /// session only once all of them exist.
+    pub fn open_tabs(&self, urls: &[String], active: usize) -> anyhow::Result<()> {
+        let mut ids = Vec::with_capacity(urls.len());
+        for url in urls {
+            ids.push(self.insert_tab(url)?);
+        }
+        if let Some(id) = ids.get(active) {
+            self.tabs.borrow_mut().activate(*id);
+        }
+        self.tabs_changed();
+        Ok(())
+    }
+
+    fn insert_tab(&self, url: &str) -> anyhow::Result<TabId> {
+        let id = self.tabs.borrow_mut().next_id();
+        let view = {
+            let factory = self.tab_factory.borrow();
+            let factory = factory
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("Tabs can't be created yet"))?;
+            factory(id, url)?
+        };
+        self.tabs.borrow_mut().insert(view, id, url);
+        Ok(id)
+    }
+
+    /// Closes a tab. Closing the last tab opens a fresh home tab in its place.
+    pub fn close_tab(&self, id: TabId) -> anyhow::Result<()> {
+        let closed = self.tabs.borrow_mut().close(id);
+        if closed.is_none() {
+            anyhow::bail!("No tab {}", id);
+        }
+        if let Ok(mut pages) = self.reader_pages.lock() {
+            pages.remove(&id);
+        }
+        self.cancel_ai(id);
+        self.ai_actions.borrow_mut().remove(&id);
+        // Drop the webview before a replacement is created
+        drop(closed);
+        if self.tabs.borrow().is_empty() {
+            self.open_tab(&self.home_url)?;
+        } else {
+            self.tabs_changed();
+        }
+        Ok(())
+    }
+
+    /// Reopens the tabs of the recovered session in place of the tab that
+    /// asked for it.
+    pub fn restore_recovered_session(&self, from_tab: TabId) -> anyhow::Result<()> {
+        let session = self.recovered_session.lock().ok().and_then(|mut s| s.take());
+        let Some(session) = session else {
+            anyhow::bail!("No session to restore");
+        };
+        let (urls, active) = session.restore_tabs(&self.home_url);
+        self.open_tabs(&urls, active)?;
+        self.close_tab(from_tab)
+    }
+
+    pub fn switch_tab(&self, id: TabId) -> anyhow::Result<()> {
+        if !self.tabs.borrow_mut().activate(id) {
+            anyhow::bail!("No tab {}", id);
+        }
+        self.tabs_changed();
+        Ok(())
+    }
+
+    /// Address-bar input as a URL, searching with the configured engine.
+    pub fn resolve_input(&self, input: &str) -> Url {
+        search::resolve_input(input, &self.search_engine())
+    }
+
+    /// Search results URL for `query` with the configured engine.
+    pub fn search_url(&self, query: &str) -> anyhow::Result<Url> {
+        self.search_engine().search_url(query)
+    }
+
+    fn search_engine(&self) -> search::SearchEngine {
+        self.settings
+            .lock()
+            .map(|settings| settings.search_engine.clone())
+            .unwrap_or_default()
+    }
+
+    /// Loads address-bar input in a tab, upgrading `http://` addresses in
+    /// HTTPS-first mode. Blocked URLs are reported to the nav bar with an
+    /// error toast instead.
+    pub fn navigate(&self, tab_id: TabId, input: &str) {
+        let url = self.resolve_input(input);
+        if let Some(reason) = input::blocked_reason(&url, &self.chosen_files.borrow()) {
+            log::warn!("Blocked navigation to {}", url);
+            self.toast("error", reason);
+            return;
+        }
+        let url = self.strip_tracking(url);
+        let https_first = self.settings.lock().map(|s| s.https_first).unwrap_or(false);
+        let target = {
+            let mut tabs = self.tabs.borrow_mut();
+            tabs.get_mut(tab_id).map(|tab| {
+                let secure = https_first.then(|| tab.https_first.upgrade(&url)).flatten();
+                (tab.view.clone(), secure.unwrap_or(url))
+            })
+        };
+        if let Some((view, url)) = target {
+            view.load_url(url.as_str());
+        }
+    }
+
+    /// Switches tab `tab_id` to the reader view of its page, or back to the
+    /// page. The reader view isn't a history entry: the tab's history stays
+    /// on the original page.
+    pub fn toggle_reader(&self, tab_id: TabId) {
+        let target = self.tabs.borrow_mut().get_mut(tab_id).and_then(|tab| {
+            let url = tab.navigation.current_url()?.clone();
+            Some((tab.view.clone(), url, tab.reader))
+        });
+        let Some((view, url, reader)) = target else {
+            return;
+        };
+        if reader {
+            self.close_reader(tab_id);
+            view.load_url(url.as_str());
+            return;
+        }
+        if !matches!(url.scheme(), "http" | "https") {
+            self.toast("error", "이 페이지는 읽기 모드로 볼 수 없습니다.");
+            return;
+        }
+        let images = url
+            .host_str()
+            .is_none_or(|host| self.settings.lock().map_or(true, |settings| settings.site(host).images));
+        let proxy = self.proxy.clone();
+        view.evaluate_script_with_callback(
+            "document.documentElement.outerHTML",
+            Box::new(move |result| {
+                let html = serde_json::from_str::<String>(&result).unwrap_or_default();
+                let proxy = proxy.clone();
+                let url = url.clone();
+                // Parsing a long page takes a while; keep it off the main thread
+                worker::spawn_blocking(move || {
+                    let processor = ContentProcessor::new();
+                    let page = processor
+                        .extract_article(&html, &url)
+                        .map(|mut article| {
+                            // The site's images are off; they load one by one when clicked
+                            if !images {
+                                let placeholder = ImageMode::Placeholder(pages::IMAGE_PLACEHOLDER.to_string());
+                                article.html = processor.sanitize_with(&article.html, &placeholder);
+                            }
+                            pages::reader(&article, &processor.analyze(&article.text), url.as_str())
+                        })
+                        .map_err(|e| format!("{:#}", e));
+                    let _ = proxy.send_event(AppEvent::Reader { tab: tab_id, url: url.into(), page });
+                });
+            }),
+        );
+    }
+
+    /// Saves the active tab's page to the reading list. The offline copy is
+    /// made in the background; a toast says when it's done. Incognito
+    /// windows don't save pages, which would leave them in the profile.
+    pub fn read_later(&self) {
+        if self.incognito {
+            self.toast("error", "시크릿 창에서는 읽기 목록에 저장할 수 없습니다.");
+            return;
+        }
+        let target = self.tabs.borrow().active().and_then(|tab| {
+            let url = tab.navigation.current_url()?.clone();
+            Some((tab.view.clone(), url))
+        });
+        let Some((view, url)) = target else {
+            return;
+        };
+        if !matches!(url.scheme(), "http" | "https") {
+            self.toast("error", "이 페이지는 읽기 목록에 저장할 수 없습니다.");
+            return;
+        }
+        let Ok(dir) = self.reading_list.lock().map(|list| list.dir().to_path_buf()) else {
+            return;
+        };
+        self.toast("info", "읽기 목록에 저장하는 중…");
+        let proxy = self.proxy.clone();
+        let proxy_settings = self.proxy_settings();
+        // In the reader view this is the reader page, whose article is the same
+        view.evaluate_script_with_callback(
+            "document.documentElement.outerHTML",
+            Box::new(move |result| {
+                let html = serde_json::from_str::<String>(&result).unwrap_or_default();
+                let proxy = proxy.clone();
+                let url = url.clone();
+                let dir = dir.clone();
+                let proxy_settings = proxy_settings.clone();
+                // Images download one after another; keep it off the main thread
+                worker::spawn_blocking(move || {
+                    let item =
+                        reading_list::snapshot(&html, &url, &dir, &proxy_settings).map_err(|e| format!("{:#}", e));
+                    let _ = proxy.send_event(AppEvent::ReadLater { url: url.into(), item });
+                });
+            }),
+        );
+    }
+
+    /// Saves the page `url` links to to the reading list, downloading it
+    /// with the active tab's cookies for it. Like [`read_later`](Self::read_later),
+    /// not from incognito windows.
+    pub fn read_link_later(&self, url: &str) {
+        if self.incognito {
+            self.toast("error", "시크릿 창에서는 읽기 목록에 저장할 수 없습니다.");
+            return;
+        }
+        let Some(url) = Url::parse(url).ok().filter(|url| matches!(url.scheme(), "http" | "https")) else {
+            self.toast("error", "이 링크는 읽기 목록에 저장할 수 없습니다.");
+            return;
+        };
+        let Ok(dir) = self.reading_list.lock().map(|list| list.dir().to_path_buf()) else {
+            return;
+        };
+        // Only the webview knows the cookies; read them here, on the main thread
+        let cookie = self.tabs.borrow().active().and_then(|tab| tab.view.cookie_header(url.as_str()));
+        self.toast("info", "읽기 목록에 저장하는 중…");
+        let proxy = self.proxy.clone();
+        let proxy_settings = self.proxy_settings();
+        worker::spawn_blocking(move || {
+            let item = reading_list::snapshot_link(&url, cookie.as_deref(), &dir, &proxy_settings)
+                .map_err(|e| format!("{:#}", e));
+            let _ = proxy.send_event(AppEvent::ReadLater { url: url.into(), item });
+        });
+    }
+
+    /// Adds the page at `url` to the reading list once its offline copy is
+    /// made, or says why it couldn't be.
+    pub fn saved_for_later(&self, url: &str, item: Result<ReadingItem, String>) {
+        match item {
+            Ok(item) => {
+                let Ok(mut list) = self.reading_list.lock() else {
+                    return;
+                };
+                let replaced = list.contains(&item.url);
+                list.add(item);
+                drop(list);
+                self.toast("info", if replaced { "읽기 목록의 사본을 새로 저장했습니다." } else { "읽기 목록에 저장했습니다." });
+            }
+            Err(e) => {
+                log::warn!("Failed to save {} for later: {}", url, e);
+                self.toast("error", "이 페이지는 읽기 목록에 저장할 수 없습니다.");
+            }
+        }
+    }
+
+    /// Asks which local file to open in the active tab; once the user
+    /// chose, [`AppEvent::OpenFile`] opens it.
+    pub fn open_file(&self) {
+        let Some((tab_id, view)) = self.tabs.borrow().active().map(|tab| (tab.id, tab.view.clone())) else {
+            return;
+        };
+        let proxy = self.proxy.clone();
+        view.choose_file(
+            &downloads::default_dir(),
+            Box::new(move |path| {
+                if let Some(path) = path {
+                    let _ = proxy.send_event(AppEvent::OpenFile { tab: tab_id, path });
+                }
+            }),
+        );
+    }
+
+    /// Opens `path`, which the user chose in the open-file dialog, in tab
+    /// `tab_id`, and lets the address bar open it again.
+    pub fn open_chosen_file(&self, tab_id: TabId, path: &Path) {
+        let Some(url) = path.canonicalize().ok().and_then(|path| {
+            let url = Url::from_file_path(&path).ok()?;
+            self.chosen_files.borrow_mut().insert(path);
+            Some(url)
+        }) else {
+            self.toast("error", "파일을 열 수 없습니다.");
+            return;
+        };
+        self.navigate(tab_id, url.as_str());
+    }
+
+    /// Asks where to save the active tab's page as one HTML file; once the
+    /// user chose, [`AppEvent::SavePage`] saves it.
+    pub fn save_page(&self) {
+        let Some((tab_id, view, url, title)) = self.page_to_save() else {
+            return;
+        };
+        if !matches!(url.scheme(), "http" | "https" | "file") {
+            self.toast("error", "이 페이지는 저장할 수 없습니다.");
+            return;
+        }
+        let proxy = self.proxy.clone();
+        view.choose_save_path(
+            SaveAs::Html,
+            &downloads::default_dir(),
+            &downloads::page_file_name(&title, &url, "html"),
+            Box::new(move |path| {
+                if let Some(path) = path {
+                    let _ = proxy.send_event(AppEvent::SavePage { tab: tab_id, path });
+                }
+            }),
+        );
+    }
+
+    /// The active tab's id, view, URL and title, for saving its page.
+    fn page_to_save(&self) -> Option<(TabId, Rc<dyn TabView>, Url, String)> {
+        let tabs = self.tabs.borrow();
+        let tab = tabs.active()?;
+        let url = tab.navigation.current_url()?.clone();
+        let title = tab.navigation.current_entry().and_then(|e| e.title.clone()).unwrap_or_default();
+        Some((tab.id, tab.view.clone(), url, title))
+    }
+
+    /// Saves the page in tab `tab_id` to `path` as one HTML file. Its
+    /// resources download in the background while a toast counts them.
+    pub fn save_page_to(&self, tab_id: TabId, path: PathBuf) {
+        let target = self.tabs.borrow_mut().get_mut(tab_id).and_then(|tab| {
+            let url = tab.navigation.current_url()?.clone();
+            Some((tab.view.clone(), url))
+        });
+        let Some((view, url)) = target else {
+            return;
+        };
+        // Only the webview knows the cookies; read them here, on the main thread
+        let cookie = view.cookie_header(url.as_str());
+        self.emit("toast", &serde_json::json!({ "kind": "info", "message": "페이지 저장 중…", "duration": 60_000 }));
+        let proxy = self.proxy.clone();
+        let proxy_settings = self.proxy_settings();
+        view.evaluate_script_with_callback(
+            archive::CAPTURE_SCRIPT,
+            Box::new(move |result| {
+                let page = serde_json::from_str::<String>(&result)
+                    .ok()
+                    .and_then(|json| serde_json::from_str::<CapturedPage>(&json).ok());
+                let proxy = proxy.clone();
+                let proxy_settings = proxy_settings.clone();
+                let url = url.clone();
+                let path = path.clone();
+                let cookie = cookie.clone();
+                // Pages can have hundreds of resources; keep it off the main thread
+                worker::spawn_blocking(move || {
+                    let report = match page {
+                        Some(page) => {
+                            let mut last_progress: Option<Instant> = None;
+                            let mut progress = |done: usize, found: usize| {
+                                if last_progress.is_some_and(|last| last.elapsed() < SAVE_PROGRESS_INTERVAL) {
+                                    return;
+                                }
+                                last_progress = Some(Instant::now());
+                                let _ = proxy.send_event(AppEvent::SavePageProgress { done, found });
+                            };
+                            archive::save(&page, &url, cookie, &proxy_settings, &path, &mut progress)
+                                .map_err(|e| format!("{:#}", e))
+                        }
+                        None => Err("Failed to read the page".to_string()),
+                    };
+                    let _ = proxy.send_event(AppEvent::PageSaved { path, report });
+                });
+            }),
+        );
+    }
+
+    /// Says where a page was saved and what was left out of it, or why it
+    /// couldn't be saved.
+    pub fn page_saved(&self, path: &Path, report: Result<ArchiveReport, String>) {
+        let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+        match report {
+            Ok(report) if report.capped => {
+                let message = format!(
+                    "{}에 저장했지만 {}MB를 넘는 리소스는 넣지 않았습니다. 그 부분은 인터넷에 연결해야 보입니다.",
+                    name,
+                    archive::MAX_TOTAL_BYTES / (1024 * 1024)
+                );
+                self.emit("toast", &serde_json::json!({ "kind": "error", "message": message, "duration": 8000 }));
+            }
+            Ok(report) if report.failed > 0 => {
+                self.toast("info", &format!("{}에 저장했습니다. 리소스 {}개는 받지 못했습니다.", name, report.failed));
+            }
+            Ok(_) => self.toast("info", &format!("{}에 저장했습니다.", name)),
+            Err(e) => {
+                log::warn!("Failed to save the page to {}: {}", path.display(), e);
+                self.toast("error", "페이지를 저장하지 못했습니다.");
+            }
+        }
+    }
+
+    /// Prints the active tab's page to a PDF at `path`, or where the user
+    /// chooses without one. Only WebKitGTK prints to a file; elsewhere the
+    /// print dialog opens, where the page can be saved as a PDF.
+    pub fn save_pdf(&self, path: Option<PathBuf>) {
+        let Some((tab_id, view, url, title)) = self.page_to_save() else {
+            return;
+        };
+        if cfg!(not(target_os = "linux")) {
+            view.print(self.print_backgrounds());
+            self.toast("info", "인쇄 창에서 PDF로 저장을 선택하세요.");
+            return;
+        }
+        if let Some(path) = path {
+            self.save_pdf_to(tab_id, path);
+            return;
+        }
+        let proxy = self.proxy.clone();
+        view.choose_save_path(
+            SaveAs::Pdf,
+            &downloads::default_dir(),
+            &downloads::page_file_name(&title, &url, "pdf"),
+            Box::new(move |path| {
+                if let Some(path) = path {
+                    let _ = proxy.send_event(AppEvent::SavePdf { tab: tab_id, path });
+                }
+            }),
+        );
+    }
+
+    /// Prints the whole page in tab `tab_id` to a PDF at `path`.
+    pub fn save_pdf_to(&self, tab_id: TabId, path: PathBuf) {
+        let Some(view) = self.tabs.borrow_mut().get_mut(tab_id).map(|tab| tab.view.clone()) else {
+            return;
+        };
+        self.toast("info", "PDF로 저장하는 중…");
+        let proxy = self.proxy.clone();
+        let target = path.clone();
+        view.print_to_pdf(
+            &target,
+            self.print_backgrounds(),
+            Box::new(move |result| {
+                let _ = proxy.send_event(AppEvent::PdfSaved { path, result });
+            }),
+        );
+    }
+
+    /// Says where a PDF was saved, with a button to show it, or why it
+    /// couldn't be.
+    pub fn pdf_saved(&self, path: &Path, result: Result<(), String>) {
+        if let Err(e) = result {
+            log::warn!("Failed to print the page to {}: {}", path.display(), e);
+            self.toast("error", "PDF로 저장하지 못했습니다.");
+            return;
+        }
+        let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+        let label = if cfg!(target_os = "macos") { "Finder에서 보기" } else { "폴더에서 보기" };
+        self.emit(
+            "toast",
+            &serde_json::json!({
+                "kind": "info",
+                "message": format!("{}에 저장했습니다.", name),
+                "duration": 8000,
+                "action": { "label": label, "op": "reveal_file", "payload": { "path": path } },
+            }),
+        );
+    }
+
+    /// Asks where to save the active tab's page as Markdown; once the
+    /// user chose, [`AppEvent::SaveMarkdown`] saves it.
+    pub fn save_markdown(&self) {
+        let Some((tab_id, view, url, title)) = self.page_to_save() else {
+            return;
+        };
+        if !matches!(url.scheme(), "http" | "https" | "file") {
+            self.toast("error", "이 페이지는 저장할 수 없습니다.");
+            return;
+        }
+        let proxy = self.proxy.clone();
+        view.choose_save_path(
+            SaveAs::Markdown,
+            &downloads::default_dir(),
+            &downloads::page_file_name(&title, &url, "md"),
+            Box::new(move |path| {
+                if let Some(path) = path {
+                    let _ = proxy.send_event(AppEvent::SaveMarkdown { tab: tab_id, path });
+                }
+            }),
+        );
+    }
+
+    /// Saves the page in tab `tab_id` to `path` as Markdown, with its links
+    /// and images pointing at the site.
+    pub fn save_markdown_to(&self, tab_id: TabId, path: PathBuf) {
+        let target = self.tabs.borrow_mut().get_mut(tab_id).and_then(|tab| {
+            let url = tab.navigation.current_url()?.clone();
+            Some((tab.view.clone(), url))
+        });
+        let Some((view, url)) = target else {
+            return;
+        };
+        let proxy = self.proxy.clone();
+        view.evaluate_script_with_callback(
+            "document.documentElement.outerHTML",
+            Box::new(move |result| {
+                let html = serde_json::from_str::<String>(&result).unwrap_or_default();
+                let proxy = proxy.clone();
+                let url = url.clone();
+                let path = path.clone();
+                worker::spawn_blocking(move || {
+                    let markdown = ContentProcessor::new().to_markdown(&html, &url);
+                    let result = std::fs::write(&path, markdown).map_err(|e| e.to_string());
+                    let _ = proxy.send_event(AppEvent::MarkdownSaved { path, result });
+                });
+            }),
+        );
+    }
+
+    /// Says where a page was saved as Markdown, or why it couldn't be.
+    pub fn markdown_saved(&self, path: &Path, result: Result<(), String>) {
+        match result {
+            Ok(()) => {
+                let name =
+                    path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+                self.toast("info", &format!("{}에 저장했습니다.", name));
+            }
+            Err(e) => {
+                log::warn!("Failed to save the page to {}: {}", path.display(), e);
+                self.toast("error", "페이지를 저장하지 못했습니다.");
+            }
+        }
+    }
+
+    fn print_backgrounds(&self) -> bool {
+        self.settings.lock().is_ok_and(|settings| settings.print_backgrounds)
+    }
+
+    /// Shows a rendered reader view, if tab `tab_id` is still on `url`.
+    pub fn show_reader(&self, tab_id: TabId, url: &str, page: Result<String, String>) {
+        let view = self.tabs.borrow_mut().get_mut(tab_id).and_then(|tab| {
+            let current = tab.navigation.current_url()?;
+            (current.as_str() == url).then(|| tab.view.clone())
+        });
+        let Some(view) = view else {
+            return;
+        };
+        let page = match page {
+            Ok(page) => page,
+            Err(e) => {
+                log::warn!("No reader view for {}: {}", url, e);
+                self.toast("error", "이 페이지는 읽기 모드로 볼 수 없습니다.");
+                return;
+            }
+        };
+        if let Ok(mut pages) = self.reader_pages.lock() {
+            pages.insert(tab_id, page);
+        }
+        if let Some(tab) = self.tabs.borrow_mut().get_mut(tab_id) {
+            tab.reader = true;
+        }
+        view.load_url(&assets::reader_url(tab_id));
+    }
+
+    /// Forgets tab `tab_id`'s reader view.
+    fn close_reader(&self, tab_id: TabId) {
+        if let Some(tab) = self.tabs.borrow_mut().get_mut(tab_id) {
+            tab.reader = false;
+        }
+        if let Ok(mut pages) = self.reader_pages.lock() {
+            pages.remove(&tab_id);
+        }
+    }
+
+    /// Runs `leave` on the active tab, after the page's `beforeunload`
+    /// handlers agree or the user confirms.
+    pub fn leave(&self, leave: Leave) {
+        let target = self.tabs.borrow().active().and_then(|tab| {
+            let possible = match leave {
+                Leave::Back => tab.navigation.can_go_back(),
+                Leave::Forward => tab.navigation.can_go_forward(),
+                Leave::Home => true,
+            };
+            possible.then(|| (tab.id, tab.view.clone(), tab.loading.loading))
+        });
+        let Some((id, view, loading)) = target else {
+            return;
+        };
+        // A page still loading can't have been interacted with, and wry
+        // drops callbacks of scripts queued before the first load finishes
+        if loading {
+            return self.leave_now(id, leave);
+        }
+        let proxy = self.proxy.clone();
+        view.evaluate_script_with_callback(
+            BEFOREUNLOAD_CHECK,
+            Box::new(move |result| {
+                let ask = result == "true";
+                let _ = proxy.send_event(AppEvent::Leave { tab: id, leave, ask });
+            }),
+        );
+    }
+
+    /// Runs `leave` on tab `tab_id` without asking the page. Back and
+    /// forward are driven by the tab's Rust-side history, not the webview's.
+    pub fn leave_now(&self, tab_id: TabId, leave: Leave) {
+        let target = {
+            let mut tabs = self.tabs.borrow_mut();
+            tabs.get_mut(tab_id).and_then(|tab| {
+                let url = match leave {
+                    Leave::Back => tab.navigation.go_back().map(String::from),
+                    Leave::Forward => tab.navigation.go_forward().map(String::from),
+                    Leave::Home => Some(self.home_page()),
+                };
+                url.map(|url| (tab.view.clone(), url))
+            })
+        };
+        if let Some((view, url)) = target {
+            view.load_url(&url);
+        }
+    }
+
+    /// A page in `tab_id` asked for a new window. It opens as a new tab if
+    /// the user clicked, as the webview says or the page reported within
+    /// [`POPUP_GESTURE_WINDOW`], or if its site may open popups. Otherwise
+    /// it's listed under the nav bar's popup chip.
+    pub fn new_window_requested(&self, tab_id: TabId, url: &str, user_gesture: bool) {
+        let user_gesture = user_gesture || self.popup_allowed(tab_id);
+        // Rather than a blank tab for a link that opens elsewhere
+        if let Some(url) = Url::parse(url).ok().filter(|url| external::is_forwardable(url.scheme())) {
+            if user_gesture {
+                self.external_link(tab_id, url);
+            }
+            return;
+        }
+        // App windows have no other tabs; their site's links open in place
+        if let (Some(app), true) = (&self.app, user_gesture) {
+            match Url::parse(url) {
+                Ok(url) if app.allows(&url) => self.navigate(tab_id, url.as_str()),
+                Ok(url) => self.open_externally(&url),
+                Err(_) => log::warn!("Not opening invalid URL {}", url),
+            }
+            return;
+        }
+        if user_gesture {
+            self.open_in_new_tab(url);
+            return;
+        }
+        log::info!("Blocked popup {} from tab {}", url, tab_id);
+        let blocked = {
+            let mut tabs = self.tabs.borrow_mut();
+            let Some(tab) = tabs.get_mut(tab_id) else {
+                return;
+            };
+            tab.blocked_popups.retain(|blocked| blocked != url);
+            tab.blocked_popups.push(url.to_string());
+            if tab.blocked_popups.len() > MAX_BLOCKED_POPUPS {
+                tab.blocked_popups.remove(0);
+            }
+            let blocked = tab.blocked_popups.clone();
+            tabs.active().is_some_and(|tab| tab.id == tab_id).then_some(blocked)
+        };
+        if let Some(blocked) = blocked {
+            self.emit("popups_blocked", &serde_json::json!(blocked));
+        }
+    }
+
+    /// The page in `tab_id` reported a click or key press.
+    pub fn user_gesture(&self, tab_id: TabId) {
+        if let Some(tab) = self.tabs.borrow_mut().get_mut(tab_id) {
+            tab.last_gesture = Some(Instant::now());
+        }
+    }
+
+    /// Whether the page in `tab_id` may open a popup now: the user just
+    /// clicked or typed in it, or its site may open popups anyway.
+    fn popup_allowed(&self, tab_id: TabId) -> bool {
+        let tabs = self.tabs.borrow();
+        let Some(tab) = tabs.iter().find(|tab| tab.id == tab_id) else {
+            return false;
+        };
+        if tab.last_gesture.is_some_and(|at| at.elapsed() <= POPUP_GESTURE_WINDOW) {
+            return true;
+        }
+        let host = tab.navigation.current_url().and_then(|url| url.host_str());
+        host.is_some_and(|host| self.settings.lock().is_ok_and(|settings| settings.allow_popups.contains(host)))
+    }
+
+    /// Opens `url`, a popup the active tab's page was blocked from opening,
+    /// in a new tab.
+    pub fn open_blocked_popup(&self, url: &str) {
+        let blocked = {
+            let mut tabs = self.tabs.borrow_mut();
+            tabs.active_mut().map(|tab| {
+                tab.blocked_popups.retain(|blocked| blocked != url);
+                tab.blocked_popups.clone()
+            })
+        };
+        self.open_in_new_tab(url);
+        if let Some(blocked) = blocked {
+            self.emit("popups_blocked", &serde_json::json!(blocked));
+        }
+    }
+
+    /// Opens the popups the active tab's page was blocked from opening.
+    /// With `always`, its site may open popups from now on; in incognito
+    /// windows, until the window closes, as the setting isn't saved there.
+    pub fn allow_popups(&self, always: bool) -> anyhow::Result<()> {
+        let (host, blocked) = {
+            let mut tabs = self.tabs.borrow_mut();
+            let Some(tab) = tabs.active_mut() else {
+                return Ok(());
+            };
+            let host = tab.navigation.current_url().and_then(|url| url.host_str()).map(str::to_string);
+            (host, std::mem::take(&mut tab.blocked_popups))
+        };
+        if always {
+            let host = host.ok_or_else(|| anyhow::anyhow!("The page has no host"))?;
+            self.update_settings(|settings| settings.allow_popups.insert(host))?;
+        }
+        self.emit("popups_blocked", &serde_json::json!([]));
+        for url in blocked {
+            self.open_in_new_tab(&url);
+        }
+        Ok(())
+    }
+
+    /// Opens a new tab and navigates it to `input` like the address bar.
+    pub fn open_in_new_tab(&self, input: &str) {
+        match self.open_tab("about:blank") {
+            Ok(id) => self.navigate(id, input),
+            Err(e) => log::warn!("Failed to open a tab for {}: {:#}", input, e),
+        }
+    }
+
+    /// Called before a tab navigates. Navigations to URLs with tracking
+    /// parameters are cancelled and the cleaned URL is loaded instead, and
+    /// links with schemes the browser doesn't handle go to the system.
+    pub fn allow_navigation(&self, tab_id: TabId, url: &str) -> bool {
+        let Ok(url) = Url::parse(url) else {
+            return true;
+        };
+        if external::is_forwardable(url.scheme()) {
+            self.external_link(tab_id, url);
+            return false;
+        }
+        // App windows open other sites in the system's browser
+        if self.app.as_ref().is_some_and(|app| !app.allows_navigation(&url)) {
+            self.open_externally(&url);
+            return false;
+        }
+        let cleaned = self.strip_tracking(url.clone());
+        if cleaned == url {
+            let view = self.tabs.borrow_mut().get_mut(tab_id).map(|tab| tab.view.clone());
+            if let Some(view) = view {
+                view.set_user_agent(self.user_agent(url.as_str()).as_deref());
+            }
+            return true;
+        }
+        let view = self.tabs.borrow_mut().get_mut(tab_id).map(|tab| tab.view.clone());
+        match view {
+            Some(view) => {
+                view.load_url(cleaned.as_str());
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Called when a page commits in a tab. Resets the tab's blocked-request
+    /// count and returns whether the page's requests should be filtered.
+    pub fn page_committed(&self, tab_id: TabId, url: &str) -> bool {
+        let host = Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string));
+        let blocking = !self.blocklist.is_empty()
+            && host.is_some_and(|host| {
+                self.settings
+                    .lock()
+                    .map(|s| s.blocking.is_enabled_for(&host))
+                    .unwrap_or(false)
+            });
+        if let Some(tab) = self.tabs.borrow_mut().get_mut(tab_id) {
+            tab.blocking = blocking;
+            tab.blocked_requests = 0;
+        }
+        self.emit_blocked_count(tab_id);
+        blocking
+    }
+
+    /// Counts a request from a tab's page if the blocklist stops it.
+    pub fn request_started(&self, tab_id: TabId, url: &str) {
+        let Some(host) = Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string)) else {
+            return;
+        };
+        {
+            let mut tabs = self.tabs.borrow_mut();
+            let Some(tab) = tabs.get_mut(tab_id) else {
+                return;
+            };
+            if !tab.blocking || !self.blocklist.is_blocked(&host) {
+                return;
+            }
+            tab.blocked_requests += 1;
+        }
+        self.emit_blocked_count(tab_id);
+    }
+
+    /// Pushes the active tab's blocked-request count for the nav bar badge.
+    fn emit_blocked_count(&self, tab_id: TabId) {
+        let count = match self.tabs.borrow().active() {
+            Some(tab) if tab.id == tab_id => tab.blocked_requests,
+            _ => return,
+        };
+        self.emit("blocked_count", &Value::from(count));
+    }
+
+    /// Flips ad blocking for `host`, saves the setting and reloads the
+    /// active tab so it takes effect. Returns whether blocking is now on.
+    pub fn toggle_blocking(&self, host: &str) -> anyhow::Result<bool> {
+        let blocking = self.update_settings(|settings| settings.blocking.toggle(host))?;
+        if let Some(view) = self.active_view() {
+            view.evaluate_script("window.location.reload()");
+        }
+        Ok(blocking)
+    }
+
+    /// `url` without tracking parameters, if the user turned that on.
+    fn strip_tracking(&self, url: Url) -> Url {
+        let Ok(settings) = self.settings.lock() else {
+            return url;
+        };
+        if !settings.privacy.strip_tracking_params {
+            return url;
+        }
+        let cleaned = privacy::clean_url(&url, &settings.privacy);
+        if cleaned != url {
+            log::debug!("Removed tracking parameters from {}", url);
+        }
+        cleaned
+    }
+
+    /// Called when a tab's page fails to load. Retries an upgraded address
+    /// over plain HTTP once, or else shows the error page. Returns whether
+    /// the failure was handled.
+    pub fn load_failed(&self, tab_id: TabId, url: &str, kind: LoadErrorKind) -> bool {
+        let Ok(failed) = Url::parse(url) else {
+            return false;
+        };
+        if assets::is_internal_url(url) {
+            return false;
+        }
+        let (view, fallback) = {
+            let mut tabs = self.tabs.borrow_mut();
+            let Some(tab) = tabs.get_mut(tab_id) else {
+                return false;
+            };
+            let fallback = tab.https_first.fallback(&failed);
+            if fallback.is_none() {
+                // Keep the address so the nav bar shows it and Retry reloads it
+                tab.load_error = Some(url.to_string());
+            }
+            (tab.view.clone(), fallback)
+        };
+        match fallback {
+            Some(fallback) => {
+                log::info!("{} failed over HTTPS, falling back to {}", failed, fallback);
+                view.load_url(fallback.as_str());
+                self.toast("info", "이 사이트는 HTTPS를 지원하지 않아 보안되지 않은 연결로 엽니다.");
+            }
+            None => {
+                log::warn!("Failed to load {} ({})", url, kind.as_str());
+                view.load_url(&assets::error_url(url, kind.as_str()));
+            }
+        }
+        true
+    }
+
+    /// A page committed in `tab_id` over a connection the webview described
+    /// as `security`.
+    pub fn security_committed(&self, tab_id: TabId, security: PageSecurity) {
+        if let Some(tab) = self.tabs.borrow_mut().get_mut(tab_id) {
+            if !security.certificate_errors.is_empty() {
+                log::warn!("Certificate of the page in tab {} has errors: {:?}", tab_id, security.certificate_errors);
+            }
+            tab.security = security;
+        }
+    }
+
+    /// The page in `tab_id` loaded content over plain HTTP although it was
+    /// loaded over HTTPS.
+    pub fn mixed_content(&self, tab_id: TabId) {
+        if let Some(tab) = self.tabs.borrow_mut().get_mut(tab_id) {
+            tab.security.mixed_content = true;
+        }
+    }
+
+    /// Details of the active tab's connection, or `None` without a page.
+    pub fn site_info(&self) -> Option<SiteInfo> {
+        let tabs = self.tabs.borrow();
+        let tab = tabs.active().filter(|tab| tab.load_error.is_none())?;
+        let url = tab.navigation.current_url()?;
+        let settings = url
+            .host_str()
+            .filter(|_| !assets::is_internal_url(url.as_str()))
+            .and_then(|host| Some(self.settings.lock().ok()?.site(host)));
+        Some(SiteInfo::new(url, &tab.security, settings))
+    }
+
+    /// The user agent to load `url` with, or `None` for the webview's own.
+    /// The browser's own pages get the overall one.
+    pub fn user_agent(&self, url: &str) -> Option<String> {
+        let host = Url::parse(url)
+            .ok()
+            .filter(|_| !assets::is_internal_url(url))
+            .and_then(|url| url.host_str().map(str::to_string));
+        let settings = self.settings.lock().ok()?;
+        settings.user_agent.for_host(host.as_deref()).map(str::to_string)
+    }
+
+    /// What the page at `url` may do. The browser's own pages may do
+    /// everything.
+    pub fn site_settings(&self, url: &str) -> SiteSettings {
+        if assets::is_internal_url(url) {
+            return SiteSettings::default();
+        }
+        let host = Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string));
+        match (host, self.settings.lock()) {
+            (Some(host), Ok(settings)) => settings.site(&host),
+            _ => SiteSettings::default(),
+        }
+    }
+
+    /// Changes what pages on `host` may do from the next load on, and tells
+    /// the nav bar. Incognito windows keep the change until they close.
+    pub fn set_site_settings(&self, host: &str, site: SiteSettings) -> anyhow::Result<()> {
+        self.update_settings(|settings| settings.set_site(host, site))?;
+        log::info!("Site settings of {} changed to {:?}", host, site);
+        self.emit(
+            "site_settings",
+            &serde_json::json!({ "host": host, "javascript": site.javascript, "images": site.images }),
+        );
+        Ok(())
+    }
+
+    /// A page in `tab_id` followed a link with a scheme the browser doesn't
+    /// handle. It opens with the system if the user said so for its scheme;
+    /// the first time, the user is asked from the event loop, as this runs
+    /// while the webview decides on the navigation.
+    fn external_link(&self, tab_id: TabId, url: Url) {
+        let decision =
+            self.settings.lock().ok().and_then(|settings| settings.external_schemes.get(url.scheme()).copied());
+        match decision {
+            Some(true) => self.open_externally(&url),
+            Some(false) => log::info!("Not opening {}: links to {}: are turned off", url, url.scheme()),
+            None => {
+                if self.proxy.send_event(AppEvent::ExternalLink { tab: tab_id, url, open: None }).is_err() {
+                    log::warn!("Event loop is gone; can't ask about an external link");
+                }
+            }
+        }
+    }
+
+    /// Asks over `tab` whether to open links to `url`'s scheme with the
+    /// system, then remembers the answer and acts on it.
+    fn ask_external(&self, tab: TabId, url: Url) {
+        let Some(view) = self.tabs.borrow_mut().get_mut(tab).map(|tab| tab.view.clone()) else {
+            return;
+        };
+        let proxy = self.proxy.clone();
+        let scheme = url.scheme().to_string();
+        view.confirm_external(&scheme, Box::new(move |open| {
+            let _ = proxy.send_event(AppEvent::ExternalLink { tab, url, open: Some(open) });
+        }));
+    }
+
+    /// Remembers whether links to `url`'s scheme open with the system, and
+    /// opens `url` if they do.
+    fn external_answered(&self, url: Url, open: bool) {
+        let scheme = url.scheme().to_string();
+        if let Err(e) = self.update_settings(|settings| settings.external_schemes.insert(scheme, open)) {
+            log::warn!("Failed to save the answer for {}: links: {:#}", url.scheme(), e);
+        }
+        if open {
+            self.open_externally(&url);
+        }
+    }
+
+    /// Opens `url` with the system's handler instead of in a tab.
+    fn open_externally(&self, url: &Url) {
+        log::info!("Opening {} outside the browser", url);
+        if let Err(e) = external::open(url) {
+            log::warn!("{:#}", e);
+            self.toast("error", "외부 프로그램으로 열지 못했습니다.");
+        }
+    }
+
+    /// Whether this is a kiosk window, which only closes with its exit keys.
+    pub fn is_kiosk(&self) -> bool {
+        self.app.as_ref().is_some_and(|app| app.kiosk)
+    }
+
+    /// Quits a kiosk window if `press` is its exit combination. Returns
+    /// whether it was.
+    fn kiosk_exit(&self, press: &KeyPress) -> bool {
+        let exit = self.app.as_ref().is_some_and(|app| app.kiosk && app.exit == *press);
+        if exit && self.proxy.send_event(AppEvent::Quit { confirmed: true }).is_err() {
+            log::warn!("Event loop is gone; can't quit");
+        }
+        exit
+    }
+
+    /// Content webview of the active tab.
+    fn active_view(&self) -> Option<Rc<dyn TabView>> {
+        self.tabs.borrow().active().map(|tab| tab.view.clone())
+    }
+
+    pub fn save_session(&self) {
+        if self.incognito || self.app.is_some() {
+            return;
+        }
+        let path = self.profile.session_path();
+        let session = Session::from_tabs(&self.tabs.borrow());
+        recovery::track_session(&path, &session);
+        if let Err(e) = session.save(&path) {
+            log::warn!("Failed to save session: {:#}", e);
+        }
+    }
+
+    /// Saves what's kept on disk and stops AI requests and downloads still
+    /// running, before the browser exits.
+    pub fn shut_down(&self) {
+        for (_, (_, cancel)) in self.ai_requests.borrow_mut().drain() {
+            cancel.cancel();
+        }
+        let cancelled = self.downloads.borrow_mut().cancel_all();
+        if cancelled > 0 {
+            log::info!("Cancelled {} downloads", cancelled);
+        }
+        self.save_session();
+        if let Ok(history) = self.history.lock() {
+            if let Err(e) = history.save() {
+                log::warn!("Failed to save history: {:#}", e);
+            }
+        }
+        if !self.incognito {
+            let saved = self.settings.lock().map(|settings| settings.save_to(&self.profile.settings_path()));
+            if let Ok(Err(e)) = saved {
+                log::warn!("Failed to save settings: {:#}", e);
+            }
+        }
+    }
+
+    /// Dispatches a `syncflo:<event>` DOM event to the page in a tab, as the
+    /// answer to a page message. The event is dropped if the tab has since
+    /// navigated away from internal pages.
+    pub fn notify_tab(&self, tab_id: TabId, event: &str, detail: &Value) {
+        let view = self.tabs.borrow_mut().get_mut(tab_id).map(|tab| tab.view.clone());
+        if let Some(view) = view {
+            view.evaluate_script(&dispatch_script(INTERNAL_PAGE_CHECK, event, detail));
+        }
+    }
+
+    /// Sends fresh top sites to every tab showing the home page, e.g. after
+    /// history was deleted.
+    pub fn refresh_top_sites(&self) {
+        let detail = top_sites(self);
+        let check = format!("location.href === {}", Value::String(self.home_url.clone()));
+        for tab in self.tabs.borrow().iter() {
+            tab.view.evaluate_script(&dispatch_script(&check, "top_sites", &detail));
+        }
+    }
+
+    /// Shows a short message in the nav bar. `kind` is `"info"` or `"error"`.
+    pub fn toast(&self, kind: &str, message: &str) {
+        self.emit("toast", &serde_json::json!({ "kind": kind, "message": message }));
+    }
+
+    /// Pushes an event to listeners registered with `window.syncflo.on`,
+    /// in the nav bar and the sidebar.
+    pub fn emit(&self, event: &str, data: &Value) {
+        let event = Value::String(event.to_string());
+        let script = format!("window.syncflo._emit({}, {})", event, data);
+        self.nav.evaluate_script(&script);
+        self.sidebar.evaluate_script(&script);
+    }
+
+    /// The page the home button opens: the one in the settings, or else
+    /// the start page.
+    pub fn home_page(&self) -> String {
+        if self.app.is_some() {
+            return self.home_url.clone();
+        }
+        let home_page = self.settings.lock().ok().and_then(|settings| settings.home_page().map(String::from));
+        home_page.unwrap_or_else(|| self.home_url.clone())
+    }
+
+    pub fn sidebar_open(&self) -> bool {
+        self.settings.lock().is_ok_and(|settings| settings.sidebar_open)
+    }
+
+    /// Opens or closes the AI sidebar and remembers that in the settings.
+    /// Returns whether it's now open.
+    pub fn toggle_sidebar(&self) -> bool {
+        let toggle = |settings: &mut Settings| {
+            settings.sidebar_open = !settings.sidebar_open;
+            settings.sidebar_open
+        };
+        match self.update_settings(toggle) {
+            Ok(open) => open,
+            Err(e) => {
+                log::warn!("Failed to save settings: {:#}", e);
+                self.sidebar_open()
+            }
+        }
+    }
+
+    /// Changes the settings with `change` and saves them; incognito windows
+    /// keep the change in memory. The change takes effect even if saving
+    /// fails.
+    pub fn update_settings<R>(&self, change: impl FnOnce(&mut Settings) -> R) -> anyhow::Result<R> {
+        let (before, after, result) = {
+            let mut settings = self.settings.lock().map_err(|_| anyhow::anyhow!("Settings lock poisoned"))?;
+            let before = settings.clone();
+            let result = change(&mut settings);
+            (before, settings.clone(), result)
+        };
+        self.settings_changed(&before, &after);
+        if !self.incognito {
+            after.save_to(&self.profile.settings_path())?;
+        }
+        Ok(result)
+    }
+
+    /// Changes one setting from the settings page; see [`Settings::set`].
+    /// `ai.api_key` goes to the OS keychain instead of the config file.
+    pub fn set_setting(&self, key: &str, value: Value) -> anyhow::Result<()> {
+        if key != "ai.api_key" {
+            return self.update_settings(|settings| settings.set(key, value))?;
+        }
+        let api_key = value.as_str().map(str::trim).filter(|key| !key.is_empty());
+        let provider = self.settings.lock().map(|settings| settings.ai.provider).unwrap_or_default();
+        keychain::set_api_key(provider, api_key)?;
+        // A key in the config file would take precedence
+        self.update_settings(|settings| settings.ai.api_key = None)
+    }
+
+    /// The settings as the settings page shows them, with whether there's
+    /// an API key for the AI provider.
+    pub fn settings_json(&self) -> Value {
+        let Ok(settings) = self.settings.lock() else {
+            return Value::Null;
+        };
+        let has_api_key = settings.ai.api_key.is_some() || keychain::api_key(settings.ai.provider).is_some();
+        let mut json = settings.to_ui_json();
+        if let Some(ai) = json.get_mut("ai").and_then(|ai| ai.as_object_mut()) {
+            ai.insert("has_api_key".into(), has_api_key.into());
+        }
+        json
+    }
+
+    /// The sites' permissions as the settings page lists them.
+    pub fn permissions_json(&self) -> Value {
+        let decisions = self.permissions.lock().map(|permissions| permissions.list()).unwrap_or_default();
+        serde_json::json!({ "decisions": decisions, "incognito": self.incognito })
+    }
+
+    /// The profiles as the start and settings pages list them.
+    pub fn profiles_json(&self) -> Value {
+        serde_json::json!({ "current": self.profile.name(), "names": self.profile.names() })
+    }
+
+    /// Opens the profile `name`, or the default one for `None`, in a new
+    /// browser process, unless it's this one.
+    pub fn open_profile(&self, name: Option<&str>) -> anyhow::Result<()> {
+        let profile = self.profile.sibling(name)?;
+        if profile == self.profile {
+            return Ok(());
+        }
+        spawn_browser(profile.launch_args())
+    }
+
+    /// Opens an incognito window of this profile, in a browser process of
+    /// its own.
+    pub fn open_incognito_window(&self) -> anyhow::Result<()> {
+        let mut args = self.profile.launch_args();
+        args.push("--incognito".into());
+        spawn_browser(args)
+    }
+
+    /// Applies changed settings to the window and tells the nav bar and
+    /// the sidebar with a `settings_changed` event. Everything else reads
+    /// the settings when it needs them.
+    fn settings_changed(&self, before: &Settings, after: &Settings) {
+        if before.sidebar_open != after.sidebar_open {
+            self.relayout();
+            if after.sidebar_open {
+                self.sidebar.focus();
+            }
+            self.emit("sidebar", &serde_json::json!({ "open": after.sidebar_open }));
+        }
+        if before.proxy != after.proxy {
+            for tab in self.tabs.borrow().iter() {
+                tab.view.set_proxy(&after.proxy);
+            }
+        }
+        self.emit("settings_changed", &after.to_ui_json());
+    }
+
+    /// Fetches a page through the saved proxy for `syncflo://settings` in
+    /// `tab`.
+    fn test_proxy(&self, tab: TabId) {
+        let settings = self.proxy_settings();
+        let events = self.proxy.clone();
+        worker::spawn(async move {
+            let result = proxy::test(&settings).await.map_err(|e| format!("{:#}", e));
+            if let Err(e) = &result {
+                log::warn!("Testing the proxy failed: {}", e);
+            }
+            let _ = events.send_event(AppEvent::ProxyTested { tab, result });
+        });
+    }
+
+    /// Fits the webviews to the window, e.g. after it was resized.
+    pub fn relayout(&self) {
+        let layout = self.layout();
+        for tab in self.tabs.borrow().iter() {
+            tab.view.set_bounds(layout.content);
+        }
+        if self.layout_overlay.get() {
+            self.draw_layout_overlay(&layout);
+        }
+    }
+
+    /// Shows the layout overlay, or hides it.
+    pub fn toggle_layout_overlay(&self) {
+        self.layout_overlay.set(!self.layout_overlay.get());
+        let layout = self.layout();
+        self.draw_layout_overlay(&layout);
+    }
+
+    fn layout(&self) -> WindowLayout {
+        let chrome = self.chrome.get();
+        let browser_ui = self.app.is_none();
+        (self.layout)(
+            browser_ui && chrome.shows_nav(),
+            self.popover_height.get(),
+            browser_ui && self.sidebar_open() && chrome != Chrome::Element,
+        )
+    }
+
+    /// Suggestions for `input` typed into the address bar: history and
+    /// bookmarks right away, then the search engine's, after a pause in
+    /// typing, as a `search_suggestions` event. Newer input cancels the
+    /// search engine request for the last.
+    pub fn suggest(&self, input: &str) -> Vec<Suggestion> {
+        if let Some(cancel) = self.suggest_cancel.take() {
+            cancel.cancel();
+        }
+        let local = match (self.history.lock(), self.bookmarks.lock()) {
+            (Ok(history), Ok(bookmarks)) => suggest::local(&history, &bookmarks, input),
+            _ => Vec::new(),
+        };
+        let engine = self
+            .settings
+            .lock()
+            .ok()
+            .filter(|settings| settings.search_suggestions)
+            .map(|settings| settings.search_engine.clone());
+        // Addresses aren't sent, and incognito windows send nothing
+        let searching = matches!(input::classify(input), input::InputKind::Search(_));
+        let engine = engine.filter(|_| searching && !self.incognito && !input.trim().is_empty());
+        if let Some((engine, client)) = engine.and_then(|engine| Some((engine, self.http_client()?))) {
+            let cancel = CancellationToken::new();
+            *self.suggest_cancel.borrow_mut() = Some(cancel.clone());
+            let proxy = self.proxy.clone();
+            let input = input.to_string();
+            worker::spawn(async move {
+                let found = tokio::select! {
+                    _ = cancel.cancelled() => return,
+                    found = async {
+                        tokio::time::sleep(suggest::SEARCH_DELAY).await;
+                        suggest::search(&client, &engine, &input).await
+                    } => found,
+                };
+                match found {
+                    Ok(suggestions) => {
+                        let _ = proxy.send_event(AppEvent::SearchSuggestions { input, suggestions });
+                    }
+                    Err(e) => log::debug!("No search suggestions: {:#}", e),
+                }
+            });
+        }
+        local
+    }
+
+    /// The nav bar's popover is shown `height` tall, or hidden at 0; the
+    /// nav bar grows to make room for it.
+    pub fn show_popover(&self, height: f64) {
+        let height = height.clamp(0.0, MAX_POPOVER_HEIGHT);
+        if height != self.popover_height.replace(height) {
+            self.relayout();
+        }
+    }
+
+    /// Makes the window fullscreen with the nav bar hidden, or back to how
+    /// it was. Kiosk windows stay fullscreen.
+    pub fn toggle_fullscreen(&self) {
+        if self.is_kiosk() {
+            return;
+        }
+        let fullscreen = self.chrome.get() == Chrome::Normal;
+        (self.set_fullscreen)(fullscreen);
+        self.fullscreen_changed(fullscreen);
+    }
+
+    /// Follows the window into or out of fullscreen, however it got there,
+    /// e.g. with the macOS title bar button.
+    pub fn fullscreen_changed(&self, fullscreen: bool) {
+        let chrome = match (self.chrome.get(), fullscreen) {
+            (Chrome::Normal, true) => Chrome::Fullscreen { revealed: false },
+            (Chrome::Fullscreen { .. }, false) => Chrome::Normal,
+            _ => return,
+        };
+        self.chrome.set(chrome);
+        self.relayout();
+    }
+
+    /// Shows only the content while a page element is fullscreen. The
+    /// webview makes the window fullscreen itself.
+    pub fn element_fullscreen(&self, fullscreen: bool) {
+        let chrome = match (self.chrome.get(), fullscreen) {
+            (Chrome::Element, false) => Chrome::Normal,
+            (_, true) => Chrome::Element,
+            _ => return,
+        };
+        self.chrome.set(chrome);
+        self.relayout();
+    }
+
+    /// Reveals the nav bar in fullscreen while the pointer is at the top
+    /// edge of the page in the active tab `tab_id`, and hides it once the
+    /// pointer moves down into the page again.
+    fn pointer_at_top(&self, tab_id: TabId, at_top: bool) {
+        let Chrome::Fullscreen { revealed } = self.chrome.get() else {
+            return;
+        };
+        if self.tabs.borrow().active().is_none_or(|tab| tab.id != tab_id) {
+            return;
+        }
+        if revealed != at_top {
+            self.chrome.set(Chrome::Fullscreen { revealed: at_top });
+            self.relayout();
+        }
+    }
+
+    /// Draws the bounds `layout` gave each webview over it, next to the
+    /// viewport the page actually got, or removes them once the overlay is
+    /// off.
+    fn draw_layout_overlay(&self, layout: &WindowLayout) {
+        let label = |name: &str, bounds: Rect| {
+            if !self.layout_overlay.get() {
+                return Value::Null;
+            }
+            let position = bounds.position.to_logical::<f64>(layout.scale_factor);
+            let size = bounds.size.to_logical::<f64>(layout.scale_factor);
+            Value::from(format!(
+                "{} {:.0},{:.0} {:.0}x{:.0} @{}x",
+                name, position.x, position.y, size.width, size.height, layout.scale_factor
+            ))
+        };
+        let script = |label: Value| format!("{}({})", LAYOUT_OVERLAY_SCRIPT, label);
+        self.nav.evaluate_script(&script(label("nav", layout.nav)));
+        self.sidebar.evaluate_script(&script(label("sidebar", layout.sidebar)));
+        let content = script(label("content", layout.content));
+        for tab in self.tabs.borrow().iter() {
+            tab.view.evaluate_script(&content);
+        }
+    }
+
+    /// Zooms the active tab one step in or out, or back to 100% for `None`.
+    pub fn zoom(&self, zoom_in: Option<bool>) {
+        let mut tabs = self.tabs.borrow_mut();
+        let Some(tab) = tabs.active_mut() else {
+            return;
+        };
+        tab.zoom = match zoom_in {
+            Some(true) => ZOOM_LEVELS.iter().copied().find(|&level| level > tab.zoom + 0.001),
+            Some(false) => ZOOM_LEVELS.iter().rev().copied().find(|&level| level < tab.zoom - 0.001),
+            None => Some(1.0),
+        }
+        .unwrap_or(tab.zoom);
+        tab.view.set_zoom(tab.zoom);
+    }
+
+    /// Records a download that a page started.
+    pub fn download_started(&self, url: &str, path: PathBuf, cancel: Option<Box<dyn Fn()>>) -> DownloadId {
+        log::info!("Downloading {} to {}", url, path.display());
+        let id = self.downloads.borrow_mut().start(url, path, cancel);
+        self.emit_downloads();
+        id
+    }
+
+    pub fn download_progress(&self, id: DownloadId, received: u64, total: Option<u64>) {
+        if self.downloads.borrow_mut().progress(id, received, total) {
+            self.emit_downloads();
+        }
+    }
+
+    pub fn download_finished(&self, id: DownloadId, success: bool) {
+        self.downloads.borrow_mut().finish(id, success);
+        self.download_done(id);
+    }
+
+    /// Like `download_finished`, for platforms that only report the URL.
+    #[cfg(not(target_os = "linux"))]
+    pub fn download_finished_url(&self, url: &str, path: Option<PathBuf>, success: bool) {
+        let id = self.downloads.borrow_mut().finish_url(url, path, success);
+        if let Some(id) = id {
+            self.download_done(id);
+        }
+    }
+
+    fn download_done(&self, id: DownloadId) {
+        let finished = self
+            .downloads
+            .borrow()
+            .get(id)
+            .map(|download| (download.state, download.file_name.clone()));
+        match finished {
+            Some((DownloadState::Completed, name)) => self.toast("info", &format!("다운로드 완료: {}", name)),
+            Some((DownloadState::Failed, name)) => self.toast("error", &format!("다운로드 실패: {}", name)),
+            _ => {}
+        }
+        self.emit_downloads();
+    }
+
+    fn emit_downloads(&self) {
+        match serde_json::to_value(self.downloads.borrow().list()) {
+            Ok(data) => self.emit("downloads", &data),
+            Err(e) => log::warn!("Failed to serialize downloads: {}", e),
+        }
+    }
+
+    /// Opens the find bar in the nav webview and focuses it.
+    pub fn open_find(&self) {
+        self.nav.focus();
+        self.emit("find_open", &Value::Null);
+    }
+
+    /// Passes find results to the nav bar if they're from the active tab.
+    pub fn find_result(&self, tab_id: TabId, query: &str, current: usize, total: usize) {
+        if self.tabs.borrow().active().map(|tab| tab.id) == Some(tab_id) {
+            self.emit("find_result", &serde_json::json!({ "query": query, "current": current, "total": total }));
+        }
+    }
+
+    /// Saves the session and tells the nav webview the tab list changed.
+    pub fn tabs_changed(&self) {
+        self.save_session();
+        self.emit_tabs();
+        let favicon = self.tabs.borrow().active().and_then(|tab| tab.favicon.clone());
+        self.emit("favicon", &Value::from(favicon));
+        #[cfg(target_os = "macos")]
+        if let Some(menu) = self.menu.borrow().as_ref() {
+            menu.update_recent(self);
+        }
+    }
+
+    fn emit_tabs(&self) {
+        let infos = self.tabs.borrow().infos();
+        match serde_json::to_value(infos) {
+            Ok(data) => self.emit("tabs_changed", &data),
+            Err(e) => log::warn!("Failed to serialize tabs: {}", e),
+        }
+        self.update_window_title();
+    }
+
+    /// Shows the active tab's page title in the window title.
+    fn update_window_title(&self) {
+        let title = self
+            .tabs
+            .borrow()
+            .active()
+            .and_then(|tab| tab.navigation.current_entry()?.title.clone())
+            .filter(|title| !title.trim().is_empty());
+        // App windows are the site, so they go by its title alone
+        match (title, &self.app) {
+            (Some(title), Some(_)) => (self.set_window_title)(&title),
+            (Some(title), None) => (self.set_window_title)(&format!("{} — SyncFlo", title)),
+            (None, _) => (self.set_window_title)("SyncFlo Browser"),
+        }
+    }
+
+    /// Called when a tab's page starts or finishes loading. Pushes the
+    /// active tab's load state to the nav bar.
+    pub fn load_progress(&self, tab_id: TabId, started: bool, url: &str) {
+        let mut cleared = false;
+        let changed = match self.tabs.borrow_mut().get_mut(tab_id) {
+            Some(tab) if started => {
+                tab.loading.started(url);
+                tab.blocked_popups.clear();
+                if !tab.conversation.pinned {
+                    tab.conversation.clear();
+                    cleared = true;
+                }
+                true
+            }
+            Some(tab) => tab.loading.finished(url),
+            None => false,
+        };
+        if changed {
+            self.emit_loading(tab_id);
+        }
+        if cleared {
+            self.emit("conversation_cleared", &serde_json::json!({ "tab": tab_id }));
+        }
+        if started {
+            // An answer about the previous page is no use anymore
+            self.cancel_ai(tab_id);
+        } else {
+            self.index_page(tab_id, url);
+        }
+    }
+
+    /// Streams the AI assistant's take on the page in tab `tab_id` to the
+    /// UI as `ai_text` events and a final `ai_finished`. Replaces the tab's
+    /// running request, if any.
+    pub fn ask_ai(&self, tab_id: TabId) {
+        let target = self.tabs.borrow_mut().get_mut(tab_id).and_then(|tab| {
+            let url = tab.navigation.current_url()?.clone();
+            Some((tab.view.clone(), url))
+        });
+        let Some((view, url)) = target.filter(|(_, url)| matches!(url.scheme(), "http" | "https")) else {
+            self.toast("error", "이 페이지는 AI에게 물어볼 수 없습니다.");
+            return;
+        };
+        let Some((assistant, request, cancel)) = self.start_ai_request(tab_id, false) else {
+            return;
+        };
+        let proxy = self.proxy.clone();
+        view.evaluate_script_with_callback(
+            "document.documentElement.outerHTML",
+            Box::new(move |result| {
+                let html = serde_json::from_str::<String>(&result).unwrap_or_default();
+                let page = (url.clone(), html);
+                assistant::stream_page(proxy.clone(), assistant.clone(), tab_id, request, page, cancel.clone());
+            }),
+        );
+    }
+
+    /// Summarizes the page in tab `tab_id` with the assistant, replacing
+    /// the tab's running request, if any. With `regenerate`, a cached
+    /// summary isn't used.
+    pub fn summarize(&self, tab_id: TabId, regenerate: bool) {
+        let target = self.tabs.borrow_mut().get_mut(tab_id).and_then(|tab| {
+            let url = tab.navigation.current_url()?.clone();
+            Some((tab.view.clone(), url))
+        });
+        let Some((view, url)) = target.filter(|(_, url)| matches!(url.scheme(), "http" | "https")) else {
+            self.toast("error", "이 페이지는 요약할 수 없습니다.");
+            return;
+        };
+        if !self.ai_allowed(true) {
+            return;
+        }
+        // Without a provider, the assistant summarizes offline
+        let Some(assistant) = self.assistant() else {
+            return;
+        };
+        let assistant = assistant.regenerate(regenerate);
+        let (request, cancel) = self.register_ai_request(tab_id);
+        let proxy = self.proxy.clone();
+        view.evaluate_script_with_callback(
+            "document.documentElement.outerHTML",
+            Box::new(move |result| {
+                let html = serde_json::from_str::<String>(&result).unwrap_or_default();
+                let question = Question::Summary { url: url.clone(), html };
+                assistant::ask(proxy.clone(), assistant.clone(), tab_id, request, question, cancel.clone());
+            }),
+        );
+    }
+
+    /// Translates the page in tab `tab_id` into `lang`, or the UI's
+    /// language, and shows it in the reader view. Replaces the tab's running
+    /// AI request, if any. With `regenerate`, cached translations aren't
+    /// used.
+    pub fn translate_page(&self, tab_id: TabId, lang: Option<String>, regenerate: bool) {
+        let target = self.tabs.borrow_mut().get_mut(tab_id).and_then(|tab| {
+            let url = tab.navigation.current_url()?.clone();
+            Some((tab.view.clone(), url))
+        });
+        let Some((view, url)) = target.filter(|(_, url)| matches!(url.scheme(), "http" | "https")) else {
+            self.toast("error", "이 페이지는 번역할 수 없습니다.");
+            return;
+        };
+        let lang = lang
+            .filter(|lang| !lang.trim().is_empty())
+            .or_else(|| self.settings.lock().ok().map(|settings| settings.locale.clone()))
+            .unwrap_or_else(|| "ko".to_string());
+        let Some((assistant, request, cancel)) = self.start_ai_request(tab_id, regenerate) else {
+            return;
+        };
+        self.toast("info", "페이지를 번역하는 중…");
+        let proxy = self.proxy.clone();
+        // In the reader view this is the reader page, whose article is the same
+        view.evaluate_script_with_callback(
+            "document.documentElement.outerHTML",
+            Box::new(move |result| {
+                let html = serde_json::from_str::<String>(&result).unwrap_or_default();
+                let page = (url.clone(), html);
+                let assistant = assistant.clone();
+                assistant::translate(proxy.clone(), assistant, tab_id, request, page, lang.clone(), cancel.clone());
+            }),
+        );
+    }
+
+    /// Shows the translated reader view of AI request `request`, if it's
+    /// still tab `tab_id`'s current one.
+    fn translated(&self, tab_id: TabId, request: AiRequestId, url: &str, page: Result<String, String>) {
+        if !self.is_current_ai_request(tab_id, request) {
+            return;
+        }
+        self.ai_requests.borrow_mut().remove(&tab_id);
+        let error = page.as_ref().err().cloned();
+        match page {
+            Ok(page) => self.show_reader(tab_id, url, Ok(page)),
+            Err(e) => self.toast("error", &format!("번역하지 못했습니다: {}", e)),
+        }
+        self.emit("ai_finished", &serde_json::json!({ "tab": tab_id, "request": request, "error": error }));
+    }
+
+    /// Asks the assistant about `text`, selected on the page in tab
+    /// `tab_id`, replacing the tab's running request, if any.
+    pub fn ask_selection(&self, tab_id: TabId, text: &str) {
+        let text = text.trim();
+        if text.is_empty() {
+            self.toast("error", "선택한 텍스트를 읽을 수 없습니다.");
+            return;
+        }
+        let Some(url) = self.tabs.borrow_mut().get_mut(tab_id).and_then(|tab| tab.navigation.current_url().cloned())
+        else {
+            return;
+        };
+        // Titles are kept in the shared history
+        let title = self
+            .history
+            .lock()
+            .ok()
+            .and_then(|history| history.entries().find(|entry| entry.url == url)?.title.clone())
+            .unwrap_or_default();
+        let Some((assistant, request, cancel)) = self.start_ai_request(tab_id, false) else {
+            return;
+        };
+        let question = Question::Selection { url: url.into(), title, text: text.to_string() };
+        assistant::ask(self.proxy.clone(), assistant, tab_id, request, question, cancel);
+    }
+
+    /// Asks the assistant for an action doing what `instruction` says on the
+    /// page in tab `tab_id`, replacing the tab's running request and
+    /// proposed action, if any.
+    pub fn suggest_action(&self, tab_id: TabId, instruction: &str) {
+        let instruction = instruction.trim().to_string();
+        if instruction.is_empty() {
+            return;
+        }
+        let target = self.tabs.borrow_mut().get_mut(tab_id).and_then(|tab| {
+            let url = tab.navigation.current_url()?.clone();
+            Some((tab.view.clone(), url))
+        });
+        let Some((view, url)) = target.filter(|(_, url)| matches!(url.scheme(), "http" | "https")) else {
+            self.toast("error", "이 페이지에서는 AI에게 요청할 수 없습니다.");
+            return;
+        };
+        let Some((assistant, request, cancel)) = self.start_ai_request(tab_id, false) else {
+            return;
+        };
+        self.ai_actions.borrow_mut().remove(&tab_id);
+        let proxy = self.proxy.clone();
+        view.evaluate_script_with_callback(
+            "document.documentElement.outerHTML",
+            Box::new(move |result| {
+                let html = serde_json::from_str::<String>(&result).unwrap_or_default();
+                let page = (url.clone(), html);
+                let (assistant, instruction) = (assistant.clone(), instruction.clone());
+                assistant::suggest_action(proxy.clone(), assistant, tab_id, request, page, instruction, cancel.clone());
+            }),
+        );
+    }
+
+    /// Shows the action proposed in AI request `request` for the page at
+    /// `url`, if it's still tab `tab_id`'s current request, for the user to
+    /// confirm.
+    fn action_proposed(&self, tab_id: TabId, request: AiRequestId, url: Url, action: Result<ProposedAction, String>) {
+        if !self.is_current_ai_request(tab_id, request) {
+            return;
+        }
+        self.ai_requests.borrow_mut().remove(&tab_id);
+        let error = match action {
+            Ok(ProposedAction { action: AiAction::None, explanation }) => {
+                let message = explanation.unwrap_or_else(|| "요청에 맞는 동작을 찾지 못했습니다.".to_string());
+                self.toast("info", &message);
+                None
+            }
+            Ok(ProposedAction { action, explanation }) => {
+                let label = describe_action(&action);
+                self.ai_actions.borrow_mut().insert(tab_id, (request, url, action));
+                let detail = serde_json::json!({
+                    "tab": tab_id,
+                    "request": request,
+                    "label": label,
+                    "explanation": explanation,
+                });
+                self.emit("ai_action", &detail);
+                None
+            }
+            Err(e) => {
+                self.toast("error", &e);
+                Some(e)
+            }
+        };
+        self.emit("ai_finished", &serde_json::json!({ "tab": tab_id, "request": request, "error": error }));
+    }
+
+    /// Does the action proposed in AI request `request` for tab `tab_id`,
+    /// which the user confirmed, unless the tab has left the page it was
+    /// about.
+    pub fn run_action(&self, tab_id: TabId, request: AiRequestId) {
+        let proposed = {
+            let mut actions = self.ai_actions.borrow_mut();
+            match actions.get(&tab_id) {
+                Some((id, _, _)) if *id == request => actions.remove(&tab_id),
+                _ => None,
+            }
+        };
+        let Some((_, url, action)) = proposed else {
+            return;
+        };
+        let target = self.tabs.borrow_mut().get_mut(tab_id).map(|tab| {
+            (tab.view.clone(), tab.navigation.current_url().cloned())
+        });
+        let Some((view, current)) = target else {
+            return;
+        };
+        if current.as_ref() != Some(&url) {
+            self.toast("error", "페이지가 바뀌어 실행하지 않았습니다.");
+            return;
+        }
+        match action {
+            AiAction::Navigate { url } => self.navigate(tab_id, &url),
+            AiAction::SearchWeb { query } => match self.search_url(&query) {
+                Ok(url) => self.navigate(tab_id, url.as_str()),
+                Err(e) => log::warn!("Failed to search for {:?}: {:#}", query, e),
+            },
+            AiAction::Scroll { to_heading } => {
+                view.evaluate_script(&format!("{}({})", SCROLL_TO_HEADING_SCRIPT, Value::String(to_heading)));
+            }
+            AiAction::None => {}
+        }
+    }
+
+    /// Asks the assistant about the text selected in tab `tab_id`. A
+    /// selection inside a frame can't always be read; that's reported
+    /// rather than asking about nothing.
+    pub fn ask_page_selection(&self, tab_id: TabId) {
+        let Some(view) = self.tabs.borrow_mut().get_mut(tab_id).map(|tab| tab.view.clone()) else {
+            return;
+        };
+        let proxy = self.proxy.clone();
+        view.evaluate_script_with_callback(
+            SELECTION_SCRIPT,
+            Box::new(move |result| {
+                let text = serde_json::from_str::<Option<String>>(&result).ok().flatten().unwrap_or_default();
+                let _ = proxy.send_event(AppEvent::Selection { tab: tab_id, text });
+            }),
+        );
+    }
+
+    /// Searches history for pages about what `query` says, for
+    /// `syncflo://history` in tab `tab_id`.
+    pub fn search_history(&self, tab_id: TabId, query: String) {
+        let Some(assistant) = self.assistant() else {
+            return;
+        };
+        if !self.semantic_history() || !assistant.supports_embeddings() {
+            let error = "내용으로 찾으려면 설정에서 의미 검색을 켜고 임베딩을 지원하는 AI 제공자를 설정하세요.";
+            self.notify_tab(tab_id, "history_matches", &serde_json::json!({ "error": error }));
+            return;
+        }
+        let history = self.history.clone();
+        assistant::search_history(self.proxy.clone(), assistant, history, tab_id, query, HISTORY_MATCHES);
+    }
+
+    /// Whether visited pages are embedded, so history can be searched by
+    /// meaning.
+    fn semantic_history(&self) -> bool {
+        self.settings.lock().is_ok_and(|settings| settings.ai.semantic_history) && self.ai_allowed(false)
+    }
+
+    /// The assistant as the settings set it up, with the answer cache and
+    /// the usage log. `None`, with a toast, if the proxy can't be used.
+    fn assistant(&self) -> Option<AIAssistant> {
+        let client = self.http_client()?;
+        let mut config = self.settings.lock().map(|settings| settings.ai.clone()).unwrap_or_default();
+        if config.api_key.is_none() {
+            config.api_key = keychain::api_key(config.provider);
+        }
+        let assistant = AIAssistant::new(&config, client).with_usage_log(self.ai_usage.clone());
+        Some(match &self.ai_cache {
+            Some(cache) => assistant.with_cache(cache.clone()),
+            None => assistant,
+        })
+    }
+
+    /// The proxy setting, which the browser's own requests go through.
+    fn proxy_settings(&self) -> ProxySettings {
+        self.settings.lock().map(|settings| settings.proxy.clone()).unwrap_or_default()
+    }
+
+    /// The client of the browser's own async requests, going through the
+    /// proxy the settings set. `None`, with a toast, if it can't be used.
+    fn http_client(&self) -> Option<reqwest::Client> {
+        let proxy = self.proxy_settings();
+        if let Some((_, client)) = self.http.borrow().as_ref().filter(|(used, _)| *used == proxy) {
+            return Some(client.clone());
+        }
+        match proxy.client() {
+            Ok(client) => {
+                *self.http.borrow_mut() = Some((proxy, client.clone()));
+                Some(client)
+            }
+            Err(e) => {
+                log::error!("{:#}", e);
+                self.toast("error", "프록시 설정을 사용할 수 없습니다. 설정을 확인하세요.");
+                None
+            }
+        }
+    }
+
+    /// Whether the AI assistant may see pages. Not in incognito windows,
+    /// unless the settings allow it; says so with a toast if `tell`.
+    fn ai_allowed(&self, tell: bool) -> bool {
+        let allowed = !self.incognito || self.settings.lock().is_ok_and(|settings| settings.privacy.ai_in_incognito);
+        if !allowed && tell {
+            self.toast("error", "시크릿 창에서는 AI 기능이 꺼져 있습니다. 설정의 개인정보 보호에서 켤 수 있습니다.");
+        }
+        allowed
+    }
+
+    /// Sets up a new AI request for tab `tab_id` in place of its running
+    /// one: the assistant, the request's id and what cancels it. With
+    /// `regenerate`, the assistant doesn't use cached answers. `None`,
+    /// after telling the user, if no provider is configured.
+    fn start_ai_request(
+        &self,
+        tab_id: TabId,
+        regenerate: bool,
+    ) -> Option<(AIAssistant, AiRequestId, CancellationToken)> {
+        if !self.ai_allowed(true) {
+            return None;
+        }
+        let assistant = self.assistant()?.regenerate(regenerate);
+        if !assistant.is_configured() {
+            self.toast("error", &AiError::NotConfigured.to_string());
+            return None;
+        }
+        let (request, cancel) = self.register_ai_request(tab_id);
+        Some((assistant, request, cancel))
+    }
+
+    /// Makes a new AI request tab `tab_id`'s running one, cancelling the
+    /// one before: its id and what cancels it.
+    fn register_ai_request(&self, tab_id: TabId) -> (AiRequestId, CancellationToken) {
+        self.cancel_ai(tab_id);
+        let cancel = CancellationToken::new();
+        let request = assistant::next_request_id();
+        self.ai_requests.borrow_mut().insert(tab_id, (request, cancel.clone()));
+        self.emit("ai_started", &serde_json::json!({ "tab": tab_id, "request": request }));
+        (request, cancel)
+    }
+
+    /// Shows the answer to AI request `request` of tab `tab_id`, if it's
+    /// still the tab's current one. The sidebar shows it; while it's
+    /// closed, so does a toast.
+    fn show_ai_answer(&self, tab_id: TabId, request: AiRequestId, response: Result<AIResponse, String>) {
+        if !self.is_current_ai_request(tab_id, request) {
+            return;
+        }
+        self.ai_requests.borrow_mut().remove(&tab_id);
+        // The sidebar shows errors too, from `ai_finished`
+        let toast = !self.sidebar_open();
+        let error = match response {
+            Ok(answer) => {
+                self.emit("ai_reply", &serde_json::json!({ "tab": tab_id, "request": request, "answer": answer }));
+                if toast {
+                    self.toast_answer(&answer);
+                }
+                None
+            }
+            Err(e) => {
+                if toast {
+                    self.toast("error", &e);
+                }
+                Some(e)
+            }
+        };
+        self.emit("ai_finished", &serde_json::json!({ "tab": tab_id, "request": request, "error": error }));
+    }
+
+    /// Shows `answer` as a toast that stays longer the longer it is.
+    fn toast_answer(&self, answer: &AIResponse) {
+        let mut message = answer.suggestion.clone();
+        if answer.truncated {
+            message.insert_str(0, "(너무 길어서 앞부분만 보냈습니다) ");
+        }
+        if answer.redacted > 0 {
+            message.insert_str(0, &format!("(개인정보 {}건을 가렸습니다) ", answer.redacted));
+        }
+        let duration = (message.chars().count() * 80).clamp(4_000, 20_000);
+        self.emit("toast", &serde_json::json!({ "kind": "info", "message": message, "duration": duration }));
+    }
+
+    /// Sends `message` in tab `tab_id`'s conversation with the assistant,
+    /// first making it about the current page if it's about none yet.
+    /// Replaces the tab's running request, if any.
+    pub fn chat(&self, tab_id: TabId, message: String) {
+        let target = self.tabs.borrow_mut().get_mut(tab_id).map(|tab| {
+            (tab.view.clone(), tab.navigation.current_url().cloned(), tab.conversation.clone())
+        });
+        let Some((view, url, conversation)) = target else {
+            return;
+        };
+        let Some((assistant, request, cancel)) = self.start_ai_request(tab_id, false) else {
+            return;
+        };
+        let has_page = conversation.has_page();
+        let turn = ChatTurn { tab: tab_id, request, conversation, page: None, message };
+        let url = url.filter(|url| !has_page && matches!(url.scheme(), "http" | "https"));
+        let Some(url) = url else {
+            assistant::chat(self.proxy.clone(), assistant, turn, cancel);
+            return;
+        };
+        let proxy = self.proxy.clone();
+        view.evaluate_script_with_callback(
+            "document.documentElement.outerHTML",
+            Box::new(move |result| {
+                let page = serde_json::from_str::<String>(&result).ok().map(|html| (url.clone(), html));
+                let turn = ChatTurn { page, ..turn.clone() };
+                assistant::chat(proxy.clone(), assistant.clone(), turn, cancel.clone());
+            }),
+        );
+    }
+
+    /// Takes the conversation of a finished chat request back into its tab
+    /// and shows the answer.
+    fn chat_finished(
+        &self,
+        tab_id: TabId,
+        request: AiRequestId,
+        conversation: Conversation,
+        response: Result<AIResponse, String>,
+    ) {
+        if !self.is_current_ai_request(tab_id, request) {
+            return;
+        }
+        if let Some(tab) = self.tabs.borrow_mut().get_mut(tab_id) {
+            // The pin may have changed while the request was running
+            let pinned = tab.conversation.pinned;
+            tab.conversation = conversation;
+            tab.conversation.pinned = pinned;
+        }
+        self.show_ai_answer(tab_id, request, response);
+    }
+
+    /// Cancels the running AI request of tab `tab_id`, closing its
+    /// connection.
+    pub fn cancel_ai(&self, tab_id: TabId) {
+        let Some((request, cancel)) = self.ai_requests.borrow_mut().remove(&tab_id) else {
+            return;
+        };
+        cancel.cancel();
+        self.emit("ai_finished", &serde_json::json!({ "tab": tab_id, "request": request, "cancelled": true }));
+    }
+
+    /// Whether `request` is still the running AI request of tab `tab_id`.
+    fn is_current_ai_request(&self, tab_id: TabId, request: AiRequestId) -> bool {
+        self.ai_requests.borrow().get(&tab_id).is_some_and(|(current, _)| *current == request)
+    }
+
+    /// Stores keywords and the canonical URL of the page that finished
+    /// loading in tab `tab_id` with its history entry, for searching
+    /// history by content and telling variants of one page apart.
+    fn index_page(&self, tab_id: TabId, url: &str) {
+        let Some(url) = Url::parse(url).ok().filter(|url| matches!(url.scheme(), "http" | "https")) else {
+            return;
+        };
+        let Some(view) = self.tabs.borrow_mut().get_mut(tab_id).map(|tab| tab.view.clone()) else {
+            return;
+        };
+        let history = self.history.clone();
+        let embedder = self
+            .semantic_history()
+            .then(|| self.assistant())
+            .flatten()
+            .filter(|assistant| assistant.supports_embeddings());
+        #[derive(Deserialize)]
+        struct PageContent {
+            text: String,
+            head: String,
+        }
+        view.evaluate_script_with_callback(
+            "({ text: document.body ? document.body.innerText : '', \
+                head: document.head ? document.head.outerHTML : '' })",
+            Box::new(move |result| {
+                let Ok(content) = serde_json::from_str::<PageContent>(&result) else {
+                    return;
+                };
+                if let Some(assistant) = embedder.clone().filter(|_| !content.text.trim().is_empty()) {
+                    assistant::embed_page(assistant, history.clone(), url.clone(), content.text.clone());
+                }
+                let history = history.clone();
+                let url = url.clone();
+                worker::spawn_blocking(move || {
+                    let processor = ContentProcessor::new();
+                    let canonical = match processor.extract_rel_links(&content.head, &url) {
+                        Ok(links) => links.canonical,
+                        Err(e) => {
+                            log::warn!("Failed to read links of {}: {:#}", url, e);
+                            None
+                        }
+                    };
+                    let keywords = processor
+                        .extract_keywords(&content.text, KEYWORDS_PER_PAGE)
+                        .into_iter()
+                        .map(|(keyword, _)| keyword)
+                        .collect();
+                    if let Ok(mut history) = history.lock() {
+                        history.set_canonical_url(&url, canonical);
+                        if !content.text.trim().is_empty() {
+                            history.set_keywords(&url, keywords);
+                        }
+                    }
+                });
+            }),
+        );
+    }
+
+    pub fn stop_loading(&self, tab_id: TabId) {
+        let view = {
+            let mut tabs = self.tabs.borrow_mut();
+            let Some(tab) = tabs.get_mut(tab_id) else {
+                return;
+            };
+            tab.loading.stopped();
+            tab.view.clone()
+        };
+        view.stop_loading();
+        self.emit_loading(tab_id);
+    }
+
+    fn emit_loading(&self, tab_id: TabId) {
+        let state = match self.tabs.borrow().active() {
+            Some(tab) if tab.id == tab_id => serde_json::to_value(&tab.loading),
+            _ => return,
+        };
+        match state {
+            Ok(mut state) => {
+                state["tab"] = Value::from(tab_id);
+                self.emit("loading", &state);
+            }
+            Err(e) => log::warn!("Failed to serialize load state: {}", e),
+        }
+    }
+
+    /// Records a tab's new page title in its history entry and pushes it
+    /// to the tab strip and window title.
+    pub fn title_changed(&self, tab_id: TabId, title: &str) {
+        let url = {
+            let mut tabs = self.tabs.borrow_mut();
+            let Some(tab) = tabs.get_mut(tab_id) else {
+                return;
+            };
+            tab.navigation.set_current_title(title);
+            tab.navigation.current_url().cloned()
+        };
+        if let (Some(url), Ok(mut history)) = (url, self.history.lock()) {
+            history.set_title(&url, title);
+        }
+        self.emit_tabs();
+    }
+
+    /// Shows a notification of the page at `page_url` in `tab_id` with the
+    /// system, if its site may and notifications aren't muted. `icon` is
+    /// the URL the page gave, fetched in the background; without one, or in
+    /// incognito windows, the site's cached icon is used if there is one.
+    pub fn web_notification(&self, tab_id: TabId, page_url: &str, title: String, body: String, icon: Option<&str>) {
+        let Some((page, origin)) = Url::parse(page_url).ok().zip(permissions::origin(page_url)) else {
+            return;
+        };
+        if self.settings.lock().is_ok_and(|settings| settings.mute_notifications) {
+            log::debug!("Muted a notification from {}", origin);
+            return;
+        }
+        let allowed = self.permissions.lock().ok().and_then(|permissions| {
+            permissions.check(&origin, &[Permission::Notifications])
+        });
+        if allowed != Some(true) {
+            log::info!("Dropped a notification from {}, which may not show them", origin);
+            return;
+        }
+        let site = page.host_str().unwrap_or_default().to_string();
+        let site_icon = favicon::cached_path(&self.profile.favicons_dir(), &site);
+        let icon_url = icon.and_then(|icon| page.join(icon).ok()).filter(|_| !self.incognito);
+        let icons_dir = self.profile.notification_icons_dir();
+        let proxy_settings = self.proxy_settings();
+        let notification = WebNotification { tab: tab_id, url: page_url.to_string(), site, title, body };
+        let proxy = self.proxy.clone();
+        worker::spawn_blocking(move || {
+            let fetched = icon_url.and_then(|url| match notifications::icon(&url, &icons_dir, &proxy_settings) {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    log::debug!("No icon for a notification: {:#}", e);
+                    None
+                }
+            });
+            let icon = fetched.or(site_icon);
+            if let Err(e) = notifications::show(&notification, icon.as_deref(), &proxy) {
+                log::warn!("{:#}", e);
+            }
+        });
+    }
+
+    /// Tells the page at `page_url` in `tab_id` whether its site may show
+    /// notifications, for [`notifications::SCRIPT`].
+    pub fn notification_permission(&self, tab_id: TabId, page_url: &str) {
+        let decided = permissions::origin(page_url).and_then(|origin| {
+            self.permissions.lock().ok()?.check(&origin, &[Permission::Notifications])
+        });
+        let answer = match decided {
+            Some(true) => "granted",
+            Some(false) => "denied",
+            None => "default",
+        };
+        let Some(view) = self.tabs.borrow_mut().get_mut(tab_id).map(|tab| tab.view.clone()) else {
+            return;
+        };
+        view.evaluate_script(&format!(
+            "window.__syncfloNotificationPermission && window.__syncfloNotificationPermission('{}')",
+            answer
+        ));
+    }
+
+    /// Asks whether the site of the page at `page_url` in `tab_id` may show
+    /// notifications, unless it was answered before, then tells the page.
+    #[cfg(not(target_os = "linux"))]
+    pub fn request_notification_permission(&self, tab_id: TabId, page_url: &str) {
+        let Some(origin) = permissions::origin(page_url) else {
+            return;
+        };
+        let asked = [Permission::Notifications];
+        let decided = self.permissions.lock().ok().and_then(|permissions| permissions.check(&origin, &asked));
+        if decided.is_none() {
+            let allowed = dialogs::confirm_permission(&origin, &asked, !self.incognito);
+            if let Ok(mut permissions) = self.permissions.lock() {
+                permissions.decide(&origin, &asked, allowed);
+            }
+        }
+        self.notification_permission(tab_id, page_url);
+    }
+
+    /// Shows the icon of a page that finished parsing in a tab, from the
+    /// disk cache or else fetched in the background.
+    pub fn page_icons(&self, tab_id: TabId, page_url: &str, links_html: &str) {
+        let page = Url::parse(page_url).ok().filter(|url| matches!(url.scheme(), "http" | "https"));
+        let Some((page, host)) = page.and_then(|url| Some((url.clone(), url.host_str()?.to_string()))) else {
+            self.set_favicon(tab_id, None);
+            return;
+        };
+        if let Some(data_url) = favicon::cached(&self.profile.favicons_dir(), &host) {
+            self.set_favicon(tab_id, Some(data_url));
+            return;
+        }
+        self.set_favicon(tab_id, None);
+        // One attempt per host and run, so failing sites aren't refetched
+        // on every page. Incognito windows only show icons already cached.
+        if self.incognito || !self.favicon_fetches.borrow_mut().insert(host.clone()) {
+            return;
+        }
+        let icon_url = ContentProcessor::new()
+            .extract_icon_url(links_html, &page)
+            .ok()
+            .flatten()
+            .or_else(|| page.join("/favicon.ico").ok());
+        let Some(icon_url) = icon_url else {
+            return;
+        };
+        let proxy = self.proxy.clone();
+        let cache_dir = self.profile.favicons_dir();
+        let proxy_settings = self.proxy_settings();
+        worker::spawn_blocking(move || match favicon::fetch(&icon_url, &host, &cache_dir, &proxy_settings) {
+            Ok(data_url) => {
+                let _ = proxy.send_event(AppEvent::Favicon { host, data_url });
+            }
+            Err(e) => log::debug!("No icon for {}: {:#}", host, e),
+        });
+    }
+
+    fn set_favicon(&self, tab_id: TabId, data_url: Option<String>) {
+        let active = {
+            let mut tabs = self.tabs.borrow_mut();
+            let Some(tab) = tabs.get_mut(tab_id) else {
+                return;
+            };
+            if tab.favicon == data_url {
+                return;
+            }
+            tab.favicon = data_url.clone();
+            tabs.active().is_some_and(|tab| tab.id == tab_id)
+        };
+        if active {
+            self.emit("favicon", &Value::from(data_url));
+        }
+    }
+}
+
+/// Work finished off the main thread, delivered through the event loop.
+#[derive(Debug)]
+pub enum AppEvent {
+    Favicon { host: String, data_url: String },
+    /// Closes the window, like the close button. `confirmed` once the user
+    /// agreed to stop the downloads in progress.
+    Quit { confirmed: bool },
+    /// A menu item was chosen; carries the item id.
+    Menu(String),
+    /// The browser was launched again with this profile; opens `urls` as
+    /// tabs and brings the window to the front.
+    Launched { urls: Vec<String> },
+    /// A notification of the page at `url` in `tab` was clicked; brings
+    /// the window to the front with that tab, or the page in a new one.
+    NotificationClicked { tab: TabId, url: String },
+    /// The page in `tab` was asked about leaving it; with `ask`, the user
+    /// confirms first.
+    Leave { tab: TabId, leave: Leave, ask: bool },
+    /// A page in `tab` followed a link to `url`, whose scheme the browser
+    /// doesn't handle; `open` once the user answered whether to open such
+    /// links with the system.
+    ExternalLink { tab: TabId, url: Url, open: Option<bool> },
+    /// The search engine suggested `suggestions` for `input` typed into
+    /// the address bar.
+    SearchSuggestions { input: String, suggestions: Vec<Suggestion> },
+    /// The reader view of `url` in `tab` was rendered, or failed to.
+    Reader { tab: TabId, url: String, page: Result<String, String> },
+    /// The offline copy of `url` for the reading list was made, or failed
+    /// to be.
+    ReadLater { url: String, item: Result<ReadingItem, String> },
+    /// The user chose `path` to open in `tab`.
+    OpenFile { tab: TabId, path: PathBuf },
+    /// The user chose `path` to save the page in `tab` to.
+    SavePage { tab: TabId, path: PathBuf },
+    /// `done` of the `found` resources of a page being saved are
+    /// downloaded.
+    SavePageProgress { done: usize, found: usize },
+    /// A page was saved to `path`, or failed to be.
+    PageSaved { path: PathBuf, report: Result<ArchiveReport, String> },
+    /// The user chose `path` to save the page in `tab` to as a PDF.
+    SavePdf { tab: TabId, path: PathBuf },
+    /// A page was printed to the PDF at `path`, or failed to be.
+    PdfSaved { path: PathBuf, result: Result<(), String> },
+    /// The user chose `path` to save the page in `tab` to as Markdown.
+    SaveMarkdown { tab: TabId, path: PathBuf },
+    /// A page was saved to the Markdown file at `path`, or failed to be.
+    MarkdownSaved { path: PathBuf, result: Result<(), String> },
+    /// More text of the answer to AI request `request` of `tab`.
+    AiText { tab: TabId, request: AiRequestId, text: String },
+    /// AI request `request` of `tab` is done, with an error message if it
+    /// failed.
+    AiFinished { tab: TabId, request: AiRequestId, error: Option<String> },
+    /// The text selected in `tab`'s page was read, for asking the
+    /// assistant about; empty if there was none or it couldn't be read.
+    Selection { tab: TabId, text: String },
+    /// The reader view of `url` translated for AI request `request` of
+    /// `tab` was rendered, or the translation failed.
+    Translated { tab: TabId, request: AiRequestId, url: String, page: Result<String, String> },
+    /// The assistant proposed `action` on the page at `url` for request
+    /// `request` of `tab`, or failed to.
+    AiAction { tab: TabId, request: AiRequestId, url: Url, action: Result<ProposedAction, String> },
+    /// A test request to the AI API for `syncflo://settings` in `tab`
+    /// answered, or why it failed.
+    AiValidated { tab: TabId, result: Result<ProviderInfo, String> },
+    /// A page fetched through the proxy for `syncflo://settings` in `tab`
+    /// in this long, or why it failed.
+    ProxyTested { tab: TabId, result: Result<Duration, String> },
+    /// History entries `[{url, title, score}]` found by meaning for
+    /// `syncflo://history` in `tab`, or why the search failed.
+    HistoryMatches { tab: TabId, matches: Result<Vec<Value>, String> },
+    /// The assistant answered request `request` of `tab`, or failed to.
+    AiAnswer { tab: TabId, request: AiRequestId, response: Result<AIResponse, String> },
+    /// The assistant answered chat request `request` of `tab`, or failed
+    /// to; carries the tab's conversation with the new turns.
+    AiReply {
+        tab: TabId,
+        request: AiRequestId,
+        conversation: Box<Conversation>,
+        response: Result<AIResponse, String>,
+    },
+}
+
+/// Nav bar commands that leave the current page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Leave {
+    Back,
+    Forward,
+    Home,
+}
+
+/// Applies the result of background work.
+pub fn handle_app_event(ctx: &IpcContext, event: AppEvent) {
+    match event {
+        AppEvent::Favicon { host, data_url } => {
+            let tabs: Vec<TabId> = ctx
+                .tabs
+                .borrow()
+                .iter()
+                .filter(|tab| tab.navigation.current_url().and_then(|url| url.host_str()) == Some(host.as_str()))
+                .map(|tab| tab.id)
+                .collect();
+            for id in tabs {
+                ctx.set_favicon(id, Some(data_url.clone()));
+            }
+        }
+        // Handled by the event loop
+        AppEvent::Quit { .. } | AppEvent::Launched { .. } | AppEvent::NotificationClicked { .. } => {}
+        AppEvent::Menu(id) => {
+            if !context_menu::handle(ctx, &id) {
+                #[cfg(target_os = "macos")]
+                crate::browser::menu::handle(ctx, &id);
+            }
+        }
+        AppEvent::Reader { tab, url, page } => ctx.show_reader(tab, &url, page),
+        AppEvent::ReadLater { url, item } => ctx.saved_for_later(&url, item),
+        AppEvent::OpenFile { tab, path } => ctx.open_chosen_file(tab, &path),
+        AppEvent::SavePage { tab, path } => ctx.save_page_to(tab, path),
+        AppEvent::SavePageProgress { done, found } => {
+            let message = format!("페이지 저장 중… 리소스 {}/{}", done, found);
+            ctx.emit("toast", &serde_json::json!({ "kind": "info", "message": message, "duration": 60_000 }));
+        }
+        AppEvent::PageSaved { path, report } => ctx.page_saved(&path, report),
+        AppEvent::SavePdf { tab, path } => ctx.save_pdf_to(tab, path),
+        AppEvent::PdfSaved { path, result } => ctx.pdf_saved(&path, result),
+        AppEvent::SaveMarkdown { tab, path } => ctx.save_markdown_to(tab, path),
+        AppEvent::MarkdownSaved { path, result } => ctx.markdown_saved(&path, result),
+        AppEvent::AiText { tab, request, text } => {
+            if ctx.is_current_ai_request(tab, request) {
+                ctx.emit("ai_text", &serde_json::json!({ "tab": tab, "request": request, "text": text }));
+            }
+        }
+        AppEvent::AiFinished { tab, request, error } => {
+            if ctx.is_current_ai_request(tab, request) {
+                ctx.ai_requests.borrow_mut().remove(&tab);
+                ctx.emit("ai_finished", &serde_json::json!({ "tab": tab, "request": request, "error": error }));
+            }
+        }
+        AppEvent::Selection { tab, text } => ctx.ask_selection(tab, &text),
+        AppEvent::Translated { tab, request, url, page } => ctx.translated(tab, request, &url, page),
+        AppEvent::HistoryMatches { tab, matches } => {
+            let detail = match matches {
+                Ok(matches) => serde_json::json!({ "matches": matches }),
+                Err(error) => serde_json::json!({ "error": error }),
+            };
+            ctx.notify_tab(tab, "history_matches", &detail);
+        }
+        AppEvent::AiValidated { tab, result } => {
+            let detail = match result {
+                Ok(info) => serde_json::json!({ "info": info }),
+                Err(error) => serde_json::json!({ "error": error }),
+            };
+            ctx.notify_tab(tab, "ai_connection", &detail);
+        }
+        AppEvent::ProxyTested { tab, result } => {
+            let detail = match result {
+                Ok(elapsed) => serde_json::json!({ "latency_ms": elapsed.as_millis() as u64 }),
+                Err(error) => serde_json::json!({ "error": error }),
+            };
+            ctx.notify_tab(tab, "proxy_test", &detail);
+        }
+        AppEvent::AiAnswer { tab, request, response } => ctx.show_ai_answer(tab, request, response),
+        AppEvent::AiAction { tab, request, url, action } => ctx.action_proposed(tab, request, url, action),
+        AppEvent::AiReply { tab, request, conversation, response } => {
+            ctx.chat_finished(tab, request, *conversation, response)
+        }
+        AppEvent::SearchSuggestions { input, suggestions } => {
+            ctx.emit("search_suggestions", &serde_json::json!({ "input": input, "suggestions": suggestions }));
+        }
+        AppEvent::ExternalLink { tab, url, open: None } => ctx.ask_external(tab, url),
+        AppEvent::ExternalLink { url, open: Some(open), .. } => ctx.external_answered(url, open),
+        AppEvent::Leave { tab, leave, ask: false } => ctx.leave_now(tab, leave),
+        AppEvent::Leave { tab, leave, ask: true } => {
+            let Some(view) = ctx.tabs.borrow_mut().get_mut(tab).map(|tab| tab.view.clone()) else {
+                return;
+            };
+            let proxy = ctx.proxy.clone();
+            view.confirm_leave(Box::new(move |confirmed| {
+                if confirmed {
+                    let _ = proxy.send_event(AppEvent::Leave { tab, leave, ask: false });
+                }
+            }));
+        }
+    }
+}
+
+/// Parses a raw IPC message, logging anything that isn't a known command.
+pub fn parse<T: for<'de> Deserialize<'de>>(raw: &str) -> Option<T> {
+    match serde_json::from_str(raw) {
+        Ok(message) => Some(message),
+        Err(e) => {
+            log::warn!("Ignoring malformed IPC message ({}): {}", e, raw);
+            None
+        }
+    }
+}
+
+/// Handles a raw message from the nav webview, replying if it carries an id.
+pub fn handle_nav_message(raw: &str, ctx: &IpcContext) {
+    handle_ui_message(raw, ctx, ctx.nav.as_ref());
+}
+
+/// Handles a raw message from the sidebar, which takes the same commands
+/// as the nav bar.
+pub fn handle_sidebar_message(raw: &str, ctx: &IpcContext) {
+    handle_ui_message(raw, ctx, ctx.sidebar.as_ref());
+}
+
+fn handle_ui_message(raw: &str, ctx: &IpcContext, from: &dyn WebViewHandle) {
+    let Some(request) = parse::<IpcRequest>(raw) else {
+        return;
+    };
+    let result = dispatch(&request.command, ctx);
+    if let Some(id) = request.id {
+        reply(from, id, result);
+    }
+}
+
+/// Settles the pending `window.syncflo.invoke` promise for `id` in `nav`,
+/// the nav bar or the sidebar.
+pub fn reply(nav: &dyn WebViewHandle, id: u64, result: Result<Value, String>) {
+    let (value, error) = match result {
+        Ok(value) => (value, Value::Null),
+        Err(message) => (Value::Null, Value::String(message)),
+    };
+    nav.evaluate_script(&format!("window.syncflo._resolve({}, {}, {})", id, value, error));
+}
+
+/// Handles a raw message from a page in tab `tab_id`. `page_url` is the URL
+/// of the sending page; only internal pages may change stored data.
+pub fn handle_page_message(raw: &str, page_url: &str, tab_id: TabId, ctx: &IpcContext) {
+    match parse::<PageEvent>(raw) {
+        Some(PageEvent::Committed { url }) => {
+            record_navigation(ctx, tab_id, &url);
+            ctx.tabs_changed();
+        }
+        Some(PageEvent::PageIcons { links }) => ctx.page_icons(tab_id, page_url, &links),
+        Some(PageEvent::FindResult { query, current, total }) => ctx.find_result(tab_id, &query, current, total),
+        Some(PageEvent::UserGesture) => ctx.user_gesture(tab_id),
+        Some(PageEvent::ContextMenu(target)) => context_menu::show(ctx, tab_id, target),
+        Some(PageEvent::PointerAtTop { at_top }) => ctx.pointer_at_top(tab_id, at_top),
+        Some(PageEvent::ShowNotification { title, body, icon }) => {
+            ctx.web_notification(tab_id, page_url, title, body, icon.as_deref());
+        }
+        Some(PageEvent::NotificationPermission) => ctx.notification_permission(tab_id, page_url),
+        // WebKitGTK asks with a permission request of its own instead
+        #[cfg(target_os = "linux")]
+        Some(PageEvent::RequestNotificationPermission) => ctx.notification_permission(tab_id, page_url),
+        #[cfg(not(target_os = "linux"))]
+        Some(PageEvent::RequestNotificationPermission) => ctx.request_notification_permission(tab_id, page_url),
+        Some(PageEvent::Shortcut(press)) if ctx.kiosk_exit(&press) => {}
+        Some(PageEvent::Shortcut(press)) => {
+            match press.shortcut() {
+                // Web pages can send this message themselves
+                Some(Shortcut::Quit) if !assets::is_internal_url(page_url) => {
+                    log::warn!("Ignoring quit shortcut from {}", page_url);
+                }
+                Some(shortcut) => run_shortcut(ctx, shortcut),
+                None => {}
+            }
+        }
+        Some(event) if !assets::is_internal_url(page_url) => {
+            log::warn!("Ignoring {:?} from {}", event, page_url);
+        }
+        Some(PageEvent::Navigate { input }) => ctx.navigate(tab_id, &input),
+        Some(PageEvent::DeleteHistory { url }) => {
+            if let (Ok(url), Ok(mut history)) = (Url::parse(&url), ctx.history.lock()) {
+                history.remove_entry(&url);
+            }
+            ctx.refresh_top_sites();
+        }
+        Some(PageEvent::SearchHistory { query }) => ctx.search_history(tab_id, query),
+        Some(PageEvent::GetTopSites) => {
+            ctx.notify_tab(tab_id, "top_sites", &top_sites(ctx));
+        }
+        Some(PageEvent::DeleteBookmark { url }) => {
+            if let (Ok(url), Ok(mut bookmarks)) = (Url::parse(&url), ctx.bookmarks.lock()) {
+                bookmarks.remove(&url);
+            }
+        }
+        Some(PageEvent::SetReadingItemRead { id, read }) => {
+            if let Ok(mut list) = ctx.reading_list.lock() {
+                list.set_read(&id, read);
+            }
+        }
+        Some(PageEvent::DeleteReadingItems { ids }) => {
+            if let Ok(mut list) = ctx.reading_list.lock() {
+                list.remove(&ids);
+            }
+        }
+        Some(PageEvent::ImportBookmarks { path }) => {
+            let result = ctx
+                .bookmarks
+                .lock()
+                .map_err(|e| anyhow::anyhow!(e.to_string()))
+                .and_then(|mut bookmarks| bookmarks.import_file(Path::new(path.trim())));
+            let detail = match result {
+                Ok(report) => serde_json::json!({ "report": report }),
+                Err(e) => serde_json::json!({ "error": format!("{:#}", e) }),
+            };
+            ctx.notify_tab(tab_id, "import_bookmarks", &detail);
+        }
+        Some(PageEvent::ExportHistory { path }) => {
+            let result = ctx
+                .history
+                .lock()
+                .map_err(|e| anyhow::anyhow!(e.to_string()))
+                .and_then(|history| history.export_json(create_export_file(Path::new(path.trim()))?));
+            notify_export_result(ctx, tab_id, &path, result);
+        }
+        Some(PageEvent::ExportBookmarks { path }) => {
+            let result = ctx
+                .bookmarks
+                .lock()
+                .map_err(|e| anyhow::anyhow!(e.to_string()))
+                .and_then(|bookmarks| bookmarks.export_netscape_html(create_export_file(Path::new(path.trim()))?));
+            notify_export_result(ctx, tab_id, &path, result);
+        }
+        Some(PageEvent::ClearData(request)) => {
+            let detail = match clear_browsing_data(ctx, &request) {
+                Ok(removed) => serde_json::json!({ "history_removed": removed }),
+                Err(e) => serde_json::json!({ "error": format!("{:#}", e) }),
+            };
+            ctx.notify_tab(tab_id, "clear_data", &detail);
+        }
+        Some(PageEvent::TestAiConnection) => {
+            if let Some(assistant) = ctx.assistant() {
+                assistant::validate(ctx.proxy.clone(), assistant, tab_id);
+            }
+        }
+        Some(PageEvent::TestProxy) => ctx.test_proxy(tab_id),
+        Some(PageEvent::GetPermissions) => ctx.notify_tab(tab_id, "permissions", &ctx.permissions_json()),
+        Some(PageEvent::RevokePermission { origin, permission }) => {
+            if let Ok(mut permissions) = ctx.permissions.lock() {
+                permissions.revoke(&origin, permission);
+            }
+            ctx.notify_tab(tab_id, "permissions", &ctx.permissions_json());
+        }
+        Some(PageEvent::SetDefaultBrowser) => {
+            let detail = match default_browser::set_as_default() {
+                Ok(()) => serde_json::json!({}),
+                Err(e) => serde_json::json!({ "error": format!("{:#}", e) }),
+            };
+            ctx.notify_tab(tab_id, "default_browser", &detail);
+        }
+        Some(PageEvent::GetSettings) => ctx.notify_tab(tab_id, "settings", &ctx.settings_json()),
+        Some(PageEvent::SetSetting { key, value }) => {
+            let detail = match ctx.set_setting(&key, value) {
+                Ok(()) => serde_json::json!({ "key": key, "settings": ctx.settings_json() }),
+                Err(e) => serde_json::json!({ "key": key, "error": format!("{:#}", e) }),
+            };
+            ctx.notify_tab(tab_id, "setting", &detail);
+        }
+        Some(PageEvent::GetProfiles) => ctx.notify_tab(tab_id, "profiles", &ctx.profiles_json()),
+        Some(PageEvent::OpenProfile { name }) => {
+            if let Err(e) = ctx.open_profile(name.as_deref()) {
+                notify_profiles(ctx, tab_id, Err(e));
+            }
+        }
+        Some(PageEvent::CreateProfile { name }) => {
+            let result = ctx.profile.create(name.trim()).and_then(|profile| ctx.open_profile(profile.name()));
+            notify_profiles(ctx, tab_id, result);
+        }
+        Some(PageEvent::DeleteProfile { name }) => {
+            let result = ctx.profile.delete(&name);
+            notify_profiles(ctx, tab_id, result);
+        }
+        Some(PageEvent::ResetUsage) => {
+            ctx.ai_usage.reset();
+            ctx.notify_tab(tab_id, "reset_usage", &serde_json::json!({}));
+        }
+        Some(PageEvent::RestoreSession) => {
+            if let Err(e) = ctx.restore_recovered_session(tab_id) {
+                log::warn!("Failed to restore session: {:#}", e);
+            }
+        }
+        Some(PageEvent::DismissRecovery) => {
+            if let Ok(mut session) = ctx.recovered_session.lock() {
+                *session = None;
+            }
+        }
+        None => {}
+    }
+}
+
+/// Zoom steps, as in other browsers.
+const ZOOM_LEVELS: &[f64] = &[0.25, 0.33, 0.5, 0.67, 0.75, 0.8, 0.9, 1.0, 1.1, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0];
+
+/// Popups opened this long after a click or key press the page reported
+/// count as the user's.
+const POPUP_GESTURE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Blocked popups listed per page; older ones are dropped.
+const MAX_BLOCKED_POPUPS: usize = 20;
+
+/// How often the toast of a page being saved counts its resources again.
+const SAVE_PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Tiles shown on the home page.
+const TOP_SITES_COUNT: usize = 8;
+
+/// Most the nav bar grows by for its popovers, in logical pixels.
+const MAX_POPOVER_HEIGHT: f64 = 480.0;
+
+/// Content keywords kept per history entry.
+const KEYWORDS_PER_PAGE: usize = 20;
+
+/// History entries found by a search by meaning.
+const HISTORY_MATCHES: usize = 20;
+
+/// JS condition that's true only on internal pages.
+const INTERNAL_PAGE_CHECK: &str =
+    "location.protocol === 'syncflo:' || location.hostname.indexOf('syncflo.') === 0";
+
+fn dispatch_script(condition: &str, event: &str, detail: &Value) -> String {
+    let name = Value::String(format!("syncflo:{}", event));
+    format!(
+        "if ({}) window.dispatchEvent(new CustomEvent({}, {{ detail: {} }}))",
+        condition, name, detail
+    )
+}
+
+/// What `action` does, for the chip confirming it.
+fn describe_action(action: &AiAction) -> String {
+    match action {
+        AiAction::Navigate { url } => format!("{} 열기", url),
+        AiAction::SearchWeb { query } => format!("웹에서 \"{}\" 검색", query),
+        AiAction::Scroll { to_heading } => format!("\"{}\" 제목으로 이동", to_heading),
+        AiAction::None => String::new(),
+    }
+}
+
+/// `[{url, title}]` for the home page tiles.
+fn top_sites(ctx: &IpcContext) -> Value {
+    let Ok(history) = ctx.history.lock() else {
+        return Value::Array(Vec::new());
+    };
+    history
+        .top_sites(TOP_SITES_COUNT)
+        .into_iter()
+        .map(|entry| serde_json::json!({ "url": entry.url.as_str(), "title": entry.title }))
+        .collect()
+}
+
+/// Clears history and webview data as requested. Returns the number of
+/// history entries removed.
+pub fn clear_browsing_data(ctx: &IpcContext, request: &ClearDataRequest) -> anyhow::Result<usize> {
+    let since = request.range.duration();
+    let mut removed = 0;
+    if request.history {
+        let range = since.unwrap_or(Duration::MAX);
+        removed = ctx
+            .history
+            .lock()
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?
+            .clear_range(range);
+        for tab in ctx.tabs.borrow_mut().iter_mut() {
+            tab.navigation.clear_range(range);
+        }
+        ctx.refresh_top_sites();
+    }
+    if request.cookies || request.cache {
+        let view = ctx.active_view().ok_or_else(|| anyhow::anyhow!("No webview to clear data through"))?;
+        view.clear_website_data(request.cookies, request.cache, since)?;
+    }
+    log::info!("Cleared browsing data: {:?}", request);
+    Ok(removed)
+}
+
+/// Creates (or truncates) a file to export data into.
+pub fn create_export_file(path: &Path) -> anyhow::Result<std::io::BufWriter<std::fs::File>> {
+    let file = std::fs::File::create(path)
+        .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", path.display(), e))?;
+    Ok(std::io::BufWriter::new(file))
+}
+
+fn notify_export_result(ctx: &IpcContext, tab_id: TabId, path: &str, result: anyhow::Result<()>) {
+    let detail = match result {
+        Ok(()) => serde_json::json!({ "path": path.trim() }),
+        Err(e) => serde_json::json!({ "error": format!("{:#}", e) }),
+    };
+    ctx.notify_tab(tab_id, "export", &detail);
+}
+
+/// Starts another browser process with `args`.
+fn spawn_browser(args: Vec<std::ffi::OsString>) -> anyhow::Result<()> {
+    let exe = std::env::current_exe().map_err(|e| anyhow::anyhow!("Failed to find the browser: {}", e))?;
+    std::process::Command::new(exe)
+        .args(args)
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to start the browser: {}", e))?;
+    Ok(())
+}
+
+/// Sends the profiles to the page, with why the last change failed if it did.
+fn notify_profiles(ctx: &IpcContext, tab_id: TabId, result: anyhow::Result<()>) {
+    let mut detail = ctx.profiles_json();
+    if let Err(e) = result {
+        detail["error"] = format!("{:#}", e).into();
+    }
+    ctx.notify_tab(tab_id, "profiles", &detail);
+}
+
+/// Runs a command and returns its result for the reply, if any.
+pub fn dispatch(command: &IpcCommand, ctx: &IpcContext) -> Result<Value, String> {
+    log::debug!("IPC command: {:?}", command);
+    match command {
+        IpcCommand::Back => ctx.leave(Leave::Back),
+        IpcCommand::Forward => ctx.leave(Leave::Forward),
+        IpcCommand::Stop => {
+            if let Some(id) = ctx.tabs.borrow().active().map(|tab| tab.id) {
+                ctx.stop_loading(id);
+            }
+        }
+        IpcCommand::StopAi => {
+            if let Some(id) = ctx.tabs.borrow().active().map(|tab| tab.id) {
+                ctx.cancel_ai(id);
+            }
+        }
+        IpcCommand::Summarize { regenerate } => {
+            if let Some(id) = ctx.tabs.borrow().active().map(|tab| tab.id) {
+                ctx.summarize(id, *regenerate);
+            }
+        }
+        IpcCommand::TranslatePage { lang, regenerate } => {
+            if let Some(id) = ctx.tabs.borrow().active().map(|tab| tab.id) {
+                ctx.translate_page(id, lang.clone(), *regenerate);
+            }
+        }
+        IpcCommand::Chat { message } => {
+            if let Some(id) = ctx.tabs.borrow().active().map(|tab| tab.id) {
+                ctx.chat(id, message.clone());
+            }
+        }
+        IpcCommand::AskAi => {
+            if let Some(id) = ctx.tabs.borrow().active().map(|tab| tab.id) {
+                ctx.ask_ai(id);
+            }
+        }
+        IpcCommand::PinConversation { pinned } => {
+            if let Some(tab) = ctx.tabs.borrow_mut().active_mut() {
+                tab.conversation.pinned = *pinned;
+            }
+        }
+        IpcCommand::Refresh => {
+            if let Some(view) = ctx.active_view() {
+                view.evaluate_script("window.location.reload()");
+            }
+        }
+        IpcCommand::Home => ctx.leave(Leave::Home),
+        IpcCommand::Navigate { url } => {
+            let active = ctx.tabs.borrow().active().map(|tab| tab.id);
+            if let Some(id) = active {
+                ctx.navigate(id, url);
+            }
+        }
+        IpcCommand::GetState => {
+            let state = nav_state(ctx).ok_or("no active tab")?;
+            return serde_json::to_value(state).map_err(|e| e.to_string());
+        }
+        IpcCommand::GetTabs => {
+            return serde_json::to_value(ctx.tabs.borrow().infos()).map_err(|e| e.to_string());
+        }
+        IpcCommand::NewTab => {
+            let id = ctx.open_tab(&ctx.home_url).map_err(|e| format!("{:#}", e))?;
+            return Ok(Value::from(id));
+        }
+        IpcCommand::BookmarkCurrent => {
+            let bookmarked = toggle_bookmark(ctx).ok_or("no page to bookmark")?;
+            return Ok(serde_json::json!({ "bookmarked": bookmarked }));
+        }
+        IpcCommand::ListBookmarks => {
+            let bookmarks = ctx.bookmarks.lock().map_err(|e| e.to_string())?;
+            return serde_json::to_value(bookmarks.list()).map_err(|e| e.to_string());
+        }
+        IpcCommand::ReadLater => ctx.read_later(),
+        IpcCommand::ReadLinkLater { url } => ctx.read_link_later(url),
+        IpcCommand::OpenFile => ctx.open_file(),
+        IpcCommand::SavePage => ctx.save_page(),
+        IpcCommand::SavePdf { path } => ctx.save_pdf(path.clone()),
+        IpcCommand::SaveMarkdown => ctx.save_markdown(),
+        IpcCommand::RevealFile { path } => downloads::reveal(path).map_err(|e| format!("{:#}", e))?,
+        IpcCommand::ToggleBlocking { host } => {
+            let blocking = ctx.toggle_blocking(host).map_err(|e| format!("{:#}", e))?;
+            return Ok(serde_json::json!({ "blocking": blocking }));
+        }
+        IpcCommand::Find { query, forward, case_sensitive } => {
+            if let Some(view) = ctx.active_view() {
+                view.evaluate_script(&format!(
+                    "{}\nwindow.__syncfloFind.find({}, {}, {})",
+                    FIND_SCRIPT,
+                    Value::String(query.clone()),
+                    forward,
+                    case_sensitive
+                ));
+            }
+        }
+        IpcCommand::FindClear => {
+            if let Some(view) = ctx.active_view() {
+                view.evaluate_script("window.__syncfloFind && window.__syncfloFind.clear()");
+            }
+        }
+        IpcCommand::Devtools => {
+            if let Some(view) = ctx.active_view() {
+                if !view.toggle_devtools() {
+                    log::warn!("DevTools aren't available in this build; enable the `devtools` feature");
+                }
+            }
+        }
+        IpcCommand::ListDownloads => {
+            return serde_json::to_value(ctx.downloads.borrow().list()).map_err(|e| e.to_string());
+        }
+        IpcCommand::OpenDownload { id } | IpcCommand::RevealDownload { id } => {
+            let path = ctx
+                .downloads
+                .borrow()
+                .get(*id)
+                .map(|download| download.path.clone())
+                .ok_or("no such download")?;
+            let opened = if matches!(command, IpcCommand::OpenDownload { .. }) {
+                downloads::open(&path)
+            } else {
+                downloads::reveal(&path)
+            };
+            opened.map_err(|e| format!("{:#}", e))?;
+        }
+        IpcCommand::CancelDownload { id } => {
+            if !ctx.downloads.borrow_mut().cancel(*id) {
+                return Err("download can't be cancelled".into());
+            }
+            ctx.emit_downloads();
+        }
+        IpcCommand::OpenLogs => {
+            let dir = ctx.profile.logs_dir();
+            if !dir.is_dir() {
+                return Err("there are no logs yet".into());
+            }
+            downloads::open(&dir).map_err(|e| format!("{:#}", e))?;
+        }
+        IpcCommand::OpenPopup { url } => ctx.open_blocked_popup(url),
+        IpcCommand::AllowPopups { always } => ctx.allow_popups(*always).map_err(|e| format!("{:#}", e))?,
+        IpcCommand::OpenTab { url } => ctx.open_in_new_tab(url),
+        IpcCommand::CopyText { text } => {
+            context_menu::copy_to_clipboard(text).map_err(|e| format!("{:#}", e))?;
+        }
+        IpcCommand::Download { url } => {
+            if let Some(view) = ctx.active_view() {
+                view.download(url);
+            }
+        }
+        IpcCommand::ToggleReader => {
+            if let Some(id) = ctx.tabs.borrow().active().map(|tab| tab.id) {
+                ctx.toggle_reader(id);
+            }
+        }
+        IpcCommand::AskSelection { text } => {
+            if let Some(id) = ctx.tabs.borrow().active().map(|tab| tab.id) {
+                ctx.ask_selection(id, text);
+            }
+        }
+        IpcCommand::SuggestAction { instruction } => {
+            if let Some(id) = ctx.tabs.borrow().active().map(|tab| tab.id) {
+                ctx.suggest_action(id, instruction);
+            }
+        }
+        IpcCommand::RunAction { request } => {
+            if let Some(id) = ctx.tabs.borrow().active().map(|tab| tab.id) {
+                ctx.run_action(id, *request);
+            }
+        }
+        IpcCommand::DismissAction => {
+            if let Some(id) = ctx.tabs.borrow().active().map(|tab| tab.id) {
+                ctx.ai_actions.borrow_mut().remove(&id);
+            }
+        }
+        IpcCommand::ToggleLayoutOverlay => ctx.toggle_layout_overlay(),
+        IpcCommand::ToggleFullscreen => ctx.toggle_fullscreen(),
+        IpcCommand::Suggest { input } => {
+            return serde_json::to_value(ctx.suggest(input)).map_err(|e| e.to_string());
+        }
+        IpcCommand::ShowPopover { height } => ctx.show_popover(*height),
+        IpcCommand::SiteInfo => {
+            let info = ctx.site_info().ok_or("No page")?;
+            return serde_json::to_value(info).map_err(|e| e.to_string());
+        }
+        IpcCommand::SetSiteSettings { host, settings } => {
+            ctx.set_site_settings(host, *settings).map_err(|e| format!("{:#}", e))?;
+        }
+        IpcCommand::ToggleSidebar => {
+            let open = ctx.toggle_sidebar();
+            return Ok(serde_json::json!({ "open": open }));
+        }
+        IpcCommand::ZoomIn => ctx.zoom(Some(true)),
+        IpcCommand::ZoomOut => ctx.zoom(Some(false)),
+        IpcCommand::ResetZoom => ctx.zoom(None),
+        IpcCommand::Shortcut(press) => {
+            if ctx.kiosk_exit(press) {
+                return Ok(Value::Null);
+            }
+            if let Some(shortcut) = press.shortcut() {
+                run_shortcut(ctx, shortcut);
+            }
+        }
+        IpcCommand::CloseTab { id } => ctx.close_tab(*id).map_err(|e| format!("{:#}", e))?,
+        IpcCommand::SwitchTab { id } => ctx.switch_tab(*id).map_err(|e| format!("{:#}", e))?,
+    }
+    Ok(Value::Null)
+}
+
+/// Does what a keyboard shortcut is bound to, through the same commands as
+/// the nav bar buttons.
+pub fn run_shortcut(ctx: &IpcContext, shortcut: Shortcut) {
+    // App windows have no nav bar, sidebar or windows of their own, and
+    // kiosk windows stay open and fullscreen
+    let app_only = matches!(
+        shortcut,
+        Shortcut::FocusAddress | Shortcut::ToggleSidebar | Shortcut::NewIncognitoWindow | Shortcut::OpenFile
+    );
+    let kiosk_only = matches!(shortcut, Shortcut::Quit | Shortcut::Fullscreen);
+    if (app_only && ctx.app.is_some()) || (kiosk_only && ctx.is_kiosk()) {
+        log::info!("Ignoring {:?} in an app window", shortcut);
+        return;
+    }
+    let command = match shortcut {
+        Shortcut::FocusAddress => {
+            ctx.nav.focus();
+            ctx.emit("focus_address", &Value::Null);
+            return;
+        }
+        Shortcut::Find => {
+            ctx.open_find();
+            return;
+        }
+        Shortcut::AskSelection => {
+            if let Some(id) = ctx.tabs.borrow().active().map(|tab| tab.id) {
+                ctx.ask_page_selection(id);
+            }
+            return;
+        }
+        Shortcut::ToggleSidebar => IpcCommand::ToggleSidebar,
+        Shortcut::LayoutOverlay => IpcCommand::ToggleLayoutOverlay,
+        Shortcut::Fullscreen => IpcCommand::ToggleFullscreen,
+        Shortcut::NewIncognitoWindow => {
+            if let Err(e) = ctx.open_incognito_window() {
+                ctx.toast("error", &format!("시크릿 창을 열지 못했습니다: {:#}", e));
+            }
+            return;
+        }
+        Shortcut::Quit => {
+            if ctx.proxy.send_event(AppEvent::Quit { confirmed: false }).is_err() {
+                log::warn!("Event loop is gone; can't quit");
+            }
+            return;
+        }
+        Shortcut::Reload => IpcCommand::Refresh,
+        Shortcut::Back => IpcCommand::Back,
+        Shortcut::Forward => IpcCommand::Forward,
+        Shortcut::Home => IpcCommand::Home,
+        Shortcut::ZoomIn => IpcCommand::ZoomIn,
+        Shortcut::ZoomOut => IpcCommand::ZoomOut,
+        Shortcut::ResetZoom => IpcCommand::ResetZoom,
+        Shortcut::Devtools => IpcCommand::Devtools,
+        Shortcut::OpenFile => IpcCommand::OpenFile,
+        Shortcut::SavePage => IpcCommand::SavePage,
+        Shortcut::SavePdf => IpcCommand::SavePdf { path: None },
+    };
+    if let Err(e) = dispatch(&command, ctx) {
+        log::warn!("Shortcut {:?} failed: {}", shortcut, e);
+    }
+}
+
+/// Navigation state of the active tab.
+pub fn nav_state(ctx: &IpcContext) -> Option<NavState> {
+    let tabs = ctx.tabs.borrow();
+    let tab = tabs.active()?;
+    let nav = &tab.navigation;
+    let url = nav.current_url();
+    // Titles are kept in the shared history
+    let title = url.and_then(|url| {
+        let history = ctx.history.lock().ok()?;
+        let entry = history.entries().find(|e| &e.url == url)?;
+        entry.title.clone()
+    });
+    Some(NavState {
+        url: tab.load_error.clone().or_else(|| url.map(|u| u.to_string())),
+        title,
+        can_go_back: nav.can_go_back(),
+        can_go_forward: nav.can_go_forward(),
+        bookmarked: url.is_some_and(|url| {
+            ctx.bookmarks.lock().map(|b| b.is_bookmarked(url)).unwrap_or(false)
+        }),
+        // The error page stands in for an address that never connected
+        security: match tab.load_error {
+            Some(_) => Security::Other,
+            None => site_info::security(url, &tab.security),
+        },
+        blocking: url
+            .and_then(|url| url.host_str())
+            .filter(|_| !ctx.blocklist.is_empty())
+            .map(|host| ctx.settings.lock().map(|s| s.blocking.is_enabled_for(host)).unwrap_or(false)),
+        blocked: tab.blocked_requests,
+        blocked_popups: tab.blocked_popups.clone(),
+        loading: tab.loading.loading,
+        reader: tab.reader,
+        sidebar: ctx.sidebar_open(),
+        incognito: ctx.incognito,
+    })
+}
+
+/// Flips the bookmark on the active tab's page. Returns whether it's now
+/// bookmarked, or `None` if the tab has no page.
+fn toggle_bookmark(ctx: &IpcContext) -> Option<bool> {
+    let url = ctx.tabs.borrow().active()?.navigation.current_url()?.clone();
+    let mut bookmarks = ctx.bookmarks.lock().ok()?;
+    if bookmarks.remove(&url) {
+        return Some(false);
+    }
+    let title = ctx
+        .history
+        .lock()
+        .ok()
+        .and_then(|history| history.entries().find(|e| e.url == url)?.title.clone())
+        .unwrap_or_else(|| url.to_string());
+    bookmarks.add(url, title);
+    Some(true)
+}
+
+/// Records a committed page load in the tab's back/forward list and the
+/// shared history. Blank and `data:` pages are transient and never recorded.
+pub fn record_navigation(ctx: &IpcContext, tab_id: TabId, url: &str) {
+    if url.starts_with("about:") || url.starts_with("data:") {
+        return;
+    }
+    // The reader view shows the page that's already the current entry
+    if assets::is_reader_url(url) {
+        return;
+    }
+    if ctx.tabs.borrow().iter().any(|tab| tab.id == tab_id && tab.reader) {
+        ctx.close_reader(tab_id);
+    }
+    let parsed = match ctx.tabs.borrow_mut().get_mut(tab_id) {
+        // The error page stands in for the failed address, which isn't a visit
+        Some(tab) if assets::is_error_url(url) => {
+            tab.https_first.committed();
+            return;
+        }
+        Some(tab) => {
+            tab.https_first.committed();
+            tab.load_error = None;
+            tab.navigation.navigate(url.to_string())
+        }
+        None => return,
+    };
+    match parsed {
+        Ok(url) => {
+            if let Ok(mut history) = ctx.history.lock() {
+                history.visit(&url);
+            }
+        }
+        Err(e) => log::warn!("Not recording invalid URL {}: {}", url, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_parse_from_op_and_payload() {
+        assert_eq!(parse::<IpcCommand>(r#"{"op":"back"}"#), Some(IpcCommand::Back));
+        assert_eq!(
+            parse::<IpcCommand>(r#"{"op":"navigate","payload":{"url":"https://example.com"}}"#),
+            Some(IpcCommand::Navigate { url: "https://example.com".into() })
+        );
+        assert_eq!(parse::<IpcCommand>(r#"{"op":"close_tab","payload":{"id":3}}"#), Some(IpcCommand::CloseTab { id: 3 }));
+    }
+
+    #[test]
+    fn optional_payload_fields_default() {
+        assert_eq!(parse::<IpcCommand>(r#"{"op":"save_pdf","payload":{}}"#), Some(IpcCommand::SavePdf { path: None }));
+    }
+
+    #[test]
+    fn unknown_or_malformed_commands_are_rejected() {
+        assert_eq!(parse::<IpcCommand>(r#"{"op":"bakc"}"#), None);
+        assert_eq!(parse::<IpcCommand>(r#"{"op":"navigate","payload":{}}"#), None);
+        assert_eq!(parse::<IpcCommand>(r#"{"op":"close_tab","payload":{"id":"3"}}"#), None);
+        assert_eq!(parse::<IpcCommand>("not json"), None);
+    }
+
+    #[test]
+    fn requests_carry_an_optional_id() {
+        let request = parse::<IpcRequest>(r#"{"id":7,"op":"get_state"}"#).unwrap();
+        assert_eq!(request, IpcRequest { id: Some(7), command: IpcCommand::GetState });
+        let request = parse::<IpcRequest>(r#"{"op":"forward"}"#).unwrap();
+        assert_eq!(request, IpcRequest { id: None, command: IpcCommand::Forward });
+    }
+
+    #[test]
+    fn page_events_parse_like_commands() {
+        assert_eq!(
+            parse::<PageEvent>(r#"{"op":"committed","payload":{"url":"https://example.com/"}}"#),
+            Some(PageEvent::Committed { url: "https://example.com/".into() })
+        );
+        // Pages can't send the nav bar's commands
+        assert_eq!(parse::<PageEvent>(r#"{"op":"close_tab","payload":{"id":1}}"#), None);
+    }
+}
@@ -0,0 +1,47 @@
+//! AI API keys kept in the OS keychain rather than in the config file.
+
+use crate::ai::ProviderKind;
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Service name the keys are stored under.
+const SERVICE: &str = "syncflo";
+
+/// Keys looked up so far by provider, so the keychain is asked once a run.
+static KEYS: Lazy<Mutex<HashMap<&'static str, Option<String>>>> = Lazy::new(Default::default);
+
+/// The API key stored for `provider`, if any.
+pub fn api_key(provider: ProviderKind) -> Option<String> {
+    let mut keys = KEYS.lock().ok()?;
+    keys.entry(provider.name())
+        .or_insert_with(|| {
+            let entry = keyring::Entry::new(SERVICE, provider.name());
+            match entry.and_then(|entry| entry.get_password()) {
+                Ok(key) => Some(key),
+                Err(keyring::Error::NoEntry) => None,
+                Err(e) => {
+                    log::warn!("Failed to read the {} API key from the keychain: {}", provider.name(), e);
+                    None
+                }
+            }
+        })
+        .clone()
+}
+
+/// Stores `key` for `provider`, or deletes the stored one for `None`.
+pub fn set_api_key(provider: ProviderKind, key: Option<&str>) -> Result<()> {
+    let entry = keyring::Entry::new(SERVICE, provider.name()).context("Failed to open the keychain")?;
+    match key {
+        Some(key) => entry.set_password(key).context("Failed to store the API key in the keychain")?,
+        None => match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(e).context("Failed to delete the API key from the keychain"),
+        },
+    }
+    if let Ok(mut keys) = KEYS.lock() {
+        keys.insert(provider.name(), key.map(String::from));
+    }
+    Ok(())
+}
@@ -0,0 +1,139 @@
+//! Logging to stderr and to a rotating file in the profile, so there's a
+//! log to look at when the browser was started from Finder or a launcher.
+
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the current log file; older ones get `.1`, `.2`, … appended.
+const LOG_FILE: &str = "syncflo.log";
+/// Size at which the log file is rotated.
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+/// Log files kept, counting the current one.
+const KEPT_LOG_FILES: usize = 5;
+/// Environment variable with the log filter, used when `--log-level` isn't
+/// given.
+const LOG_ENV: &str = "SYNCFLO_LOG";
+/// Module paths of this crate start with its name, not with `browser::`.
+const CRATE: &str = env!("CARGO_CRATE_NAME");
+
+/// Starts logging with `level`, else `SYNCFLO_LOG`, else `RUST_LOG`, else
+/// info. Records also go to the rotating log in `dir`, if given and it can
+/// be opened.
+pub fn init(level: Option<&str>, dir: Option<&Path>) {
+    let spec = level
+        .map(String::from)
+        .or_else(|| std::env::var(LOG_ENV).ok())
+        .or_else(|| std::env::var("RUST_LOG").ok())
+        .unwrap_or_else(|| "info".to_string());
+    let mut builder = env_logger::Builder::new();
+    builder.parse_filters(&filters(&spec));
+    let file = dir.map(RotatingFile::open).transpose();
+    let file_error = match file {
+        Ok(Some(file)) => {
+            builder.target(env_logger::Target::Pipe(Box::new(file)));
+            None
+        }
+        Ok(None) => None,
+        Err(e) => Some(e),
+    };
+    builder.init();
+    if let Some(e) = file_error {
+        log::warn!("Logging to stderr only: {:#}", e);
+    }
+}
+
+/// `spec`, as in `--log-level`, as env_logger filters. A bare level, like
+/// `debug`, applies to the browser only, and directives for its modules,
+/// like `browser::ipc=debug`, don't need the crate name. Everything else,
+/// like the webview and HTTP crates, stays at warnings unless named.
+fn filters(spec: &str) -> String {
+    let mut filters = vec!["warn".to_string()];
+    for directive in spec.split(',').map(str::trim).filter(|directive| !directive.is_empty()) {
+        let (target, level) = match directive.split_once('=') {
+            Some((target, level)) => (Some(target.trim()), level.trim()),
+            None if directive.parse::<log::LevelFilter>().is_ok() => (None, directive),
+            None => (Some(directive), ""),
+        };
+        let target = match target {
+            None => CRATE.to_string(),
+            Some(target) if ["browser", "ai", "ui"].iter().any(|module| is_module(target, module)) => {
+                format!("{}::{}", CRATE, target)
+            }
+            Some(target) => target.to_string(),
+        };
+        filters.push(if level.is_empty() { target } else { format!("{}={}", target, level) });
+    }
+    filters.join(",")
+}
+
+/// Whether `target` is `module` or inside it.
+fn is_module(target: &str, module: &str) -> bool {
+    target.strip_prefix(module).is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// Logs a panic with its backtrace, for the panic hook.
+pub fn log_panic(info: &std::panic::PanicHookInfo<'_>) {
+    let backtrace = std::backtrace::Backtrace::force_capture();
+    log::error!("Panic: {}\n{}", info, backtrace);
+}
+
+/// The log in a directory, moved aside to `.1` once it reaches
+/// [`MAX_LOG_BYTES`]. Everything written is copied to stderr.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    len: u64,
+}
+
+impl RotatingFile {
+    fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(LOG_FILE);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let len = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        Ok(Self { path, file, len })
+    }
+
+    /// Shifts `syncflo.log.N` to `.N+1`, dropping the oldest, and starts a
+    /// new `syncflo.log`.
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |n: usize| {
+            let mut path = self.path.clone().into_os_string();
+            path.push(format!(".{}", n));
+            PathBuf::from(path)
+        };
+        let _ = fs::remove_file(rotated(KEPT_LOG_FILES - 1));
+        for n in (1..KEPT_LOG_FILES - 1).rev() {
+            let _ = fs::rename(rotated(n), rotated(n + 1));
+        }
+        fs::rename(&self.path, rotated(1))?;
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = io::stderr().write_all(buf);
+        if self.len > 0 && self.len + buf.len() as u64 > MAX_LOG_BYTES {
+            // Keep writing to the full file rather than losing records
+            if let Err(e) = self.rotate() {
+                let _ = writeln!(io::stderr(), "Failed to rotate {}: {}", self.path.display(), e);
+            }
+        }
+        self.file.write_all(buf)?;
+        self.len += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
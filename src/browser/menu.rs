@@ -0,0 +1,191 @@
+//! The macOS application menu. Items run the same IPC commands as the nav
+//! bar, so choosing one behaves exactly like clicking the button.
+//!
+//! Key equivalents that pages report through the shortcut script are
+//! handled there first; the menu only sees the ones the page let through.
+
+use crate::browser::ipc::{self, IpcCommand, IpcContext};
+use crate::browser::shortcuts::Shortcut;
+use anyhow::Result;
+use muda::accelerator::{Accelerator, Code, Modifiers, CMD_OR_CTRL};
+use muda::{AboutMetadata, Menu, MenuItem, PredefinedMenuItem, Submenu};
+
+/// Recent pages listed in the History menu.
+const RECENT_COUNT: usize = 10;
+/// Longer titles are cut off in the History menu.
+const RECENT_TITLE_CHARS: usize = 60;
+/// Prefix of History menu item ids; the rest is the page URL.
+const RECENT_PREFIX: &str = "history:";
+
+pub struct AppMenu {
+    // Dropping the menu would remove it from the app
+    _menu: Menu,
+    history: Submenu,
+    /// Items in the History menu before the recent pages.
+    fixed_history_items: usize,
+}
+
+impl AppMenu {
+    /// Builds the menu bar and installs it. Selections arrive as
+    /// `AppEvent::Menu`.
+    pub fn install() -> Result<Self> {
+        let item = |id: &str, text: &str, modifiers: Modifiers, key: Code| {
+            MenuItem::with_id(id, text, true, Some(Accelerator::new(Some(modifiers), key)))
+        };
+
+        let app = Submenu::with_items(
+            "SyncFlo",
+            true,
+            &[
+                &PredefinedMenuItem::about(
+                    Some("SyncFlo 정보"),
+                    Some(AboutMetadata {
+                        name: Some("SyncFlo Browser".into()),
+                        version: Some(env!("CARGO_PKG_VERSION").into()),
+                        ..Default::default()
+                    }),
+                ),
+                &PredefinedMenuItem::separator(),
+                &PredefinedMenuItem::services(Some("서비스")),
+                &PredefinedMenuItem::separator(),
+                &PredefinedMenuItem::hide(Some("SyncFlo 가리기")),
+                &PredefinedMenuItem::hide_others(Some("기타 가리기")),
+                &PredefinedMenuItem::show_all(Some("모두 보기")),
+            ],
+        )?;
+        let file = Submenu::with_items(
+            "파일",
+            true,
+            &[
+                &item("new_tab", "새 탭", CMD_OR_CTRL, Code::KeyT),
+                &item("new_incognito_window", "새 시크릿 창", CMD_OR_CTRL | Modifiers::SHIFT, Code::KeyN),
+                &item("close_tab", "탭 닫기", CMD_OR_CTRL, Code::KeyW),
+                &PredefinedMenuItem::separator(),
+                &item("open_file", "파일 열기…", CMD_OR_CTRL, Code::KeyO),
+                &item("save_page", "페이지 저장…", CMD_OR_CTRL, Code::KeyS),
+                &item("save_pdf", "PDF로 저장…", CMD_OR_CTRL, Code::KeyP),
+                &MenuItem::with_id("save_markdown", "Markdown으로 저장…", true, None),
+                &PredefinedMenuItem::separator(),
+                &item("quit", "SyncFlo 종료", CMD_OR_CTRL, Code::KeyQ),
+            ],
+        )?;
+        // Predefined items go through the responder chain, so they act on
+        // whichever webview has focus
+        let edit = Submenu::with_items(
+            "편집",
+            true,
+            &[
+                &PredefinedMenuItem::undo(Some("실행 취소")),
+                &PredefinedMenuItem::redo(Some("실행 복귀")),
+                &PredefinedMenuItem::separator(),
+                &PredefinedMenuItem::cut(Some("잘라내기")),
+                &PredefinedMenuItem::copy(Some("복사하기")),
+                &PredefinedMenuItem::paste(Some("붙여넣기")),
+                &PredefinedMenuItem::select_all(Some("전체 선택")),
+                &PredefinedMenuItem::separator(),
+                &item("find", "찾기…", CMD_OR_CTRL, Code::KeyF),
+            ],
+        )?;
+        let view = Submenu::with_items(
+            "보기",
+            true,
+            &[
+                &item("reload", "새로고침", CMD_OR_CTRL, Code::KeyR),
+                &MenuItem::with_id("reader", "읽기 모드", true, None),
+                &MenuItem::with_id("read_later", "나중에 읽기", true, None),
+                &PredefinedMenuItem::separator(),
+                &item("zoom_in", "확대", CMD_OR_CTRL, Code::Equal),
+                &item("zoom_out", "축소", CMD_OR_CTRL, Code::Minus),
+                &item("reset_zoom", "실제 크기", CMD_OR_CTRL, Code::Digit0),
+                &PredefinedMenuItem::separator(),
+                &item("fullscreen", "전체 화면", CMD_OR_CTRL | Modifiers::CONTROL, Code::KeyF),
+                &PredefinedMenuItem::separator(),
+                &item("devtools", "개발자 도구", CMD_OR_CTRL | Modifiers::ALT, Code::KeyI),
+            ],
+        )?;
+        let history = Submenu::with_items(
+            "기록",
+            true,
+            &[
+                &item("back", "뒤로", CMD_OR_CTRL, Code::BracketLeft),
+                &item("forward", "앞으로", CMD_OR_CTRL, Code::BracketRight),
+                &item("home", "홈", CMD_OR_CTRL | Modifiers::SHIFT, Code::KeyH),
+                &PredefinedMenuItem::separator(),
+            ],
+        )?;
+        let fixed_history_items = history.items().len();
+
+        let menu = Menu::with_items(&[&app, &file, &edit, &view, &history])?;
+        menu.init_for_nsapp();
+        Ok(Self {
+            _menu: menu,
+            history,
+            fixed_history_items,
+        })
+    }
+
+    /// Lists the most recently visited pages at the end of the History menu.
+    pub fn update_recent(&self, ctx: &IpcContext) {
+        while self.history.items().len() > self.fixed_history_items {
+            self.history.remove_at(self.fixed_history_items);
+        }
+        let Ok(history) = ctx.history.lock() else {
+            return;
+        };
+        let recent = history
+            .entries()
+            .filter(|entry| matches!(entry.url.scheme(), "http" | "https"))
+            .take(RECENT_COUNT);
+        for entry in recent {
+            let title = entry.title.as_deref().filter(|t| !t.is_empty()).unwrap_or(entry.url.as_str());
+            let mut text: String = title.chars().take(RECENT_TITLE_CHARS).collect();
+            if text.len() < title.len() {
+                text.push('…');
+            }
+            let id = format!("{}{}", RECENT_PREFIX, entry.url);
+            if let Err(e) = self.history.append(&MenuItem::with_id(id, text, true, None)) {
+                log::warn!("Failed to update History menu: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// Runs the menu item with id `id`.
+pub fn handle(ctx: &IpcContext, id: &str) {
+    let command = match id {
+        "new_tab" => IpcCommand::NewTab,
+        "close_tab" => match ctx.tabs.borrow().active() {
+            Some(tab) => IpcCommand::CloseTab { id: tab.id },
+            None => return,
+        },
+        "reload" => IpcCommand::Refresh,
+        "reader" => IpcCommand::ToggleReader,
+        "read_later" => IpcCommand::ReadLater,
+        "open_file" => IpcCommand::OpenFile,
+        "save_page" => IpcCommand::SavePage,
+        "save_pdf" => IpcCommand::SavePdf { path: None },
+        "save_markdown" => IpcCommand::SaveMarkdown,
+        "back" => IpcCommand::Back,
+        "forward" => IpcCommand::Forward,
+        "home" => IpcCommand::Home,
+        "zoom_in" => IpcCommand::ZoomIn,
+        "zoom_out" => IpcCommand::ZoomOut,
+        "reset_zoom" => IpcCommand::ResetZoom,
+        "devtools" => IpcCommand::Devtools,
+        "fullscreen" => IpcCommand::ToggleFullscreen,
+        "new_incognito_window" => return ipc::run_shortcut(ctx, Shortcut::NewIncognitoWindow),
+        "find" => return ipc::run_shortcut(ctx, Shortcut::Find),
+        "quit" => return ipc::run_shortcut(ctx, Shortcut::Quit),
+        _ => match id.strip_prefix(RECENT_PREFIX) {
+            Some(url) => IpcCommand::Navigate { url: url.to_string() },
+            None => {
+                log::warn!("Unknown menu item {}", id);
+                return;
+            }
+        },
+    };
+    if let Err(e) = ipc::dispatch(&command, ctx) {
+        log::warn!("Menu item {} failed: {}", id, e);
+    }
+}
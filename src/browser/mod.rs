@@ -1,5 +1,43 @@
+pub mod app_mode;
+pub mod archive;
+pub mod assets;
+pub mod assistant;
+pub mod blocker;
+pub mod bookmarks;
+pub mod context_menu;
+pub mod default_browser;
+pub mod dialogs;
+pub mod downloads;
 mod engine;
+pub mod external;
+pub mod favicon;
+pub mod https_first;
+pub mod input;
+pub mod ipc;
+pub mod keychain;
+pub mod logging;
+#[cfg(target_os = "macos")]
+pub mod menu;
 mod navigation;
+pub mod notifications;
+mod pages;
+pub mod permissions;
+pub mod privacy;
+pub mod profile;
+pub mod proxy;
+pub mod reading_list;
+pub mod recovery;
+pub mod search;
+pub mod session;
+pub mod settings;
+pub mod shortcuts;
+pub mod single_instance;
+pub mod site_info;
+pub mod suggest;
+pub mod tabs;
+pub mod user_agent;
+pub mod window_state;
+pub mod worker;
 
-pub use engine::Browser;
+pub use engine::{Browser, LaunchOptions};
 pub use navigation::Navigation;
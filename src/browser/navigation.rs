@@ -1,54 +1,528 @@
+use crate::ai::Embedding;
+use crate::browser::worker::FileWriter;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 use url::Url;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Default)]
+/// Maximum number of entries kept in the persisted history.
+pub const MAX_HISTORY_ENTRIES: usize = 10_000;
+
+/// Age at which a visit counts half as much toward a page's frecency.
+const FRECENCY_HALF_LIFE_DAYS: f64 = 14.0;
+
+/// A visited page. Each URL appears once; revisits bump `visit_count`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub url: Url,
+    pub title: Option<String>,
+    pub visited_at: SystemTime,
+    pub visit_count: u32,
+    /// Terms characterizing the page's content, so search finds it by
+    /// what it was about.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+    /// The address the page gives as its canonical one, when it differs,
+    /// e.g. without tracking parameters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canonical_url: Option<Url>,
+    /// What the page's text means as a vector, so search finds it by
+    /// meaning. Only with semantic history on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Embedding>,
+}
+
+impl HistoryEntry {
+    fn new(url: Url) -> Self {
+        Self {
+            url,
+            title: None,
+            visited_at: SystemTime::now(),
+            visit_count: 1,
+            keywords: Vec::new(),
+            canonical_url: None,
+            embedding: None,
+        }
+    }
+
+    /// Visit count decayed by age, for ranking suggestions.
+    pub fn frecency(&self) -> f64 {
+        frecency(self, SystemTime::now())
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
 pub struct Navigation {
-    history: VecDeque<String>,
+    /// Visited pages, least recently visited first.
+    history: VecDeque<HistoryEntry>,
+    /// Back/forward list.
+    stack: VecDeque<Url>,
     current_index: usize,
+    /// Writes the history to its file, if it was loaded from one.
+    #[serde(skip)]
+    store: Option<FileWriter>,
 }
 
 impl Navigation {
     pub fn new() -> Self {
         Self {
             history: VecDeque::new(),
+            stack: VecDeque::new(),
             current_index: 0,
+            store: None,
+        }
+    }
+
+    /// Loads history from `path` and autosaves back to it on every change.
+    /// A missing or corrupt file yields an empty history instead of an error.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let mut nav = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str::<Navigation>(&text).unwrap_or_else(|e| {
+                log::warn!("Ignoring corrupt history file {}: {}", path.display(), e);
+                Navigation::new()
+            }),
+            Err(_) => Navigation::new(),
+        };
+        if nav.current_index >= nav.stack.len() {
+            nav.current_index = nav.stack.len().saturating_sub(1);
+        }
+        nav.store = Some(FileWriter::new(path));
+        nav
+    }
+
+    /// Writes the history to its file now, if it has one.
+    pub fn save(&self) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let json = serde_json::to_vec(self).context("Failed to serialize history")?;
+        store.write(&json)
+    }
+
+    /// Writes the history to its file in the background, if it has one.
+    fn autosave(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        match serde_json::to_vec(self) {
+            Ok(json) => store.queue(json),
+            Err(e) => log::warn!("Failed to serialize history: {}", e),
         }
     }
 
     pub fn navigate(&mut self, url: String) -> Result<Url, url::ParseError> {
         let parsed = Url::parse(&url)?;
-        self.history.push_back(url);
-        self.current_index = self.history.len() - 1;
+        self.record_visit(&parsed);
+        // Reloading or redirecting to the current page doesn't add a stack entry
+        if self.stack.get(self.current_index) != Some(&parsed) {
+            // Drop any forward entries, like a regular browser history does
+            if !self.stack.is_empty() {
+                self.stack.truncate(self.current_index + 1);
+            }
+            self.stack.push_back(parsed.clone());
+            while self.stack.len() > MAX_HISTORY_ENTRIES {
+                self.stack.pop_front();
+            }
+            self.current_index = self.stack.len() - 1;
+        }
+        self.autosave();
         Ok(parsed)
     }
 
+    /// Records a visit in the history without touching the back/forward list.
+    pub fn visit(&mut self, url: &Url) {
+        self.record_visit(url);
+        self.autosave();
+    }
+
+    fn record_visit(&mut self, url: &Url) {
+        let entry = match self.history.iter().position(|e| &e.url == url) {
+            Some(pos) => {
+                let mut entry = self.history.remove(pos).expect("position is in bounds");
+                entry.visit_count = entry.visit_count.saturating_add(1);
+                entry.visited_at = SystemTime::now();
+                entry
+            }
+            None => HistoryEntry::new(url.clone()),
+        };
+        self.history.push_back(entry);
+        while self.history.len() > MAX_HISTORY_ENTRIES {
+            self.history.pop_front();
+        }
+    }
+
+    /// Deletes `url` from the history. The back/forward list is left alone.
+    pub fn remove_entry(&mut self, url: &Url) -> bool {
+        let before = self.history.len();
+        self.history.retain(|e| &e.url != url);
+        let removed = self.history.len() != before;
+        if removed {
+            self.autosave();
+        }
+        removed
+    }
+
+    /// Deletes history entries visited within the last `since`. A range
+    /// reaching past the Unix epoch (e.g. `Duration::MAX`) clears everything.
+    /// The back/forward list is left alone.
+    pub fn clear_range(&mut self, since: Duration) -> usize {
+        let before = self.history.len();
+        match SystemTime::now().checked_sub(since) {
+            Some(cutoff) if cutoff > UNIX_EPOCH => self.history.retain(|e| e.visited_at < cutoff),
+            _ => self.history.clear(),
+        }
+        let removed = before - self.history.len();
+        if removed > 0 {
+            self.autosave();
+        }
+        removed
+    }
+
+    /// Sets the title of the current page once the webview reports it.
+    pub fn set_current_title(&mut self, title: impl Into<String>) {
+        if let Some(url) = self.stack.get(self.current_index).cloned() {
+            self.set_title(&url, title);
+        }
+    }
+
+    /// Sets the title of the latest history entry for `url`.
+    pub fn set_title(&mut self, url: &Url, title: impl Into<String>) {
+        let title = title.into();
+        if let Some(entry) = self.history.iter_mut().rev().find(|e| &e.url == url) {
+            if entry.title.as_deref() != Some(title.as_str()) {
+                entry.title = Some(title);
+                self.autosave();
+            }
+        }
+    }
+
+    /// Sets the content keywords of the latest history entry for `url`.
+    pub fn set_keywords(&mut self, url: &Url, keywords: Vec<String>) {
+        if let Some(entry) = self.history.iter_mut().rev().find(|e| &e.url == url) {
+            if entry.keywords != keywords {
+                entry.keywords = keywords;
+                self.autosave();
+            }
+        }
+    }
+
+    /// Sets the embedding of the latest history entry for `url`.
+    pub fn set_embedding(&mut self, url: &Url, embedding: Embedding) {
+        if let Some(entry) = self.history.iter_mut().rev().find(|e| &e.url == url) {
+            entry.embedding = Some(embedding);
+            self.autosave();
+        }
+    }
+
+    /// Whether the latest history entry for `url` has an embedding yet.
+    pub fn has_embedding(&self, url: &Url) -> bool {
+        self.history.iter().rev().find(|e| &e.url == url).is_some_and(|e| e.embedding.is_some())
+    }
+
+    /// Sets the canonical URL of the latest history entry for `url`.
+    /// Only a URL on the same host is taken, so a page can't pass itself
+    /// off as another site.
+    pub fn set_canonical_url(&mut self, url: &Url, canonical: Option<Url>) {
+        let canonical = canonical.filter(|canonical| canonical != url && canonical.host() == url.host());
+        if let Some(entry) = self.history.iter_mut().rev().find(|e| &e.url == url) {
+            if entry.canonical_url != canonical {
+                entry.canonical_url = canonical;
+                self.autosave();
+            }
+        }
+    }
+
     pub fn can_go_back(&self) -> bool {
         self.current_index > 0
     }
 
     pub fn can_go_forward(&self) -> bool {
-        self.current_index < self.history.len() - 1
+        self.current_index + 1 < self.stack.len()
     }
 
-    pub fn go_back(&mut self) -> Option<String> {
+    pub fn go_back(&mut self) -> Option<Url> {
         if self.can_go_back() {
             self.current_index -= 1;
-            self.history.get(self.current_index).cloned()
+            self.autosave();
+            self.stack.get(self.current_index).cloned()
         } else {
             None
         }
     }
 
-    pub fn go_forward(&mut self) -> Option<String> {
+    pub fn go_forward(&mut self) -> Option<Url> {
         if self.can_go_forward() {
             self.current_index += 1;
-            self.history.get(self.current_index).cloned()
+            self.autosave();
+            self.stack.get(self.current_index).cloned()
         } else {
             None
         }
     }
 
-    pub fn current_url(&self) -> Option<&String> {
-        self.history.get(self.current_index)
+    pub fn current_url(&self) -> Option<&Url> {
+        self.stack.get(self.current_index)
+    }
+
+    /// History entry of the current page, if it's still in the history.
+    pub fn current_entry(&self) -> Option<&HistoryEntry> {
+        let url = self.current_url()?;
+        self.history.iter().rev().find(|e| &e.url == url)
+    }
+
+    /// Searches history by URL, title and content for address-bar suggestions.
+    ///
+    /// Matching is case-insensitive and ignores the scheme and a leading `www.`.
+    /// Entries whose host starts with the query rank first, then entries with a
+    /// matching host label or title word, then plain substring matches, then
+    /// pages whose content keywords match; ties are broken by visit count and
+    /// recency.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<&HistoryEntry> {
+        let query = normalize_query(query);
+        if query.is_empty() || limit == 0 {
+            return Vec::new();
+        }
+
+        let mut matches: Vec<(u8, &HistoryEntry)> = self
+            .history
+            .iter()
+            .filter_map(|entry| match_rank(entry, &query).map(|rank| (rank, entry)))
+            .collect();
+        matches.sort_by(|(ra, a), (rb, b)| {
+            ra.cmp(rb)
+                .then(b.visit_count.cmp(&a.visit_count))
+                .then(b.visited_at.cmp(&a.visited_at))
+        });
+        matches.into_iter().take(limit).map(|(_, entry)| entry).collect()
+    }
+
+    /// The `k` entries whose pages mean the most like `query`, the
+    /// embedding of what's searched for, most alike first, with their
+    /// cosine similarity. Every embedded entry is compared, which is fast
+    /// enough for a history of tens of thousands of pages.
+    pub fn semantic_search(&self, query: &Embedding, k: usize) -> Vec<(&HistoryEntry, f32)> {
+        let mut matches: Vec<(&HistoryEntry, f32)> = self
+            .history
+            .iter()
+            .filter_map(|entry| Some((entry, entry.embedding.as_ref()?.similarity(query))))
+            .collect();
+        matches.sort_by(|(a, a_score), (b, b_score)| {
+            b_score.total_cmp(a_score).then(b.visited_at.cmp(&a.visited_at))
+        });
+        matches.truncate(k);
+        matches
+    }
+
+    /// The `n` most frecent web pages (visit count decayed by age) for the
+    /// home page tiles. Internal, `file://` and other non-web URLs are skipped.
+    /// Entries with the same canonical URL count as one page, shown by the
+    /// most frecent of them.
+    pub fn top_sites(&self, n: usize) -> Vec<&HistoryEntry> {
+        let now = SystemTime::now();
+        let mut pages: HashMap<&Url, (f64, f64, &HistoryEntry)> = HashMap::new();
+        for entry in self.history.iter().filter(|entry| matches!(entry.url.scheme(), "http" | "https")) {
+            let score = frecency(entry, now);
+            let page = pages
+                .entry(entry.canonical_url.as_ref().unwrap_or(&entry.url))
+                .or_insert((0.0, score, entry));
+            page.0 += score;
+            if score > page.1 {
+                (page.1, page.2) = (score, entry);
+            }
+        }
+        let mut scored: Vec<(f64, &HistoryEntry)> = pages.into_values().map(|(score, _, entry)| (score, entry)).collect();
+        scored.sort_by(|(a, a_entry), (b, b_entry)| b.total_cmp(a).then_with(|| a_entry.url.cmp(&b_entry.url)));
+        scored.into_iter().take(n).map(|(_, entry)| entry).collect()
+    }
+
+    /// All history entries, most recently visited first.
+    pub fn entries(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.history.iter().rev()
+    }
+
+    /// Writes the history as a JSON array, most recent first, one entry at a
+    /// time: `[{"url", "title", "visit_count", "last_visit_time"}]`, with
+    /// `last_visit_time` in milliseconds since the Unix epoch.
+    pub fn export_json(&self, mut writer: impl Write) -> Result<()> {
+        #[derive(Serialize)]
+        struct ExportedEntry<'a> {
+            url: &'a str,
+            title: Option<&'a str>,
+            visit_count: u32,
+            last_visit_time: u128,
+        }
+
+        writer.write_all(b"[")?;
+        for (i, entry) in self.entries().enumerate() {
+            if i > 0 {
+                writer.write_all(b",")?;
+            }
+            writer.write_all(b"\n  ")?;
+            let exported = ExportedEntry {
+                url: entry.url.as_str(),
+                title: entry.title.as_deref(),
+                visit_count: entry.visit_count,
+                last_visit_time: entry
+                    .visited_at
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis())
+                    .unwrap_or(0),
+            };
+            serde_json::to_writer(&mut writer, &exported).context("Failed to write history entry")?;
+        }
+        writer.write_all(b"\n]\n")?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn frecency(entry: &HistoryEntry, now: SystemTime) -> f64 {
+    let age_days = now
+        .duration_since(entry.visited_at)
+        .map(|d| d.as_secs_f64() / 86_400.0)
+        .unwrap_or(0.0);
+    entry.visit_count as f64 * 0.5f64.powf(age_days / FRECENCY_HALF_LIFE_DAYS)
+}
+
+fn normalize_query(query: &str) -> String {
+    let query = query.trim().to_lowercase();
+    let query = query
+        .strip_prefix("https://")
+        .or_else(|| query.strip_prefix("http://"))
+        .unwrap_or(&query);
+    query.strip_prefix("www.").unwrap_or(query).to_string()
+}
+
+/// Lower rank is a better match; `None` means no match.
+fn match_rank(entry: &HistoryEntry, query: &str) -> Option<u8> {
+    let url = entry.url.as_str();
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let address = without_scheme.strip_prefix("www.").unwrap_or(without_scheme);
+    if starts_with_ignore_case(address, query) {
+        return Some(0);
+    }
+
+    let host = entry.url.host_str().unwrap_or("");
+    let title = entry.title.as_deref().unwrap_or("");
+    let word_prefix = host.split('.').any(|label| starts_with_ignore_case(label, query))
+        || title
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| starts_with_ignore_case(word, query));
+    if word_prefix {
+        return Some(1);
+    }
+
+    if contains_ignore_case(address, query) || contains_ignore_case(title, query) {
+        return Some(2);
+    }
+
+    // Every query word starts a keyword word, e.g. "borrow lifetimes"
+    let keyword_match = !entry.keywords.is_empty()
+        && query.split_whitespace().all(|word| {
+            entry
+                .keywords
+                .iter()
+                .flat_map(|keyword| keyword.split(' '))
+                .any(|keyword| keyword.starts_with(word))
+        });
+    if keyword_match {
+        return Some(3);
+    }
+    None
+}
+
+/// `needle` must already be lowercase.
+fn starts_with_ignore_case(haystack: &str, needle: &str) -> bool {
+    if needle.is_ascii() {
+        haystack.len() >= needle.len()
+            && haystack.as_bytes()[..needle.len()].eq_ignore_ascii_case(needle.as_bytes())
+    } else {
+        haystack.to_lowercase().starts_with(needle)
+    }
+}
+
+/// `needle` must already be lowercase.
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    if needle.is_ascii() {
+        haystack
+            .as_bytes()
+            .windows(needle.len())
+            .any(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+    } else {
+        haystack.to_lowercase().contains(needle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    /// A navigation through `urls`, in order.
+    fn visited(urls: &[&str]) -> Navigation {
+        let mut nav = Navigation::new();
+        for u in urls {
+            nav.navigate(u.to_string()).unwrap();
+        }
+        nav
+    }
+
+    #[test]
+    fn navigating_after_going_back_drops_forward_entries() {
+        let mut nav = visited(&["https://a.example/", "https://b.example/", "https://c.example/"]);
+        assert_eq!(nav.go_back(), Some(url("https://b.example/")));
+
+        nav.navigate("https://d.example/".into()).unwrap();
+
+        assert!(!nav.can_go_forward());
+        assert_eq!(nav.go_forward(), None);
+        assert_eq!(nav.current_url(), Some(&url("https://d.example/")));
+        assert_eq!(nav.go_back(), Some(url("https://b.example/")));
+        assert_eq!(nav.go_back(), Some(url("https://a.example/")));
+        assert_eq!(nav.go_back(), None);
+    }
+
+    #[test]
+    fn navigating_from_the_first_entry_keeps_only_it() {
+        let mut nav = visited(&["https://a.example/", "https://b.example/", "https://c.example/"]);
+        nav.go_back();
+        nav.go_back();
+        assert!(!nav.can_go_back());
+
+        nav.navigate("https://d.example/".into()).unwrap();
+
+        assert_eq!(nav.stack, [url("https://a.example/"), url("https://d.example/")]);
+        assert_eq!(nav.current_index, 1);
+        assert!(!nav.can_go_forward());
+    }
+
+    #[test]
+    fn an_empty_history_has_nowhere_to_go() {
+        let mut nav = Navigation::new();
+        assert_eq!(nav.current_url(), None);
+        assert!(!nav.can_go_back());
+        assert!(!nav.can_go_forward());
+        assert_eq!(nav.go_back(), None);
+        assert_eq!(nav.go_forward(), None);
+
+        nav.navigate("https://a.example/".into()).unwrap();
+        assert_eq!(nav.current_index, 0);
+        assert_eq!(nav.current_url(), Some(&url("https://a.example/")));
+    }
+
+    #[test]
+    fn reloading_the_current_page_adds_no_entry() {
+        let nav = visited(&["https://a.example/", "https://a.example/"]);
+        assert_eq!(nav.stack.len(), 1);
+        assert!(!nav.can_go_back());
+        assert_eq!(nav.current_entry().map(|entry| entry.visit_count), Some(2));
     }
 }
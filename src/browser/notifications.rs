@@ -0,0 +1,169 @@
+//! Notifications of web pages, shown by the system. WebKitGTK reports the
+//! ones pages show; elsewhere [`SCRIPT`] stands in for the page's
+//! `Notification`, which WKWebView doesn't have at all.
+
+use crate::browser::favicon;
+use crate::browser::ipc::AppEvent;
+use crate::browser::proxy::ProxySettings;
+use crate::browser::tabs::TabId;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tao::event_loop::EventLoopProxy;
+use url::Url;
+
+/// `Notification` for pages on other platforms than Linux, passing what
+/// they show and ask for to the browser. The browser answers permission
+/// requests by calling `__syncfloNotificationPermission`.
+#[cfg(not(target_os = "linux"))]
+pub const SCRIPT: &str = r#"
+(function() {
+  if (window.top !== window || !/^https?:$/.test(location.protocol)) return;
+  var permission = 'default';
+  var waiting = [];
+  function post(op, payload) {
+    try { window.ipc.postMessage(JSON.stringify({ op: op, payload: payload })); } catch (e) {}
+  }
+  function Notification(title, options) {
+    if (!(this instanceof Notification)) throw new TypeError("Failed to construct 'Notification': Please use the 'new' operator.");
+    options = options || {};
+    this.title = String(title);
+    this.body = options.body ? String(options.body) : '';
+    this.tag = options.tag ? String(options.tag) : '';
+    this.icon = '';
+    try { if (options.icon) this.icon = new URL(options.icon, location.href).href; } catch (e) {}
+    this.onclick = this.onshow = this.onclose = this.onerror = null;
+    if (permission === 'granted') post('show_notification', { title: this.title, body: this.body, icon: this.icon || null });
+  }
+  Notification.prototype.close = function() {};
+  Notification.prototype.addEventListener = function() {};
+  Notification.prototype.removeEventListener = function() {};
+  Object.defineProperty(Notification, 'permission', { get: function() { return permission; } });
+  Notification.requestPermission = function(callback) {
+    return new Promise(function(resolve) {
+      waiting.push(function(result) {
+        if (typeof callback === 'function') callback(result);
+        resolve(result);
+      });
+      post('request_notification_permission');
+    });
+  };
+  Object.defineProperty(window, '__syncfloNotificationPermission', {
+    value: function(result) {
+      permission = result;
+      var done = waiting;
+      waiting = [];
+      done.forEach(function(answer) { answer(result); });
+    }
+  });
+  window.Notification = Notification;
+  post('notification_permission');
+})();
+"#;
+
+/// A notification a page showed.
+#[derive(Debug, Clone)]
+pub struct WebNotification {
+    /// The tab of the page, brought to the front when it's clicked.
+    pub tab: TabId,
+    /// The page, opened again if its tab was closed by then.
+    pub url: String,
+    /// Host of the page, shown so sites can't pass for others.
+    pub site: String,
+    pub title: String,
+    pub body: String,
+}
+
+/// The icon at `icon_url`, fetched through `proxy` into `dir` unless it's
+/// there already. Blocks, so call it off the main thread.
+pub fn icon(icon_url: &Url, dir: &Path, proxy: &ProxySettings) -> Result<PathBuf> {
+    let key: String = Sha256::digest(icon_url.as_str().as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+    let path = dir.join(key);
+    if path.is_file() {
+        return Ok(path);
+    }
+    let bytes = favicon::download(icon_url, proxy)?;
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    fs::write(&path, bytes).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Shows `notification` with the system, with `icon` if there is one, and
+/// sends [`AppEvent::NotificationClicked`] if it's clicked. Blocks until
+/// the notification is gone where the system says it was clicked, so call
+/// it off the main thread.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn show(notification: &WebNotification, icon: Option<&Path>, proxy: &EventLoopProxy<AppEvent>) -> Result<()> {
+    let mut shown = notify_rust::Notification::new();
+    shown
+        .appname("SyncFlo")
+        .summary(&notification.title)
+        .body(&format!("{}\n{}", notification.site, notification.body))
+        .action("default", "열기");
+    if let Some(icon) = icon {
+        shown.icon(&icon.to_string_lossy());
+    }
+    let handle = shown.show().context("Failed to show a notification")?;
+    handle.wait_for_action(|action| {
+        if action == "default" {
+            clicked(notification, proxy);
+        }
+    });
+    Ok(())
+}
+
+/// Shows `notification` with the system, with `icon` if there is one, and
+/// sends [`AppEvent::NotificationClicked`] if it's clicked. Blocks until
+/// the notification is gone, so call it off the main thread.
+#[cfg(target_os = "macos")]
+pub fn show(notification: &WebNotification, icon: Option<&Path>, proxy: &EventLoopProxy<AppEvent>) -> Result<()> {
+    use mac_notification_sys::NotificationResponse;
+    use std::sync::Once;
+
+    static APPLICATION: Once = Once::new();
+    APPLICATION.call_once(|| {
+        if let Err(e) = mac_notification_sys::set_application("com.syncflo.browser") {
+            log::warn!("Failed to set the notifications' application: {}", e);
+        }
+    });
+    let icon = icon.map(|icon| icon.to_string_lossy().into_owned());
+    let mut shown = mac_notification_sys::Notification::new();
+    shown
+        .title(&notification.title)
+        .subtitle(&notification.site)
+        .message(&notification.body)
+        .wait_for_click(true);
+    if let Some(icon) = &icon {
+        shown.content_image(icon);
+    }
+    let response = shown.send().context("Failed to show a notification")?;
+    if matches!(response, NotificationResponse::Click) {
+        clicked(notification, proxy);
+    }
+    Ok(())
+}
+
+/// Shows `notification` with the system, with `icon` if there is one.
+/// Windows doesn't say when it's clicked.
+#[cfg(windows)]
+pub fn show(notification: &WebNotification, icon: Option<&Path>, _proxy: &EventLoopProxy<AppEvent>) -> Result<()> {
+    let mut shown = notify_rust::Notification::new();
+    shown
+        .appname("SyncFlo")
+        .summary(&notification.title)
+        .subtitle(&notification.site)
+        .body(&notification.body);
+    if let Some(icon) = icon {
+        shown.image_path(&icon.to_string_lossy());
+    }
+    shown.show().context("Failed to show a notification")?;
+    Ok(())
+}
+
+fn clicked(notification: &WebNotification, proxy: &EventLoopProxy<AppEvent>) {
+    let event = AppEvent::NotificationClicked { tab: notification.tab, url: notification.url.clone() };
+    if proxy.send_event(event).is_err() {
+        log::warn!("Event loop is gone; can't open a clicked notification");
+    }
+}
@@ -0,0 +1,1153 @@
+//! HTML for the `syncflo://` internal pages.
+
+use crate::ai::{Article, TextStats, UsageLog, UsageTotals};
+use crate::browser::bookmarks::BookmarkStore;
+use crate::browser::profile::Profile;
+use crate::browser::reading_list::ReadingList;
+use crate::browser::search::SearchEngine;
+use crate::browser::user_agent;
+use crate::browser::{assets, Navigation};
+use std::time::UNIX_EPOCH;
+
+/// History entries rendered on `syncflo://history`.
+const HISTORY_PAGE_LIMIT: usize = 1000;
+/// Shown in the reader view instead of the images of sites whose images
+/// are turned off.
+pub const IMAGE_PLACEHOLDER: &str = "data:image/svg+xml,%3Csvg xmlns='http://www.w3.org/2000/svg' width='320' height='180'%3E%3Crect width='100%25' height='100%25' fill='%23888' fill-opacity='.25'/%3E%3C/svg%3E";
+
+const PAGE_STYLE: &str = r#"
+    * { box-sizing: border-box; }
+    html, body { margin: 0; background: #121212; color: #e6e6e6; font-family: -apple-system, BlinkMacSystemFont, Segoe UI, Roboto, Helvetica, Arial, sans-serif; }
+    main { width: min(860px, 92vw); margin: 32px auto; }
+    h1 { font-size: 24px; font-weight: 700; color: #fafafa; }
+    h2 { font-size: 16px; color: #a7a7a7; margin-top: 28px; }
+    a { color: #6ea8fe; text-decoration: none; }
+    a:hover { text-decoration: underline; }
+    .row { display: flex; align-items: center; gap: 12px; padding: 8px 12px; border-bottom: 1px solid #2a2a2a; }
+    .row .main { flex: 1; min-width: 0; }
+    .row .title, .row .url { white-space: nowrap; overflow: hidden; text-overflow: ellipsis; }
+    .row .url, .muted { color: #a7a7a7; font-size: 12px; }
+    .row.done { opacity: 0.6; }
+    .btn { height: 28px; padding: 0 10px; border: none; border-radius: 6px; background: #2a2a2a; color: #e6e6e6; cursor: pointer; }
+    .btn:hover { background: #3a3a3a; }
+    .field { height: 28px; padding: 0 8px; border-radius: 6px; border: 1px solid #2a2a2a; background: #1b1b1b; color: #e6e6e6; }
+    .error { color: #ff8a80; font-size: 12px; }
+    table { border-collapse: collapse; }
+    td { padding: 6px 16px 6px 0; vertical-align: top; }
+"#;
+
+/// Escapes text for use in HTML element content and quoted attributes.
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn page(title: &str, body: &str, script: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="ko">
+<head>
+  <meta charset="utf-8" />
+  <meta name="viewport" content="width=device-width, initial-scale=1" />
+  <title>{title}</title>
+  <style>{style}</style>
+  <script>{script}</script>
+</head>
+<body>
+  <main>
+{body}
+  </main>
+</body>
+</html>"#,
+        title = escape_html(title),
+        style = PAGE_STYLE,
+        script = script,
+        body = body,
+    )
+}
+
+/// `syncflo://history`, with a search by meaning if `semantic` history is
+/// on.
+pub fn history(navigation: &Navigation, semantic: bool) -> String {
+    let mut rows = String::new();
+    for entry in navigation.entries().take(HISTORY_PAGE_LIMIT) {
+        let url = escape_html(entry.url.as_str());
+        let title = escape_html(entry.title.as_deref().unwrap_or(entry.url.as_str()));
+        let visited_ms = entry
+            .visited_at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        rows.push_str(&format!(
+            r#"    <div class="row">
+      <div class="main">
+        <div class="title"><a href="{url}">{title}</a></div>
+        <div class="url">{url}</div>
+      </div>
+      <div class="muted"><span class="visited" data-ms="{visited_ms}"></span> · {count}회</div>
+      <button class="btn delete" data-url="{url}" title="기록 삭제">삭제</button>
+    </div>
+"#,
+            url = url,
+            title = title,
+            visited_ms = visited_ms,
+            count = entry.visit_count,
+        ));
+    }
+    if rows.is_empty() {
+        rows.push_str("    <p class=\"muted\">방문 기록이 없습니다.</p>\n");
+    }
+
+    let search = if semantic {
+        r#"    <form id="search" class="row">
+      <input id="query" class="main" placeholder="내용으로 찾기 (예: 비동기 런타임을 비교한 글)" />
+      <button class="btn" type="submit">찾기</button>
+    </form>
+    <div id="matches"></div>
+"#
+    } else {
+        ""
+    };
+    let body = format!("    <h1>방문 기록</h1>\n{}{}", search, rows);
+    let script = r#"
+    window.addEventListener('syncflo:history_matches', function(e) {
+      var list = document.getElementById('matches');
+      list.textContent = '';
+      if (e.detail.error) {
+        list.textContent = e.detail.error;
+        list.className = 'muted';
+        return;
+      }
+      list.className = '';
+      if (!e.detail.matches.length) {
+        list.textContent = '찾은 페이지가 없습니다.';
+        list.className = 'muted';
+      }
+      e.detail.matches.forEach(function(match) {
+        var row = document.createElement('div');
+        row.className = 'row';
+        var link = document.createElement('a');
+        link.href = match.url;
+        link.textContent = match.title || match.url;
+        var score = document.createElement('span');
+        score.className = 'muted';
+        score.textContent = Math.round(match.score * 100) + '%';
+        var main = document.createElement('div');
+        main.className = 'main title';
+        main.appendChild(link);
+        row.appendChild(main);
+        row.appendChild(score);
+        list.appendChild(row);
+      });
+    });
+    window.addEventListener('DOMContentLoaded', function() {
+      var search = document.getElementById('search');
+      if (search) {
+        search.onsubmit = function(e) {
+          e.preventDefault();
+          var query = document.getElementById('query').value.trim();
+          if (!query) return;
+          document.getElementById('matches').textContent = '찾는 중…';
+          window.ipc.postMessage(JSON.stringify({ op: 'search_history', payload: { query: query } }));
+        };
+      }
+      document.querySelectorAll('.visited').forEach(function(el) {
+        el.textContent = new Date(Number(el.dataset.ms)).toLocaleString();
+      });
+      document.querySelectorAll('.delete').forEach(function(btn) {
+        btn.onclick = function() {
+          window.ipc.postMessage(JSON.stringify({ op: 'delete_history', payload: { url: btn.dataset.url } }));
+          btn.closest('.row').remove();
+        };
+      });
+    });
+"#;
+    page("방문 기록", &body, script)
+}
+
+pub fn bookmarks(store: &BookmarkStore) -> String {
+    let mut rows = String::new();
+    for bookmark in store.list().iter().rev() {
+        rows.push_str(&format!(
+            r#"    <div class="row">
+      <div class="main">
+        <div class="title"><a href="{url}">{title}</a></div>
+        <div class="url">{folder}{url}</div>
+      </div>
+      <button class="btn delete" data-url="{url}" title="북마크 삭제">삭제</button>
+    </div>
+"#,
+            url = escape_html(bookmark.url.as_str()),
+            title = escape_html(&bookmark.title),
+            folder = if bookmark.folder.is_empty() {
+                String::new()
+            } else {
+                format!("{} · ", escape_html(&bookmark.folder.join(" / ")))
+            },
+        ));
+    }
+    if rows.is_empty() {
+        rows.push_str("    <p class=\"muted\">저장된 북마크가 없습니다.</p>\n");
+    }
+
+    let body = format!("    <h1>북마크</h1>\n{}", rows);
+    let script = r#"
+    window.addEventListener('DOMContentLoaded', function() {
+      document.querySelectorAll('.delete').forEach(function(btn) {
+        btn.onclick = function() {
+          window.ipc.postMessage(JSON.stringify({ op: 'delete_bookmark', payload: { url: btn.dataset.url } }));
+          btn.closest('.row').remove();
+        };
+      });
+    });
+"#;
+    page("북마크", &body, script)
+}
+
+/// `syncflo://reading-list`. Items open the page itself while there's a
+/// connection and the offline copy otherwise.
+pub fn reading_list(list: &ReadingList) -> String {
+    let mut rows = String::new();
+    for item in list.list().iter().rev() {
+        let host = item.url.host_str().unwrap_or_default();
+        let skipped = match item.skipped_images {
+            0 => String::new(),
+            n => format!(" · 이미지 {}개 빠짐", n),
+        };
+        rows.push_str(&format!(
+            r#"    <div class="row{done}" data-id="{id}">
+      <input type="checkbox" class="pick" title="선택" />
+      <div class="main">
+        <div class="title"><a class="open" href="{url}" data-snapshot="{snapshot}">{title}</a></div>
+        <div class="url">{host}{byline} · 사본 {size}{skipped}</div>
+        <div class="url">{excerpt}</div>
+      </div>
+      <a class="muted" href="{snapshot}" title="저장한 사본 열기">오프라인 사본</a>
+      <button class="btn read" title="읽음 여부 바꾸기">{read_label}</button>
+      <button class="btn delete" title="읽기 목록에서 삭제">삭제</button>
+    </div>
+"#,
+            done = if item.read { " done" } else { "" },
+            id = escape_html(&item.id),
+            url = escape_html(item.url.as_str()),
+            snapshot = escape_html(&assets::reading_list_url(Some(&item.id))),
+            title = escape_html(&item.title),
+            host = escape_html(host),
+            byline = item.byline.as_deref().map(|byline| format!(" · {}", escape_html(byline))).unwrap_or_default(),
+            size = describe_size(item.snapshot_bytes),
+            skipped = skipped,
+            excerpt = escape_html(&item.excerpt),
+            read_label = if item.read { "안 읽음으로" } else { "읽음으로" },
+        ));
+    }
+    let body = if rows.is_empty() {
+        "    <h1>읽기 목록</h1>\n    <p class=\"muted\">저장된 페이지가 없습니다. 주소창 옆의 📥로 페이지를 저장하면 인터넷에 연결되지 않아도 읽을 수 있습니다.</p>\n".to_string()
+    } else {
+        let total: u64 = list.list().iter().map(|item| item.snapshot_bytes).sum();
+        format!(
+            r#"    <h1>읽기 목록</h1>
+    <p class="muted">인터넷에 연결되어 있으면 원래 페이지를, 연결되지 않았으면 저장한 사본을 엽니다. 사본은 모두 {total}입니다.</p>
+    <div class="row">
+      <span class="main"></span>
+      <button id="delete-picked" class="btn">선택한 항목 삭제</button>
+      <button id="delete-read" class="btn">읽은 항목 삭제</button>
+      <button id="delete-all" class="btn">모두 삭제</button>
+    </div>
+{rows}"#,
+            total = describe_size(total),
+            rows = rows,
+        )
+    };
+    let script = r#"
+    function post(op, payload) {
+      window.ipc.postMessage(JSON.stringify({ op: op, payload: payload }));
+    }
+    function setRead(row, read) {
+      row.classList.toggle('done', read);
+      row.querySelector('.read').textContent = read ? '안 읽음으로' : '읽음으로';
+      post('set_reading_item_read', { id: row.dataset.id, read: read });
+    }
+    function remove(rows) {
+      if (!rows.length) return;
+      post('delete_reading_items', { ids: rows.map(function(row) { return row.dataset.id; }) });
+      rows.forEach(function(row) { row.remove(); });
+    }
+    function rows(selector) {
+      return Array.prototype.slice.call(document.querySelectorAll('.row[data-id]' + (selector || '')));
+    }
+    window.addEventListener('DOMContentLoaded', function() {
+      rows().forEach(function(row) {
+        var open = row.querySelector('.open');
+        open.onclick = function(e) {
+          e.preventDefault();
+          setRead(row, true);
+          location.href = navigator.onLine ? open.href : open.dataset.snapshot;
+        };
+        row.querySelector('.read').onclick = function() { setRead(row, !row.classList.contains('done')); };
+        row.querySelector('.delete').onclick = function() { remove([row]); };
+      });
+      var picked = document.getElementById('delete-picked');
+      if (!picked) return;
+      picked.onclick = function() {
+        remove(rows().filter(function(row) { return row.querySelector('.pick').checked; }));
+      };
+      document.getElementById('delete-read').onclick = function() { remove(rows('.done')); };
+      document.getElementById('delete-all').onclick = function() {
+        if (confirm('읽기 목록의 페이지와 저장한 사본을 모두 삭제할까요?')) remove(rows());
+      };
+    });
+"#;
+    page("읽기 목록", &body, script)
+}
+
+/// `bytes` in KB or MB.
+fn describe_size(bytes: u64) -> String {
+    if bytes < 1024 * 1024 {
+        format!("{}KB", bytes.div_ceil(1024))
+    } else {
+        format!("{:.1}MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+pub fn settings(profile: &Profile, usage: &UsageLog) -> String {
+    let history_path = profile.history_path().display().to_string();
+    let assets_dir = std::env::var(assets::ASSETS_DIR_ENV).unwrap_or_else(|_| "(내장)".to_string());
+    let engines: String = SearchEngine::builtins()
+        .iter()
+        .map(|engine| {
+            format!(
+                r#"<option value="{}">{}</option>"#,
+                escape_html(&engine.url_template),
+                escape_html(&engine.name)
+            )
+        })
+        .collect();
+    let body = format!(
+        r#"    <h1>설정</h1>
+    <h2>일반</h2>
+    <table>
+      <tr><td>홈 페이지</td><td>
+        <input class="field" data-key="home_page" size="40" placeholder="비워 두면 시작 페이지" />
+        <div class="error" data-error="home_page"></div>
+      </td></tr>
+      <tr><td>시작할 때</td><td>
+        <select class="field" data-key="startup">
+          <option value="restore">지난 탭 다시 열기</option>
+          <option value="home">홈 페이지 열기</option>
+          <option value="blank">빈 탭 열기</option>
+        </select>
+        <div class="error" data-error="startup"></div>
+      </td></tr>
+      <tr><td>검색 엔진</td><td>
+        <select id="search-engine" class="field">{engines}<option value="">직접 입력</option></select>
+        <input id="search-template" class="field" size="40" placeholder="https://example.com/search?q=%s" hidden />
+        <div class="error" data-error="search_engine"></div>
+      </td></tr>
+      <tr><td>기본 브라우저</td><td>
+        <button id="default-browser" class="btn">기본 브라우저로 설정</button>
+        <span id="default-browser-result" class="muted"></span>
+      </td></tr>
+      <tr><td>검색어 추천</td><td>
+        <input type="checkbox" data-key="search_suggestions" />
+        <div class="muted">주소창에 입력하는 내용을 검색 엔진에 보내 추천 검색어를 받습니다. 시크릿 창에서는 쓰지 않습니다.</div>
+        <div class="error" data-error="search_suggestions"></div>
+      </td></tr>
+      <tr><td>HTTPS 우선 모드</td><td>
+        <input type="checkbox" data-key="https_first" />
+        <div class="error" data-error="https_first"></div>
+      </td></tr>
+      <tr><td>배경 그래픽 인쇄</td><td>
+        <input type="checkbox" data-key="print_backgrounds" />
+        <div class="muted">인쇄하거나 PDF로 저장할 때 배경 색과 이미지를 넣습니다.</div>
+        <div class="error" data-error="print_backgrounds"></div>
+      </td></tr>
+      <tr><td>User-Agent</td><td>
+        <select id="user-agent" class="field">
+          <option value="">WebView 기본값</option>
+          <option value="{chrome}">Chrome</option>
+          <option value="custom">직접 입력</option>
+        </select>
+        <input id="user-agent-custom" class="field" size="40" hidden />
+        <div class="muted">새로 여는 페이지부터 적용됩니다.</div>
+        <div class="error" data-error="user_agent.default"></div>
+      </td></tr>
+    </table>
+    <p>사이트별 User-Agent</p>
+    <p class="muted">하위 도메인에도 적용됩니다. Linux가 아니면 새로 여는 탭부터 적용됩니다.</p>
+    <div id="user-agent-sites"></div>
+    <div class="row">
+      <input id="user-agent-host" class="field" placeholder="example.com" />
+      <input id="user-agent-site" class="main field" placeholder="User-Agent" />
+      <button id="add-user-agent" class="btn">추가</button>
+    </div>
+    <div class="error" id="user-agent-sites-error"></div>
+    <h2>개인정보 보호</h2>
+    <table>
+      <tr><td>추적 매개변수 제거</td><td>
+        <input type="checkbox" data-key="privacy.strip_tracking_params" />
+        <div class="error" data-error="privacy.strip_tracking_params"></div>
+      </td></tr>
+      <tr><td>시크릿 창에서 AI 사용</td><td>
+        <input type="checkbox" data-key="privacy.ai_in_incognito" />
+        <div class="muted">켜면 시크릿 창의 페이지 내용도 AI 제공자에게 보냅니다.</div>
+        <div class="error" data-error="privacy.ai_in_incognito"></div>
+      </td></tr>
+      <tr><td>사이트 알림 끄기</td><td>
+        <input type="checkbox" data-key="mute_notifications" />
+        <div class="muted">알림을 허용한 사이트도 알림을 보여주지 않습니다. 권한은 그대로 둡니다.</div>
+        <div class="error" data-error="mute_notifications"></div>
+      </td></tr>
+      <tr><td>광고·추적기 차단</td><td>
+        <input type="checkbox" data-key="blocking.enabled" />
+        <div class="error" data-error="blocking.enabled"></div>
+      </td></tr>
+    </table>
+    <h2>프록시</h2>
+    <table>
+      <tr><td>종류</td><td>
+        <select id="proxy-kind" class="field">
+          <option value="none">시스템 설정 사용</option>
+          <option value="http">HTTP</option>
+          <option value="socks5">SOCKS5</option>
+        </select>
+      </td></tr>
+      <tr><td>서버</td><td>
+        <input id="proxy-host" class="field" size="30" placeholder="proxy.example.com" />
+        <input id="proxy-port" class="field" size="6" placeholder="8080" />
+      </td></tr>
+      <tr><td>사용자 이름</td><td><input id="proxy-username" class="field" size="30" autocomplete="off" /></td></tr>
+      <tr><td>비밀번호</td><td><input id="proxy-password" class="field" type="password" size="30" autocomplete="off" /></td></tr>
+      <tr><td>예외</td><td><input id="proxy-bypass" class="field" size="40" placeholder="localhost, *.corp.example" /></td></tr>
+    </table>
+    <p class="muted">{proxy_note}</p>
+    <div class="error" data-error="proxy"></div>
+    <div class="row">
+      <button id="save-proxy" class="btn">저장</button>
+      <button id="test-proxy" class="btn">연결 테스트</button>
+      <span id="test-proxy-result" class="muted"></span>
+    </div>
+    <p>외부 프로그램으로 여는 링크</p>
+    <p class="muted">mailto:, zoommtg: 처럼 브라우저가 열지 않는 링크를 처음 열 때 물어본 답입니다. 삭제하면 다음에 다시 묻습니다.</p>
+    <div id="external-schemes"></div>
+    <div class="error" id="external-schemes-error"></div>
+    <p>사이트 권한</p>
+    <p class="muted">위치 정보, 카메라, 마이크, 알림을 요청한 사이트에 준 답입니다. 묻지 않은 권한은 모두 차단합니다. 삭제하면 다음에 다시 묻습니다.</p>
+    <div id="permissions"></div>
+    <p>팝업 허용 사이트</p>
+    <p class="muted">클릭하지 않아도 팝업을 열 수 있는 사이트입니다. 주소창 옆의 팝업 차단됨에서 추가합니다.</p>
+    <div id="allow-popups"></div>
+    <div class="error" id="allow-popups-error"></div>
+    <p>인터넷 사용 기록 삭제</p>
+    <div class="row">
+      <select id="clear-range" class="field">
+        <option value="last_hour">지난 1시간</option>
+        <option value="last_day">지난 24시간</option>
+        <option value="everything">전체 기간</option>
+      </select>
+      <label><input type="checkbox" id="clear-history" checked /> 방문 기록</label>
+      <label><input type="checkbox" id="clear-cookies" /> 쿠키</label>
+      <label><input type="checkbox" id="clear-cache" checked /> 캐시</label>
+      <button id="clear" class="btn">삭제</button>
+    </div>
+    <p id="clear-result" class="muted"></p>
+    <h2>AI</h2>
+    <table>
+      <tr><td>제공자</td><td>
+        <select class="field" data-key="ai.provider">
+          <option value="openai">OpenAI</option>
+          <option value="anthropic">Anthropic</option>
+          <option value="ollama">Ollama</option>
+        </select>
+        <div class="error" data-error="ai.provider"></div>
+      </td></tr>
+      <tr><td>엔드포인트</td><td>
+        <input class="field" data-key="ai.endpoint" size="40" placeholder="비워 두면 제공자 기본값" />
+        <div class="error" data-error="ai.endpoint"></div>
+      </td></tr>
+      <tr><td>모델</td><td>
+        <input class="field" data-key="ai.model" size="40" placeholder="비워 두면 기본 모델" />
+        <div class="error" data-error="ai.model"></div>
+      </td></tr>
+      <tr><td>API 키</td><td>
+        <input id="api-key" class="field" type="password" autocomplete="off" size="40" />
+        <button id="save-api-key" class="btn">저장</button>
+        <button id="delete-api-key" class="btn">삭제</button>
+        <div class="muted">운영체제 키체인에 저장합니다.</div>
+        <div class="error" data-error="ai.api_key"></div>
+      </td></tr>
+    </table>
+    <p class="muted">AI_PROVIDER, AI_API_KEY, AI_API_ENDPOINT, AI_MODEL 환경 변수가 설정보다 우선합니다.</p>
+    <div class="row">
+      <button id="test-ai" class="btn">연결 테스트</button>
+      <span id="test-ai-result" class="muted"></span>
+    </div>
+    <h2>AI 사용량</h2>
+    <table>
+      <tr><td>이번 달</td><td>{usage_month}</td></tr>
+      <tr><td>전체</td><td>{usage_total}</td></tr>
+    </table>
+    <p class="muted">비용은 모델별 가격표로 추정한 값입니다.</p>
+    <button id="reset-usage" class="btn">사용량 초기화</button>
+    <h2>프로필</h2>
+    <p class="muted">프로필마다 설정, 방문 기록, 북마크, 쿠키를 따로 보관합니다. 다른 프로필은 새 창에서 열립니다.</p>
+    <div id="profiles"></div>
+    <div class="row">
+      <input id="profile-name" class="main field" placeholder="새 프로필 이름 (예: work)" />
+      <button id="create-profile" class="btn">만들기</button>
+    </div>
+    <p id="profile-result" class="error"></p>
+    <h2>데이터</h2>
+    <table>
+      <tr><td>방문 기록 파일</td><td>{history}</td></tr>
+      <tr><td>에셋 경로</td><td>{assets}</td></tr>
+    </table>
+    <p><a href="{history_url}">방문 기록 보기</a> · <a href="{bookmarks_url}">북마크 보기</a></p>
+    <h2>북마크 가져오기</h2>
+    <p class="muted">Chrome, Firefox, Safari에서 내보낸 HTML 파일 또는 Chrome의 Bookmarks 파일 경로</p>
+    <div class="row">
+      <input id="import-path" class="main field" placeholder="/path/to/bookmarks.html" />
+      <button id="import" class="btn">가져오기</button>
+    </div>
+    <p id="import-result" class="muted"></p>
+    <h2>내보내기</h2>
+    <p class="muted">방문 기록은 JSON, 북마크는 다른 브라우저에서 가져올 수 있는 HTML로 저장합니다.</p>
+    <div class="row">
+      <input id="export-path" class="main field" placeholder="/path/to/export" />
+      <button id="export-history" class="btn">방문 기록</button>
+      <button id="export-bookmarks" class="btn">북마크</button>
+    </div>
+    <p id="export-result" class="muted"></p>
+"#,
+        history = escape_html(&history_path),
+        assets = escape_html(&assets_dir),
+        history_url = escape_html(&assets::url("history", "")),
+        bookmarks_url = escape_html(&assets::url("bookmarks", "")),
+        usage_month = describe_usage(&usage.this_month()),
+        usage_total = describe_usage(&usage.total()),
+        chrome = escape_html(user_agent::CHROME),
+        proxy_note = if cfg!(target_os = "linux") {
+            "저장하면 열려 있는 탭에도 바로 적용됩니다."
+        } else {
+            "다시 시작하면 적용됩니다. 인증과 예외 목록은 이 플랫폼의 WebView가 지원하지 않습니다."
+        },
+    );
+    let script = r#"
+    // 값은 Rust 쪽에서 검증해 저장하고, 거부되면 그 항목 아래에 이유를 보여줌
+    function setSetting(key, value) {
+      window.ipc.postMessage(JSON.stringify({ op: 'set_setting', payload: { key: key, value: value } }));
+    }
+    function lookup(settings, key) {
+      return key.split('.').reduce(function(value, part) { return value == null ? null : value[part]; }, settings);
+    }
+    // key가 있으면 그 항목만 채움
+    function fill(settings, key) {
+      document.querySelectorAll('[data-key]').forEach(function(el) {
+        if (key && el.dataset.key !== key) return;
+        var value = lookup(settings, el.dataset.key);
+        if (el.type === 'checkbox') el.checked = !!value;
+        else el.value = value == null ? '' : value;
+      });
+      if (!key || key === 'search_engine') {
+        var engine = document.getElementById('search-engine');
+        var custom = document.getElementById('search-template');
+        var template = settings.search_engine.url_template;
+        var builtin = Array.prototype.some.call(engine.options, function(o) { return o.value === template; });
+        engine.value = builtin ? template : '';
+        custom.value = builtin ? '' : template;
+        custom.hidden = builtin;
+      }
+      document.getElementById('api-key').placeholder = settings.ai.has_api_key ? '저장됨' : '없음';
+      if (!key || key.indexOf('external_schemes.') === 0) fillExternalSchemes(settings.external_schemes);
+      if (!key || key.indexOf('allow_popups.') === 0) fillAllowPopups(settings.allow_popups);
+      if (!key || key === 'user_agent.default') {
+        var agent = document.getElementById('user-agent');
+        var customAgent = document.getElementById('user-agent-custom');
+        var current = settings.user_agent.default || '';
+        var preset = Array.prototype.some.call(agent.options, function(o) { return o.value === current; });
+        agent.value = preset ? current : 'custom';
+        customAgent.value = preset ? '' : current;
+        customAgent.hidden = preset;
+      }
+      if (!key || key.indexOf('user_agent.sites.') === 0) fillUserAgentSites(settings.user_agent.sites);
+      if (!key || key === 'proxy') {
+        var proxy = settings.proxy;
+        document.getElementById('proxy-kind').value = proxy.kind;
+        document.getElementById('proxy-host').value = proxy.host;
+        document.getElementById('proxy-port').value = proxy.port || '';
+        document.getElementById('proxy-username').value = proxy.username || '';
+        document.getElementById('proxy-password').value = '';
+        document.getElementById('proxy-password').placeholder = proxy.has_password ? '저장됨' : '';
+        document.getElementById('proxy-bypass').value = proxy.bypass.join(', ');
+      }
+    }
+    function fillUserAgentSites(sites) {
+      var list = document.getElementById('user-agent-sites');
+      list.textContent = '';
+      Object.keys(sites || {}).forEach(function(host) {
+        var row = document.createElement('div');
+        row.className = 'row';
+        var label = document.createElement('span');
+        label.textContent = host;
+        row.appendChild(label);
+        var agent = document.createElement('input');
+        agent.className = 'main field';
+        agent.value = sites[host];
+        agent.onchange = function() { setSetting('user_agent.sites.' + host, agent.value); };
+        row.appendChild(agent);
+        var remove = document.createElement('button');
+        remove.className = 'btn';
+        remove.textContent = '삭제';
+        remove.onclick = function() { setSetting('user_agent.sites.' + host, null); };
+        row.appendChild(remove);
+        list.appendChild(row);
+      });
+    }
+    // 스킴마다 열기 여부를 고르거나 답을 지울 수 있음
+    function fillExternalSchemes(schemes) {
+      var list = document.getElementById('external-schemes');
+      list.textContent = '';
+      var names = Object.keys(schemes || {});
+      if (!names.length) {
+        var empty = document.createElement('p');
+        empty.className = 'muted';
+        empty.textContent = '아직 없습니다.';
+        list.appendChild(empty);
+        return;
+      }
+      names.forEach(function(scheme) {
+        var key = 'external_schemes.' + scheme;
+        var row = document.createElement('div');
+        row.className = 'row';
+        var label = document.createElement('span');
+        label.className = 'main';
+        label.textContent = scheme + ':';
+        row.appendChild(label);
+        var choice = document.createElement('select');
+        choice.className = 'field';
+        choice.innerHTML = '<option value="open">시스템에서 열기</option><option value="ignore">열지 않기</option>';
+        choice.value = schemes[scheme] ? 'open' : 'ignore';
+        choice.onchange = function() { setSetting(key, choice.value === 'open'); };
+        row.appendChild(choice);
+        var remove = document.createElement('button');
+        remove.className = 'btn';
+        remove.textContent = '삭제';
+        remove.onclick = function() { setSetting(key, null); };
+        row.appendChild(remove);
+        list.appendChild(row);
+      });
+    }
+    function fillAllowPopups(hosts) {
+      var list = document.getElementById('allow-popups');
+      list.textContent = '';
+      if (!hosts.length) {
+        var empty = document.createElement('p');
+        empty.className = 'muted';
+        empty.textContent = '아직 없습니다.';
+        list.appendChild(empty);
+        return;
+      }
+      hosts.forEach(function(host) {
+        var row = document.createElement('div');
+        row.className = 'row';
+        var label = document.createElement('span');
+        label.className = 'main';
+        label.textContent = host;
+        row.appendChild(label);
+        var remove = document.createElement('button');
+        remove.className = 'btn';
+        remove.textContent = '삭제';
+        remove.onclick = function() { setSetting('allow_popups.' + host, null); };
+        row.appendChild(remove);
+        list.appendChild(row);
+      });
+    }
+    window.addEventListener('syncflo:permissions', function(e) {
+      var list = document.getElementById('permissions');
+      list.textContent = '';
+      if (!e.detail.decisions.length) {
+        var empty = document.createElement('p');
+        empty.className = 'muted';
+        empty.textContent = e.detail.incognito ? '아직 없습니다. 시크릿 창의 답은 창을 닫으면 잊혀집니다.' : '아직 없습니다.';
+        list.appendChild(empty);
+        return;
+      }
+      e.detail.decisions.forEach(function(decision) {
+        var row = document.createElement('div');
+        row.className = 'row';
+        var label = document.createElement('span');
+        label.className = 'main';
+        label.textContent = decision.origin + ' · ' + decision.label;
+        row.appendChild(label);
+        var state = document.createElement('span');
+        state.className = 'muted';
+        state.textContent = decision.allowed ? '허용' : '차단';
+        row.appendChild(state);
+        var remove = document.createElement('button');
+        remove.className = 'btn';
+        remove.textContent = '삭제';
+        remove.onclick = function() {
+          window.ipc.postMessage(JSON.stringify({ op: 'revoke_permission', payload: {
+            origin: decision.origin, permission: decision.permission
+          } }));
+        };
+        row.appendChild(remove);
+        list.appendChild(row);
+      });
+    });
+    window.addEventListener('syncflo:settings', function(e) {
+      fill(e.detail);
+    });
+    window.addEventListener('syncflo:setting', function(e) {
+      var error = document.querySelector('[data-error="' + e.detail.key + '"]');
+      if (error) error.textContent = e.detail.error || '';
+      if (e.detail.key.indexOf('external_schemes.') === 0) {
+        document.getElementById('external-schemes-error').textContent = e.detail.error || '';
+      }
+      if (e.detail.key.indexOf('allow_popups.') === 0) {
+        document.getElementById('allow-popups-error').textContent = e.detail.error || '';
+      }
+      if (e.detail.key.indexOf('user_agent.sites.') === 0) {
+        document.getElementById('user-agent-sites-error').textContent = e.detail.error || '';
+      }
+      if (e.detail.error) return;
+      if (e.detail.key === 'ai.api_key') document.getElementById('api-key').value = '';
+      fill(e.detail.settings, e.detail.key);
+    });
+    // 기본 프로필은 이름이 null이고 삭제할 수 없음
+    function profileRow(name, current) {
+      var row = document.createElement('div');
+      row.className = 'row';
+      var label = document.createElement('span');
+      label.className = 'main';
+      label.textContent = (name === null ? '기본' : name) + (name === current ? ' (현재)' : '');
+      row.appendChild(label);
+      if (name === current) return row;
+      var open = document.createElement('button');
+      open.className = 'btn';
+      open.textContent = '열기';
+      open.onclick = function() {
+        window.ipc.postMessage(JSON.stringify({ op: 'open_profile', payload: { name: name } }));
+      };
+      row.appendChild(open);
+      if (name === null) return row;
+      var remove = document.createElement('button');
+      remove.className = 'btn';
+      remove.textContent = '삭제';
+      remove.onclick = function() {
+        if (confirm('프로필 "' + name + '"과 그 설정, 방문 기록, 북마크, 쿠키를 모두 삭제할까요? 되돌릴 수 없습니다.')) {
+          window.ipc.postMessage(JSON.stringify({ op: 'delete_profile', payload: { name: name } }));
+        }
+      };
+      row.appendChild(remove);
+      return row;
+    }
+    window.addEventListener('syncflo:profiles', function(e) {
+      var list = document.getElementById('profiles');
+      list.textContent = '';
+      [null].concat(e.detail.names).forEach(function(name) {
+        list.appendChild(profileRow(name, e.detail.current));
+      });
+      document.getElementById('profile-result').textContent = e.detail.error || '';
+    });
+    window.addEventListener('syncflo:import_bookmarks', function(e) {
+      var result = document.getElementById('import-result');
+      if (e.detail.error) {
+        result.textContent = '가져오기 실패: ' + e.detail.error;
+      } else {
+        var r = e.detail.report;
+        result.textContent = r.imported + '개 가져옴 · 중복 ' + r.skipped + '개 건너뜀' + (r.invalid ? ' · 잘못된 항목 ' + r.invalid + '개' : '');
+      }
+    });
+    window.addEventListener('syncflo:export', function(e) {
+      document.getElementById('export-result').textContent = e.detail.error
+        ? '내보내기 실패: ' + e.detail.error
+        : e.detail.path + '에 저장했습니다.';
+    });
+    window.addEventListener('syncflo:clear_data', function(e) {
+      document.getElementById('clear-result').textContent = e.detail.error
+        ? '삭제 실패: ' + e.detail.error
+        : '삭제했습니다. (방문 기록 ' + e.detail.history_removed + '개)';
+    });
+    window.addEventListener('syncflo:ai_connection', function(e) {
+      var info = e.detail.info;
+      document.getElementById('test-ai-result').textContent = info
+        ? '연결됨: ' + info.provider + ' · ' + info.model + ' · ' + info.latency_ms + 'ms'
+        : '연결 실패: ' + e.detail.error;
+    });
+    window.addEventListener('syncflo:proxy_test', function(e) {
+      document.getElementById('test-proxy-result').textContent = e.detail.error
+        ? '연결 실패: ' + e.detail.error
+        : '연결됨 · ' + e.detail.latency_ms + 'ms';
+    });
+    window.addEventListener('syncflo:default_browser', function(e) {
+      document.getElementById('default-browser-result').textContent = e.detail.error
+        ? '설정 실패: ' + e.detail.error
+        : '요청했습니다. 시스템 설정에서 확인하세요.';
+    });
+    window.addEventListener('syncflo:reset_usage', function() {
+      location.reload();
+    });
+    window.addEventListener('DOMContentLoaded', function() {
+      document.querySelectorAll('[data-key]').forEach(function(el) {
+        el.addEventListener('change', function() {
+          setSetting(el.dataset.key, el.type === 'checkbox' ? el.checked : el.value);
+        });
+      });
+      var engine = document.getElementById('search-engine');
+      var custom = document.getElementById('search-template');
+      engine.onchange = function() {
+        custom.hidden = engine.value !== '';
+        if (engine.value) {
+          setSetting('search_engine', { name: engine.selectedOptions[0].text, url_template: engine.value });
+        } else {
+          custom.focus();
+        }
+      };
+      custom.onchange = function() {
+        setSetting('search_engine', { name: '사용자 지정', url_template: custom.value.trim() });
+      };
+      var agent = document.getElementById('user-agent');
+      var customAgent = document.getElementById('user-agent-custom');
+      agent.onchange = function() {
+        customAgent.hidden = agent.value !== 'custom';
+        if (agent.value !== 'custom') {
+          setSetting('user_agent.default', agent.value);
+        } else {
+          customAgent.focus();
+        }
+      };
+      customAgent.onchange = function() {
+        setSetting('user_agent.default', customAgent.value);
+      };
+      document.getElementById('add-user-agent').onclick = function() {
+        var host = document.getElementById('user-agent-host');
+        var site = document.getElementById('user-agent-site');
+        if (!host.value.trim() || !site.value.trim()) return;
+        setSetting('user_agent.sites.' + host.value.trim(), site.value);
+        host.value = '';
+        site.value = '';
+      };
+      // 비밀번호는 새로 입력했을 때만 보냄
+      document.getElementById('save-proxy').onclick = function() {
+        var password = document.getElementById('proxy-password').value;
+        var proxy = {
+          kind: document.getElementById('proxy-kind').value,
+          host: document.getElementById('proxy-host').value,
+          port: Number(document.getElementById('proxy-port').value) || 0,
+          username: document.getElementById('proxy-username').value || null,
+          bypass: document.getElementById('proxy-bypass').value.split(',')
+        };
+        if (password) proxy.password = password;
+        setSetting('proxy', proxy);
+      };
+      document.getElementById('test-proxy').onclick = function() {
+        document.getElementById('test-proxy-result').textContent = '확인하는 중...';
+        window.ipc.postMessage(JSON.stringify({ op: 'test_proxy' }));
+      };
+      var apiKey = document.getElementById('api-key');
+      document.getElementById('save-api-key').onclick = function() {
+        if (apiKey.value.trim()) setSetting('ai.api_key', apiKey.value);
+      };
+      document.getElementById('delete-api-key').onclick = function() {
+        if (confirm('저장된 API 키를 삭제할까요?')) setSetting('ai.api_key', null);
+      };
+      document.getElementById('default-browser').onclick = function() {
+        window.ipc.postMessage(JSON.stringify({ op: 'set_default_browser' }));
+      };
+      document.getElementById('test-ai').onclick = function() {
+        document.getElementById('test-ai-result').textContent = '확인하는 중...';
+        window.ipc.postMessage(JSON.stringify({ op: 'test_ai_connection' }));
+      };
+      document.getElementById('reset-usage').onclick = function() {
+        if (confirm('AI 사용량 기록을 초기화할까요?')) {
+          window.ipc.postMessage(JSON.stringify({ op: 'reset_usage' }));
+        }
+      };
+      document.getElementById('clear').onclick = function() {
+        window.ipc.postMessage(JSON.stringify({ op: 'clear_data', payload: {
+          range: document.getElementById('clear-range').value,
+          history: document.getElementById('clear-history').checked,
+          cookies: document.getElementById('clear-cookies').checked,
+          cache: document.getElementById('clear-cache').checked
+        } }));
+      };
+      function exportTo(op) {
+        var path = document.getElementById('export-path').value.trim();
+        if (!path) return;
+        window.ipc.postMessage(JSON.stringify({ op: op, payload: { path: path } }));
+      }
+      document.getElementById('export-history').onclick = function() { exportTo('export_history'); };
+      document.getElementById('export-bookmarks').onclick = function() { exportTo('export_bookmarks'); };
+      document.getElementById('import').onclick = function() {
+        var path = document.getElementById('import-path').value.trim();
+        if (!path) return;
+        document.getElementById('import-result').textContent = '가져오는 중...';
+        window.ipc.postMessage(JSON.stringify({ op: 'import_bookmarks', payload: { path: path } }));
+      };
+      document.getElementById('create-profile').onclick = function() {
+        var name = document.getElementById('profile-name');
+        if (!name.value.trim()) return;
+        window.ipc.postMessage(JSON.stringify({ op: 'create_profile', payload: { name: name.value } }));
+        name.value = '';
+      };
+      window.ipc.postMessage(JSON.stringify({ op: 'get_settings' }));
+      window.ipc.postMessage(JSON.stringify({ op: 'get_profiles' }));
+      window.ipc.postMessage(JSON.stringify({ op: 'get_permissions' }));
+    });
+"#;
+    page("설정", &body, script)
+}
+
+/// AI usage like `182k 토큰 · 약 $0.41 (요청 57회)`.
+fn describe_usage(totals: &UsageTotals) -> String {
+    let tokens = match totals.tokens() {
+        tokens if tokens >= 1_000_000 => format!("{:.1}M", tokens as f64 / 1_000_000.0),
+        tokens if tokens >= 1_000 => format!("{}k", tokens / 1_000),
+        tokens => tokens.to_string(),
+    };
+    format!("{} 토큰 · 약 ${:.2} (요청 {}회)", tokens, totals.cost, totals.requests)
+}
+
+/// `syncflo://about`. `user_agent` is the one set in the settings, if any;
+/// the page itself reads the one it was loaded with.
+pub fn about(user_agent: Option<&str>) -> String {
+    let profile = if cfg!(debug_assertions) { "debug" } else { "release" };
+    let webview = wry::webview_version().unwrap_or_else(|_| "unknown".to_string());
+    let body = format!(
+        r#"    <h1>SyncFlo Browser</h1>
+    <p class="muted">{description}</p>
+    <table>
+      <tr><td>버전</td><td>{version}</td></tr>
+      <tr><td>빌드</td><td>{profile}</td></tr>
+      <tr><td>플랫폼</td><td>{os} ({arch})</td></tr>
+      <tr><td>WebView</td><td>{webview}</td></tr>
+      <tr><td>User-Agent</td><td><span id="user-agent"></span><div class="muted">{user_agent}</div></td></tr>
+    </table>
+"#,
+        description = escape_html(env!("CARGO_PKG_DESCRIPTION")),
+        version = env!("CARGO_PKG_VERSION"),
+        profile = profile,
+        os = std::env::consts::OS,
+        arch = std::env::consts::ARCH,
+        webview = escape_html(&webview),
+        user_agent = if user_agent.is_some() { "설정에서 지정함" } else { "WebView 기본값" },
+    );
+    let script = r#"
+    window.addEventListener('DOMContentLoaded', function() {
+      document.getElementById('user-agent').textContent = navigator.userAgent;
+    });
+"#;
+    page("SyncFlo 정보", &body, script)
+}
+
+/// Reader view of `article` from `source_url`. The article HTML must
+/// already be cleaned; it's inserted as is. Its images may also be next to
+/// the page, as in the reading list's offline copies.
+pub fn reader(article: &Article, stats: &TextStats, source_url: &str) -> String {
+    let host = url::Url::parse(source_url)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from))
+        .unwrap_or_default();
+    let byline = article
+        .byline
+        .as_deref()
+        .map(|byline| format!(" · {}", escape_html(byline)))
+        .unwrap_or_default();
+    let language = stats.language.as_deref();
+    let details = match language.and_then(language_name) {
+        Some(name) => format!("{} · 약 {}분", name, stats.reading_minutes.ceil().max(1.0)),
+        None => format!("약 {}분", stats.reading_minutes.ceil().max(1.0)),
+    };
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="ko">
+<head>
+  <meta charset="utf-8" />
+  <meta name="viewport" content="width=device-width, initial-scale=1" />
+  <meta http-equiv="Content-Security-Policy" content="default-src 'none'; img-src 'self' http: https: data:; style-src 'unsafe-inline'; script-src 'unsafe-inline'" />
+  <title>{title}</title>
+  <style>
+    :root {{ --bg: #121212; --fg: #e6e6e6; --muted: #a7a7a7; --line: #2a2a2a; --link: #6ea8fe; --size: 19px; }}
+    :root.light {{ --bg: #fbfaf7; --fg: #1f1f1f; --muted: #6b6b6b; --line: #e2e0db; --link: #1a5fd1; }}
+    html, body {{ margin: 0; background: var(--bg); color: var(--fg); }}
+    body {{ font-family: Georgia, 'Noto Serif KR', 'Apple SD Gothic Neo', serif; font-size: var(--size); line-height: 1.7; }}
+    .toolbar {{ position: sticky; top: 0; display: flex; justify-content: flex-end; gap: 6px; padding: 8px 12px; background: var(--bg); border-bottom: 1px solid var(--line); font-family: -apple-system, BlinkMacSystemFont, Segoe UI, Roboto, Helvetica, Arial, sans-serif; }}
+    .toolbar button {{ min-width: 32px; height: 28px; border: 1px solid var(--line); border-radius: 6px; background: transparent; color: var(--fg); cursor: pointer; }}
+    article {{ width: min(720px, 90vw); margin: 32px auto 64px; }}
+    h1.title {{ font-size: 1.8em; line-height: 1.3; margin: 0 0 8px; }}
+    .source {{ color: var(--muted); font-size: 0.75em; margin-bottom: 32px; font-family: -apple-system, BlinkMacSystemFont, Segoe UI, Roboto, Helvetica, Arial, sans-serif; }}
+    a {{ color: var(--link); }}
+    img {{ max-width: 100%; height: auto; }}
+    pre {{ overflow-x: auto; padding: 12px; border: 1px solid var(--line); border-radius: 6px; font-size: 0.8em; }}
+    blockquote {{ margin-left: 0; padding-left: 16px; border-left: 3px solid var(--line); color: var(--muted); }}
+    table {{ border-collapse: collapse; }}
+    td, th {{ padding: 4px 8px; border: 1px solid var(--line); }}
+  </style>
+  <script>
+    (function() {{
+      var prefs = {{ size: 19, light: false }};
+      try {{ prefs = Object.assign(prefs, JSON.parse(localStorage.getItem('syncflo-reader') || '{{}}')); }} catch (e) {{}}
+      function apply() {{
+        document.documentElement.style.setProperty('--size', prefs.size + 'px');
+        document.documentElement.classList.toggle('light', prefs.light);
+        try {{ localStorage.setItem('syncflo-reader', JSON.stringify(prefs)); }} catch (e) {{}}
+      }}
+      apply();
+      document.addEventListener('DOMContentLoaded', function() {{
+        document.getElementById('smaller').onclick = function() {{ prefs.size = Math.max(14, prefs.size - 1); apply(); }};
+        document.getElementById('larger').onclick = function() {{ prefs.size = Math.min(32, prefs.size + 1); apply(); }};
+        document.getElementById('theme').onclick = function() {{ prefs.light = !prefs.light; apply(); }};
+        document.querySelectorAll('img[data-src]').forEach(function(img) {{
+          img.title = '눌러서 이미지 보기';
+          img.style.cursor = 'pointer';
+          img.onclick = function() {{ img.src = img.dataset.src; img.removeAttribute('data-src'); img.onclick = null; }};
+        }});
+      }});
+    }})();
+  </script>
+</head>
+<body>
+  <div class="toolbar">
+    <button id="smaller" title="글자 작게">가-</button>
+    <button id="larger" title="글자 크게">가+</button>
+    <button id="theme" title="밝은/어두운 테마">◐</button>
+  </div>
+  <article{lang}>
+    <h1 class="title">{title}</h1>
+    <div class="source">{host}{byline}</div>
+    <div class="source">{details}</div>
+{content}
+  </article>
+</body>
+</html>"#,
+        title = escape_html(&article.title),
+        host = escape_html(&host),
+        byline = byline,
+        details = details,
+        lang = language.map(|lang| format!(" lang=\"{}\"", lang)).unwrap_or_default(),
+        content = article.html,
+    )
+}
+
+/// Name of a language detected by [`ContentProcessor::analyze`](crate::ai::ContentProcessor::analyze).
+fn language_name(code: &str) -> Option<&'static str> {
+    Some(match code {
+        "ko" => "한국어",
+        "en" => "영어",
+        "ja" => "일본어",
+        "zh" => "중국어",
+        "es" => "스페인어",
+        "de" => "독일어",
+        "fr" => "프랑스어",
+        _ => return None,
+    })
+}
+
+/// Why a page failed to load, as shown on `syncflo://error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadErrorKind {
+    Dns,
+    Timeout,
+    Tls,
+    Network,
+}
+
+impl LoadErrorKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dns => "dns",
+            Self::Timeout => "timeout",
+            Self::Tls => "tls",
+            Self::Network => "network",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Self {
+        match kind {
+            "dns" => Self::Dns,
+            "timeout" => Self::Timeout,
+            "tls" => Self::Tls,
+            _ => Self::Network,
+        }
+    }
+
+    fn describe(self, host: &str) -> (&'static str, String) {
+        match self {
+            Self::Dns => ("사이트를 찾을 수 없습니다", format!("{}의 서버 주소를 찾을 수 없습니다.", host)),
+            Self::Timeout => ("연결 시간이 초과되었습니다", format!("{}에서 응답하는 데 너무 오래 걸립니다.", host)),
+            Self::Tls => ("안전하게 연결할 수 없습니다", format!("{}의 보안 인증서를 확인할 수 없습니다.", host)),
+            Self::Network => ("페이지를 열 수 없습니다", format!("{}에 연결하는 중 문제가 발생했습니다.", host)),
+        }
+    }
+}
+
+/// `syncflo://error`, shown in place of a page that failed to load.
+pub fn load_error(failed_url: &str, kind: LoadErrorKind) -> String {
+    let host = url::Url::parse(failed_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| failed_url.to_string());
+    let (title, detail) = kind.describe(&host);
+    let body = format!(
+        r#"    <h1>{title}</h1>
+    <p>{detail}</p>
+    <p class="muted">{url} ({kind})</p>
+    <p>
+      <button class="btn" id="retry" data-url="{url}">다시 시도</button>
+      <button class="btn" id="home" data-url="{home}">홈으로</button>
+    </p>
+"#,
+        title = title,
+        detail = escape_html(&detail),
+        url = escape_html(failed_url),
+        kind = kind.as_str(),
+        home = escape_html(&assets::home_url()),
+    );
+    let script = r#"
+    function go(button) {
+      window.ipc.postMessage(JSON.stringify({ op: 'navigate', payload: { input: button.dataset.url } }));
+    }
+    document.addEventListener('DOMContentLoaded', function() {
+      document.getElementById('retry').onclick = function() { go(this); };
+      document.getElementById('home').onclick = function() { go(this); };
+    });
+"#;
+    page(title, &body, script)
+}
+
+/// Adds the "restore previous session" banner to the home page's HTML.
+pub fn with_recovery_banner(home_html: &str, tab_count: usize) -> String {
+    let banner = format!(
+        r#"<div id="syncflo-recovery" style="position: fixed; top: 16px; left: 50%; transform: translateX(-50%); display: flex; align-items: center; gap: 12px; padding: 10px 14px; border-radius: 10px; background: #1e1e1e; border: 1px solid #2a2a2a; color: #e6e6e6; font-size: 14px; z-index: 10000;">
+  <span>브라우저가 정상적으로 종료되지 않았습니다. (탭 {count}개)</span>
+  <button id="syncflo-restore" style="height: 32px; padding: 0 12px; border-radius: 6px; font-size: 14px;">이전 세션 복원</button>
+  <button id="syncflo-dismiss" style="height: 32px; padding: 0 10px; border-radius: 6px; font-size: 14px; background: #2a2a2a;" title="닫기">×</button>
+</div>
+<script>
+  (function() {{
+    function post(op) {{ window.ipc.postMessage(JSON.stringify({{ op: op }})); }}
+    document.getElementById('syncflo-restore').onclick = function() {{ post('restore_session'); }};
+    document.getElementById('syncflo-dismiss').onclick = function() {{
+      post('dismiss_recovery');
+      document.getElementById('syncflo-recovery').remove();
+    }};
+  }})();
+</script>
+"#,
+        count = tab_count,
+    );
+    match home_html.rfind("</body>") {
+        Some(pos) => format!("{}{}{}", &home_html[..pos], banner, &home_html[pos..]),
+        None => format!("{}{}", home_html, banner),
+    }
+}
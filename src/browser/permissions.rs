@@ -0,0 +1,161 @@
+//! Location, camera, microphone and notifications for pages. The broker
+//! answers a page's request from what was decided for its origin before;
+//! otherwise the user is asked, and anything not allowed is denied.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Geolocation,
+    Camera,
+    Microphone,
+    Notifications,
+}
+
+impl Permission {
+    /// Name shown in the prompt and on the settings page.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Geolocation => "위치 정보",
+            Self::Camera => "카메라",
+            Self::Microphone => "마이크",
+            Self::Notifications => "알림",
+        }
+    }
+}
+
+/// A saved decision, as the settings page lists them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Decision {
+    pub origin: String,
+    pub permission: Permission,
+    pub label: &'static str,
+    pub allowed: bool,
+}
+
+/// Decisions by origin, like `https://meet.example.com`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PermissionBroker {
+    origins: BTreeMap<String, BTreeMap<Permission, bool>>,
+    #[serde(skip)]
+    store_path: Option<PathBuf>,
+}
+
+impl PermissionBroker {
+    /// A broker whose decisions are forgotten when the window closes, for
+    /// incognito windows.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads decisions from `path` and saves back to it on every change.
+    /// A missing or corrupt file yields no decisions.
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let mut broker = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str::<PermissionBroker>(&text).unwrap_or_else(|e| {
+                log::warn!("Ignoring corrupt permissions file {}: {}", path.display(), e);
+                PermissionBroker::new()
+            }),
+            Err(_) => PermissionBroker::new(),
+        };
+        broker.store_path = Some(path);
+        broker
+    }
+
+    /// What was decided for a page on `origin` asking for all of
+    /// `permissions`: allowed if all of them were, blocked if any was, or
+    /// `None` if the user has to be asked.
+    pub fn check(&self, origin: &str, permissions: &[Permission]) -> Option<bool> {
+        let decided = self.origins.get(origin);
+        let mut allowed = true;
+        for permission in permissions {
+            match decided.and_then(|decided| decided.get(permission)) {
+                Some(false) => return Some(false),
+                Some(true) => {}
+                None => allowed = false,
+            }
+        }
+        allowed.then_some(true)
+    }
+
+    /// Remembers the user's answer to `origin` asking for `permissions`.
+    pub fn decide(&mut self, origin: &str, permissions: &[Permission], allowed: bool) {
+        let decided = self.origins.entry(origin.to_string()).or_default();
+        for permission in permissions {
+            decided.insert(*permission, allowed);
+        }
+        self.autosave();
+    }
+
+    /// Forgets the decision about `permission` for `origin`, so the next
+    /// request asks again. Returns whether there was one.
+    pub fn revoke(&mut self, origin: &str, permission: Permission) -> bool {
+        let Some(decided) = self.origins.get_mut(origin) else {
+            return false;
+        };
+        let removed = decided.remove(&permission).is_some();
+        if decided.is_empty() {
+            self.origins.remove(origin);
+        }
+        if removed {
+            self.autosave();
+        }
+        removed
+    }
+
+    /// All decisions, by origin.
+    pub fn list(&self) -> Vec<Decision> {
+        self.origins
+            .iter()
+            .flat_map(|(origin, decided)| {
+                decided.iter().map(|(permission, allowed)| Decision {
+                    origin: origin.clone(),
+                    permission: *permission,
+                    label: permission.label(),
+                    allowed: *allowed,
+                })
+            })
+            .collect()
+    }
+
+    /// Writes the decisions to their store path, if any.
+    pub fn save(&self) -> Result<()> {
+        match &self.store_path {
+            Some(path) => self.save_to(path),
+            None => Ok(()),
+        }
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let json = serde_json::to_string(self).context("Failed to serialize permissions")?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(())
+    }
+
+    fn autosave(&self) {
+        if let Err(e) = self.save() {
+            log::warn!("Failed to save permissions: {:#}", e);
+        }
+    }
+}
+
+/// The origin decisions about a page on `url` are kept under. `None` for
+/// pages without one, like `data:` URLs, which are always denied.
+pub fn origin(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let origin = url.origin();
+    origin.is_tuple().then(|| origin.ascii_serialization())
+}
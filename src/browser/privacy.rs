@@ -0,0 +1,72 @@
+//! Removing tracking parameters from URLs before they're loaded.
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Query parameters that only identify where a click came from.
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "twclid", "ttclid",
+    "igshid", "mc_cid", "mc_eid", "_hsenc", "_hsmi", "oly_anon_id", "oly_enc_id",
+];
+
+/// Parameter prefixes that are always tracking, e.g. `utm_source`.
+const TRACKING_PREFIXES: &[&str] = &["utm_"];
+
+/// Domains (and their subdomains) that need these parameters to work, such
+/// as ad click redirects.
+const ALLOWED_DOMAINS: &[&str] = &["googleadservices.com", "doubleclick.net", "ads.google.com"];
+
+/// The `[privacy]` section of the config file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrivacySettings {
+    /// Remove tracking parameters from URLs before loading them.
+    pub strip_tracking_params: bool,
+    /// Parameters to remove on top of the built-in list.
+    pub extra_tracking_params: Vec<String>,
+    /// Domains to leave alone on top of the built-in allowlist.
+    pub allowed_domains: Vec<String>,
+    /// Let the AI assistant see pages in incognito windows.
+    pub ai_in_incognito: bool,
+}
+
+/// `url` without tracking parameters. URLs on allowlisted domains, and
+/// URLs without any, are returned unchanged.
+pub fn clean_url(url: &Url, settings: &PrivacySettings) -> Url {
+    let (Some(host), Some(query)) = (url.host_str(), url.query()) else {
+        return url.clone();
+    };
+    let allowed = ALLOWED_DOMAINS
+        .iter()
+        .copied()
+        .chain(settings.allowed_domains.iter().map(String::as_str))
+        .any(|domain| is_same_or_subdomain(host, domain));
+    if allowed {
+        return url.clone();
+    }
+
+    // Filter the raw query so the remaining parameters keep their encoding
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|pair| !is_tracking_param(pair.split('=').next().unwrap_or(""), settings))
+        .collect();
+    let mut cleaned = url.clone();
+    let kept = kept.join("&");
+    cleaned.set_query((!kept.is_empty()).then_some(kept.as_str()));
+    cleaned
+}
+
+fn is_tracking_param(name: &str, settings: &PrivacySettings) -> bool {
+    let name = name.to_ascii_lowercase();
+    TRACKING_PARAMS.contains(&name.as_str())
+        || TRACKING_PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+        || settings.extra_tracking_params.iter().any(|p| p.eq_ignore_ascii_case(&name))
+}
+
+fn is_same_or_subdomain(host: &str, domain: &str) -> bool {
+    host.eq_ignore_ascii_case(domain)
+        || host
+            .len()
+            .checked_sub(domain.len() + 1)
+            .is_some_and(|dot| host.as_bytes()[dot] == b'.' && host[dot + 1..].eq_ignore_ascii_case(domain))
+}
@@ -0,0 +1,209 @@
+//! Profiles: separate settings, history, bookmarks and cookies, e.g. for work
+//! and personal browsing. The default profile keeps its files in the
+//! platform's directories, or in the `--user-data-dir`; named profiles each
+//! have a directory in its `profiles` directory.
+
+use anyhow::{bail, Context, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where a profile keeps its files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// `None` for the default profile.
+    name: Option<String>,
+    /// Directory given in place of the platform's, if any.
+    user_data_dir: Option<PathBuf>,
+    config_dir: PathBuf,
+    data_dir: PathBuf,
+    cache_dir: PathBuf,
+}
+
+impl Profile {
+    /// The profile `name`, or the default one for `None`, with its files in
+    /// `user_data_dir` or else in the platform's directories.
+    pub fn open(name: Option<&str>, user_data_dir: Option<&Path>) -> Result<Self> {
+        let (config_dir, data_dir, cache_dir) = match (name, user_data_dir) {
+            (None, None) => {
+                let dirs = project_dirs()?;
+                (dirs.config_dir().to_path_buf(), dirs.data_dir().to_path_buf(), dirs.cache_dir().to_path_buf())
+            }
+            (None, Some(dir)) => (dir.to_path_buf(), dir.to_path_buf(), dir.join("cache")),
+            (Some(name), _) => {
+                check_name(name)?;
+                let dir = profiles_dir(user_data_dir)?.join(name);
+                (dir.clone(), dir.clone(), dir.join("cache"))
+            }
+        };
+        Ok(Self {
+            name: name.map(String::from),
+            user_data_dir: user_data_dir.map(Path::to_path_buf),
+            config_dir,
+            data_dir,
+            cache_dir,
+        })
+    }
+
+    /// `None` for the default profile.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    pub fn settings_path(&self) -> PathBuf {
+        self.config_dir.join("config.toml")
+    }
+
+    pub fn history_path(&self) -> PathBuf {
+        self.data_dir.join("history.json")
+    }
+
+    pub fn bookmarks_path(&self) -> PathBuf {
+        self.data_dir.join("bookmarks.json")
+    }
+
+    /// Directory of the reading list and its pages' offline copies.
+    pub fn reading_list_dir(&self) -> PathBuf {
+        self.data_dir.join("reading-list")
+    }
+
+    /// Sites' answers to permission prompts.
+    pub fn permissions_path(&self) -> PathBuf {
+        self.data_dir.join("permissions.json")
+    }
+
+    pub fn session_path(&self) -> PathBuf {
+        self.data_dir.join("session.json")
+    }
+
+    /// Directory of the rotating log files.
+    pub fn logs_dir(&self) -> PathBuf {
+        self.data_dir.join("logs")
+    }
+
+    /// Marker file that exists while the profile is open.
+    pub fn running_marker_path(&self) -> PathBuf {
+        self.data_dir.join("running")
+    }
+
+    /// Where the process running the profile listens for later launches:
+    /// a socket, or on Windows a file with a port number.
+    pub fn instance_path(&self) -> PathBuf {
+        self.data_dir.join(if cfg!(unix) { "instance.sock" } else { "instance.port" })
+    }
+
+    /// Directory holding the blocker's filter lists.
+    pub fn filters_dir(&self) -> PathBuf {
+        self.config_dir.join("filters")
+    }
+
+    pub fn favicons_dir(&self) -> PathBuf {
+        self.cache_dir.join("favicons")
+    }
+
+    /// Icons of web pages' notifications, by URL.
+    pub fn notification_icons_dir(&self) -> PathBuf {
+        self.cache_dir.join("notification-icons")
+    }
+
+    /// Directory of the compiled WebKit content filters.
+    pub fn content_filters_dir(&self) -> PathBuf {
+        self.cache_dir.join("content-filters")
+    }
+
+    /// Directory of the webviews' cookies, cache and storage; the webview's
+    /// own default for the default profile in the platform's directories.
+    pub fn webview_dir(&self) -> Option<PathBuf> {
+        (self.name.is_some() || self.user_data_dir.is_some()).then(|| self.data_dir.join("webview"))
+    }
+
+    /// Command-line arguments that start the browser with this profile.
+    pub fn launch_args(&self) -> Vec<OsString> {
+        let mut args = Vec::new();
+        if let Some(name) = &self.name {
+            args.extend(["--profile".into(), name.into()]);
+        }
+        if let Some(dir) = &self.user_data_dir {
+            args.extend(["--user-data-dir".into(), dir.into()]);
+        }
+        args
+    }
+
+    /// The named profiles next to this one, including it, sorted by name.
+    pub fn names(&self) -> Vec<String> {
+        let entries = profiles_dir(self.user_data_dir.as_deref()).ok().and_then(|dir| fs::read_dir(dir).ok());
+        let Some(entries) = entries else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = entries
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| check_name(name).is_ok())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// The profile `name` next to this one, or the default one for `None`.
+    pub fn sibling(&self, name: Option<&str>) -> Result<Self> {
+        Self::open(name, self.user_data_dir.as_deref())
+    }
+
+    /// Creates the profile `name` next to this one.
+    pub fn create(&self, name: &str) -> Result<Self> {
+        let profile = self.sibling(Some(name))?;
+        if profile.data_dir.exists() {
+            bail!("Profile {} already exists", name);
+        }
+        fs::create_dir_all(&profile.data_dir)
+            .with_context(|| format!("Failed to create {}", profile.data_dir.display()))?;
+        Ok(profile)
+    }
+
+    /// Deletes the profile `name` next to this one with all its files. Only
+    /// a real directory in the profiles directory is removed; symbolic
+    /// links aren't followed.
+    pub fn delete(&self, name: &str) -> Result<()> {
+        if self.name.as_deref() == Some(name) {
+            bail!("Profile {} is in use", name);
+        }
+        let profile = self.sibling(Some(name))?;
+        let metadata = fs::symlink_metadata(&profile.data_dir).with_context(|| format!("No profile {}", name))?;
+        if !metadata.is_dir() {
+            bail!("{} isn't a profile directory", profile.data_dir.display());
+        }
+        if profile.running_marker_path().exists() {
+            bail!("Profile {} is open, or didn't shut down cleanly; open and close it first", name);
+        }
+        fs::remove_dir_all(&profile.data_dir)
+            .with_context(|| format!("Failed to delete {}", profile.data_dir.display()))
+    }
+}
+
+/// Checks that `name` is usable as a directory name everywhere.
+fn check_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_'));
+    if !valid {
+        bail!("Invalid profile name {:?}: use letters, digits, - and _", name);
+    }
+    Ok(())
+}
+
+/// Directory of the named profiles, in `user_data_dir` or else in the
+/// platform's data directory.
+fn profiles_dir(user_data_dir: Option<&Path>) -> Result<PathBuf> {
+    let base = match user_data_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => project_dirs()?.data_dir().to_path_buf(),
+    };
+    Ok(base.join("profiles"))
+}
+
+fn project_dirs() -> Result<directories::ProjectDirs> {
+    directories::ProjectDirs::from("", "", "syncflo").context("No home directory")
+}
@@ -0,0 +1,321 @@
+//! Pages saved to read later. Unlike bookmarks, each keeps an offline copy:
+//! the article as the reader view shows it, with its images, in a directory
+//! of its own next to the list.
+
+use crate::ai::{Article, ContentProcessor};
+use crate::browser::proxy::ProxySettings;
+use crate::browser::{favicon, pages};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use url::Url;
+
+/// Larger images aren't saved; the copy loads them from the site instead.
+const MAX_IMAGE_BYTES: u64 = 2 * 1024 * 1024;
+/// Linked pages saved without opening them are read up to this size.
+const MAX_PAGE_BYTES: u64 = 10 * 1024 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+/// Characters of the article shown under its title in the list.
+const EXCERPT_CHARS: usize = 160;
+/// The list itself, in the reading list directory.
+const LIST_FILE: &str = "reading-list.json";
+/// The article, in an item's directory.
+const PAGE_FILE: &str = "index.html";
+
+/// A page saved to read later. Each URL is saved at most once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingItem {
+    /// Name of the directory of the offline copy, from the URL.
+    pub id: String,
+    pub url: Url,
+    pub title: String,
+    pub byline: Option<String>,
+    /// Start of the article.
+    pub excerpt: String,
+    pub added_at: SystemTime,
+    pub read: bool,
+    /// Size of the offline copy with its images.
+    pub snapshot_bytes: u64,
+    /// Images left out of the copy for their size, or because they didn't
+    /// download.
+    pub skipped_images: usize,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ReadingList {
+    /// Items in the order they were saved.
+    items: Vec<ReadingItem>,
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+impl ReadingList {
+    /// Loads the list kept in `dir` and autosaves back to it on every
+    /// change. A missing or corrupt file yields an empty list.
+    pub fn load(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        let path = dir.join(LIST_FILE);
+        let mut list = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str::<ReadingList>(&text).unwrap_or_else(|e| {
+                log::warn!("Ignoring corrupt reading list {}: {}", path.display(), e);
+                ReadingList::default()
+            }),
+            Err(_) => ReadingList::default(),
+        };
+        list.dir = dir;
+        list
+    }
+
+    /// Directory of the list and the offline copies.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes the list to its directory.
+    pub fn save(&self) -> Result<()> {
+        fs::create_dir_all(&self.dir).with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let path = self.dir.join(LIST_FILE);
+        let json = serde_json::to_string(self).context("Failed to serialize the reading list")?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &path).with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(())
+    }
+
+    fn autosave(&self) {
+        if let Err(e) = self.save() {
+            log::warn!("Failed to save the reading list: {:#}", e);
+        }
+    }
+
+    /// Adds `item`, made by [`snapshot`]. Saving a page again replaces its
+    /// item and marks it unread.
+    pub fn add(&mut self, item: ReadingItem) {
+        self.items.retain(|saved| saved.id != item.id);
+        self.items.push(item);
+        self.autosave();
+    }
+
+    pub fn contains(&self, url: &Url) -> bool {
+        self.items.iter().any(|item| &item.url == url)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ReadingItem> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    /// Marks the item `id` read or unread. Returns whether there is one.
+    pub fn set_read(&mut self, id: &str, read: bool) -> bool {
+        let Some(item) = self.items.iter_mut().find(|item| item.id == id) else {
+            return false;
+        };
+        if item.read != read {
+            item.read = read;
+            self.autosave();
+        }
+        true
+    }
+
+    /// Removes the items `ids` with their offline copies. Returns how many
+    /// there were.
+    pub fn remove(&mut self, ids: &[String]) -> usize {
+        let before = self.items.len();
+        self.items.retain(|item| !ids.contains(&item.id));
+        let removed = before - self.items.len();
+        if removed == 0 {
+            return 0;
+        }
+        for id in ids.iter().filter(|id| is_item_id(id)) {
+            let dir = self.dir.join(id);
+            if let Err(e) = fs::remove_dir_all(&dir) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("Failed to delete {}: {}", dir.display(), e);
+                }
+            }
+        }
+        self.autosave();
+        removed
+    }
+
+    /// All items, oldest first.
+    pub fn list(&self) -> &[ReadingItem] {
+        &self.items
+    }
+
+    /// The file `name` of the offline copy of item `id`: the article for an
+    /// empty name, else one of its images.
+    pub fn snapshot_file(&self, id: &str, name: &str) -> Option<PathBuf> {
+        self.get(id)?;
+        let name = if name.is_empty() { PAGE_FILE } else { name };
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-') || name.starts_with('.') {
+            return None;
+        }
+        Some(self.dir.join(id).join(name))
+    }
+}
+
+/// Directory name of the item for `url`.
+pub fn item_id(url: &Url) -> String {
+    Sha256::digest(url.as_str().as_bytes()).iter().take(12).map(|b| format!("{:02x}", b)).collect()
+}
+
+fn is_item_id(id: &str) -> bool {
+    id.len() == 24 && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Makes the offline copy of the page at `url` with the source `html` in
+/// `dir`, the reading list's directory, replacing an earlier one. Blocks
+/// while the images download through `proxy`, so call it off the main
+/// thread.
+pub fn snapshot(html: &str, url: &Url, dir: &Path, proxy: &ProxySettings) -> Result<ReadingItem> {
+    let article = ContentProcessor::new().extract_article(html, url)?;
+    save_article(article, url, dir, &proxy.blocking_client(FETCH_TIMEOUT)?)
+}
+
+/// Like [`snapshot`], for a linked page that isn't open: downloads it with
+/// `cookie` as the `Cookie` header, in whatever charset it declares.
+pub fn snapshot_link(url: &Url, cookie: Option<&str>, dir: &Path, proxy: &ProxySettings) -> Result<ReadingItem> {
+    let client = proxy.blocking_client(FETCH_TIMEOUT)?;
+    let mut request = client.get(url.as_str());
+    if let Some(cookie) = cookie {
+        request = request.header(reqwest::header::COOKIE, cookie);
+    }
+    let response = request
+        .send()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch {}", url))?;
+    // Redirects end up elsewhere, which relative links are resolved against
+    let url = response.url().clone();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    if content_type.as_deref().is_some_and(|t| !t.to_ascii_lowercase().contains("html")) {
+        bail!("{} isn't a web page", url);
+    }
+    let mut bytes = Vec::new();
+    response.take(MAX_PAGE_BYTES).read_to_end(&mut bytes)?;
+    // All of what was fetched, not just what a page open in a tab gets
+    let processor = ContentProcessor::new().with_max_html_bytes(MAX_PAGE_BYTES as usize);
+    let page = processor.from_bytes(&bytes, content_type.as_deref())?.with_base(&url);
+    let article = processor.extract_article_from(&page, &url)?;
+    save_article(article, &url, dir, &client)
+}
+
+/// Writes the offline copy of `article`, the page at `url`, in `dir`,
+/// downloading its images with `client`.
+fn save_article(
+    mut article: Article,
+    url: &Url,
+    dir: &Path,
+    client: &reqwest::blocking::Client,
+) -> Result<ReadingItem> {
+    let processor = ContentProcessor::new();
+    article.html = processor.sanitize(&article.html);
+
+    let id = item_id(url);
+    let tmp = dir.join(format!("{}.tmp", id));
+    if tmp.exists() {
+        fs::remove_dir_all(&tmp).with_context(|| format!("Failed to delete {}", tmp.display()))?;
+    }
+    fs::create_dir_all(&tmp).with_context(|| format!("Failed to create {}", tmp.display()))?;
+    let skipped_images = save_images(&mut article.html, url, &tmp, client);
+    let page = pages::reader(&article, &processor.analyze(&article.text), url.as_str());
+    let page_path = tmp.join(PAGE_FILE);
+    fs::write(&page_path, page).with_context(|| format!("Failed to write {}", page_path.display()))?;
+
+    let target = dir.join(&id);
+    if target.exists() {
+        fs::remove_dir_all(&target).with_context(|| format!("Failed to delete {}", target.display()))?;
+    }
+    fs::rename(&tmp, &target).with_context(|| format!("Failed to replace {}", target.display()))?;
+
+    let mut excerpt: String = article.text.chars().take(EXCERPT_CHARS).collect();
+    if excerpt.len() < article.text.len() {
+        excerpt.push('…');
+    }
+    Ok(ReadingItem {
+        id,
+        url: url.clone(),
+        title: if article.title.trim().is_empty() { url.to_string() } else { article.title },
+        byline: article.byline,
+        excerpt,
+        added_at: SystemTime::now(),
+        read: false,
+        snapshot_bytes: dir_size(&target),
+        skipped_images,
+    })
+}
+
+/// Downloads the images of `html`, the article of the page at `url`, into
+/// `dir` with `client` and points it at them. Returns how many were left on
+/// the site.
+fn save_images(html: &mut String, url: &Url, dir: &Path, client: &reqwest::blocking::Client) -> usize {
+    let sources: Vec<Url> = ContentProcessor::new()
+        .extract_images(html, url)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|image| image.url)
+        .filter(|src| matches!(src.scheme(), "http" | "https"))
+        .collect();
+
+    let mut saved = HashMap::new();
+    for (n, src) in sources.iter().enumerate() {
+        match download_image(client, src) {
+            Ok((bytes, extension)) => {
+                let name = format!("image-{}.{}", n + 1, extension);
+                match fs::write(dir.join(&name), bytes) {
+                    Ok(()) => {
+                        saved.insert(src.as_str(), name);
+                    }
+                    Err(e) => log::warn!("Failed to save image {}: {}", src, e),
+                }
+            }
+            Err(e) => log::info!("Leaving image out of the offline copy: {:#}", e),
+        }
+    }
+    for (src, name) in &saved {
+        let attr = |value: &str| format!("src=\"{}\"", pages::escape_html(value));
+        *html = html.replace(&attr(src), &attr(name));
+    }
+    sources.len() - saved.len()
+}
+
+/// The image at `url` with the extension for its type, if it's a raster
+/// image of at most [`MAX_IMAGE_BYTES`].
+fn download_image(client: &reqwest::blocking::Client, url: &Url) -> Result<(Vec<u8>, &'static str)> {
+    let response = client
+        .get(url.as_str())
+        .send()
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch {}", url))?;
+    if response.content_length().is_some_and(|length| length > MAX_IMAGE_BYTES) {
+        bail!("{} is larger than 2 MB", url);
+    }
+    let mut bytes = Vec::new();
+    response.take(MAX_IMAGE_BYTES + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > MAX_IMAGE_BYTES {
+        bail!("{} is larger than 2 MB", url);
+    }
+    // SVG can carry script
+    let extension = match favicon::image_type(&bytes) {
+        Some("image/png") => "png",
+        Some("image/gif") => "gif",
+        Some("image/jpeg") => "jpg",
+        Some("image/webp") => "webp",
+        _ => bail!("{} isn't a raster image", url),
+    };
+    Ok((bytes, extension))
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .map(|entries| entries.flatten().filter_map(|entry| entry.metadata().ok()).map(|m| m.len()).sum())
+        .unwrap_or(0)
+}
@@ -0,0 +1,69 @@
+//! Detecting unclean shutdowns and flushing state when the process panics.
+
+use crate::browser::session::Session;
+use crate::browser::Navigation;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Persisted history, saved again by [`flush`].
+static HISTORY: OnceLock<Arc<Mutex<Navigation>>> = OnceLock::new();
+/// Last session snapshot and where it goes, saved again by [`flush`].
+static SESSION: Mutex<Option<(PathBuf, Session)>> = Mutex::new(None);
+
+/// Creates the running marker at `path`, a file that exists while the
+/// browser is running. Returns whether it was already there, which means
+/// the previous run didn't shut down cleanly.
+pub fn mark_running(path: &Path) -> bool {
+    let crashed = path.exists();
+    if let Err(e) = write_marker(path) {
+        log::warn!("Failed to write {}: {}", path.display(), e);
+    }
+    crashed
+}
+
+fn write_marker(path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, std::process::id().to_string())
+}
+
+/// Removes the running marker at `path` on a clean exit.
+pub fn clear_running(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            log::warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+pub fn track_history(history: Arc<Mutex<Navigation>>) {
+    let _ = HISTORY.set(history);
+}
+
+/// Remembers the latest session snapshot so a panic can still write it.
+pub fn track_session(path: &Path, session: &Session) {
+    if let Ok(mut slot) = SESSION.lock() {
+        *slot = Some((path.to_path_buf(), session.clone()));
+    }
+}
+
+/// Writes the history and last session snapshot to disk. Called from the
+/// panic hook, so it never blocks on a lock the panicking code might hold.
+pub fn flush() {
+    if let Some(history) = HISTORY.get() {
+        if let Ok(nav) = history.try_lock() {
+            if let Err(e) = nav.save() {
+                log::error!("Failed to flush history: {:#}", e);
+            }
+        }
+    }
+    if let Ok(slot) = SESSION.try_lock() {
+        if let Some((path, session)) = slot.as_ref() {
+            if let Err(e) = session.save(path) {
+                log::error!("Failed to flush session: {:#}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::browser::input::{self, InputKind};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Placeholder for the encoded query in a search URL template.
+pub const QUERY_PLACEHOLDER: &str = "%s";
+
+/// A web search engine, e.g. `https://duckduckgo.com/?q=%s`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchEngine {
+    pub name: String,
+    pub url_template: String,
+}
+
+impl SearchEngine {
+    /// Checks that the template has a `%s` and makes a valid http(s) URL.
+    pub fn validate(&self) -> Result<()> {
+        if !self.url_template.contains(QUERY_PLACEHOLDER) {
+            bail!("Search URL template must contain {}", QUERY_PLACEHOLDER);
+        }
+        let url = Url::parse(&self.url_template.replace(QUERY_PLACEHOLDER, "test"))
+            .with_context(|| format!("Invalid search URL template {}", self.url_template))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("Search URL template must be http or https");
+        }
+        Ok(())
+    }
+
+    /// Search results URL for `query`.
+    pub fn search_url(&self, query: &str) -> Result<Url> {
+        let url = self
+            .url_template
+            .replace(QUERY_PLACEHOLDER, &urlencoding::encode(query));
+        Url::parse(&url).with_context(|| format!("Invalid search URL {}", url))
+    }
+
+    /// Where the engine suggests completions of `query`, in the OpenSearch
+    /// suggestions format. Only the built-in engines have one.
+    pub fn suggest_url(&self, query: &str) -> Option<Url> {
+        let template = match Url::parse(&self.url_template).ok()?.host_str()? {
+            "www.google.com" => "https://suggestqueries.google.com/complete/search?client=firefox&q=%s",
+            "duckduckgo.com" => "https://duckduckgo.com/ac/?type=list&q=%s",
+            "www.bing.com" => "https://api.bing.com/osjson.aspx?query=%s",
+            "kagi.com" => "https://kagi.com/api/autosuggest?q=%s",
+            _ => return None,
+        };
+        Url::parse(&template.replace(QUERY_PLACEHOLDER, &urlencoding::encode(query))).ok()
+    }
+
+    pub fn google() -> Self {
+        Self {
+            name: "Google".into(),
+            url_template: "https://www.google.com/search?q=%s".into(),
+        }
+    }
+
+    /// Engines offered in settings.
+    pub fn builtins() -> Vec<SearchEngine> {
+        vec![
+            Self::google(),
+            Self {
+                name: "DuckDuckGo".into(),
+                url_template: "https://duckduckgo.com/?q=%s".into(),
+            },
+            Self {
+                name: "Bing".into(),
+                url_template: "https://www.bing.com/search?q=%s".into(),
+            },
+            Self {
+                name: "Kagi".into(),
+                url_template: "https://kagi.com/search?q=%s".into(),
+            },
+        ]
+    }
+}
+
+impl Default for SearchEngine {
+    fn default() -> Self {
+        Self::google()
+    }
+}
+
+/// Turns address-bar input into a URL, falling back to a search with `engine`.
+pub fn resolve_input(input: &str, engine: &SearchEngine) -> Url {
+    match input::classify(input) {
+        InputKind::Url(url) => url,
+        InputKind::Search(query) => search(&query, engine),
+    }
+}
+
+fn search(query: &str, engine: &SearchEngine) -> Url {
+    engine.search_url(query).unwrap_or_else(|e| {
+        log::warn!("{:#}; searching with {} instead", e, SearchEngine::google().name);
+        SearchEngine::google()
+            .search_url(query)
+            .expect("built-in search template is valid")
+    })
+}
@@ -0,0 +1,111 @@
+use crate::browser::tabs::TabManager;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use url::Url;
+
+/// What to show when the browser starts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StartupMode {
+    /// Reopen the tabs from the last session.
+    #[default]
+    Restore,
+    Home,
+    Blank,
+}
+
+/// Open tabs, saved so the next launch can pick up where this one left off.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Session {
+    pub tabs: Vec<Url>,
+    /// Index into `tabs` of the active tab.
+    pub active: usize,
+}
+
+impl Session {
+    /// Snapshot of the open tabs. Tabs still on their opening URL are saved
+    /// with that; blank tabs are left out.
+    pub fn from_tabs(tabs: &TabManager) -> Self {
+        let active_id = tabs.active().map(|tab| tab.id);
+        let mut session = Session::default();
+        for tab in tabs.iter() {
+            let url = match tab.navigation.current_url() {
+                Some(url) => url.clone(),
+                None => match Url::parse(&tab.opened_url) {
+                    Ok(url) if url.scheme() != "about" => url,
+                    _ => continue,
+                },
+            };
+            if Some(tab.id) == active_id {
+                session.active = session.tabs.len();
+            }
+            session.tabs.push(url);
+        }
+        session
+    }
+
+    /// Reads a session file. A missing or corrupt file yields `None`.
+    pub fn load(path: &Path) -> Option<Self> {
+        let text = fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&text) {
+            Ok(session) => Some(session),
+            Err(e) => {
+                log::warn!("Ignoring corrupt session file {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let json = serde_json::to_string(self).context("Failed to serialize session")?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json).with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+        Ok(())
+    }
+
+    /// URLs to open at startup for `mode`, and the index of the one to activate.
+    /// Always returns at least one URL.
+    pub fn startup_tabs(mode: StartupMode, path: &Path, home_url: &str) -> (Vec<String>, usize) {
+        match mode {
+            StartupMode::Home => (vec![home_url.to_string()], 0),
+            StartupMode::Blank => (vec!["about:blank".to_string()], 0),
+            StartupMode::Restore => Session::load(path)
+                .map(|session| session.restore_tabs(home_url))
+                .unwrap_or_else(|| (vec![home_url.to_string()], 0)),
+        }
+    }
+
+    /// URLs to reopen this session's tabs with, and the index of the active
+    /// one. Local files that no longer exist open the home page instead.
+    pub fn restore_tabs(&self, home_url: &str) -> (Vec<String>, usize) {
+        if self.tabs.is_empty() {
+            return (vec![home_url.to_string()], 0);
+        }
+        let urls = self
+            .tabs
+            .iter()
+            .map(|url| restorable_url(url).unwrap_or_else(|| home_url.to_string()))
+            .collect::<Vec<_>>();
+        let active = self.active.min(urls.len() - 1);
+        (urls, active)
+    }
+}
+
+/// `url` as a string, or `None` for a local file that no longer exists.
+fn restorable_url(url: &Url) -> Option<String> {
+    if url.scheme() == "file" {
+        let path = url.to_file_path().ok()?;
+        if !path.exists() {
+            log::info!("Not restoring {}: file no longer exists", url);
+            return None;
+        }
+    }
+    Some(url.to_string())
+}
@@ -0,0 +1,361 @@
+use crate::ai::AiConfig;
+use crate::browser::blocker::BlockingSettings;
+use crate::browser::external;
+use crate::browser::privacy::PrivacySettings;
+use crate::browser::proxy::ProxySettings;
+use crate::browser::search::SearchEngine;
+use crate::browser::session::StartupMode;
+use crate::browser::shortcuts::KeyPress;
+use crate::browser::site_info::SiteSettings;
+use crate::browser::user_agent::UserAgentSettings;
+use crate::browser::window_state::WindowState;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use toml::{Table, Value};
+use url::Url;
+
+/// User settings, stored as TOML in the platform config directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub search_engine: SearchEngine,
+    /// Page the home button opens; the start page if unset.
+    pub home_page: Option<String>,
+    /// What to open when the browser starts, unless `--startup` says otherwise.
+    pub startup: StartupMode,
+    /// Try `http://` addresses over HTTPS first.
+    pub https_first: bool,
+    /// Ask the search engine for suggestions while typing in the address
+    /// bar, which sends it what's typed. Never in incognito windows.
+    pub search_suggestions: bool,
+    /// Sites allowed to show notifications don't, without losing the
+    /// permission.
+    pub mute_notifications: bool,
+    /// Pages printed or saved as PDF keep their background colors and
+    /// images.
+    pub print_backgrounds: bool,
+    pub privacy: PrivacySettings,
+    pub blocking: BlockingSettings,
+    /// The AI assistant's provider and credentials.
+    pub ai: AiConfig,
+    /// Language of the user interface as a BCP 47 tag, which pages are
+    /// translated into unless another is asked for.
+    pub locale: String,
+    /// The AI sidebar is open.
+    pub sidebar_open: bool,
+    /// Where the window was last left, saved as it's moved and resized.
+    pub window: WindowState,
+    /// The only way to close a `--kiosk` window.
+    pub kiosk_exit: KeyPress,
+    /// Whether links with a scheme the browser doesn't handle, like
+    /// `zoommtg`, open in the system's program for it, as answered the
+    /// first time one was clicked.
+    pub external_schemes: BTreeMap<String, bool>,
+    /// Sites whose scripts or images are turned off, by host.
+    pub sites: BTreeMap<String, SiteSettings>,
+    /// Hosts whose pages may open popups without a click.
+    pub allow_popups: BTreeSet<String>,
+    /// The user agent tabs send, overall and for some sites.
+    pub user_agent: UserAgentSettings,
+    /// The proxy tabs connect through, if not the system's.
+    pub proxy: ProxySettings,
+    /// Keys of the config file that these settings don't have, written
+    /// back as they were so a save doesn't lose them.
+    #[serde(skip)]
+    unknown: Table,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            search_engine: SearchEngine::default(),
+            home_page: None,
+            startup: StartupMode::default(),
+            https_first: true,
+            search_suggestions: false,
+            mute_notifications: false,
+            print_backgrounds: false,
+            privacy: PrivacySettings::default(),
+            blocking: BlockingSettings::default(),
+            ai: AiConfig::default(),
+            locale: "ko".to_string(),
+            sidebar_open: false,
+            window: WindowState::default(),
+            kiosk_exit: KeyPress { key: "q".into(), primary: true, alt: true, shift: true },
+            external_schemes: BTreeMap::new(),
+            sites: BTreeMap::new(),
+            allow_popups: BTreeSet::new(),
+            user_agent: UserAgentSettings::default(),
+            proxy: ProxySettings::default(),
+            unknown: Table::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Loads settings from `path`, writing the defaults there on first run.
+    pub fn load(path: &Path) -> Self {
+        if path.exists() {
+            return Self::load_from(path);
+        }
+        let settings = Self::default();
+        match settings.save_to(path) {
+            Ok(()) => log::info!("Created {}", path.display()),
+            Err(e) => log::warn!("Failed to create the config file: {:#}", e),
+        }
+        settings
+    }
+
+    /// Loads settings from `path`. A missing or unparsable file yields
+    /// defaults; an invalid value yields its default, with a warning.
+    pub fn load_from(path: &Path) -> Self {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return Self::default(),
+        };
+        let table = match text.parse::<Table>() {
+            Ok(table) => table,
+            Err(e) => {
+                log::warn!("Ignoring invalid config file {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+        let valid = match Self::from_table(&table) {
+            Some(_) => table,
+            None => {
+                let mut valid = Table::new();
+                keep_valid(&mut valid, &table, &mut Vec::new(), path);
+                valid
+            }
+        };
+        let mut settings = Self::from_table(&valid).unwrap_or_default();
+        // Whatever deserializing skipped is unknown
+        let known = Table::try_from(&settings).unwrap_or_default();
+        settings.unknown = unknown_keys(&valid, &known);
+        if let Err(e) = settings.search_engine.validate() {
+            log::warn!("Invalid search engine in {}: {:#}", path.display(), e);
+            settings.search_engine = SearchEngine::default();
+        }
+        if let Err(e) = settings.proxy.validate() {
+            log::warn!("Ignoring the proxy in {}: {}", path.display(), e);
+            settings.proxy = ProxySettings::default();
+        }
+        if let Some(home_page) = settings.home_page.as_deref().filter(|url| !is_web_url(url)) {
+            log::warn!("Ignoring home page {:?} in {}: not an http(s) URL", home_page, path.display());
+            settings.home_page = None;
+        }
+        settings
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let mut table = Table::try_from(self).context("Failed to serialize settings")?;
+        add_missing(&mut table, &self.unknown);
+        let text = toml::to_string_pretty(&table).context("Failed to serialize settings")?;
+        fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// The page the home button opens, if one is set.
+    pub fn home_page(&self) -> Option<&str> {
+        self.home_page.as_deref().filter(|url| is_web_url(url))
+    }
+
+    /// What pages on `host` may do.
+    pub fn site(&self, host: &str) -> SiteSettings {
+        self.sites.get(&host.to_ascii_lowercase()).copied().unwrap_or_default()
+    }
+
+    /// Changes what pages on `host` may do. Sites allowed everything
+    /// aren't kept.
+    pub fn set_site(&mut self, host: &str, site: SiteSettings) {
+        let host = host.to_ascii_lowercase();
+        if site == SiteSettings::default() {
+            self.sites.remove(&host);
+        } else {
+            self.sites.insert(host, site);
+        }
+    }
+
+    /// Sets the setting at `key`, like `ai.model`, to `value` from the
+    /// settings page. Empty text unsets optional settings. Fails, leaving
+    /// the settings as they were, for an unknown key or an invalid value.
+    pub fn set(&mut self, key: &str, value: serde_json::Value) -> Result<()> {
+        match key {
+            "home_page" => self.home_page = web_url(value)?,
+            "startup" => self.startup = serde_json::from_value(value).context("Invalid startup mode")?,
+            "search_engine" => {
+                let engine: SearchEngine = serde_json::from_value(value).context("Invalid search engine")?;
+                engine.validate()?;
+                self.search_engine = engine;
+            }
+            "https_first" => self.https_first = flag(value)?,
+            "proxy" => {
+                let mut proxy = serde_json::from_value::<ProxySettings>(value).context("Invalid proxy")?.trimmed();
+                // The page never sees the password, so it stays unless a new one is sent
+                if proxy.password.is_none() && proxy.username.is_some() && proxy.username == self.proxy.username {
+                    proxy.password = self.proxy.password.clone();
+                }
+                proxy.validate()?;
+                self.proxy = proxy;
+            }
+            "search_suggestions" => self.search_suggestions = flag(value)?,
+            "mute_notifications" => self.mute_notifications = flag(value)?,
+            "print_backgrounds" => self.print_backgrounds = flag(value)?,
+            "privacy.strip_tracking_params" => self.privacy.strip_tracking_params = flag(value)?,
+            "privacy.ai_in_incognito" => self.privacy.ai_in_incognito = flag(value)?,
+            "blocking.enabled" => self.blocking.enabled = flag(value)?,
+            "ai.provider" => self.ai.provider = serde_json::from_value(value).context("Unknown AI provider")?,
+            "ai.endpoint" => self.ai.endpoint = web_url(value)?,
+            "ai.model" => self.ai.model = text(value)?,
+            "user_agent.default" => self.user_agent.default = text(value)?,
+            // Empty text or `null` removes the site's override
+            _ if key.starts_with("user_agent.sites.") => {
+                self.user_agent.set_site(&key["user_agent.sites.".len()..], text(value)?)?;
+            }
+            // `null` blocks the host's popups again
+            _ if key.starts_with("allow_popups.") => {
+                let host = key["allow_popups.".len()..].to_ascii_lowercase();
+                match value.as_bool() {
+                    Some(true) => self.allow_popups.insert(host),
+                    Some(false) => self.allow_popups.remove(&host),
+                    None if value.is_null() => self.allow_popups.remove(&host),
+                    None => bail!("Expected true or false"),
+                };
+            }
+            // `null` forgets the answer, so the next link asks again
+            _ if key.starts_with("external_schemes.") => {
+                let scheme = key["external_schemes.".len()..].to_ascii_lowercase();
+                if !external::is_forwardable(&scheme) {
+                    bail!("{}: links can't be opened outside the browser", scheme);
+                }
+                match value {
+                    serde_json::Value::Null => self.external_schemes.remove(&scheme),
+                    value => self.external_schemes.insert(scheme, flag(value)?),
+                };
+            }
+            _ => bail!("Unknown setting {}", key),
+        }
+        Ok(())
+    }
+
+    /// The settings as the UI sees them: all but the API key and the
+    /// proxy's password.
+    pub fn to_ui_json(&self) -> serde_json::Value {
+        let mut json = serde_json::to_value(self).unwrap_or_default();
+        if let Some(ai) = json.get_mut("ai").and_then(|ai| ai.as_object_mut()) {
+            ai.remove("api_key");
+        }
+        if let Some(proxy) = json.get_mut("proxy").and_then(|proxy| proxy.as_object_mut()) {
+            let has_password = proxy.remove("password").is_some_and(|password| !password.is_null());
+            proxy.insert("has_password".into(), has_password.into());
+        }
+        json
+    }
+
+    fn from_table(table: &Table) -> Option<Self> {
+        Value::Table(table.clone()).try_into().ok()
+    }
+}
+
+fn is_web_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+fn flag(value: serde_json::Value) -> Result<bool> {
+    value.as_bool().context("Expected true or false")
+}
+
+/// Trimmed text of `value`; `None` if it's empty or null.
+fn text(value: serde_json::Value) -> Result<Option<String>> {
+    match value {
+        serde_json::Value::Null => Ok(None),
+        serde_json::Value::String(text) => Ok(Some(text.trim().to_string()).filter(|text| !text.is_empty())),
+        _ => bail!("Expected text"),
+    }
+}
+
+/// Like [`text`], but the text must be an http(s) URL.
+fn web_url(value: serde_json::Value) -> Result<Option<String>> {
+    let url = text(value)?;
+    if let Some(url) = url.as_deref().filter(|url| !is_web_url(url)) {
+        bail!("{} is not an http(s) URL", url);
+    }
+    Ok(url)
+}
+
+/// Copies the values of `table`, found at `path` in the config file at
+/// `file`, into `valid` as long as the settings stay valid. Sections with
+/// an invalid value are copied key by key.
+fn keep_valid(valid: &mut Table, table: &Table, path: &mut Vec<String>, file: &Path) {
+    for (key, value) in table {
+        path.push(key.clone());
+        let mut candidate = valid.clone();
+        insert_at(&mut candidate, path, value.clone());
+        if Settings::from_table(&candidate).is_some() {
+            *valid = candidate;
+        } else if let Value::Table(section) = value {
+            keep_valid(valid, section, path, file);
+        } else {
+            log::warn!("Ignoring invalid setting {} in {}", path.join("."), file.display());
+        }
+        path.pop();
+    }
+}
+
+/// Sets the value at `path` in `table`, adding the tables on the way.
+fn insert_at(table: &mut Table, path: &[String], value: Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut table = table;
+    for key in parents {
+        let entry = table.entry(key.clone()).or_insert_with(|| Value::Table(Table::new()));
+        if !entry.is_table() {
+            *entry = Value::Table(Table::new());
+        }
+        let Value::Table(next) = entry else {
+            return;
+        };
+        table = next;
+    }
+    table.insert(last.clone(), value);
+}
+
+/// The keys of `table` that aren't in `known`, with their values.
+fn unknown_keys(table: &Table, known: &Table) -> Table {
+    let mut unknown = Table::new();
+    for (key, value) in table {
+        match (known.get(key), value) {
+            (None, _) => {
+                unknown.insert(key.clone(), value.clone());
+            }
+            (Some(Value::Table(known)), Value::Table(section)) => {
+                let section = unknown_keys(section, known);
+                if !section.is_empty() {
+                    unknown.insert(key.clone(), Value::Table(section));
+                }
+            }
+            _ => {}
+        }
+    }
+    unknown
+}
+
+/// Adds the keys of `extra` that `table` doesn't have.
+fn add_missing(table: &mut Table, extra: &Table) {
+    for (key, value) in extra {
+        match (table.get_mut(key), value) {
+            (None, _) => {
+                table.insert(key.clone(), value.clone());
+            }
+            (Some(Value::Table(section)), Value::Table(extra)) => add_missing(section, extra),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Keyboard shortcuts. The webviews keep key presses to themselves, so a
+//! script in each webview reports bound key combinations and Rust decides
+//! what they do. The mouse's back and forward buttons and horizontal
+//! trackpad swipes are reported as the Browser Back and Forward keys.
+
+use serde::{Deserialize, Serialize};
+
+/// What a shortcut does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shortcut {
+    FocusAddress,
+    Reload,
+    Back,
+    Forward,
+    Home,
+    Find,
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+    Devtools,
+    /// Asks the AI assistant about the selected text.
+    AskSelection,
+    /// Opens or closes the AI sidebar.
+    ToggleSidebar,
+    /// Outlines the webviews with their computed bounds, for debugging
+    /// the layout.
+    LayoutOverlay,
+    Fullscreen,
+    /// Opens a local file the user picks in the active tab.
+    OpenFile,
+    /// Saves the page as one HTML file.
+    SavePage,
+    /// Saves the page as a PDF, or opens the print dialog where that
+    /// needs one.
+    SavePdf,
+    NewIncognitoWindow,
+    Quit,
+}
+
+/// A key combination. `primary` is Cmd on macOS and Ctrl elsewhere; `key`
+/// is the DOM `KeyboardEvent.key`, lowercased for single characters. With
+/// Alt held, letters are taken from the physical key, since macOS turns
+/// Option+letter into other characters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyPress {
+    pub key: String,
+    #[serde(default)]
+    pub primary: bool,
+    #[serde(default)]
+    pub alt: bool,
+    #[serde(default)]
+    pub shift: bool,
+}
+
+struct Binding {
+    key: &'static str,
+    primary: bool,
+    alt: bool,
+    shift: bool,
+    shortcut: Shortcut,
+    /// Whether it also fires while a text field has focus.
+    in_text_fields: bool,
+}
+
+const fn bind(key: &'static str, primary: bool, alt: bool, shortcut: Shortcut, in_text_fields: bool) -> Binding {
+    Binding {
+        key,
+        primary,
+        alt,
+        shift: false,
+        shortcut,
+        in_text_fields,
+    }
+}
+
+const BINDINGS: &[Binding] = &[
+    bind("l", true, false, Shortcut::FocusAddress, true),
+    bind("F6", false, false, Shortcut::FocusAddress, true),
+    bind("r", true, false, Shortcut::Reload, true),
+    bind("F5", false, false, Shortcut::Reload, true),
+    // Alt+arrows move by word in text fields on macOS
+    bind("[", true, false, Shortcut::Back, false),
+    bind("ArrowLeft", false, true, Shortcut::Back, false),
+    bind("]", true, false, Shortcut::Forward, false),
+    bind("ArrowRight", false, true, Shortcut::Forward, false),
+    bind("BrowserBack", false, false, Shortcut::Back, false),
+    bind("BrowserForward", false, false, Shortcut::Forward, false),
+    bind("Home", false, true, Shortcut::Home, false),
+    bind("f", true, false, Shortcut::Find, true),
+    bind("o", true, false, Shortcut::OpenFile, true),
+    bind("s", true, false, Shortcut::SavePage, true),
+    bind("p", true, false, Shortcut::SavePdf, true),
+    bind("=", true, false, Shortcut::ZoomIn, true),
+    bind("-", true, false, Shortcut::ZoomOut, true),
+    bind("0", true, false, Shortcut::ResetZoom, true),
+    bind("F12", false, false, Shortcut::Devtools, true),
+    bind("F11", false, false, Shortcut::Fullscreen, true),
+    bind("i", true, true, Shortcut::Devtools, true),
+    Binding {
+        key: "i",
+        primary: true,
+        alt: false,
+        shift: true,
+        shortcut: Shortcut::Devtools,
+        in_text_fields: true,
+    },
+    Binding {
+        key: "a",
+        primary: true,
+        alt: false,
+        shift: true,
+        shortcut: Shortcut::AskSelection,
+        in_text_fields: false,
+    },
+    Binding {
+        key: "l",
+        primary: true,
+        alt: false,
+        shift: true,
+        shortcut: Shortcut::ToggleSidebar,
+        in_text_fields: true,
+    },
+    Binding {
+        key: "n",
+        primary: true,
+        alt: false,
+        shift: true,
+        shortcut: Shortcut::NewIncognitoWindow,
+        in_text_fields: true,
+    },
+    Binding {
+        key: "d",
+        primary: true,
+        alt: true,
+        shift: true,
+        shortcut: Shortcut::LayoutOverlay,
+        in_text_fields: true,
+    },
+    bind("q", true, false, Shortcut::Quit, true),
+];
+
+impl KeyPress {
+    /// The shortcut bound to this key combination, if any.
+    pub fn shortcut(&self) -> Option<Shortcut> {
+        BINDINGS
+            .iter()
+            .find(|b| b.key == self.key && b.primary == self.primary && b.alt == self.alt && b.shift == self.shift)
+            .map(|b| b.shortcut)
+    }
+}
+
+/// Injected into every webview. Reports bound key combinations, and
+/// `extra` ones, with the `shortcut` IPC op and keeps them from reaching
+/// the page.
+pub fn script(extra: &[KeyPress]) -> String {
+    let binding = |key: &str, primary: bool, alt: bool, shift: bool, text: bool| {
+        serde_json::json!({ "key": key, "primary": primary, "alt": alt, "shift": shift, "text": text })
+    };
+    let bindings: Vec<serde_json::Value> = BINDINGS
+        .iter()
+        .map(|b| binding(b.key, b.primary, b.alt, b.shift, b.in_text_fields))
+        .chain(extra.iter().map(|press| binding(&press.key, press.primary, press.alt, press.shift, true)))
+        .collect();
+    let primary = if cfg!(target_os = "macos") { "metaKey" } else { "ctrlKey" };
+    SCRIPT
+        .replace("__BINDINGS__", &serde_json::Value::Array(bindings).to_string())
+        .replace("__PRIMARY__", primary)
+}
+
+const SCRIPT: &str = r#"
+(function() {
+  if (window.top !== window || window.__syncfloShortcuts) return;
+  window.__syncfloShortcuts = true;
+  var BINDINGS = __BINDINGS__;
+  function inTextField() {
+    var el = document.activeElement;
+    while (el && el.shadowRoot && el.shadowRoot.activeElement) el = el.shadowRoot.activeElement;
+    if (!el) return false;
+    if (el.isContentEditable || el.tagName === 'TEXTAREA' || el.tagName === 'SELECT') return true;
+    return el.tagName === 'INPUT' &&
+      ['button', 'checkbox', 'radio', 'range', 'color', 'file', 'submit', 'reset', 'image'].indexOf(el.type) === -1;
+  }
+  window.addEventListener('keydown', function(e) {
+    var letter = e.altKey && /^Key[A-Z]$/.test(e.code);
+    var press = {
+      key: letter ? e.code.slice(3).toLowerCase() : e.key.length === 1 ? e.key.toLowerCase() : e.key,
+      primary: e.__PRIMARY__, alt: e.altKey, shift: e.shiftKey
+    };
+    var binding = BINDINGS.find(function(b) {
+      return b.key === press.key && b.primary === press.primary && b.alt === press.alt && b.shift === press.shift;
+    });
+    if (!binding || (!binding.text && inTextField())) return;
+    e.preventDefault();
+    e.stopPropagation();
+    send(press);
+  }, true);
+  function send(press) {
+    try { window.ipc.postMessage(JSON.stringify({ op: 'shortcut', payload: press })); } catch (err) {}
+  }
+  function navigate(back) {
+    if (inTextField()) return;
+    send({ key: back ? 'BrowserBack' : 'BrowserForward', primary: false, alt: false, shift: false });
+  }
+  // Mouse buttons 3 and 4 are back and forward; the page doesn't get them
+  ['mousedown', 'mouseup', 'auxclick'].forEach(function(type) {
+    window.addEventListener(type, function(e) {
+      if (e.button !== 3 && e.button !== 4) return;
+      e.preventDefault();
+      e.stopPropagation();
+      if (type === 'mouseup') navigate(e.button === 3);
+    }, true);
+  });
+  // A horizontal swipe that nothing under the pointer scrolls with. Counts
+  // once per gesture, which ends after a pause in wheel events.
+  var SWIPE_DISTANCE = 150;
+  var swipe = 0, swiped = false, swipeTimer = null;
+  function scrollsSideways(el, dx) {
+    for (; el && el !== document; el = el.parentNode) {
+      if (el.nodeType !== 1 || el.scrollWidth <= el.clientWidth) continue;
+      var overflow = getComputedStyle(el).overflowX;
+      if (el !== document.scrollingElement && overflow !== 'auto' && overflow !== 'scroll') continue;
+      if (dx < 0 ? el.scrollLeft > 0 : el.scrollLeft + el.clientWidth < el.scrollWidth) return true;
+    }
+    return false;
+  }
+  window.addEventListener('wheel', function(e) {
+    clearTimeout(swipeTimer);
+    swipeTimer = setTimeout(function() { swipe = 0; swiped = false; }, 200);
+    if (swiped || e.ctrlKey || Math.abs(e.deltaX) <= Math.abs(e.deltaY) || scrollsSideways(e.target, e.deltaX)) return;
+    swipe += e.deltaX;
+    if (Math.abs(swipe) < SWIPE_DISTANCE) return;
+    swiped = true;
+    navigate(swipe < 0);
+  }, { capture: true, passive: true });
+})();
+"#;
@@ -0,0 +1,168 @@
+//! One browser process per profile. The first launch listens on a local
+//! endpoint in the profile; later launches send it their URLs and exit, so
+//! the profile's history and session files have a single writer.
+//!
+//! The endpoint is a Unix domain socket, or on Windows a loopback TCP port
+//! written to a file. Either may be left over from a process that crashed;
+//! nobody answers there then, and the next launch takes it over.
+
+use crate::browser::ipc::AppEvent;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use tao::event_loop::EventLoopProxy;
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener as Listener, UnixStream as Stream};
+#[cfg(not(unix))]
+use std::net::{TcpListener as Listener, TcpStream as Stream};
+
+/// What a later launch sends to the running one, as a line of JSON.
+#[derive(Debug, Serialize, Deserialize)]
+struct Launch {
+    /// URLs, or absolute paths of local files, to open as tabs. Empty if
+    /// the window should just come to the front.
+    urls: Vec<String>,
+}
+
+/// The result of [`claim`].
+pub enum Claim {
+    /// This process runs the profile and listens for later launches.
+    Primary(Instance),
+    /// Another process runs the profile and was handed the URLs.
+    Forwarded,
+}
+
+/// The endpoint of the process running a profile.
+pub struct Instance {
+    path: PathBuf,
+    listener: Listener,
+}
+
+/// Makes this process the one running the profile whose endpoint is at
+/// `path`, or hands `urls` to the process that already is.
+pub fn claim(path: &Path, urls: &[String]) -> Result<Claim> {
+    let launch = Launch { urls: urls.iter().map(|url| absolute(url)).collect() };
+    if forward(path, &launch) {
+        return Ok(Claim::Forwarded);
+    }
+    match bind(path) {
+        Ok(listener) => Ok(Claim::Primary(Instance { path: path.to_path_buf(), listener })),
+        // Another launch took the endpoint over in the meantime
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse && forward(path, &launch) => Ok(Claim::Forwarded),
+        Err(e) => Err(e).with_context(|| format!("Failed to listen at {}", path.display())),
+    }
+}
+
+impl Instance {
+    /// Passes the URLs of later launches to the event loop behind `proxy`
+    /// as [`AppEvent::Launched`]. Returns the endpoint's path, for
+    /// [`release`].
+    pub fn serve(self, proxy: EventLoopProxy<AppEvent>) -> PathBuf {
+        let Self { path, listener } = self;
+        let spawned = std::thread::Builder::new().name("single-instance".into()).spawn(move || {
+            for stream in listener.incoming() {
+                let launch = stream.and_then(|stream| {
+                    let mut line = String::new();
+                    BufReader::new(stream).read_line(&mut line)?;
+                    serde_json::from_str::<Launch>(&line).map_err(io::Error::other)
+                });
+                match launch {
+                    Ok(launch) => {
+                        if proxy.send_event(AppEvent::Launched { urls: launch.urls }).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => log::warn!("Ignoring a launch that couldn't be read: {}", e),
+                }
+            }
+        });
+        if let Err(e) = spawned {
+            log::warn!("Failed to listen for later launches: {}", e);
+        }
+        path
+    }
+}
+
+/// Removes the endpoint at `path` on a clean exit.
+pub fn release(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != io::ErrorKind::NotFound {
+            log::warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Sends `launch` to the process listening at `path`. Returns whether one
+/// was there to take it.
+fn forward(path: &Path, launch: &Launch) -> bool {
+    let sent = connect(path).and_then(|mut stream| {
+        let mut line = serde_json::to_string(launch).map_err(io::Error::other)?;
+        line.push('\n');
+        stream.write_all(line.as_bytes())?;
+        stream.flush()
+    });
+    match sent {
+        Ok(()) => {
+            log::info!("Handed {} URLs to the browser already running this profile", launch.urls.len());
+            true
+        }
+        Err(e) => {
+            log::debug!("No browser answered at {}: {}", path.display(), e);
+            false
+        }
+    }
+}
+
+#[cfg(unix)]
+fn connect(path: &Path) -> io::Result<Stream> {
+    Stream::connect(path)
+}
+
+#[cfg(unix)]
+fn bind(path: &Path) -> io::Result<Listener> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Nobody answered there, so a socket file is left over from a crash
+    match Listener::bind(path) {
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+            log::info!("Taking over the endpoint left at {}", path.display());
+            fs::remove_file(path)?;
+            Listener::bind(path)
+        }
+        result => result,
+    }
+}
+
+#[cfg(not(unix))]
+fn connect(path: &Path) -> io::Result<Stream> {
+    let port: u16 = fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let address = std::net::SocketAddr::from(([127, 0, 0, 1], port));
+    Stream::connect_timeout(&address, std::time::Duration::from_secs(1))
+}
+
+#[cfg(not(unix))]
+fn bind(path: &Path) -> io::Result<Listener> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // A port file left over from a crash is simply overwritten
+    let listener = Listener::bind(("127.0.0.1", 0))?;
+    fs::write(path, listener.local_addr()?.port().to_string())?;
+    Ok(listener)
+}
+
+/// `arg` from the command line with a relative file path made absolute,
+/// since the running process has another working directory.
+fn absolute(arg: &str) -> String {
+    match Path::new(arg).canonicalize() {
+        Ok(path) => path.display().to_string(),
+        Err(_) => arg.to_string(),
+    }
+}
@@ -0,0 +1,129 @@
+//! How secure the connection to the current page is, for the chip in the
+//! address bar and the panel it opens, and what the site may run.
+
+use crate::browser::assets;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Whether the webview can turn scripts and images off per site. Only
+/// WebKitGTK can, between page loads; elsewhere the panel has no toggles.
+pub const SITE_SETTINGS_SUPPORTED: bool = cfg!(target_os = "linux");
+
+/// What the address bar chip shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Security {
+    /// HTTPS with a trusted certificate and nothing loaded over HTTP.
+    Secure,
+    /// HTTPS, but the page loaded scripts or images over plain HTTP.
+    MixedContent,
+    /// HTTPS with a certificate that wasn't trusted.
+    CertificateError,
+    /// Plain HTTP.
+    Insecure,
+    /// A local file.
+    File,
+    /// One of the browser's own pages.
+    Internal,
+    /// A `data:` or `blob:` URL, whose content came from another page.
+    Data,
+    /// A blank tab, or a scheme none of the others cover.
+    Other,
+}
+
+/// What the webview reported about the connection of the current page.
+/// Only WebKitGTK reports anything; elsewhere this stays empty and the
+/// chip goes by the scheme.
+#[derive(Debug, Clone, Default)]
+pub struct PageSecurity {
+    /// The page loaded scripts or images over plain HTTP.
+    pub mixed_content: bool,
+    /// What was wrong with the certificate, like `expired`.
+    pub certificate_errors: Vec<&'static str>,
+    /// The server's certificate in PEM.
+    pub certificate_pem: Option<String>,
+}
+
+/// What pages of a site may do, from the site info panel. Changes take
+/// effect on the next load.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SiteSettings {
+    /// The page's own scripts run. The browser's scripts always do.
+    pub javascript: bool,
+    pub images: bool,
+}
+
+impl Default for SiteSettings {
+    fn default() -> Self {
+        Self { javascript: true, images: true }
+    }
+}
+
+/// Reply to `site_info`: the details behind the chip.
+#[derive(Debug, Clone, Serialize)]
+pub struct SiteInfo {
+    pub url: String,
+    pub scheme: String,
+    pub host: Option<String>,
+    pub security: Security,
+    pub mixed_content: bool,
+    pub certificate_errors: Vec<&'static str>,
+    pub certificate_pem: Option<String>,
+    /// The site's settings, if it has a host and they can be changed.
+    pub settings: Option<SiteSettings>,
+}
+
+impl SiteInfo {
+    pub fn new(url: &Url, page: &PageSecurity, settings: Option<SiteSettings>) -> Self {
+        Self {
+            url: url.to_string(),
+            scheme: url.scheme().to_string(),
+            host: url.host_str().map(str::to_string),
+            security: security(Some(url), page),
+            mixed_content: page.mixed_content,
+            certificate_errors: page.certificate_errors.clone(),
+            certificate_pem: page.certificate_pem.clone(),
+            settings: settings.filter(|_| SITE_SETTINGS_SUPPORTED),
+        }
+    }
+}
+
+/// The chip for a tab on `url`, or without a page.
+pub fn security(url: Option<&Url>, page: &PageSecurity) -> Security {
+    let Some(url) = url else {
+        return Security::Other;
+    };
+    if assets::is_internal_url(url.as_str()) {
+        return Security::Internal;
+    }
+    match url.scheme() {
+        "https" if !page.certificate_errors.is_empty() => Security::CertificateError,
+        "https" if page.mixed_content => Security::MixedContent,
+        "https" => Security::Secure,
+        "http" => Security::Insecure,
+        "file" => Security::File,
+        "data" | "blob" => Security::Data,
+        _ => Security::Other,
+    }
+}
+
+/// Names of the problems GIO found with a certificate.
+#[cfg(target_os = "linux")]
+pub fn certificate_errors(flags: gtk::gio::TlsCertificateFlags) -> Vec<&'static str> {
+    use gtk::gio::TlsCertificateFlags;
+
+    [
+        (TlsCertificateFlags::UNKNOWN_CA, "unknown_ca"),
+        (TlsCertificateFlags::BAD_IDENTITY, "bad_identity"),
+        (TlsCertificateFlags::NOT_ACTIVATED, "not_activated"),
+        (TlsCertificateFlags::EXPIRED, "expired"),
+        (TlsCertificateFlags::REVOKED, "revoked"),
+        (TlsCertificateFlags::INSECURE, "insecure"),
+        (TlsCertificateFlags::GENERIC_ERROR, "generic_error"),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| flags.contains(*flag))
+    .map(|(_, name)| name)
+    .collect()
+}
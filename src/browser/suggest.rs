@@ -0,0 +1,99 @@
+//! Address bar suggestions: pages from history and bookmarks and, with
+//! `search_suggestions` on, queries the search engine suggests.
+
+use crate::browser::bookmarks::BookmarkStore;
+use crate::browser::search::SearchEngine;
+use crate::browser::Navigation;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+
+const HISTORY_SUGGESTIONS: usize = 6;
+const BOOKMARK_SUGGESTIONS: usize = 3;
+const SEARCH_SUGGESTIONS: usize = 4;
+/// Pause in typing before the search engine is asked.
+pub const SEARCH_DELAY: Duration = Duration::from_millis(200);
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionKind {
+    History,
+    Bookmark,
+    /// A query the search engine suggested.
+    Search,
+}
+
+/// An entry of the address bar's dropdown.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub kind: SuggestionKind,
+    pub title: String,
+    /// Where choosing it goes.
+    pub url: String,
+    /// Frecency of history pages; 0 for the others, which follow them.
+    pub score: f64,
+}
+
+/// History and bookmark suggestions for `input`: the matching history
+/// pages, most frecent first, then bookmarks that aren't among them.
+pub fn local(history: &Navigation, bookmarks: &BookmarkStore, input: &str) -> Vec<Suggestion> {
+    let mut pages: Vec<Suggestion> = history
+        .search(input, HISTORY_SUGGESTIONS)
+        .into_iter()
+        .map(|entry| Suggestion {
+            kind: SuggestionKind::History,
+            title: entry.title.clone().unwrap_or_default(),
+            url: entry.url.to_string(),
+            score: entry.frecency(),
+        })
+        .collect();
+    pages.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let saved: Vec<Suggestion> = bookmarks
+        .search(input, BOOKMARK_SUGGESTIONS + pages.len())
+        .into_iter()
+        .filter(|bookmark| !pages.iter().any(|page| page.url == bookmark.url.as_str()))
+        .take(BOOKMARK_SUGGESTIONS)
+        .map(|bookmark| Suggestion {
+            kind: SuggestionKind::Bookmark,
+            title: bookmark.title.clone(),
+            url: bookmark.url.to_string(),
+            score: 0.0,
+        })
+        .collect();
+    pages.extend(saved);
+    pages
+}
+
+/// Queries `engine` suggests for `input`, asked with `client`, each
+/// searching for itself. Fails for engines without a suggestion service.
+pub async fn search(client: &reqwest::Client, engine: &SearchEngine, input: &str) -> Result<Vec<Suggestion>> {
+    let url = engine.suggest_url(input).with_context(|| format!("{} doesn't suggest queries", engine.name))?;
+    let body: Value = client
+        .get(url)
+        .timeout(SEARCH_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .context("Failed to ask for search suggestions")?
+        .json()
+        .await
+        .context("Invalid search suggestions")?;
+    // OpenSearch suggestions: [query, [completion, ...], ...]
+    let queries = body.get(1).and_then(Value::as_array).context("Invalid search suggestions")?;
+    Ok(queries
+        .iter()
+        .filter_map(Value::as_str)
+        .filter(|query| *query != input.trim())
+        .filter_map(|query| {
+            Some(Suggestion {
+                kind: SuggestionKind::Search,
+                title: query.to_string(),
+                url: engine.search_url(query).ok()?.into(),
+                score: 0.0,
+            })
+        })
+        .take(SEARCH_SUGGESTIONS)
+        .collect())
+}
@@ -0,0 +1,674 @@
+use crate::ai::Conversation;
+use crate::browser::dialogs::{self, SaveAs};
+use crate::browser::https_first::HttpsFirst;
+use crate::browser::ipc::WebViewHandle;
+use crate::browser::proxy::ProxySettings;
+use crate::browser::site_info::PageSecurity;
+use crate::browser::Navigation;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use wry::{Rect, WebView};
+
+pub type TabId = u32;
+
+/// A content webview that can be shown as a tab.
+pub trait TabView: WebViewHandle {
+    fn set_visible(&self, visible: bool);
+    fn set_bounds(&self, bounds: Rect);
+    /// Clears cookies and/or caches modified within `since` (everything if
+    /// `None`). Webviews share one data store, so any tab's view will do.
+    fn clear_website_data(&self, cookies: bool, cache: bool, since: Option<Duration>) -> anyhow::Result<()>;
+    /// Stops the page that's loading, if any.
+    fn stop_loading(&self);
+    /// Sets the page zoom, where `1.0` is 100%.
+    fn set_zoom(&self, level: f64);
+    /// Opens the web inspector, or closes it if it's open. Returns `false`
+    /// if devtools aren't compiled in.
+    fn toggle_devtools(&self) -> bool;
+    /// Runs `js` in the page and passes its JSON-encoded result to `callback`.
+    fn evaluate_script_with_callback(&self, js: &str, callback: Box<dyn Fn(String) + Send>);
+    /// Asks the user whether to leave the page, then calls `done` with the answer.
+    fn confirm_leave(&self, done: Box<dyn FnOnce(bool)>);
+    /// Asks the user whether to open `scheme:` links with the system, then
+    /// calls `done` with the answer.
+    fn confirm_external(&self, scheme: &str, done: Box<dyn FnOnce(bool)>);
+    /// Asks the user which file to open, starting in `dir`, then calls
+    /// `done` with the path, or `None` if they cancelled.
+    fn choose_file(&self, dir: &Path, done: Box<dyn FnOnce(Option<PathBuf>)>);
+    /// Asks the user where to save `kind`, starting in `dir` with
+    /// `file_name`, then calls `done` with the path, or `None` if they
+    /// cancelled.
+    fn choose_save_path(&self, kind: SaveAs, dir: &Path, file_name: &str, done: Box<dyn FnOnce(Option<PathBuf>)>);
+    /// The `Cookie` header the webview would send with a request to `url`,
+    /// if it has cookies for it.
+    fn cookie_header(&self, url: &str) -> Option<String>;
+    /// Downloads `url` as if the page linked to it with `download`.
+    fn download(&self, url: &str);
+    /// Prints the whole page to a PDF at `path` without a dialog, with
+    /// background colors and images if `backgrounds`, then calls `done`
+    /// with whether it worked. Only WebKitGTK can; elsewhere use
+    /// [`TabView::print`].
+    fn print_to_pdf(&self, path: &Path, backgrounds: bool, done: Box<dyn FnOnce(Result<(), String>)>);
+    /// Opens the system's print dialog for the page, which can also save
+    /// it as a PDF. `backgrounds` prints background colors and images.
+    fn print(&self, backgrounds: bool);
+    /// Sends `user_agent`, or the webview's own with `None`, from the next
+    /// request on. Only WebKitGTK can change it once the webview exists;
+    /// elsewhere a tab keeps the one it was opened with.
+    fn set_user_agent(&self, user_agent: Option<&str>);
+    /// Routes the tab's requests through `proxy`. Only WebKitGTK can change
+    /// it once the webview exists; elsewhere it's set when the tab opens.
+    fn set_proxy(&self, proxy: &ProxySettings);
+}
+
+impl TabView for WebView {
+    fn set_visible(&self, visible: bool) {
+        if let Err(e) = WebView::set_visible(self, visible) {
+            log::warn!("Failed to change tab visibility: {}", e);
+        }
+    }
+
+    fn set_bounds(&self, bounds: Rect) {
+        if let Err(e) = WebView::set_bounds(self, bounds) {
+            log::warn!("Failed to resize tab: {}", e);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn clear_website_data(&self, cookies: bool, cache: bool, since: Option<Duration>) -> anyhow::Result<()> {
+        use gtk::glib::TimeSpan;
+        use webkit2gtk::{WebContextExt, WebViewExt, WebsiteDataManagerExtManual, WebsiteDataTypes};
+        use wry::WebViewExtUnix;
+
+        let mut types = WebsiteDataTypes::empty();
+        if cookies {
+            types |= WebsiteDataTypes::COOKIES;
+        }
+        if cache {
+            types |= WebsiteDataTypes::MEMORY_CACHE
+                | WebsiteDataTypes::DISK_CACHE
+                | WebsiteDataTypes::OFFLINE_APPLICATION_CACHE;
+        }
+        if types.is_empty() {
+            return Ok(());
+        }
+        let manager = WebViewExtUnix::webview(self)
+            .context()
+            .and_then(|context| context.website_data_manager())
+            .ok_or_else(|| anyhow::anyhow!("No website data manager"))?;
+        // A zero timespan means "since the beginning of time"
+        let timespan = since.map_or(TimeSpan::from_seconds(0), |d| {
+            TimeSpan::from_seconds(d.as_secs().min(i64::MAX as u64) as i64)
+        });
+        manager.clear(types, timespan, None::<&gtk::gio::Cancellable>, |result| {
+            if let Err(e) = result {
+                log::warn!("Failed to clear website data: {}", e);
+            }
+        });
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn clear_website_data(&self, cookies: bool, cache: bool, since: Option<Duration>) -> anyhow::Result<()> {
+        if !cookies && !cache {
+            return Ok(());
+        }
+        // wry only offers an all-or-nothing wipe on the other platforms
+        if since.is_some() {
+            anyhow::bail!("Clearing cookies and cache by time range isn't supported on this platform");
+        }
+        self.clear_all_browsing_data()?;
+        Ok(())
+    }
+
+    fn set_zoom(&self, level: f64) {
+        if let Err(e) = self.zoom(level) {
+            log::warn!("Failed to zoom tab: {}", e);
+        }
+    }
+
+    #[cfg(any(debug_assertions, feature = "devtools"))]
+    fn toggle_devtools(&self) -> bool {
+        if self.is_devtools_open() {
+            self.close_devtools();
+        } else {
+            self.open_devtools();
+        }
+        true
+    }
+
+    #[cfg(not(any(debug_assertions, feature = "devtools")))]
+    fn toggle_devtools(&self) -> bool {
+        false
+    }
+
+    fn evaluate_script_with_callback(&self, js: &str, callback: Box<dyn Fn(String) + Send>) {
+        if let Err(e) = WebView::evaluate_script_with_callback(self, js, callback) {
+            log::warn!("Failed to evaluate script: {}", e);
+        }
+    }
+
+    fn confirm_leave(&self, done: Box<dyn FnOnce(bool)>) {
+        dialogs::confirm_leave(self, done);
+    }
+
+    fn confirm_external(&self, scheme: &str, done: Box<dyn FnOnce(bool)>) {
+        dialogs::confirm_external(self, scheme, done);
+    }
+
+    fn choose_file(&self, dir: &Path, done: Box<dyn FnOnce(Option<PathBuf>)>) {
+        dialogs::choose_file(self, dir, done);
+    }
+
+    fn choose_save_path(&self, kind: SaveAs, dir: &Path, file_name: &str, done: Box<dyn FnOnce(Option<PathBuf>)>) {
+        dialogs::choose_save_path(self, kind, dir, file_name, done);
+    }
+
+    fn cookie_header(&self, url: &str) -> Option<String> {
+        let cookies = match self.cookies_for_url(url) {
+            Ok(cookies) => cookies,
+            Err(e) => {
+                log::warn!("Failed to read cookies for {}: {}", url, e);
+                return None;
+            }
+        };
+        let pairs: Vec<String> =
+            cookies.iter().map(|cookie| format!("{}={}", cookie.name(), cookie.value())).collect();
+        (!pairs.is_empty()).then(|| pairs.join("; "))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn download(&self, url: &str) {
+        use webkit2gtk::WebViewExt;
+        use wry::WebViewExtUnix;
+
+        WebViewExtUnix::webview(self).download_uri(url);
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn download(&self, url: &str) {
+        // wry has no download API. Pages can only force downloads of
+        // same-origin URLs; others open in the tab instead.
+        let js = format!(
+            "(function() {{ var a = document.createElement('a'); a.href = {}; a.download = ''; \
+             document.body.appendChild(a); a.click(); a.remove(); }})()",
+            serde_json::Value::String(url.to_string())
+        );
+        WebViewHandle::evaluate_script(self, &js);
+    }
+
+    #[cfg(target_os = "linux")]
+    fn print_to_pdf(&self, path: &Path, backgrounds: bool, done: Box<dyn FnOnce(Result<(), String>)>) {
+        use std::cell::Cell;
+        use webkit2gtk::{PrintOperation, PrintOperationExt, SettingsExt, WebViewExt};
+        use wry::WebViewExtUnix;
+
+        let webview = WebViewExtUnix::webview(self);
+        let uri = match gtk::glib::filename_to_uri(path, None) {
+            Ok(uri) => uri,
+            Err(e) => return done(Err(e.to_string())),
+        };
+        if let Some(settings) = WebViewExt::settings(&webview) {
+            settings.set_print_backgrounds(backgrounds);
+        }
+        // GTK's own "Print to File" printer writes the PDF
+        let print_settings = gtk::PrintSettings::new();
+        print_settings.set_printer("Print to File");
+        print_settings.set(gtk::PRINT_SETTINGS_OUTPUT_FILE_FORMAT, Some("pdf"));
+        print_settings.set(gtk::PRINT_SETTINGS_OUTPUT_URI, Some(uri.as_str()));
+        let operation = PrintOperation::new(&webview);
+        operation.set_print_settings(&print_settings);
+
+        // `failed` comes before `finished`, which follows either way
+        let done = Rc::new(Cell::new(Some(done)));
+        let failed = done.clone();
+        operation.connect_failed(move |_, error| {
+            if let Some(done) = failed.take() {
+                done(Err(error.to_string()));
+            }
+        });
+        operation.connect_finished(move |_| {
+            if let Some(done) = done.take() {
+                done(Ok(()));
+            }
+        });
+        operation.print();
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn print_to_pdf(&self, _path: &Path, _backgrounds: bool, done: Box<dyn FnOnce(Result<(), String>)>) {
+        done(Err("Printing to a file isn't supported on this platform".into()));
+    }
+
+    #[cfg(target_os = "linux")]
+    fn print(&self, backgrounds: bool) {
+        use gtk::prelude::*;
+        use webkit2gtk::{PrintOperation, PrintOperationExt, SettingsExt, WebViewExt};
+        use wry::WebViewExtUnix;
+
+        let webview = WebViewExtUnix::webview(self);
+        if let Some(settings) = WebViewExt::settings(&webview) {
+            settings.set_print_backgrounds(backgrounds);
+        }
+        let parent = webview.toplevel().and_then(|w| w.downcast::<gtk::Window>().ok());
+        PrintOperation::new(&webview).run_dialog(parent.as_ref());
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn print(&self, backgrounds: bool) {
+        // WKWebView and WebView2 print backgrounds only where the page asks to
+        let js = format!(
+            "(function() {{ var id = '__syncflo_print_backgrounds'; var style = document.getElementById(id); \
+             if ({}) {{ if (!style) {{ style = document.createElement('style'); style.id = id; \
+             style.textContent = '@media print {{ * {{ -webkit-print-color-adjust: exact !important; \
+             print-color-adjust: exact !important; }} }}'; document.documentElement.appendChild(style); }} }} \
+             else if (style) {{ style.remove(); }} }})()",
+            backgrounds
+        );
+        WebViewHandle::evaluate_script(self, &js);
+        if let Err(e) = WebView::print(self) {
+            log::warn!("Failed to open the print dialog: {}", e);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn stop_loading(&self) {
+        use webkit2gtk::WebViewExt;
+        use wry::WebViewExtUnix;
+
+        WebViewExtUnix::webview(self).stop_loading();
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn stop_loading(&self) {
+        // wry has no stop API; this also cancels a navigation in progress
+        let _ = self.evaluate_script("window.stop()");
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_user_agent(&self, user_agent: Option<&str>) {
+        use webkit2gtk::{SettingsExt, WebViewExt};
+        use wry::WebViewExtUnix;
+
+        if let Some(settings) = WebViewExt::settings(&WebViewExtUnix::webview(self)) {
+            if settings.user_agent().as_deref() != user_agent {
+                // `None` restores WebKit's own
+                settings.set_user_agent(user_agent);
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn set_user_agent(&self, _user_agent: Option<&str>) {}
+
+    #[cfg(target_os = "linux")]
+    fn set_proxy(&self, proxy: &ProxySettings) {
+        use webkit2gtk::{NetworkProxyMode, NetworkProxySettings, WebContextExt, WebViewExt, WebsiteDataManagerExt};
+        use wry::WebViewExtUnix;
+
+        let Some(manager) = WebViewExtUnix::webview(self).context().and_then(|context| context.website_data_manager())
+        else {
+            log::warn!("No website data manager to set the proxy on");
+            return;
+        };
+        match proxy.uri() {
+            Some(uri) => {
+                let bypass: Vec<&str> = proxy.bypass.iter().map(String::as_str).collect();
+                let mut settings = NetworkProxySettings::new(Some(&uri), &bypass);
+                manager.set_network_proxy_settings(NetworkProxyMode::Custom, Some(&mut settings));
+            }
+            None => manager.set_network_proxy_settings(NetworkProxyMode::Default, None),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn set_proxy(&self, _proxy: &ProxySettings) {}
+}
+
+pub struct Tab {
+    pub id: TabId,
+    pub view: Rc<dyn TabView>,
+    /// URL the tab was opened with, until a page commits in it.
+    pub opened_url: String,
+    /// Back/forward list of this tab. Not persisted; the shared history is.
+    pub navigation: Navigation,
+    pub https_first: HttpsFirst,
+    /// Whether the current page's requests are filtered by the blocklist.
+    pub blocking: bool,
+    /// Requests the blocklist stopped on the current page.
+    pub blocked_requests: usize,
+    /// When the page last reported a click or key press, which popups
+    /// opened soon after are taken to come from.
+    pub last_gesture: Option<Instant>,
+    /// Popups the current page opened without one, oldest first.
+    pub blocked_popups: Vec<String>,
+    /// Address that failed to load while the error page is shown.
+    pub load_error: Option<String>,
+    /// What the webview reported about the current page's connection.
+    pub security: PageSecurity,
+    /// Icon of the current site as a data URL.
+    pub favicon: Option<String>,
+    pub loading: LoadState,
+    /// Page zoom, `1.0` being 100%.
+    pub zoom: f64,
+    /// Whether the current page is shown in the reader view.
+    pub reader: bool,
+    /// Chat with the AI assistant, about the current page unless pinned.
+    pub conversation: Conversation,
+}
+
+/// Page load progress of a tab.
+///
+/// Each load start bumps the generation, which the nav bar uses to drop
+/// stale pushes. A late finish from a superseded navigation is ignored, so
+/// rapid navigations can't leave the spinner in the wrong state.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LoadState {
+    pub loading: bool,
+    pub generation: u64,
+    pub url: Option<String>,
+    /// URL of the superseded load, whose finish is ignored.
+    #[serde(skip)]
+    previous_url: Option<String>,
+}
+
+impl LoadState {
+    pub fn started(&mut self, url: &str) {
+        self.generation += 1;
+        self.loading = true;
+        self.previous_url = self.url.replace(url.to_string());
+    }
+
+    /// Returns whether the finish ended the current load.
+    pub fn finished(&mut self, url: &str) -> bool {
+        if !self.loading {
+            return false;
+        }
+        // Redirects finish on a different URL than they started, so only a
+        // finish for the superseded load's URL is known to be stale
+        let current = self.url.as_deref() == Some(url);
+        if !current && self.previous_url.as_deref() == Some(url) {
+            return false;
+        }
+        self.loading = false;
+        true
+    }
+
+    pub fn stopped(&mut self) {
+        self.generation += 1;
+        self.loading = false;
+    }
+}
+
+/// Tab summary pushed to the nav webview with `tabs_changed`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TabInfo {
+    pub id: TabId,
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub active: bool,
+}
+
+/// Open tabs in strip order. Whenever there are tabs, exactly one is active
+/// and only the active tab's view is visible.
+#[derive(Default)]
+pub struct TabManager {
+    tabs: Vec<Tab>,
+    active: Option<TabId>,
+    next_id: TabId,
+}
+
+impl TabManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserves the id for a tab whose view is about to be created.
+    pub fn next_id(&mut self) -> TabId {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Adds a tab right after the active one and activates it.
+    pub fn insert(&mut self, view: Rc<dyn TabView>, id: TabId, url: &str) {
+        let index = self.active_index().map_or(self.tabs.len(), |i| i + 1);
+        self.tabs.insert(
+            index,
+            Tab {
+                id,
+                view,
+                opened_url: url.to_string(),
+                navigation: Navigation::new(),
+                https_first: HttpsFirst::default(),
+                blocking: false,
+                blocked_requests: 0,
+                last_gesture: None,
+                blocked_popups: Vec::new(),
+                load_error: None,
+                security: PageSecurity::default(),
+                favicon: None,
+                loading: LoadState::default(),
+                zoom: 1.0,
+                reader: false,
+                conversation: Conversation::new(),
+            },
+        );
+        self.activate(id);
+    }
+
+    /// Removes a tab. If it was active, the tab to its right (or else its
+    /// left) becomes active. Returns `None` if there's no such tab.
+    pub fn close(&mut self, id: TabId) -> Option<Tab> {
+        let index = self.tabs.iter().position(|t| t.id == id)?;
+        let tab = self.tabs.remove(index);
+        if self.active == Some(id) {
+            self.active = None;
+            let next = self.tabs.get(index).or_else(|| self.tabs.last()).map(|t| t.id);
+            if let Some(next) = next {
+                self.activate(next);
+            }
+        }
+        Some(tab)
+    }
+
+    /// Makes `id` the active, visible tab.
+    pub fn activate(&mut self, id: TabId) -> bool {
+        if !self.tabs.iter().any(|t| t.id == id) {
+            return false;
+        }
+        self.active = Some(id);
+        for tab in &self.tabs {
+            tab.view.set_visible(tab.id == id);
+        }
+        true
+    }
+
+    fn active_index(&self) -> Option<usize> {
+        let active = self.active?;
+        self.tabs.iter().position(|t| t.id == active)
+    }
+
+    pub fn active(&self) -> Option<&Tab> {
+        self.active_index().map(|i| &self.tabs[i])
+    }
+
+    pub fn active_mut(&mut self) -> Option<&mut Tab> {
+        self.active_index().map(move |i| &mut self.tabs[i])
+    }
+
+    pub fn get_mut(&mut self, id: TabId) -> Option<&mut Tab> {
+        self.tabs.iter_mut().find(|t| t.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Tab> {
+        self.tabs.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Tab> {
+        self.tabs.iter_mut()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tabs.is_empty()
+    }
+
+    pub fn infos(&self) -> Vec<TabInfo> {
+        self.tabs
+            .iter()
+            .map(|tab| {
+                let entry = tab.navigation.current_entry();
+                TabInfo {
+                    id: tab.id,
+                    url: tab.navigation.current_url().map(|u| u.to_string()),
+                    title: entry.and_then(|e| e.title.clone()),
+                    active: self.active == Some(tab.id),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// View that only remembers whether it's visible.
+    #[derive(Default)]
+    struct FakeView {
+        visible: Cell<bool>,
+    }
+
+    impl WebViewHandle for FakeView {
+        fn load_url(&self, _url: &str) {}
+        fn evaluate_script(&self, _js: &str) {}
+        fn focus(&self) {}
+    }
+
+    impl TabView for FakeView {
+        fn set_visible(&self, visible: bool) {
+            self.visible.set(visible);
+        }
+        fn set_bounds(&self, _bounds: Rect) {}
+        fn clear_website_data(&self, _cookies: bool, _cache: bool, _since: Option<Duration>) -> anyhow::Result<()> {
+            Ok(())
+        }
+        fn stop_loading(&self) {}
+        fn set_zoom(&self, _level: f64) {}
+        fn toggle_devtools(&self) -> bool {
+            false
+        }
+        fn evaluate_script_with_callback(&self, _js: &str, _callback: Box<dyn Fn(String) + Send>) {}
+        fn confirm_leave(&self, _done: Box<dyn FnOnce(bool)>) {}
+        fn confirm_external(&self, _scheme: &str, _done: Box<dyn FnOnce(bool)>) {}
+        fn choose_file(&self, _dir: &Path, _done: Box<dyn FnOnce(Option<PathBuf>)>) {}
+        fn choose_save_path(&self, _: SaveAs, _: &Path, _: &str, _: Box<dyn FnOnce(Option<PathBuf>)>) {}
+        fn cookie_header(&self, _url: &str) -> Option<String> {
+            None
+        }
+        fn download(&self, _url: &str) {}
+        fn print_to_pdf(&self, _path: &Path, _backgrounds: bool, _done: Box<dyn FnOnce(Result<(), String>)>) {}
+        fn print(&self, _backgrounds: bool) {}
+        fn set_user_agent(&self, _user_agent: Option<&str>) {}
+        fn set_proxy(&self, _proxy: &ProxySettings) {}
+    }
+
+    fn open(tabs: &mut TabManager) -> (TabId, Rc<FakeView>) {
+        let view = Rc::new(FakeView::default());
+        let id = tabs.next_id();
+        tabs.insert(view.clone(), id, "about:blank");
+        (id, view)
+    }
+
+    fn order(tabs: &TabManager) -> Vec<TabId> {
+        tabs.iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn new_tabs_open_after_the_active_one() {
+        let mut tabs = TabManager::new();
+        let (a, _) = open(&mut tabs);
+        let (b, _) = open(&mut tabs);
+        tabs.activate(a);
+        let (c, _) = open(&mut tabs);
+        assert_eq!(order(&tabs), vec![a, c, b]);
+        assert_eq!(tabs.active().map(|t| t.id), Some(c));
+    }
+
+    #[test]
+    fn only_the_active_tab_is_visible() {
+        let mut tabs = TabManager::new();
+        let (a, view_a) = open(&mut tabs);
+        let (_, view_b) = open(&mut tabs);
+        assert!(!view_a.visible.get());
+        assert!(view_b.visible.get());
+        assert!(tabs.activate(a));
+        assert!(view_a.visible.get());
+        assert!(!view_b.visible.get());
+    }
+
+    #[test]
+    fn closing_the_active_tab_activates_its_right_neighbour() {
+        let mut tabs = TabManager::new();
+        let (a, _) = open(&mut tabs);
+        let (b, _) = open(&mut tabs);
+        let (c, view_c) = open(&mut tabs);
+        tabs.activate(b);
+        assert!(tabs.close(b).is_some());
+        assert_eq!(order(&tabs), vec![a, c]);
+        assert_eq!(tabs.active().map(|t| t.id), Some(c));
+        assert!(view_c.visible.get());
+    }
+
+    #[test]
+    fn closing_the_last_tab_activates_its_left_neighbour() {
+        let mut tabs = TabManager::new();
+        let (a, _) = open(&mut tabs);
+        let (b, _) = open(&mut tabs);
+        tabs.close(b);
+        assert_eq!(tabs.active().map(|t| t.id), Some(a));
+        tabs.close(a);
+        assert!(tabs.is_empty());
+        assert!(tabs.active().is_none());
+    }
+
+    #[test]
+    fn closing_an_inactive_tab_keeps_the_active_one() {
+        let mut tabs = TabManager::new();
+        let (a, _) = open(&mut tabs);
+        let (b, _) = open(&mut tabs);
+        tabs.close(a);
+        assert_eq!(tabs.active().map(|t| t.id), Some(b));
+    }
+
+    #[test]
+    fn unknown_ids_are_rejected() {
+        let mut tabs = TabManager::new();
+        let (a, _) = open(&mut tabs);
+        assert!(!tabs.activate(a + 1));
+        assert!(tabs.close(a + 1).is_none());
+        assert_eq!(tabs.active().map(|t| t.id), Some(a));
+    }
+
+    #[test]
+    fn infos_mark_the_active_tab() {
+        let mut tabs = TabManager::new();
+        let (a, _) = open(&mut tabs);
+        let (b, _) = open(&mut tabs);
+        let active: Vec<(TabId, bool)> = tabs.infos().iter().map(|i| (i.id, i.active)).collect();
+        assert_eq!(active, vec![(a, false), (b, true)]);
+    }
+
+    #[test]
+    fn a_stale_finish_is_ignored() {
+        let mut load = LoadState::default();
+        load.started("https://a.example/");
+        load.started("https://b.example/");
+        assert!(!load.finished("https://a.example/"));
+        assert!(load.loading);
+        assert!(load.finished("https://b.example/"));
+        assert!(!load.loading);
+    }
+}
@@ -0,0 +1,57 @@
+//! The user agent tabs send, for sites that serve a broken layout to the
+//! webview's own.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A current desktop Chrome on this platform, offered as a preset on the
+/// settings page.
+#[cfg(target_os = "macos")]
+pub const CHROME: &str =
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/141.0.0.0 Safari/537.36";
+#[cfg(windows)]
+pub const CHROME: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/141.0.0.0 Safari/537.36";
+#[cfg(not(any(target_os = "macos", windows)))]
+pub const CHROME: &str =
+    "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/141.0.0.0 Safari/537.36";
+
+/// `[user_agent]` in the settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserAgentSettings {
+    /// Sent to sites without an override; the webview's own if unset.
+    pub default: Option<String>,
+    /// Overrides by host. A host's also applies to its subdomains.
+    pub sites: BTreeMap<String, String>,
+}
+
+impl UserAgentSettings {
+    /// The user agent for pages on `host`, or `None` for the webview's own.
+    /// The override of the longest matching host wins.
+    pub fn for_host(&self, host: Option<&str>) -> Option<&str> {
+        let site = host.and_then(|host| {
+            let host = host.to_ascii_lowercase();
+            self.sites
+                .iter()
+                .filter(|(site, _)| host == **site || host.ends_with(&format!(".{}", site)))
+                .max_by_key(|(site, _)| site.len())
+                .map(|(_, agent)| agent.as_str())
+        });
+        site.or(self.default.as_deref())
+    }
+
+    /// Sets the override for `host`, or removes it with `None`.
+    pub fn set_site(&mut self, host: &str, agent: Option<String>) -> Result<()> {
+        let host = host.trim().trim_end_matches('.').to_ascii_lowercase();
+        if host.is_empty() || host.contains(|c: char| c.is_whitespace() || c == '/' || c == ':') {
+            bail!("Invalid host {:?}", host);
+        }
+        match agent {
+            Some(agent) => self.sites.insert(host, agent),
+            None => self.sites.remove(&host),
+        };
+        Ok(())
+    }
+}
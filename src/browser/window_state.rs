@@ -0,0 +1,145 @@
+//! The window's size, position and maximized state, kept across launches.
+
+use serde::{Deserialize, Serialize};
+use tao::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use tao::event_loop::EventLoopWindowTarget;
+use tao::monitor::MonitorHandle;
+use tao::window::{Fullscreen, Window, WindowBuilder};
+
+/// Inner size of a window that has none saved, in logical pixels.
+const DEFAULT_SIZE: (u32, u32) = (1280, 800);
+/// How far into a monitor, in physical pixels, the window's top left
+/// corner may be and still count as on it, for windows the OS placed
+/// just past the edge.
+const EDGE_SLACK: i32 = 32;
+
+/// The `[window]` section of the config file: how the window was left.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowState {
+    /// Outer position in physical pixels.
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+    /// Inner size in logical pixels.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub maximized: bool,
+    pub fullscreen: bool,
+}
+
+impl WindowState {
+    /// The state of `window` now. While it's maximized, fullscreen or
+    /// minimized, the size and position stay as they were, so the window
+    /// comes back to them when it's restored.
+    pub fn capture(&self, window: &Window) -> Self {
+        let maximized = window.is_maximized();
+        let fullscreen = window.fullscreen().is_some();
+        let mut state = Self { maximized, fullscreen, ..*self };
+        if maximized || fullscreen || window.is_minimized() {
+            return state;
+        }
+        let size = window.inner_size().to_logical::<u32>(window.scale_factor());
+        state.width = Some(size.width);
+        state.height = Some(size.height);
+        if let Ok(position) = window.outer_position() {
+            state.x = Some(position.x);
+            state.y = Some(position.y);
+        }
+        state
+    }
+
+    /// Sets up `builder` to open the window as it was left, at `size` if
+    /// given. A position on none of the connected monitors centers the
+    /// window on the primary one instead.
+    pub fn restore<T>(
+        &self,
+        builder: WindowBuilder,
+        target: &EventLoopWindowTarget<T>,
+        size: Option<(u32, u32)>,
+    ) -> WindowBuilder {
+        let (width, height) = size.or(self.width.zip(self.height)).unwrap_or(DEFAULT_SIZE);
+        let mut builder = builder.with_inner_size(LogicalSize::new(width, height)).with_maximized(self.maximized);
+        if let Some(position) = self.position(target, (width, height)) {
+            builder = builder.with_position(position);
+        }
+        if self.fullscreen {
+            builder = builder.with_fullscreen(Some(Fullscreen::Borderless(None)));
+        }
+        builder
+    }
+
+    /// The saved position if it's on a connected monitor, else the center
+    /// of the primary monitor. `None` if none was saved.
+    fn position<T>(&self, target: &EventLoopWindowTarget<T>, size: (u32, u32)) -> Option<PhysicalPosition<i32>> {
+        let (x, y) = self.x.zip(self.y)?;
+        let position = PhysicalPosition::new(x, y);
+        if target.available_monitors().any(|monitor| is_on(&monitor, position)) {
+            return Some(position);
+        }
+        log::info!("Saved window position ({}, {}) is off screen; centering the window", x, y);
+        let monitor = target.primary_monitor().or_else(|| target.available_monitors().next())?;
+        let window = LogicalSize::new(size.0, size.1).to_physical::<u32>(monitor.scale_factor());
+        Some(centered(&monitor, window))
+    }
+}
+
+/// Moves `window` back onto a connected monitor when it's on none of them,
+/// as after the monitor it was on was unplugged, and shrinks it to fit the
+/// monitor it's on. Maximized and fullscreen windows are left to the OS,
+/// which sizes them to their monitor.
+///
+/// Monitors only report their full size, so a window may still reach under
+/// a taskbar or the Dock.
+pub fn keep_on_screen(window: &Window) {
+    if window.is_maximized() || window.fullscreen().is_some() || window.is_minimized() {
+        return;
+    }
+    let Ok(position) = window.outer_position() else {
+        return;
+    };
+    let outer = window.outer_size();
+    let monitor = match window.available_monitors().find(|monitor| is_on(monitor, position)) {
+        Some(monitor) => monitor,
+        None => {
+            let Some(monitor) = window.primary_monitor().or_else(|| window.available_monitors().next()) else {
+                return;
+            };
+            log::info!("Window at ({}, {}) is off screen; moving it back", position.x, position.y);
+            window.set_outer_position(centered(&monitor, outer));
+            monitor
+        }
+    };
+    let extent = monitor.size();
+    if outer.width <= extent.width && outer.height <= extent.height {
+        return;
+    }
+    // The frame around the inner size stays as it is
+    let inner = window.inner_size();
+    let frame_width = outer.width.saturating_sub(inner.width);
+    let frame_height = outer.height.saturating_sub(inner.height);
+    window.set_inner_size(PhysicalSize::new(
+        outer.width.min(extent.width).saturating_sub(frame_width),
+        outer.height.min(extent.height).saturating_sub(frame_height),
+    ));
+    let origin = monitor.position();
+    window.set_outer_position(PhysicalPosition::new(
+        position.x.clamp(origin.x, origin.x + (extent.width - outer.width.min(extent.width)) as i32),
+        position.y.clamp(origin.y, origin.y + (extent.height - outer.height.min(extent.height)) as i32),
+    ));
+}
+
+/// Whether a window with its top left corner at `position` is on `monitor`.
+fn is_on(monitor: &MonitorHandle, position: PhysicalPosition<i32>) -> bool {
+    let (origin, extent) = (monitor.position(), monitor.size());
+    (origin.x..origin.x + extent.width as i32).contains(&(position.x + EDGE_SLACK))
+        && (origin.y..origin.y + extent.height as i32).contains(&(position.y + EDGE_SLACK))
+}
+
+/// Top left corner of a window of `size` centered on `monitor`.
+fn centered(monitor: &MonitorHandle, size: PhysicalSize<u32>) -> PhysicalPosition<i32> {
+    let (origin, extent) = (monitor.position(), monitor.size());
+    PhysicalPosition::new(
+        origin.x + (extent.width as i32 - size.width as i32).max(0) / 2,
+        origin.y + (extent.height as i32 - size.height as i32).max(0) / 2,
+    )
+}
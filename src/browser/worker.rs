@@ -0,0 +1,124 @@
+//! Background work, off the event loop: AI requests, icon fetches, page
+//! parsing and disk writes run on a small tokio runtime, and send their
+//! results back to the event loop as `AppEvent`s through its proxy.
+
+use anyhow::{Context, Result};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::runtime::{Handle, Runtime};
+
+/// Threads running async work; blocking work gets threads of its own.
+const WORKER_THREADS: usize = 2;
+
+static HANDLE: OnceLock<Handle> = OnceLock::new();
+
+/// Starts the workers. They run as long as the returned runtime is kept.
+pub fn start() -> Result<Runtime> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(WORKER_THREADS)
+        .thread_name("syncflo-worker")
+        .enable_all()
+        .build()
+        .context("Failed to start the background workers")?;
+    let _ = HANDLE.set(runtime.handle().clone());
+    Ok(runtime)
+}
+
+/// Runs `task` on the workers, or on a thread of its own if they weren't
+/// started, as for command-line commands.
+pub fn spawn(task: impl Future<Output = ()> + Send + 'static) {
+    if let Some(handle) = HANDLE.get() {
+        handle.spawn(task);
+        return;
+    }
+    std::thread::spawn(move || match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime.block_on(task),
+        Err(e) => log::error!("Failed to run background work: {}", e),
+    });
+}
+
+/// Runs `work`, which blocks, like disk IO or parsing a long page, next to
+/// the workers.
+pub fn spawn_blocking(work: impl FnOnce() + Send + 'static) {
+    match HANDLE.get() {
+        Some(handle) => drop(handle.spawn_blocking(work)),
+        None => drop(std::thread::spawn(work)),
+    }
+}
+
+/// A file written by the workers. Contents queued while a write is running
+/// replace each other and are written after it, so the file always ends
+/// up with what was queued last.
+#[derive(Debug, Clone)]
+pub struct FileWriter {
+    path: PathBuf,
+    /// Contents waiting to be written, and whether a worker will write them.
+    queue: Arc<Mutex<(Option<Vec<u8>>, bool)>>,
+    /// Held while writing, so writes don't interleave.
+    writing: Arc<Mutex<()>>,
+}
+
+impl FileWriter {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, queue: Default::default(), writing: Default::default() }
+    }
+
+    /// Writes `contents` on the workers.
+    pub fn queue(&self, contents: Vec<u8>) {
+        let Ok(mut queue) = self.queue.lock() else {
+            return;
+        };
+        queue.0 = Some(contents);
+        if std::mem::replace(&mut queue.1, true) {
+            return;
+        }
+        let writer = self.clone();
+        spawn_blocking(move || writer.drain());
+    }
+
+    /// Writes `contents` now, in place of any queued.
+    pub fn write(&self, contents: &[u8]) -> Result<()> {
+        let _writing = self.writing.lock().map_err(|_| anyhow::anyhow!("File writer lock poisoned"))?;
+        if let Ok(mut queue) = self.queue.lock() {
+            queue.0 = None;
+        }
+        write_replacing(&self.path, contents)
+    }
+
+    fn drain(&self) {
+        loop {
+            let Ok(_writing) = self.writing.lock() else {
+                return;
+            };
+            // Taken while holding the write lock, so a `write` can't come
+            // between taking older contents and writing them
+            let contents = match self.queue.lock() {
+                Ok(mut queue) => match queue.0.take() {
+                    Some(contents) => contents,
+                    None => {
+                        queue.1 = false;
+                        return;
+                    }
+                },
+                Err(_) => return,
+            };
+            if let Err(e) = write_replacing(&self.path, &contents) {
+                log::warn!("{:#}", e);
+            }
+        }
+    }
+}
+
+/// Writes `contents` to a temp file first and then moves it over `path`,
+/// so a crash mid-write can't leave half a file.
+fn write_replacing(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, contents).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))
+}
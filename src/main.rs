@@ -1,18 +1,161 @@
 mod browser;
 mod ai;
-mod ui;
 
 use anyhow::Result;
+use browser::session::StartupMode;
+use browser::profile::Profile;
+use browser::LaunchOptions;
+use clap::Parser;
 use log::info;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// URLs or files to open as tabs, instead of the startup tabs
+    #[arg(value_name = "URL")]
+    urls: Vec<String>,
+
+    /// What to open on startup, instead of what the settings say
+    #[arg(long, value_enum)]
+    startup: Option<StartupMode>,
+
+    /// Use the profile NAME, which keeps its own settings and data
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Keep all settings and data in DIR instead of the platform's directories
+    #[arg(long, value_name = "DIR")]
+    user_data_dir: Option<PathBuf>,
+
+    /// Open a private window that leaves no history or cookies behind
+    #[arg(long)]
+    incognito: bool,
+
+    /// Initial window size in logical pixels, like 1280x800
+    #[arg(long, value_name = "WxH", value_parser = parse_window_size)]
+    window_size: Option<(u32, u32)>,
+
+    /// Open only URL, without the browser's UI, as if it were an app
+    #[arg(long, value_name = "URL")]
+    app: Option<String>,
+
+    /// Keep the --app window fullscreen; it only closes with the kiosk exit
+    /// keys (Ctrl+Alt+Shift+Q by default)
+    #[arg(long, requires = "app")]
+    kiosk: bool,
+
+    /// Log filter, like `debug` or `info,browser::ipc=debug`, instead of
+    /// SYNCFLO_LOG
+    #[arg(long, value_name = "FILTER")]
+    log_level: Option<String>,
+
+    /// Import bookmarks from a browser's HTML export or Chrome's Bookmarks file, then exit
+    #[arg(long, value_name = "FILE")]
+    import_bookmarks: Option<PathBuf>,
+
+    /// Export history as JSON to FILE, then exit
+    #[arg(long, value_name = "FILE")]
+    export_history: Option<PathBuf>,
+
+    /// Export bookmarks as Netscape bookmarks HTML to FILE, then exit
+    #[arg(long, value_name = "FILE")]
+    export_bookmarks: Option<PathBuf>,
+}
 
 fn main() -> Result<()> {
-    // Initialize logger
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
-    
+    let cli = Cli::parse();
+
+    let options = LaunchOptions {
+        urls: cli.urls,
+        startup: cli.startup,
+        profile: cli.profile,
+        user_data_dir: cli.user_data_dir,
+        incognito: cli.incognito,
+        window_size: cli.window_size,
+        app: cli.app,
+        kiosk: cli.kiosk,
+    };
+
+    // Log to the profile too, except from incognito windows, which leave
+    // nothing behind there
+    let logs_dir = options.profile().ok().filter(|_| !options.incognito).map(|profile| profile.logs_dir());
+    browser::logging::init(cli.log_level.as_deref(), logs_dir.as_deref());
+
+    if let Some(path) = &cli.import_bookmarks {
+        return import_bookmarks(&options.profile()?, path);
+    }
+    if cli.export_history.is_some() || cli.export_bookmarks.is_some() {
+        return export_data(&options.profile()?, cli.export_history.as_deref(), cli.export_bookmarks.as_deref());
+    }
+
     info!("Starting SyncFlo Browser...");
+
+    // Save what we can on a panic. The running marker stays behind, so the
+    // next launch offers to restore the session.
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic_info| {
+        browser::logging::log_panic(panic_info);
+        browser::recovery::flush();
+        default_hook(panic_info);
+    }));
     
-    // Initialize and run browser (must run on main thread on macOS)
-    browser::Browser::new()?.run()?;
-    
+    // Initialize and run browser (must run on main thread on macOS).
+    // Launched from Finder or a launcher nobody sees stderr, so failures
+    // are shown in a dialog too.
+    if let Err(e) = browser::Browser::new(options).and_then(|browser| match browser {
+        Some(browser) => browser.run(),
+        // Another process runs the profile and opened the URLs
+        None => Ok(()),
+    }) {
+        log::error!("{:#}", e);
+        browser::dialogs::show_error(browser::dialogs::STARTUP_ERROR_TITLE, &format!("{:#}", e));
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+/// `1280x800` as width and height.
+fn parse_window_size(text: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("expected WIDTHxHEIGHT, like 1280x800, not {:?}", text);
+    let (width, height) = text.split_once(['x', 'X']).ok_or_else(invalid)?;
+    match (width.trim().parse(), height.trim().parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(invalid()),
+    }
+}
+
+fn import_bookmarks(profile: &Profile, path: &std::path::Path) -> Result<()> {
+    use browser::bookmarks::BookmarkStore;
+
+    let mut store = BookmarkStore::load(profile.bookmarks_path());
+    let report = store.import_file(path)?;
+    println!(
+        "Imported {} bookmarks ({} duplicates skipped, {} invalid)",
+        report.imported, report.skipped, report.invalid
+    );
+    Ok(())
+}
+
+fn export_data(
+    profile: &Profile,
+    history_path: Option<&std::path::Path>,
+    bookmarks_path: Option<&std::path::Path>,
+) -> Result<()> {
+    use browser::bookmarks::BookmarkStore;
+    use browser::ipc::create_export_file;
+    use browser::Navigation;
+
+    if let Some(path) = history_path {
+        let history = Navigation::load(profile.history_path());
+        history.export_json(create_export_file(path)?)?;
+        println!("Exported history to {}", path.display());
+    }
+    if let Some(path) = bookmarks_path {
+        let bookmarks = BookmarkStore::load(profile.bookmarks_path());
+        bookmarks.export_netscape_html(create_export_file(path)?)?;
+        println!("Exported bookmarks to {}", path.display());
+    }
     Ok(())
 }
@@ -1,10 +0,0 @@
-// UI components will be added here
-// This could include custom browser UI overlays, control panels, etc.
-
-pub struct BrowserUI;
-
-impl BrowserUI {
-    pub fn new() -> Self {
-        Self
-    }
-}